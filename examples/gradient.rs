@@ -1,51 +1,59 @@
-use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::thread::sleep;
 use std::time::Duration;
 use yaair::rufi::aggregate::{Aggregate, AggregateError, VM};
-use yaair::rufi::data::field::Field;
 use yaair::rufi::engine::Engine;
 use yaair::rufi::messages::inbound::InboundMessage;
-use yaair::rufi::network::Network;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::scheduler::Periodic;
+use yaair::rufi::sensors::neighborhood::{
+    NeighborReading, NeighborhoodReadings, NeighborhoodSensors,
+};
 use yaair_serde::rufi_serde::json::JsonSerializer;
 
 struct GradientEnv {
     pub is_source: bool,
 }
-impl GradientEnv {
-    fn distances(&self) -> Field<u32, f32> {
-        Field::new(0.0, HashMap::from([(1, 1.0), (2, 2.0), (3, 1.5)]))
-    }
-}
 
 struct DummyNetwork;
 impl Network<u32, JsonSerializer> for DummyNetwork {
-    fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) {}
+    fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        Ok(())
+    }
 
     fn prepare_inbound(&mut self) -> InboundMessage<u32> {
         InboundMessage::default()
     }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<u32> {
+        let at = |range| NeighborReading {
+            range: Some(range),
+            ..NeighborReading::default()
+        };
+        NeighborhoodReadings::new(HashMap::from([(1, at(1.0)), (2, at(2.0)), (3, at(1.5))]))
+    }
 }
 
 #[allow(clippy::print_stdout, clippy::print_stderr, clippy::use_debug)]
 pub fn main() {
     let env = GradientEnv { is_source: false };
-    let mut engine = Engine::new(0u32, DummyNetwork, env, JsonSerializer, gradient);
-    for _ in 0..10 {
-        match engine.cycle() {
-            Ok(result) => println!("Gradient result: {result:?}"),
-            Err(e) => eprintln!("Error during cycle: {e:?}"),
+    let mut engine = Engine::new(0u32, DummyNetwork, env, JsonSerializer, gradient)
+        .with_scheduler(Periodic::new(Duration::from_secs(1)));
+    let mut rounds = 0u32;
+    engine.run(Duration::from_millis(100), |round| {
+        match round.map(|report| report.output) {
+            Ok(Ok(result)) => println!("Gradient result: {result:?}"),
+            Ok(Err(e)) | Err(e) => eprintln!("Error during cycle: {e:?}"),
         }
-        sleep(Duration::from_secs(1));
-    }
+        rounds = rounds.saturating_add(1);
+        rounds < 10
+    });
 }
 
-fn gradient(env: &GradientEnv, vm: &mut VM<u32, JsonSerializer>) -> Result<f32, AggregateError> {
-    let initial = f32::MAX;
-    vm.share(&initial, |_, field| {
-        let distances = field.aligned_map(&env.distances(), |a, b| a + b);
-        let min_distance =
-            *distances.min_by(|a, b| PartialOrd::partial_cmp(&a, &b).unwrap_or(Ordering::Greater));
+fn gradient(env: &GradientEnv, vm: &mut VM<u32, JsonSerializer>) -> Result<f64, AggregateError> {
+    let initial = f64::MAX;
+    vm.share(&initial, |vm, field| {
+        let distances = field.aligned_map(&vm.nbr_range(), |a, b| a + b);
+        let min_distance = *distances.partial_min();
         if env.is_source {
             0.0
         } else {
@@ -1,6 +1,10 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(not(test), deny(clippy::panic))]
 
 #[cfg(not(feature = "std"))]
 extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
 
 pub mod rufi;
@@ -1,14 +1,30 @@
-use crate::rufi::alignment::alignment_stack::AlignmentStack;
+use crate::rufi::alignment::alignment_stack::{
+    AlignmentLimit, AlignmentLimits, AlignmentStack, PathId,
+};
+use crate::rufi::alignment::report::AlignmentReport;
 use crate::rufi::data::field::Field;
-use crate::rufi::data::state::State;
+use crate::rufi::data::snapshot::{DeviceSnapshot, SnapshotEntry, SnapshotType};
+use crate::rufi::data::state::{State, TypeMismatch};
+use crate::rufi::messages::budget::{MessageBudget, Priority};
+use crate::rufi::messages::codec::{CodecRegistry, ValueCodec};
 use crate::rufi::messages::inbound::InboundMessage;
-use crate::rufi::messages::outbound::OutboundMessage;
+use crate::rufi::messages::metadata::Metadata;
+use crate::rufi::messages::outbound::{OutboundMessage, WIRE_VERSION};
 use crate::rufi::messages::path::Path;
 use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::network::NetworkError;
+use crate::rufi::sensors::neighborhood::{LinkQuality, NeighborhoodReadings, NeighborhoodSensors};
+use crate::rufi::time::{Clock, TickClock, TimeSensor};
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
 
 #[cfg(not(feature = "std"))]
 use alloc::collections::BTreeMap as Map;
 
+#[cfg(not(feature = "std"))]
+use alloc::collections::{BTreeMap, BTreeSet};
+
 #[cfg(not(feature = "std"))]
 use alloc::format;
 
@@ -17,15 +33,42 @@ use alloc::string::String;
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::fmt::Display;
 use core::hash::Hash;
+use core::time::Duration;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use std::collections::HashMap as Map;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Path of the neighbors heard in the round, exported when symmetric links are enforced.
+const HEARD_PATH: &str = "@heard";
+
+/// Path marking the last message of a device leaving the network, see [`VM::leave_message`].
+const LEAVE_PATH: &str = "@leave";
 
 /// Represents errors that can occur during aggregate computation
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum AggregateError {
     SerializationError(String),
     DeserializationError(String),
+    InvalidRoleTransition(String),
+    MessageBudgetExceeded(String),
+    /// A neighbor sent a message in a wire format version this device cannot decode.
+    IncompatibleVersion(u32),
+    /// Required entries of a [`TypedEnv`](crate::rufi::sensors::typed::TypedEnv) are not set.
+    MissingEnvironment(Vec<String>),
+    /// A path of the state was accessed with a type different from the stored or registered
+    /// one, see [`VM::set_type_registry`].
+    TypeMismatch(TypeMismatch),
+    /// A [`StateStore`](crate::rufi::store::StateStore) could not read or write a snapshot.
+    StorageError(String),
+    /// The network could not send a message, e.g. the leave message of a device shutting down.
+    NetworkError(NetworkError),
+    /// The program exceeded the [`AlignmentLimits`] of the VM, see [`VM::set_alignment_limits`].
+    AlignmentLimitExceeded(AlignmentLimit),
 }
 
 impl core::fmt::Display for AggregateError {
@@ -35,6 +78,19 @@ impl core::fmt::Display for AggregateError {
             Self::DeserializationError(msg) => {
                 write!(f, "Deserialization error: {msg}")
             }
+            Self::InvalidRoleTransition(msg) => write!(f, "Invalid role transition: {msg}"),
+            Self::MessageBudgetExceeded(msg) => write!(f, "Message budget exceeded: {msg}"),
+            Self::IncompatibleVersion(version) => write!(
+                f,
+                "Incompatible wire format version {version}, supported up to {WIRE_VERSION}"
+            ),
+            Self::MissingEnvironment(missing) => {
+                write!(f, "Missing environment entries: {}", missing.join(", "))
+            }
+            Self::TypeMismatch(mismatch) => write!(f, "Type mismatch: {mismatch}"),
+            Self::StorageError(msg) => write!(f, "Storage error: {msg}"),
+            Self::NetworkError(error) => write!(f, "{error}"),
+            Self::AlignmentLimitExceeded(limit) => write!(f, "Alignment limit exceeded: {limit}"),
         }
     }
 }
@@ -43,8 +99,13 @@ impl core::fmt::Display for AggregateError {
 ///
 /// This trait provides the core operations for distributed aggregate computing:
 /// - `neighboring`: Share values with neighboring devices
+/// - `neighboring_owned`, `share_owned`: By-value variants avoiding clones of large values
+/// - `share_inspect`: `share` also returning the neighbor field
 /// - `repeat`: Maintain state across computation rounds
 /// - `branch`: Conditional execution with alignment
+/// - `align_on_value`: Execution aligned on a value, like a `branch` with many alternatives
+/// - `aligned_devices`: Neighbors aligned with the current position in the program
+/// - `neighbor_count`: Count aligned neighbors through a presence marker
 pub trait Aggregate<Id: Ord + Hash + Copy + Serialize> {
     /// Share a value with neighboring devices and collect their values.
     ///
@@ -57,6 +118,12 @@ pub trait Aggregate<Id: Ord + Hash + Copy + Serialize> {
     where
         V: Serialize + for<'de> Deserialize<'de> + Clone + 'static;
 
+    /// Like [`Aggregate::neighboring`], taking ownership of `value` so that it is moved into the
+    /// field instead of cloned.
+    fn neighboring_owned<V>(&mut self, value: V) -> Result<Field<Id, V>, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static;
+
     /// Maintain state across computation rounds with evolution function.
     ///
     /// # Arguments
@@ -64,7 +131,9 @@ pub trait Aggregate<Id: Ord + Hash + Copy + Serialize> {
     /// * `evolution` - Function to evolve the state
     ///
     /// # Returns
-    /// The evolved state value
+    /// The evolved state value, or `initial` evolved if the operator fails, e.g. when the stored
+    /// state has another type: the error is reported as [`VM::round_error`], see
+    /// [`VM::try_repeat`] to handle it instead
     fn repeat<V, F>(&mut self, initial: &V, evolution: F) -> V
     where
         V: Clone + 'static,
@@ -78,16 +147,103 @@ pub trait Aggregate<Id: Ord + Hash + Copy + Serialize> {
     /// * `el` - Function to execute if condition is false
     ///
     /// # Returns
-    /// Result of the executed branch
+    /// Result of the executed branch; alignment errors are reported as [`VM::round_error`], see
+    /// [`VM::try_branch`] to handle them instead
     fn branch<V, Th, El>(&mut self, condition: bool, th: Th, el: El) -> V
     where
         Th: FnOnce(&mut Self) -> V,
         El: FnOnce(&mut Self) -> V;
 
+    /// Execute `body` aligned on `key`: its operators only align with the neighbors that
+    /// executed it with a key of the same representation.
+    fn align_on_value<K, V, B>(&mut self, key: &K, body: B) -> V
+    where
+        K: Display,
+        B: FnOnce(&mut Self) -> V;
+
     fn share<V, E>(&mut self, initial: &V, evolution: E) -> Result<V, AggregateError>
     where
         V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
         E: FnOnce(&mut Self, Field<Id, V>) -> V;
+
+    /// Like [`Aggregate::share`], taking ownership of `initial` and moving the previous state
+    /// into the field instead of cloning it; only the returned value is cloned to be kept.
+    fn share_owned<V, E>(&mut self, initial: V, evolution: E) -> Result<V, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+        E: FnOnce(&mut Self, Field<Id, V>) -> V;
+
+    /// Like [`Aggregate::share`], also returning the field the value evolved from, so that the
+    /// neighbor states can be inspected without exchanging them again with `neighboring`.
+    ///
+    /// # Returns
+    /// The evolved value and a clone of the field passed to `evolution`
+    fn share_inspect<V, E>(
+        &mut self,
+        initial: &V,
+        evolution: E,
+    ) -> Result<(V, Field<Id, V>), AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+        E: FnOnce(&mut Self, Field<Id, V>) -> V,
+    {
+        let mut inspected = None;
+        let value = self.share(initial, |vm, field| {
+            inspected = Some(field.clone());
+            evolution(vm, field)
+        })?;
+        // `share` only succeeds after running the evolution
+        let field = inspected.unwrap_or_else(|| Field::new(value.clone(), Map::new()));
+        Ok((value, field))
+    }
+
+    /// Neighbors aligned with the current position in the program.
+    ///
+    /// # Returns
+    /// The ids of the neighbors that exported some value under the current alignment path in
+    /// their last round. Combine it with [`Field::restricted_to`] to project a field computed
+    /// outside a `branch` on the neighbors that took the same branch.
+    fn aligned_devices(&self) -> BTreeSet<Id>;
+
+    /// Count the neighbors executing this operator, without sharing any value.
+    ///
+    /// Like the other operators it takes part in alignment: devices only count each other if
+    /// they invoke it at the same position in the program. Only an empty presence marker is
+    /// exported.
+    ///
+    /// # Returns
+    /// The number of aligned neighbors, excluding the local device, or 0 if the operator fails
+    /// to align, see [`VM::try_neighbor_count`]
+    fn neighbor_count(&mut self) -> usize;
+
+    /// Whether no neighbor is executing this operator, see [`Aggregate::neighbor_count`].
+    fn is_isolated(&mut self) -> bool {
+        self.neighbor_count() == 0
+    }
+}
+
+/// Neighbor values decoded in the current round: ids alongside a type-erased `Vec<V>`.
+type DecodedValues<Id> = (Vec<Id>, Box<dyn Any>);
+
+/// Strategy decoding the values of the neighbors at the current path.
+type Gather<Vm, Id, V> = fn(&mut Vm) -> Result<Map<Id, V>, AggregateError>;
+
+/// Neighborhoods from which [`VM::par_neighboring`] and [`VM::par_share`] decode in parallel.
+#[cfg(feature = "rayon")]
+pub const PARALLEL_DECODING_THRESHOLD: usize = 64;
+
+/// Values exported at or below a path.
+type Exports = Vec<(Path, Vec<u8>)>;
+
+/// Serialized inputs and neighbor exports an incremental computation depends on, see
+/// [`VM::incremental`].
+type Dependencies<Id> = (Vec<u8>, Vec<(Id, Exports)>);
+
+/// Result of the last execution of an incremental computation.
+struct Memo<Id> {
+    dependencies: Dependencies<Id>,
+    result: Box<dyn Any>,
+    exports: Exports,
 }
 
 /// Virtual Machine implementation for aggregate computing.
@@ -100,23 +256,45 @@ pub struct VM<Id: Ord + Hash + Copy + Serialize, S: Serializer> {
     outbound: OutboundMessage<Id>,
     alignment_stack: AlignmentStack,
     serializer: S,
+    neighborhood: NeighborhoodReadings<Id>,
+    codecs: CodecRegistry,
+    snapshot_types: Vec<SnapshotType<S>>,
+    clock: Box<dyn Clock>,
+    round_time: Duration,
+    /// Keyed by the interned path, valid until the alignment stack is reset.
+    decoded: Map<(Option<PathId>, TypeId), DecodedValues<Id>>,
+    budget: Option<MessageBudget>,
+    /// Priorities assigned by the program to the paths exported in the current round.
+    priorities: BTreeMap<Path, Priority>,
+    /// Paths dropped from the last outbound message to fit the budget.
+    dropped: Vec<Path>,
+    round_error: Option<AggregateError>,
+    memo: Option<Map<Path, Memo<Id>>>,
+    /// Neighbor paths read in the current round, tracked only for [`VM::alignment_report`].
+    read_paths: Option<BTreeSet<Path>>,
+    /// Whether a [`Metadata`] header is attached to the outbound messages.
+    metadata: bool,
+    /// Whether neighbors that did not hear from this device are ignored.
+    symmetric_links: bool,
+    position: Option<(f64, f64)>,
+    /// Rounds started so far, the sequence number of the [`Metadata`] header.
+    rounds: u64,
+    joined: BTreeSet<Id>,
+    left: BTreeSet<Id>,
 }
 
 impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> VM<Id, S> {
     /// Create a new VM instance with default state.
     pub fn new(local_id: Id, serializer: S) -> Self {
-        Self {
-            local_id,
-            state: State::default(),
-            inbound: InboundMessage::default(),
-            outbound: OutboundMessage::empty(local_id),
-            alignment_stack: AlignmentStack::new(),
-            serializer,
-        }
+        Self::new_with_state(local_id, serializer, State::default())
     }
 
     /// Create a new VM instance with provided state.
+    ///
+    /// The VM uses a [`TickClock`] advancing by one second per round; see [`VM::set_clock`].
     pub fn new_with_state(local_id: Id, serializer: S, state: State) -> Self {
+        let mut clock = TickClock::default();
+        let round_time = clock.now();
         Self {
             local_id,
             state,
@@ -124,45 +302,1127 @@ impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> VM<Id, S> {
             outbound: OutboundMessage::empty(local_id),
             alignment_stack: AlignmentStack::new(),
             serializer,
+            neighborhood: NeighborhoodReadings::default(),
+            codecs: CodecRegistry::new(),
+            snapshot_types: Vec::new(),
+            clock: Box::new(clock),
+            round_time,
+            decoded: Map::new(),
+            budget: None,
+            priorities: BTreeMap::new(),
+            dropped: Vec::new(),
+            round_error: None,
+            memo: None,
+            read_paths: None,
+            metadata: false,
+            symmetric_links: false,
+            position: None,
+            rounds: 0,
+            joined: BTreeSet::new(),
+            left: BTreeSet::new(),
         }
     }
 
+    /// Replace the clock sampled at the beginning of every round.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+        self.round_time = self.clock.now();
+    }
+
     /// Get the serialized outbound message.
     ///
     /// # Returns
     /// Serialized outbound message as bytes, or panics on serialization error
-    pub fn get_outbound(&self) -> Result<Vec<u8>, AggregateError> {
-        self.serializer.serialize(&self.outbound).map_err(|err| {
-            AggregateError::SerializationError(format!(
-                "Failed to serialize outbound message: {err}",
-            ))
-        })
+    pub fn get_outbound(&mut self) -> Result<Vec<u8>, AggregateError> {
+        let (bytes, dropped) = self.fit_outbound(&self.outbound)?;
+        self.dropped = dropped;
+        Ok(bytes)
+    }
+
+    /// Serialize `message` as an outbound message of this VM, enforcing the message budget.
+    ///
+    /// # Errors
+    /// Returns an error if serialization fails or the message does not fit the budget
+    pub fn encode_outbound(
+        &mut self,
+        message: &OutboundMessage<Id>,
+    ) -> Result<Vec<u8>, AggregateError> {
+        let (bytes, dropped) = self.fit_outbound(message)?;
+        self.dropped = dropped;
+        Ok(bytes)
+    }
+
+    /// Serialize `message` within the message budget, if any, returning the dropped paths.
+    fn fit_outbound(
+        &self,
+        message: &OutboundMessage<Id>,
+    ) -> Result<(Vec<u8>, Vec<Path>), AggregateError> {
+        let Some(budget) = self.budget.as_ref() else {
+            let bytes = self.serializer.serialize(message).map_err(|err| {
+                AggregateError::SerializationError(format!(
+                    "Failed to serialize outbound message: {err}",
+                ))
+            })?;
+            return Ok((bytes, Vec::new()));
+        };
+        budget.fit_tagged(message, &self.serializer, &self.priorities)
     }
 
+    /// Paths dropped from the last outbound message encoded to fit the message budget, in the
+    /// order they were dropped.
+    pub fn dropped_paths(&self) -> &[Path] {
+        &self.dropped
+    }
+
+    /// Like [`Aggregate::neighboring`], assigning `priority` to the exported value: when the
+    /// message budget is exceeded with [`OverflowPolicy::DropLowestPriority`], lower priority
+    /// values are dropped first.
+    ///
+    /// [`OverflowPolicy::DropLowestPriority`]: crate::rufi::messages::budget::OverflowPolicy::DropLowestPriority
+    ///
+    /// # Errors
+    /// See [`Aggregate::neighboring`]
+    pub fn neighboring_with_priority<V>(
+        &mut self,
+        value: &V,
+        priority: Priority,
+    ) -> Result<Field<Id, V>, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+    {
+        let field = self.neighboring(value)?;
+        if let Some(path) = self.outbound.last_appended() {
+            self.priorities.insert(path, priority);
+        }
+        Ok(field)
+    }
+
+    /// Limit the size of the serialized outbound message; see [`MessageBudget`].
+    pub fn set_message_budget(&mut self, budget: MessageBudget) {
+        self.budget = Some(budget);
+    }
+
+    /// The outbound message built so far in the current round.
+    pub const fn outbound(&self) -> &OutboundMessage<Id> {
+        &self.outbound
+    }
+
+    /// Value exported at `path` in the current round, decoded like the values of the neighbors.
+    ///
+    /// # Returns
+    /// `None` if nothing was exported at `path`
+    ///
+    /// # Errors
+    /// Returns an error if the export cannot be decoded as a `V`
+    pub fn outbound_at<V>(&self, path: &Path) -> Result<Option<V>, AggregateError>
+    where
+        V: for<'de> Deserialize<'de> + 'static,
+    {
+        self.outbound
+            .at(path)
+            .map(|bytes| self.decode::<V>(path, bytes))
+            .transpose()
+    }
+
+    /// Paths exported so far in the current round, in the order they were exported.
+    pub fn exported_paths(&self) -> impl Iterator<Item = Path> + '_ {
+        self.outbound.appended_paths()
+    }
+
+    /// Start a new round with the messages received from the neighbors.
+    ///
+    /// Exports and alignment start from scratch, reusing the buffers of the previous round.
     pub fn prepare_new_round(&mut self, inbound: InboundMessage<Id>) {
-        self.outbound = OutboundMessage::empty(self.local_id);
-        self.alignment_stack = AlignmentStack::new();
+        self.outbound.reset();
+        self.alignment_stack.reset();
         self.inbound = inbound;
+        let leaving = self.inbound.devices_at_path(&Path::from(LEAVE_PATH));
+        self.inbound.retain(|id| !leaving.contains(id));
+        if self.symmetric_links {
+            self.filter_asymmetric_links();
+        }
+        self.decoded.clear();
+        self.priorities.clear();
+        if let Some(read_paths) = self.read_paths.as_mut() {
+            read_paths.clear();
+        }
+        self.round_time = self.clock.now();
+        self.round_error = None;
+        self.state.commit();
+        self.rounds = self.rounds.saturating_add(1);
+        if self.metadata {
+            self.outbound.set_metadata(Metadata {
+                timestamp: self.round_time,
+                sequence: self.rounds,
+                position: self.position,
+            });
+        }
+    }
+
+    /// Neighbors whose messages are used in the current round: the ones received, except the
+    /// leaving ones and, with [`VM::set_symmetric_links`], the ones that did not hear from
+    /// this device.
+    pub fn neighbors(&self) -> impl Iterator<Item = Id> + '_ {
+        self.inbound.neighbors()
+    }
+
+    /// Message announcing that this device leaves the network: neighbors receiving it drop the
+    /// device right away, instead of waiting for its exports to expire.
+    pub fn leave_message(&self) -> OutboundMessage<Id> {
+        let mut message = OutboundMessage::empty(self.local_id);
+        message.append(&Path::from(LEAVE_PATH), Vec::new());
+        message
+    }
+
+    /// Only consider the neighbors that heard from this device in their last round, starting
+    /// from the next round.
+    ///
+    /// Every device exports the neighbors it heard from, so all the devices of the network must
+    /// enable it; bidirectional algorithms like gossiping averages break on asymmetric links.
+    pub const fn set_symmetric_links(&mut self, enabled: bool) {
+        self.symmetric_links = enabled;
+    }
+
+    /// Drop the inbound messages of neighbors that did not list this device among the ones
+    /// they heard, then export the neighbors heard in this round.
+    ///
+    /// Ids are compared in their serialized form, since the VM cannot decode them.
+    fn filter_asymmetric_links(&mut self) {
+        let path = Path::from(HEARD_PATH);
+        let heard: Vec<Vec<u8>> = self
+            .inbound
+            .neighbors()
+            .filter_map(|id| self.serializer.serialize(&id).ok())
+            .collect();
+        let local = self.serializer.serialize(&self.local_id).ok();
+        let symmetric: BTreeSet<Id> = self
+            .inbound
+            .neighbors()
+            .filter(|id| {
+                self.inbound
+                    .get(id)
+                    .and_then(|tree| tree.get(&path))
+                    .and_then(|bytes| self.serializer.deserialize::<Vec<Vec<u8>>>(bytes).ok())
+                    .zip(local.as_ref())
+                    .is_some_and(|(listed, local)| listed.contains(local))
+            })
+            .collect();
+        self.inbound.retain(|id| symmetric.contains(id));
+        if let Ok(bytes) = self.serializer.serialize(&heard) {
+            self.outbound.append(&path, bytes);
+        }
+    }
+
+    /// Attach a [`Metadata`] header to the outbound messages, starting from the next round.
+    pub const fn set_metadata(&mut self, enabled: bool) {
+        self.metadata = enabled;
+    }
+
+    /// Position advertised in the [`Metadata`] header, starting from the next round.
+    pub const fn set_position(&mut self, position: Option<(f64, f64)>) {
+        self.position = position;
+    }
+
+    /// Time of the round in which each neighbor produced its last message, according to its
+    /// clock; neighbors that sent no [`Metadata`] header are not part of the field.
+    pub fn nbr_timestamp(&self) -> Field<Id, Duration> {
+        self.metadata_field(self.round_time, |metadata| metadata.timestamp)
+    }
+
+    /// Sequence number of the last message of each neighbor, see [`VM::nbr_timestamp`].
+    ///
+    /// Comparing it with the previous round tells apart fresh, repeated and missed messages.
+    pub fn nbr_sequence(&self) -> Field<Id, u64> {
+        self.metadata_field(self.rounds, |metadata| metadata.sequence)
+    }
+
+    /// Position advertised by each neighbor, see [`VM::nbr_timestamp`].
+    pub fn nbr_position(&self) -> Field<Id, Option<(f64, f64)>> {
+        self.metadata_field(self.position, |metadata| metadata.position)
+    }
+
+    /// Hops travelled by the last message of each neighbor, 1 if received directly from it; 0
+    /// for this device.
+    pub fn nbr_hops(&self) -> Field<Id, u8> {
+        let neighbors = self
+            .inbound
+            .neighbors()
+            .filter_map(|id| Some((id, self.inbound.get(&id)?.hops())))
+            .collect();
+        Field::new(0, neighbors)
+    }
+
+    fn metadata_field<V>(&self, local: V, read: impl Fn(&Metadata) -> V) -> Field<Id, V> {
+        let neighbors = self
+            .inbound
+            .metadata()
+            .map(|(id, metadata)| (id, read(metadata)))
+            .collect();
+        Field::new(local, neighbors)
+    }
+
+    /// First error raised by an operator in the current round, even if the program recovered.
+    pub const fn round_error(&self) -> Option<&AggregateError> {
+        self.round_error.as_ref()
+    }
+
+    /// Allow the current round to be undone by [`VM::rollback_round`], at the cost of keeping
+    /// the replaced state until the end of the round.
+    pub fn set_rollback(&mut self, enabled: bool) {
+        self.state.set_journaling(enabled);
+    }
+
+    /// Report accesses to the state with a type different from the one of the first access at
+    /// the same path as [`AggregateError::TypeMismatch`] round errors, naming the expected type
+    /// and its operator; without the registry, only accesses to a stored value of another type
+    /// are reported.
+    ///
+    /// Mismatching operators run as in their first round and do not update the state.
+    pub fn set_type_registry(&mut self, enabled: bool) {
+        self.state.set_type_registry(enabled);
+    }
+
+    /// State at the current path read by `operator`, checking its type against the registry if
+    /// enabled.
+    fn read_state<V: Any>(&mut self, operator: &'static str) -> Result<Option<&V>, AggregateError> {
+        let path = self.alignment_stack.path();
+        self.state
+            .register::<V>(path, operator)
+            .map_err(AggregateError::TypeMismatch)?;
+        self.state
+            .try_get::<V>(path)
+            .map_err(|mismatch| type_mismatch(mismatch, operator))
+    }
+
+    /// Undo the current round: the state goes back to the end of the previous round and
+    /// nothing is exported.
+    pub fn rollback_round(&mut self) {
+        self.state.rollback();
+        // memoized results may come from the round being undone
+        if let Some(memo) = self.memo.as_mut() {
+            memo.clear();
+        }
+        self.outbound.reset();
+        self.alignment_stack.reset();
+        // keyed by paths that the reset may forget
+        self.decoded.clear();
+    }
+
+    /// Leave the operator being executed because of `error`, remembering it for the round.
+    fn fail(&mut self, error: AggregateError) -> AggregateError {
+        self.alignment_stack.unalign();
+        self.record(error)
+    }
+
+    /// Remember `error` for the round, unless an earlier error was raised.
+    fn record(&mut self, error: AggregateError) -> AggregateError {
+        if self.round_error.is_none() {
+            self.round_error = Some(error.clone());
+        }
+        error
+    }
+
+    /// Bound the nesting of the operators and their number in a round, see [`AlignmentLimits`].
+    ///
+    /// Operators beyond the limits fail with [`AggregateError::AlignmentLimitExceeded`] without
+    /// reading nor exporting values; operators that cannot fail run their body like in their first
+    /// round and report the error for the round.
+    pub const fn set_alignment_limits(&mut self, limits: AlignmentLimits) {
+        self.alignment_stack.set_limits(limits);
+    }
+
+    /// Enter the next invocation of the operator `token`, remembering for the round whether it
+    /// exceeds the alignment limits; the invocation must be left with `unalign` either way.
+    fn align(&mut self, token: &str) -> Result<(), AggregateError> {
+        self.alignment_stack
+            .align(token)
+            .map_err(|limit| self.record(AggregateError::AlignmentLimitExceeded(limit)))
+    }
+
+    /// Serialize `value` with the serializer of the VM.
+    pub fn serialize_value<V: Serialize>(&self, value: &V) -> Result<Vec<u8>, AggregateError> {
+        self.serializer.serialize(value).map_err(|err| {
+            AggregateError::SerializationError(format!("Failed to serialize value: {err}"))
+        })
+    }
+
+    /// Execute `body` in the alignment namespace `name`.
+    ///
+    /// Operators invoked by `body` only align with the ones invoked in the same namespace on
+    /// the neighbors, and their exports are nested under `name`. Unlike the other operators,
+    /// namespaces do not depend on the invocation order: the same name must not be entered
+    /// twice at the same path in a round.
+    pub fn namespace<V>(&mut self, name: &str, body: impl FnOnce(&mut Self) -> V) -> V {
+        if let Err(limit) = self.alignment_stack.align_namespace(name) {
+            self.record(AggregateError::AlignmentLimitExceeded(limit));
+        }
+        let result = body(self);
+        self.alignment_stack.unalign();
+        result
+    }
+
+    /// Execute `body` aligned on `key`, like a `branch` with an alternative for every value.
+    ///
+    /// Operators invoked by `body` only align with the neighbors that executed it with a key of
+    /// the same representation, e.g. to compute only within the cluster of the same leader. The
+    /// `/` path separator is escaped as `%2F`, and `%` as `%25`, so that every key is a single
+    /// token of the path.
+    pub fn align_on_value<K: Display, V>(
+        &mut self,
+        key: &K,
+        body: impl FnOnce(&mut Self) -> V,
+    ) -> V {
+        // the operators of the body fail as well when the limits are exceeded
+        let key = format!("{key}").replace('%', "%25").replace('/', "%2F");
+        self.align(&format!("align[{key}]")).ok();
+        #[cfg(feature = "tracing")]
+        let _span = operator_span("align_on_value", self.alignment_stack.path());
+        let result = body(self);
+        self.alignment_stack.unalign();
+        result
+    }
+
+    /// Like [`Aggregate::branch`], aligned under the namespace `key` instead of the operators
+    /// invoked before it, so that library blocks do not collide with each other; see
+    /// [`VM::namespace`].
+    pub fn branch_keyed<V, Th, El>(&mut self, key: &str, condition: bool, th: Th, el: El) -> V
+    where
+        Th: FnOnce(&mut Self) -> V,
+        El: FnOnce(&mut Self) -> V,
+    {
+        self.namespace(key, |vm| vm.branch(condition, th, el))
+    }
+
+    /// Like [`Aggregate::repeat`], aligned under the namespace `key`, see [`VM::branch_keyed`].
+    pub fn repeat_keyed<V, F>(&mut self, key: &str, initial: &V, evolution: F) -> V
+    where
+        V: Clone + 'static,
+        F: FnOnce(V, &mut Self) -> V,
+    {
+        self.namespace(key, |vm| vm.repeat(initial, evolution))
+    }
+
+    /// Like [`Aggregate::share`], aligned under the namespace `key`, see [`VM::branch_keyed`].
+    ///
+    /// # Errors
+    /// See [`Aggregate::share`]
+    pub fn share_keyed<V, E>(
+        &mut self,
+        key: &str,
+        initial: &V,
+        evolution: E,
+    ) -> Result<V, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+        E: FnOnce(&mut Self, Field<Id, V>) -> V,
+    {
+        self.namespace(key, |vm| vm.share(initial, evolution))
+    }
+
+    /// Like [`Aggregate::neighboring`], aligned under the namespace `key`, see
+    /// [`VM::branch_keyed`].
+    ///
+    /// # Errors
+    /// See [`Aggregate::neighboring`]
+    pub fn neighboring_keyed<V>(
+        &mut self,
+        key: &str,
+        value: &V,
+    ) -> Result<Field<Id, V>, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+    {
+        self.namespace(key, |vm| vm.neighboring(value))
+    }
+
+    /// Run `body` under an operator-like coordinate `token`, counted like the other operators
+    /// invoked at the current path, e.g. to align the calls of a user function.
+    pub fn aligned<V>(&mut self, token: &str, body: impl FnOnce(&mut Self) -> V) -> V {
+        self.align(token).ok();
+        let result = body(self);
+        self.alignment_stack.unalign();
+        result
+    }
+
+    /// Memoize the result of `body`, executing it again only when `inputs` differ from the
+    /// ones of the previous round, e.g. for expensive local computations like pathfinding.
+    ///
+    /// The body is skipped entirely when the result is reused: it should not invoke operators
+    /// exchanging values with the neighbors, whose exports would be missing in those rounds.
+    pub fn cached<K, V>(&mut self, inputs: &K, body: impl FnOnce(&mut Self) -> V) -> V
+    where
+        K: PartialEq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        self.cached_from(inputs, body).0
+    }
+
+    /// Like [`VM::cached`], returning the error of the operator instead of the result of `body`,
+    /// e.g. when the memoized value has another type.
+    ///
+    /// # Errors
+    /// Returns the error raised by the operator, also reported as [`VM::round_error`]
+    pub fn try_cached<K, V>(
+        &mut self,
+        inputs: &K,
+        body: impl FnOnce(&mut Self) -> V,
+    ) -> Result<V, AggregateError>
+    where
+        K: PartialEq + Clone + 'static,
+        V: Clone + 'static,
+    {
+        let (result, error) = self.cached_from(inputs, body);
+        error.map_or(Ok(result), Err)
+    }
+
+    /// Like [`Aggregate::repeat`], returning the error of the operator instead of the value
+    /// evolved from `initial`, e.g. when the stored state has another type.
+    ///
+    /// # Errors
+    /// Returns the error raised by the operator, also reported as [`VM::round_error`]
+    pub fn try_repeat<V, F>(&mut self, initial: &V, evolution: F) -> Result<V, AggregateError>
+    where
+        V: Clone + 'static,
+        F: FnOnce(V, &mut Self) -> V,
+    {
+        let (updated_state, error) = self.repeat_from(initial, evolution);
+        error.map_or(Ok(updated_state), Err)
+    }
+
+    /// Like [`Aggregate::branch`], returning the error of the operator instead of the result of
+    /// the executed branch, e.g. when it exceeds the [`AlignmentLimits`].
+    ///
+    /// # Errors
+    /// Returns the error raised by the operator, also reported as [`VM::round_error`]
+    pub fn try_branch<V, Th, El>(
+        &mut self,
+        condition: bool,
+        th: Th,
+        el: El,
+    ) -> Result<V, AggregateError>
+    where
+        Th: FnOnce(&mut Self) -> V,
+        El: FnOnce(&mut Self) -> V,
+    {
+        let (result, error) = self.branch_from(condition, th, el);
+        error.map_or(Ok(result), Err)
+    }
+
+    /// Like [`Aggregate::neighbor_count`], returning the error of the operator instead of 0
+    /// when it exceeds the [`AlignmentLimits`].
+    ///
+    /// # Errors
+    /// Returns the error raised by the operator, also reported as [`VM::round_error`]
+    pub fn try_neighbor_count(&mut self) -> Result<usize, AggregateError> {
+        if let Err(error) = self.align("presence") {
+            self.alignment_stack.unalign();
+            return Err(error);
+        }
+        let path = self.alignment_stack.path();
+        Self::mark_read(&mut self.read_paths, path);
+        let count = self.inbound.get_at_path(path).count();
+        self.outbound.append(path, Vec::new());
+        self.alignment_stack.unalign();
+        Ok(count)
+    }
+
+    /// Track the neighbor paths read in every round to build an [`AlignmentReport`], or stop
+    /// tracking them.
+    pub fn set_alignment_diagnostics(&mut self, enabled: bool) {
+        self.read_paths = enabled.then(BTreeSet::new);
+    }
+
+    /// Values exchanged so far in the current round that did not align: neighbor paths never
+    /// read and local paths no neighbor exported.
+    ///
+    /// # Returns
+    /// The report, or `None` if [`VM::set_alignment_diagnostics`] is disabled
+    pub fn alignment_report(&self) -> Option<AlignmentReport<Id>> {
+        let read_paths = self.read_paths.as_ref()?;
+        let root = Path::root();
+        let mut unread: Vec<(Id, Path)> = self
+            .inbound
+            .neighbors()
+            .filter_map(|id| Some((id, self.inbound.get(&id)?)))
+            .flat_map(|(id, tree)| {
+                tree.entries_under(&root)
+                    .filter(|(path, _)| !read_paths.contains(*path))
+                    .map(move |(path, _)| (id, path.clone()))
+            })
+            .collect();
+        unread.sort_unstable();
+        let mut unmatched: Vec<Path> = self
+            .outbound
+            .appended_paths()
+            .filter(|path| self.inbound.get_at_path(path).next().is_none())
+            .collect();
+        unmatched.sort_unstable();
+        Some(AlignmentReport { unread, unmatched })
+    }
+
+    /// Remember that the values of the neighbors at `path` were read in this round.
+    fn mark_read(read_paths: &mut Option<BTreeSet<Path>>, path: &Path) {
+        if let Some(read_paths) = read_paths.as_mut() {
+            read_paths.insert(path.clone());
+        }
+    }
+
+    /// Enable the incremental evaluation of [`VM::incremental`] computations, or disable it
+    /// dropping the memoized results.
+    pub fn set_incremental(&mut self, enabled: bool) {
+        self.memo = enabled.then(Map::new);
+    }
+
+    /// Execute `body` only if `inputs` or the values exported by the neighbors inside it changed
+    /// since the last round, otherwise reuse its previous result and exports.
+    ///
+    /// `body` must depend only on `inputs` and on the values of the neighbors: sensors, time and
+    /// captured variables must be passed as inputs. Skipping `body` keeps its state and exports
+    /// as they were, so neighbors stay aligned. Without [`VM::set_incremental`] `body` is always
+    /// executed.
+    ///
+    /// # Errors
+    /// Returns an error if `inputs` cannot be serialized
+    pub fn incremental<I, V>(
+        &mut self,
+        inputs: &I,
+        body: impl FnOnce(&mut Self) -> V,
+    ) -> Result<V, AggregateError>
+    where
+        I: Serialize,
+        V: Clone + 'static,
+    {
+        self.align("incremental").map_err(|err| self.fail(err))?;
+        let path = self.alignment_stack.path().clone();
+        if self.memo.is_none() {
+            let result = body(self);
+            self.alignment_stack.unalign();
+            return Ok(result);
+        }
+        let inputs = self.serialize_value(inputs).map_err(|err| self.fail(err))?;
+        let dependencies = (inputs, self.neighbor_exports_under(&path));
+        let reused = self
+            .memo
+            .as_ref()
+            .and_then(|memo| memo.get(&path))
+            .filter(|memo| memo.dependencies == dependencies)
+            .and_then(|memo| {
+                let result = memo.result.downcast_ref::<V>()?.clone();
+                Some((result, memo.exports.clone()))
+            });
+        if let Some((result, exports)) = reused {
+            for (_, neighbor_exports) in &dependencies.1 {
+                for (export_path, _) in neighbor_exports {
+                    Self::mark_read(&mut self.read_paths, export_path);
+                }
+            }
+            for (export_path, value) in exports {
+                self.outbound.append(&export_path, value);
+            }
+            self.alignment_stack.unalign();
+            return Ok(result);
+        }
+        let failed_before = self.round_error.is_some();
+        let result = body(self);
+        // a failed execution is not memoized, so that it is retried in the next round
+        if failed_before || self.round_error.is_none() {
+            let exports: Exports = self
+                .outbound
+                .appended_paths()
+                .filter(|export_path| export_path.starts_with(&path))
+                .filter_map(|export_path| {
+                    let value = self.outbound.at(&export_path)?.clone();
+                    Some((export_path, value))
+                })
+                .collect();
+            if let Some(memo) = self.memo.as_mut() {
+                memo.insert(
+                    path,
+                    Memo {
+                        dependencies,
+                        result: Box::new(result.clone()),
+                        exports,
+                    },
+                );
+            }
+        }
+        self.alignment_stack.unalign();
+        Ok(result)
+    }
+
+    /// Values exported by every neighbor at `prefix` or below it, sorted by neighbor and path.
+    fn neighbor_exports_under(&self, prefix: &Path) -> Vec<(Id, Exports)> {
+        let mut neighbors: Vec<Id> = self.inbound.neighbors().collect();
+        neighbors.sort_unstable();
+        neighbors
+            .into_iter()
+            .filter_map(|id| {
+                let mut exports: Exports = self
+                    .inbound
+                    .get(&id)?
+                    .entries_under(prefix)
+                    .map(|(path, value)| (path.clone(), value.to_vec()))
+                    .collect();
+                exports.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                (!exports.is_empty()).then_some((id, exports))
+            })
+            .collect()
+    }
+
+    /// Neighborhood readings of the current round.
+    pub const fn neighborhood(&self) -> &NeighborhoodReadings<Id> {
+        &self.neighborhood
+    }
+
+    /// Update the neighborhood readings exposed through [`NeighborhoodSensors`].
+    pub fn update_neighborhood(&mut self, readings: NeighborhoodReadings<Id>) {
+        self.neighborhood = readings;
+    }
+
+    /// Update the neighbors that joined and left since the previous round, as observed by a
+    /// [`Discovery`](crate::rufi::discovery::Discovery).
+    pub fn update_membership(&mut self, joined: BTreeSet<Id>, left: BTreeSet<Id>) {
+        self.joined = joined;
+        self.left = left;
+    }
+
+    /// Neighbors discovered since the previous round, see [`VM::update_membership`].
+    pub const fn joined_neighbors(&self) -> &BTreeSet<Id> {
+        &self.joined
+    }
+
+    /// Neighbors lost since the previous round, see [`VM::update_membership`].
+    pub const fn left_neighbors(&self) -> &BTreeSet<Id> {
+        &self.left
+    }
+
+    /// Like [`Aggregate::neighboring`], letting the exported value travel at most `hops` hops
+    /// through the relays of the network, see [`OutboundMessage::relay`].
+    ///
+    /// # Errors
+    /// See [`Aggregate::neighboring`]
+    pub fn neighboring_with_hop_limit<V>(
+        &mut self,
+        value: &V,
+        hops: u8,
+    ) -> Result<Field<Id, V>, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+    {
+        let field = self.neighboring(value)?;
+        if let Some(path) = self.outbound.last_appended() {
+            self.outbound.set_hop_limit(&path, hops);
+        }
+        Ok(field)
+    }
+
+    /// Include the state values of type `V` in the snapshots of this VM, see [`VM::snapshot`].
+    ///
+    /// Types are identified by their name, so the devices exchanging snapshots must run the
+    /// same build and register the same types.
+    pub fn register_snapshot_type<V>(&mut self)
+    where
+        V: Serialize + serde::de::DeserializeOwned + 'static,
+    {
+        if !self
+            .snapshot_types
+            .iter()
+            .any(|registered| registered.type_id == TypeId::of::<V>())
+        {
+            self.snapshot_types.push(SnapshotType::of::<V>());
+        }
+    }
+
+    /// Take a transferable copy of the state and the last outbound message of this device.
+    ///
+    /// Values whose type is not registered with [`VM::register_snapshot_type`] are left out,
+    /// and listed in [`DeviceSnapshot::skipped`].
+    pub fn snapshot(&self) -> DeviceSnapshot<Id> {
+        let mut entries = Vec::new();
+        let mut skipped = Vec::new();
+        for (path, value) in self.state.entries() {
+            let encoded = self
+                .snapshot_types
+                .iter()
+                .find(|registered| registered.type_id == value.type_id())
+                .and_then(|registered| {
+                    Some((
+                        registered.name,
+                        (registered.encode)(value, &self.serializer)?,
+                    ))
+                });
+            match encoded {
+                Some((type_name, bytes)) => entries.push(SnapshotEntry {
+                    path: path.clone(),
+                    type_name: type_name.into(),
+                    value: bytes,
+                }),
+                None => skipped.push(path.clone()),
+            }
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        skipped.sort();
+        DeviceSnapshot {
+            device: self.local_id,
+            entries,
+            outbound: self.outbound.clone(),
+            skipped,
+        }
+    }
+
+    /// Warm start this device from the `snapshot` of another one, e.g. the device it replaces,
+    /// overwriting the state at the paths of the snapshot.
+    ///
+    /// # Returns
+    /// The last outbound message of the snapshot on behalf of this device, to be sent before
+    /// the first round so that neighbors keep observing the previous values
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::DeserializationError`] if a value has a type that is not
+    /// registered with [`VM::register_snapshot_type`] or cannot be decoded; the state is left
+    /// unchanged
+    pub fn import_snapshot(
+        &mut self,
+        snapshot: DeviceSnapshot<Id>,
+    ) -> Result<OutboundMessage<Id>, AggregateError> {
+        let values = snapshot
+            .entries
+            .iter()
+            .map(|entry| {
+                self.snapshot_types
+                    .iter()
+                    .find(|registered| registered.name == entry.type_name)
+                    .and_then(|registered| (registered.decode)(&entry.value, &self.serializer))
+                    .map(|value| (entry.path.clone(), value))
+                    .ok_or_else(|| {
+                        AggregateError::DeserializationError(format!(
+                            "Failed to import the snapshot value at path {} of type {}",
+                            entry.path, entry.type_name
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (path, value) in values {
+            self.state.insert_boxed(path, value);
+        }
+        let mut outbound = snapshot.outbound;
+        outbound.sender = self.local_id;
+        Ok(outbound)
+    }
+
+    /// Register a codec for the values of type `V` exported under `prefix`.
+    ///
+    /// All the devices of the network must register the same codecs.
+    pub fn register_codec<V, C>(&mut self, prefix: Path, codec: C)
+    where
+        V: 'static,
+        C: ValueCodec<V> + 'static,
+    {
+        self.codecs.register(prefix, codec);
+    }
+
+    /// Register a codec for the values of type `V` exported at any path, e.g. to quantize every
+    /// `f32` with [`Half`](crate::rufi::messages::codec::Half); codecs registered with
+    /// [`VM::register_codec`] take precedence.
+    ///
+    /// All the devices of the network must register the same codecs.
+    pub fn register_type_codec<V, C>(&mut self, codec: C)
+    where
+        V: 'static,
+        C: ValueCodec<V> + 'static,
+    {
+        self.codecs.register_for_type(codec);
+    }
+
+    fn encode<V>(&self, path: &Path, value: &V) -> Result<Vec<u8>, S::Error>
+    where
+        V: Serialize + 'static,
+    {
+        self.codecs.find::<V>(path).map_or_else(
+            || self.serializer.serialize(value),
+            |codec| Ok(codec.encode(value)),
+        )
+    }
+
+    fn decode<V>(&self, path: &Path, bytes: &[u8]) -> Result<V, AggregateError>
+    where
+        V: for<'de> Deserialize<'de> + 'static,
+    {
+        self.codecs.find::<V>(path).map_or_else(
+            || {
+                self.serializer.deserialize::<V>(bytes).map_err(|err| {
+                    AggregateError::DeserializationError(format!(
+                        "Failed to deserialize value at path {path}: {err}",
+                    ))
+                })
+            },
+            |codec| {
+                codec.decode(bytes).ok_or_else(|| {
+                    AggregateError::DeserializationError(format!(
+                        "Failed to decode value at path {path}: malformed payload for codec",
+                    ))
+                })
+            },
+        )
+    }
+
+    /// Like [`VM::get_at_path`], deserializing on the rayon thread pool when the neighborhood is
+    /// large; values handled by a codec are decoded sequentially.
+    #[cfg(feature = "rayon")]
+    fn par_get_at_path<V>(&mut self) -> Result<Map<Id, V>, AggregateError>
+    where
+        Id: Send + Sync,
+        S: Sync,
+        V: for<'de> Deserialize<'de> + Clone + Send + 'static,
+    {
+        use rayon::prelude::*;
+        let path = self.alignment_stack.path();
+        let key = (self.alignment_stack.path_id(), TypeId::of::<V>());
+        if self.decoded.contains_key(&key)
+            || self.inbound.len() < PARALLEL_DECODING_THRESHOLD
+            || self.codecs.find::<V>(path).is_some()
+        {
+            return self.get_at_path();
+        }
+        Self::mark_read(&mut self.read_paths, path);
+        let payloads: Vec<(Id, &[u8])> = self.inbound.get_at_path(path).collect();
+        let serializer = &self.serializer;
+        let values = payloads
+            .par_iter()
+            .map(|(_, bytes)| {
+                serializer.deserialize::<V>(bytes).map_err(|err| {
+                    AggregateError::DeserializationError(format!(
+                        "Failed to deserialize value at path {path}: {err}",
+                    ))
+                })
+            })
+            .collect::<Result<Vec<V>, _>>()?;
+        let ids: Vec<Id> = payloads.into_iter().map(|(id, _)| id).collect();
+        let result = ids.iter().copied().zip(values.iter().cloned()).collect();
+        self.decoded.insert(key, (ids, Box::new(values)));
+        Ok(result)
+    }
+
+    /// Neighbor values at the current path, decoded at most once per round and type.
+    fn get_at_path<V>(&mut self) -> Result<Map<Id, V>, AggregateError>
+    where
+        V: for<'de> Deserialize<'de> + Clone + 'static,
+    {
+        let path = self.alignment_stack.path();
+        Self::mark_read(&mut self.read_paths, path);
+        let key = (self.alignment_stack.path_id(), TypeId::of::<V>());
+        if let Some((ids, values)) = self.decoded.get(&key) {
+            if let Some(values) = values.downcast_ref::<Vec<V>>() {
+                return Ok(ids.iter().copied().zip(values.iter().cloned()).collect());
+            }
+        }
+        let mut ids = Vec::new();
+        let mut values = Vec::new();
+        for (id, elem) in self.inbound.get_at_path(path) {
+            ids.push(id);
+            values.push(self.decode::<V>(path, elem)?);
+        }
+        let result = ids.iter().copied().zip(values.iter().cloned()).collect();
+        self.decoded.insert(key, (ids, Box::new(values)));
+        Ok(result)
+    }
+
+    /// Like [`Aggregate::neighboring`], decoding the values of large neighborhoods in parallel,
+    /// see [`PARALLEL_DECODING_THRESHOLD`].
+    ///
+    /// # Errors
+    /// Returns an error if the value cannot be serialized or a neighbor value cannot be decoded
+    #[cfg(feature = "rayon")]
+    pub fn par_neighboring<V>(&mut self, value: &V) -> Result<Field<Id, V>, AggregateError>
+    where
+        Id: Send + Sync,
+        S: Sync,
+        V: Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
+    {
+        self.neighboring_from(value.clone(), Self::par_get_at_path)
+    }
+
+    /// Like [`Aggregate::share`], decoding the values of large neighborhoods in parallel, see
+    /// [`PARALLEL_DECODING_THRESHOLD`].
+    ///
+    /// # Errors
+    /// Returns an error if the value cannot be serialized or a neighbor value cannot be decoded
+    #[cfg(feature = "rayon")]
+    pub fn par_share<V, E>(&mut self, initial: &V, evolution: E) -> Result<V, AggregateError>
+    where
+        Id: Send + Sync,
+        S: Sync,
+        V: Serialize + for<'de> Deserialize<'de> + Clone + Send + 'static,
+        E: FnOnce(&mut Self, Field<Id, V>) -> V,
+    {
+        self.share_from(|| initial.clone(), evolution, Self::par_get_at_path)
+    }
+
+    /// Implementation of `neighboring`, gathering the neighbor values with `gather`.
+    fn neighboring_from<V>(
+        &mut self,
+        value: V,
+        gather: Gather<Self, Id, V>,
+    ) -> Result<Field<Id, V>, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+    {
+        self.align("neighboring").map_err(|err| self.fail(err))?;
+        #[cfg(feature = "tracing")]
+        let span = operator_span("neighboring", self.alignment_stack.path());
+
+        // Collect neighboring values with improved error handling
+        let neighboring_values = gather(self).map_err(|err| self.fail(err))?;
+        #[cfg(feature = "tracing")]
+        span.record("neighbors", neighboring_values.len());
+
+        // Serialize and append to outbound
+        let serialized_value = self
+            .encode(self.alignment_stack.path(), &value)
+            .map_err(|err| {
+                self.fail(AggregateError::SerializationError(format!(
+                    "Failed to serialize neighboring value: {err}"
+                )))
+            })?;
+        #[cfg(feature = "tracing")]
+        span.record("bytes", serialized_value.len());
+
+        self.outbound
+            .append(self.alignment_stack.path(), serialized_value);
+        self.alignment_stack.unalign();
+        Ok(Field::new(value, neighboring_values))
+    }
+
+    /// Implementation of `share`, moving the previous state out of the state store and falling
+    /// back to `initial` on the first round.
+    fn share_from<V, E>(
+        &mut self,
+        initial: impl FnOnce() -> V,
+        evolution: E,
+        gather: Gather<Self, Id, V>,
+    ) -> Result<V, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+        E: FnOnce(&mut Self, Field<Id, V>) -> V,
+    {
+        self.align("share").map_err(|err| self.fail(err))?;
+        #[cfg(feature = "tracing")]
+        let span = operator_span("share", self.alignment_stack.path());
+        self.read_state::<V>("share")
+            .map(|_| ())
+            .map_err(|err| self.fail(err))?;
+        let neighboring_values = gather(self).map_err(|err| self.fail(err))?;
+        // taken only once the neighbors are gathered, its type is checked above
+        let previous_state = self
+            .state
+            .try_take::<V>(self.alignment_stack.path())
+            .map_err(|mismatch| self.fail(type_mismatch(mismatch, "share")))?
+            .unwrap_or_else(initial);
+        #[cfg(feature = "tracing")]
+        span.record("neighbors", neighboring_values.len());
+        let field = Field::new(previous_state, neighboring_values);
+        let updated_state = evolution(self, field);
+        self.state
+            .update(self.alignment_stack.path(), updated_state.clone());
+        let serialized_value = self
+            .encode(self.alignment_stack.path(), &updated_state)
+            .map_err(|err| {
+                self.fail(AggregateError::SerializationError(format!(
+                    "Failed to serialize share value: {err}"
+                )))
+            })?;
+        #[cfg(feature = "tracing")]
+        span.record("bytes", serialized_value.len());
+        self.outbound
+            .append(self.alignment_stack.path(), serialized_value);
+        self.alignment_stack.unalign();
+        Ok(updated_state)
+    }
+
+    /// Implementation of `repeat`, also returning the error that made it evolve `initial`
+    /// without storing the result.
+    fn repeat_from<V, F>(&mut self, initial: &V, evolution: F) -> (V, Option<AggregateError>)
+    where
+        V: Clone + 'static,
+        F: FnOnce(V, &mut Self) -> V,
+    {
+        let aligned = self.align("repeat");
+        #[cfg(feature = "tracing")]
+        let _span = operator_span("repeat", self.alignment_stack.path());
+        let previous_state =
+            match aligned.and_then(|()| self.read_state::<V>("repeat").map(Option::<&V>::cloned)) {
+                Ok(previous_state) => previous_state,
+                Err(error) => {
+                    let updated_state = evolution(initial.clone(), self);
+                    return (updated_state, Some(self.fail(error)));
+                }
+            };
+        let updated_state = evolution(previous_state.unwrap_or_else(|| initial.clone()), self);
+        self.state
+            .update(self.alignment_stack.path(), updated_state.clone());
+        self.alignment_stack.unalign();
+        (updated_state, None)
+    }
+
+    /// Implementation of `branch`, also returning the error raised when entering it.
+    fn branch_from<V, Th, El>(
+        &mut self,
+        condition: bool,
+        th: Th,
+        el: El,
+    ) -> (V, Option<AggregateError>)
+    where
+        Th: FnOnce(&mut Self) -> V,
+        El: FnOnce(&mut Self) -> V,
+    {
+        // the operators of the branch fail as well when the limits are exceeded
+        let aligned = self.align(if condition {
+            "branch[true]"
+        } else {
+            "branch[false]"
+        });
+        #[cfg(feature = "tracing")]
+        let _span = operator_span("branch", self.alignment_stack.path());
+        let result = if condition { th(self) } else { el(self) };
+        self.alignment_stack.unalign();
+        (result, aligned.err())
     }
 
-    fn get_at_path<V>(&self, path: &Path) -> Result<Map<Id, V>, AggregateError>
+    /// Implementation of `cached`, also returning the error that made it execute `body`
+    /// without memoizing the result.
+    fn cached_from<K, V>(
+        &mut self,
+        inputs: &K,
+        body: impl FnOnce(&mut Self) -> V,
+    ) -> (V, Option<AggregateError>)
     where
-        V: for<'de> Deserialize<'de>,
+        K: PartialEq + Clone + 'static,
+        V: Clone + 'static,
     {
-        let mut result = Map::new();
-        for (id, elem) in self.inbound.get_at_path(path) {
-            match self.serializer.deserialize::<V>(&elem) {
-                Ok(deserialized_value) => {
-                    result.insert(id, deserialized_value);
-                }
-                Err(err) => {
-                    return Err(AggregateError::DeserializationError(format!(
-                        "Failed to deserialize value at path {path}: {err}",
-                    )));
-                }
+        let aligned = self.align("cached");
+        #[cfg(feature = "tracing")]
+        let _span = operator_span("cached", self.alignment_stack.path());
+        let memoized = match aligned.and_then(|()| {
+            self.read_state::<(K, V)>("cached").map(|memoized| {
+                memoized
+                    .filter(|(previous, _)| previous == inputs)
+                    .map(|(_, value)| value.clone())
+            })
+        }) {
+            Ok(memoized) => memoized,
+            Err(error) => {
+                let value = body(self);
+                return (value, Some(self.fail(error)));
             }
-        }
-        Ok(result)
+        };
+        let result = memoized.unwrap_or_else(|| {
+            let value = body(self);
+            self.state
+                .update(self.alignment_stack.path(), (inputs.clone(), value.clone()));
+            value
+        });
+        self.alignment_stack.unalign();
+        (result, None)
     }
 }
 
@@ -171,25 +1431,14 @@ impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> Aggregate<Id> for VM<Id,
     where
         V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
     {
-        self.alignment_stack.align("neighboring");
-        let path = Path::new(self.alignment_stack.current_path());
-
-        // Collect neighboring values with improved error handling
-        let neighboring_values = self.get_at_path(&path)?;
-
-        let result = Field::new(value.clone(), neighboring_values);
-
-        // Serialize and append to outbound
-        let serialized_value = self.serializer.serialize(&value).map_err(|err| {
-            self.alignment_stack.unalign();
-            AggregateError::SerializationError(format!(
-                "Failed to serialize neighboring value: {err}"
-            ))
-        })?;
+        self.neighboring_owned(value.clone())
+    }
 
-        self.outbound.append(&path, serialized_value);
-        self.alignment_stack.unalign();
-        Ok(result)
+    fn neighboring_owned<V>(&mut self, value: V) -> Result<Field<Id, V>, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+    {
+        self.neighboring_from(value, Self::get_at_path)
     }
 
     fn repeat<V, F>(&mut self, initial: &V, evolution: F) -> V
@@ -197,16 +1446,7 @@ impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> Aggregate<Id> for VM<Id,
         V: Clone + 'static,
         F: FnOnce(V, &mut Self) -> V,
     {
-        self.alignment_stack.align("repeat");
-        let current_path = Path::new(self.alignment_stack.current_path());
-        let previous_state = self
-            .state
-            .get::<V>(&current_path)
-            .map_or_else(|| initial.clone(), Clone::clone);
-        let updated_state = evolution(previous_state, self);
-        self.state.insert(current_path, updated_state.clone());
-        self.alignment_stack.unalign();
-        updated_state
+        self.repeat_from(initial, evolution).0
     }
 
     fn branch<V, Th, El>(&mut self, condition: bool, th: Th, el: El) -> V
@@ -214,10 +1454,7 @@ impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> Aggregate<Id> for VM<Id,
         Th: FnOnce(&mut Self) -> V,
         El: FnOnce(&mut Self) -> V,
     {
-        self.alignment_stack.align(format!("branch[{condition}]"));
-        let result = if condition { th(self) } else { el(self) };
-        self.alignment_stack.unalign();
-        result
+        self.branch_from(condition, th, el).0
     }
 
     fn share<V, E>(&mut self, initial: &V, evolution: E) -> Result<V, AggregateError>
@@ -225,24 +1462,81 @@ impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> Aggregate<Id> for VM<Id,
         V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
         E: FnOnce(&mut Self, Field<Id, V>) -> V,
     {
-        self.alignment_stack.align("share");
-        let current_path = Path::new(self.alignment_stack.current_path());
-        let previous_state = self
-            .state
-            .get::<V>(&current_path)
-            .map_or_else(|| initial.clone(), Clone::clone);
-        let neighboring_values = self.get_at_path(&current_path)?;
-        let field = Field::new(previous_state, neighboring_values);
-        let updated_state = evolution(self, field);
-        self.state
-            .insert(current_path.clone(), updated_state.clone());
-        let serialized_value = self.serializer.serialize(&updated_state).map_err(|err| {
-            self.alignment_stack.unalign();
-            AggregateError::SerializationError(format!("Failed to serialize share value: {err}"))
-        })?;
-        self.outbound.append(&current_path, serialized_value);
-        self.alignment_stack.unalign();
-        Ok(updated_state)
+        self.share_from(|| initial.clone(), evolution, Self::get_at_path)
+    }
+
+    fn share_owned<V, E>(&mut self, initial: V, evolution: E) -> Result<V, AggregateError>
+    where
+        V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+        E: FnOnce(&mut Self, Field<Id, V>) -> V,
+    {
+        self.share_from(|| initial, evolution, Self::get_at_path)
+    }
+
+    fn align_on_value<K, V, B>(&mut self, key: &K, body: B) -> V
+    where
+        K: Display,
+        B: FnOnce(&mut Self) -> V,
+    {
+        Self::align_on_value(self, key, body)
+    }
+
+    fn aligned_devices(&self) -> BTreeSet<Id> {
+        self.inbound
+            .devices_under(self.alignment_stack.path())
+            .collect()
+    }
+
+    fn neighbor_count(&mut self) -> usize {
+        self.try_neighbor_count().unwrap_or(0)
+    }
+}
+
+/// Error of `operator` accessing the state with the type of `mismatch`.
+fn type_mismatch(mismatch: TypeMismatch, operator: &'static str) -> AggregateError {
+    AggregateError::TypeMismatch(TypeMismatch {
+        operator,
+        ..mismatch
+    })
+}
+
+/// Span of an operator invocation; `neighbors` and `bytes` are recorded once known.
+#[cfg(feature = "tracing")]
+fn operator_span(operator: &'static str, path: &Path) -> tracing::span::EnteredSpan {
+    tracing::debug_span!(
+        "operator",
+        operator,
+        %path,
+        neighbors = tracing::field::Empty,
+        bytes = tracing::field::Empty,
+    )
+    .entered()
+}
+
+impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> NeighborhoodSensors<Id> for VM<Id, S> {
+    fn nbr_range(&self) -> Field<Id, f64> {
+        self.neighborhood.to_field(0.0, |reading| reading.range)
+    }
+
+    fn nbr_lag(&self) -> Field<Id, Duration> {
+        self.neighborhood
+            .to_field(Duration::ZERO, |reading| reading.lag)
+    }
+
+    fn nbr_vector(&self) -> Field<Id, (f64, f64)> {
+        self.neighborhood
+            .to_field((0.0, 0.0), |reading| reading.vector)
+    }
+
+    fn nbr_link_quality(&self) -> Field<Id, LinkQuality> {
+        self.neighborhood
+            .to_field(LinkQuality::default(), |reading| reading.link)
+    }
+}
+
+impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> TimeSensor for VM<Id, S> {
+    fn current_time(&self) -> Duration {
+        self.round_time
     }
 }
 
@@ -250,6 +1544,7 @@ impl<Id: Ord + Hash + Copy + Serialize, S: Serializer> Aggregate<Id> for VM<Id,
 mod tests {
     use super::*;
     use crate::rufi::messages::valuetree::ValueTree;
+    use crate::rufi::test_utils::MockSerializer;
     #[cfg(not(feature = "std"))]
     use alloc::boxed::Box;
 
@@ -257,24 +1552,6 @@ mod tests {
     use alloc::collections::BTreeMap as Map;
     use core::any::Any;
 
-    // Mock serializer for testing
-    struct MockSerializer;
-
-    impl Serializer for MockSerializer {
-        type Error = serde_json::Error;
-
-        fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
-            serde_json::to_vec(value)
-        }
-
-        fn deserialize<T: for<'de> Deserialize<'de>>(
-            &self,
-            value: &[u8],
-        ) -> Result<T, Self::Error> {
-            serde_json::from_slice(value)
-        }
-    }
-
     #[test]
     fn test_vm_creation() {
         let vm = VM::new(42u32, MockSerializer);
@@ -289,6 +1566,21 @@ mod tests {
         assert_eq!(result, initial_value + 1);
     }
 
+    #[test]
+    fn cached_recomputes_only_when_inputs_change() {
+        let mut vm = VM::new(1u32, MockSerializer);
+        let mut executions = 0;
+        for (round, target) in [(0, 5u32), (1, 5), (2, 7)] {
+            vm.prepare_new_round(InboundMessage::default());
+            let value = vm.cached(&target, |_| {
+                executions += 1;
+                target * 10 + round
+            });
+            assert_eq!(value, if target == 5 { 50 } else { 72 });
+        }
+        assert_eq!(executions, 2);
+    }
+
     #[test]
     fn repeat_should_use_last_available_state() {
         let mut state_map: Map<Path, Box<dyn Any>> = Map::new();
@@ -351,6 +1643,344 @@ mod tests {
         assert_eq!(field, expected_field);
     }
 
+    #[test]
+    fn align_on_value_projects_field_on_devices_with_the_same_key() {
+        let serializer = MockSerializer;
+        let export = |cluster: u8| {
+            let path = Path::from(format!("align[{cluster}]:0/neighboring:0").as_str());
+            ValueTree::new(Map::from([(path, serializer.serialize(&cluster).unwrap())]))
+        };
+        let inbound = InboundMessage::new(Map::from([(1u32, export(3)), (2u32, export(5))]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(inbound);
+        let field = vm.align_on_value(&5u8, |vm| vm.neighboring(&5u8).unwrap());
+        assert_eq!(field, Field::new(5, Map::from([(2u32, 5u8)])));
+    }
+
+    #[test]
+    fn align_on_value_escapes_the_path_separator() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::default());
+        vm.align_on_value(&"a/b", |vm| vm.neighboring(&1u8).unwrap());
+        vm.align_on_value(&"a%2Fb", |vm| vm.neighboring(&2u8).unwrap());
+        assert_eq!(
+            vm.outbound().appended_paths().collect::<Vec<_>>(),
+            vec![
+                Path::new(vec!["align[a%2Fb]:0", "neighboring:0"]),
+                Path::new(vec!["align[a%252Fb]:1", "neighboring:0"]),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn keyed_operators_align_regardless_of_the_preceding_ones() {
+        use crate::rufi::test_utils::run_rounds;
+        let topology = Map::from([(0, vec![1]), (1, vec![0])]);
+        let results = run_rounds(&topology, 2, |id, vm| {
+            // a block executed only by odd devices shifts the operators following it
+            if id % 2 == 1 {
+                vm.neighboring(&0u8).unwrap();
+            }
+            let neighbor = |field: Field<u32, u32>| {
+                field
+                    .excluding_self()
+                    .map(|(_, value)| *value)
+                    .collect::<Vec<_>>()
+            };
+            let unkeyed = neighbor(vm.neighboring(&id).unwrap());
+            let keyed = neighbor(vm.neighboring_keyed("block", &id).unwrap());
+            let branched = vm.branch_keyed("block", true, Aggregate::neighbor_count, |_| 0);
+            (unkeyed, keyed, branched)
+        });
+        // device 0 reads the export of the other block, device 1 misses the one of device 0
+        assert_eq!(results.get(&0), Some(&(vec![0], vec![1], 1)));
+        assert_eq!(results.get(&1), Some(&(vec![], vec![0], 1)));
+    }
+
+    #[test]
+    fn aligned_devices_follow_the_alignment_path() {
+        fn cardinality<A: Aggregate<u32>>(aggregate: &A) -> usize {
+            aggregate.aligned_devices().len()
+        }
+        let export = |path: &str| ValueTree::new(Map::from([(Path::from(path), b"0".to_vec())]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        assert_eq!(cardinality(&vm), 0);
+        vm.prepare_new_round(InboundMessage::new(Map::from([
+            (1u32, export("share:0")),
+            (2u32, export("neighboring:0")),
+        ])));
+        assert_eq!(cardinality(&vm), 2);
+        let in_share = vm
+            .share(&0u32, |vm, _| u32::try_from(cardinality(vm)).unwrap())
+            .unwrap();
+        assert_eq!(in_share, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn neighbor_count_only_counts_aligned_neighbors() {
+        use crate::rufi::test_utils::run_rounds;
+        let topology = Map::from([(0, vec![1, 2]), (1, vec![]), (2, vec![]), (3, vec![])]);
+        let results = run_rounds(&topology, 2, |id, vm| {
+            let all = vm.neighbor_count();
+            let same_parity = vm.branch(
+                id.is_multiple_of(2),
+                Aggregate::neighbor_count,
+                Aggregate::neighbor_count,
+            );
+            (all, same_parity, vm.is_isolated())
+        });
+        assert_eq!(results.get(&0), Some(&(2, 1, false)));
+        assert_eq!(results.get(&1), Some(&(1, 0, false)));
+        assert_eq!(results.get(&3), Some(&(0, 0, true)));
+    }
+
+    #[test]
+    fn fields_captured_outside_a_branch_can_be_restricted_to_aligned_devices() {
+        let serializer = MockSerializer;
+        let export = |branch: &str| {
+            ValueTree::new(Map::from([
+                (
+                    Path::from("neighboring:0"),
+                    serializer.serialize(&1u32).unwrap(),
+                ),
+                (Path::from(branch), serializer.serialize(&1u32).unwrap()),
+            ]))
+        };
+        let inbound = InboundMessage::new(Map::from([
+            (1u32, export("branch[true]:1/neighboring:0")),
+            (2u32, export("branch[false]:1/neighboring:0")),
+        ]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(inbound);
+        assert_eq!(vm.aligned_devices(), BTreeSet::from([1, 2]));
+        let outside = vm.neighboring(&0u32).unwrap();
+        let projected = vm.branch(
+            true,
+            |vm| outside.restricted_to(&vm.aligned_devices()),
+            |_| Field::new(0, Map::new()),
+        );
+        assert_eq!(projected, Field::new(0, Map::from([(1, 1)])));
+    }
+
+    #[test]
+    fn alignment_report_lists_unread_and_unmatched_paths() {
+        let serializer = MockSerializer;
+        let neighbor = ValueTree::new(Map::from([
+            (
+                Path::from("neighboring:0"),
+                serializer.serialize(&1u32).unwrap(),
+            ),
+            (
+                Path::from("branch[true]:1/neighboring:0"),
+                serializer.serialize(&1u32).unwrap(),
+            ),
+        ]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::new(Map::from([(1u32, neighbor)])));
+        assert_eq!(vm.alignment_report(), None);
+
+        vm.set_alignment_diagnostics(true);
+        vm.neighboring(&0u32).unwrap();
+        vm.branch(
+            false,
+            |vm| vm.neighboring(&0u32).unwrap(),
+            |vm| vm.neighboring(&0u32).unwrap(),
+        );
+        let report = vm.alignment_report().unwrap();
+        assert!(!report.is_aligned());
+        assert_eq!(
+            report.unread,
+            vec![(1, Path::from("branch[true]:1/neighboring:0"))]
+        );
+        assert_eq!(
+            report.unmatched,
+            vec![Path::from("branch[false]:1/neighboring:0")]
+        );
+    }
+
+    #[test]
+    fn metadata_headers_are_exposed_as_fields() {
+        let mut sender = VM::new(1u32, MockSerializer);
+        sender.set_metadata(true);
+        sender.set_position(Some((3.0, 4.0)));
+        for _ in 0..2 {
+            sender.prepare_new_round(InboundMessage::default());
+        }
+        let message: OutboundMessage<u32> = MockSerializer
+            .deserialize(&sender.get_outbound().unwrap())
+            .unwrap();
+        let anonymous = ValueTree::empty();
+
+        let mut receiver = VM::new(0u32, MockSerializer);
+        receiver.prepare_new_round(InboundMessage::new(Map::from([
+            (1, ValueTree::from(message)),
+            (2, anonymous),
+        ])));
+        assert_eq!(receiver.nbr_sequence(), Field::new(1, Map::from([(1, 2)])));
+        assert_eq!(
+            receiver.nbr_position(),
+            Field::new(None, Map::from([(1, Some((3.0, 4.0)))]))
+        );
+        assert_eq!(
+            receiver.nbr_timestamp().get(&1),
+            Some(&Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn relayed_messages_report_their_hops() {
+        let mut sender = VM::new(1u32, MockSerializer);
+        sender.prepare_new_round(InboundMessage::default());
+        sender.neighboring_with_hop_limit(&7u8, 2).unwrap();
+        let mut relayed = sender.outbound().clone();
+        assert!(relayed.relay());
+
+        let mut receiver = VM::new(0u32, MockSerializer);
+        receiver.prepare_new_round(InboundMessage::new(Map::from([
+            (1, ValueTree::from(relayed)),
+            (2, ValueTree::empty()),
+        ])));
+        assert_eq!(
+            receiver.nbr_hops(),
+            Field::new(0, Map::from([(1, 2), (2, 1)]))
+        );
+        assert_eq!(receiver.neighboring(&0u8).unwrap().get(&1), Some(&7));
+    }
+
+    #[test]
+    fn asymmetric_links_are_ignored() {
+        let mut vms: Vec<_> = (0..3u32)
+            .map(|id| {
+                let mut vm = VM::new(id, MockSerializer);
+                vm.set_symmetric_links(true);
+                vm
+            })
+            .collect();
+        // 0 and 1 hear each other, 2 hears 0 but 0 does not hear 2
+        let links = [(0, 1), (1, 0), (2, 0)];
+        let mut exports: Map<u32, ValueTree> = Map::new();
+        for _ in 0..3 {
+            for vm in &mut vms {
+                let id = vm.local_id;
+                let inbound = links
+                    .iter()
+                    .filter(|(receiver, _)| *receiver == id)
+                    .filter_map(|(_, sender)| Some((*sender, exports.get(sender)?.clone())))
+                    .collect();
+                vm.prepare_new_round(InboundMessage::new(inbound));
+                vm.neighboring(&id).unwrap();
+            }
+            exports = vms
+                .iter()
+                .map(|vm| (vm.local_id, ValueTree::from(vm.outbound().clone())))
+                .collect();
+        }
+        let neighbors = |vm: &VM<u32, MockSerializer>| vm.inbound.neighbors().collect::<Vec<_>>();
+        assert_eq!(vms.first().map(neighbors), Some(vec![1]));
+        assert_eq!(vms.get(1).map(neighbors), Some(vec![0]));
+        assert_eq!(vms.get(2).map(neighbors), Some(vec![]));
+    }
+
+    #[test]
+    fn type_registry_reports_drift_as_round_error() {
+        let mut vm = VM::new(1u32, MockSerializer);
+        vm.set_type_registry(true);
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(vm.repeat(&1u32, |count, _| count + 1), 2);
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(vm.repeat(&1i64, |count, _| count + 1), 2);
+        let Some(AggregateError::TypeMismatch(mismatch)) = vm.round_error() else {
+            panic!("expected a type mismatch, got {:?}", vm.round_error());
+        };
+        assert_eq!((mismatch.expected, mismatch.found), ("u32", "i64"));
+        assert_eq!(mismatch.path, Path::from("repeat:0"));
+        // the operator following the failed one is still aligned
+        assert_eq!(vm.repeat(&0u8, |count, _| count + 1), 1);
+    }
+
+    #[test]
+    fn recursion_beyond_the_alignment_limits_fails_the_round() {
+        fn nest(vm: &mut VM<u32, MockSerializer>, depth: u32) -> Result<u32, AggregateError> {
+            vm.neighboring(&depth)?;
+            vm.branch(true, |vm| nest(vm, depth + 1), |_| Ok(depth))
+        }
+        let mut vm = VM::new(1u32, MockSerializer);
+        vm.set_alignment_limits(AlignmentLimits::default().with_max_depth(5));
+        vm.prepare_new_round(InboundMessage::default());
+        let exceeded = AggregateError::AlignmentLimitExceeded(AlignmentLimit::Depth(5));
+        assert_eq!(nest(&mut vm, 0), Err(exceeded.clone()));
+        assert_eq!(vm.round_error(), Some(&exceeded));
+        assert_eq!(vm.exported_paths().count(), 5);
+        assert_eq!(vm.alignment_stack.path(), &Path::root());
+
+        vm.set_alignment_limits(AlignmentLimits::default().with_max_paths(3));
+        vm.prepare_new_round(InboundMessage::default());
+        for value in 0..3u8 {
+            assert!(vm.neighboring(&value).is_ok());
+        }
+        assert_eq!(
+            vm.neighboring(&3u8).map(|field| *field.local()),
+            Err(AggregateError::AlignmentLimitExceeded(
+                AlignmentLimit::Paths(3)
+            ))
+        );
+        assert_eq!(vm.neighbor_count(), 0);
+        assert_eq!(vm.exported_paths().count(), 3);
+    }
+
+    #[test]
+    fn drift_is_reported_without_the_registry() {
+        let mut vm = VM::new(1u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(vm.share(&1u32, |_, field| field.local() + 1), Ok(2));
+        vm.prepare_new_round(InboundMessage::default());
+        let Err(AggregateError::TypeMismatch(mismatch)) =
+            vm.share(&1i64, |_, field| field.local() + 1)
+        else {
+            panic!("expected a type mismatch, got {:?}", vm.round_error());
+        };
+        assert_eq!((mismatch.found, mismatch.operator), ("i64", "share"));
+        assert_eq!(vm.repeat(&0u8, |count, _| count + 1), 1);
+    }
+
+    #[test]
+    fn fallible_operators_return_their_errors() {
+        let mut vm = VM::new(1u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(vm.try_repeat(&1u32, |count, _| count + 1), Ok(2));
+        assert_eq!(vm.try_cached(&1u8, |_| 10u32), Ok(10));
+        vm.prepare_new_round(InboundMessage::default());
+        let Err(AggregateError::TypeMismatch(mismatch)) =
+            vm.try_repeat(&1i64, |count, _| count + 1)
+        else {
+            panic!("expected a type mismatch, got {:?}", vm.round_error());
+        };
+        assert_eq!((mismatch.found, mismatch.operator), ("i64", "repeat"));
+        let Err(AggregateError::TypeMismatch(memoized)) = vm.try_cached(&1u8, |_| 10i64) else {
+            panic!("expected a type mismatch, got {:?}", vm.round_error());
+        };
+        assert_eq!(memoized.operator, "cached");
+
+        vm.set_alignment_limits(AlignmentLimits::default().with_max_depth(0));
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(
+            vm.try_branch(true, |_| 1, |_| 2),
+            Err(AggregateError::AlignmentLimitExceeded(
+                AlignmentLimit::Depth(0)
+            ))
+        );
+        assert_eq!(vm.branch(true, |_| 1, |_| 2), 1);
+        assert_eq!(
+            vm.try_neighbor_count(),
+            Err(AggregateError::AlignmentLimitExceeded(
+                AlignmentLimit::Depth(0)
+            ))
+        );
+        assert_eq!(vm.neighbor_count(), 0);
+    }
+
     #[test]
     fn share_should_use_initial_value_when_no_previous_state() {
         let serializer = MockSerializer;
@@ -394,4 +2024,270 @@ mod tests {
         let next_result = program(&mut vm).unwrap();
         assert_eq!(next_result, 5);
     }
+
+    #[test]
+    fn share_inspect_returns_the_neighbor_field() {
+        let serializer = MockSerializer;
+        let path = Path::from("share:0");
+        let device_1 = ValueTree::new(Map::from([(path, serializer.serialize(&10i32).unwrap())]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::new(Map::from([(1u32, device_1)])));
+        let (value, field) = vm
+            .share_inspect(&1i32, |_, field| field.local() + field.get(&1).unwrap())
+            .unwrap();
+        assert_eq!(value, 11);
+        assert_eq!(field.local(), &1);
+        assert_eq!(field.get(&1), Some(&10));
+        assert!(vm.outbound().at(&Path::from("share:0")).is_some());
+    }
+
+    #[test]
+    fn exports_can_be_inspected_by_path() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.repeat(&0u8, |count, vm| {
+            vm.neighboring(&7i32).unwrap();
+            count
+        });
+        vm.share(&1.5f64, |_, field| *field.local()).unwrap();
+        assert_eq!(
+            vm.exported_paths().collect::<Vec<_>>(),
+            [Path::from("repeat:0/neighboring:0"), Path::from("share:1")]
+        );
+        assert_eq!(
+            vm.outbound_at::<i32>(&Path::from("repeat:0/neighboring:0")),
+            Ok(Some(7))
+        );
+        assert_eq!(vm.outbound_at::<f64>(&Path::from("share:1")), Ok(Some(1.5)));
+        assert_eq!(vm.outbound_at::<i32>(&Path::from("share:0")), Ok(None));
+        assert!(vm.outbound_at::<String>(&Path::from("share:1")).is_err());
+    }
+
+    #[test]
+    fn new_rounds_start_from_empty_exports() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.neighboring(&1u8).unwrap();
+        vm.neighboring(&2u8).unwrap();
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(vm.exported_paths().count(), 0);
+        vm.neighboring(&3u8).unwrap();
+        assert_eq!(
+            vm.exported_paths().collect::<Vec<_>>(),
+            [Path::from("neighboring:0")]
+        );
+        assert_eq!(vm.outbound_at::<u8>(&Path::from("neighboring:1")), Ok(None));
+    }
+
+    #[test]
+    fn owned_operators_do_not_clone_the_local_value() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Counted(Vec<f64>);
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Self(self.0.clone())
+            }
+        }
+        fn program(vm: &mut VM<u32, MockSerializer>) -> Result<usize, AggregateError> {
+            let field = vm.neighboring_owned(Counted(vec![1.0; 4]))?;
+            let shared = vm.share_owned(Counted(Vec::new()), |_, state| {
+                let Counted(mut values) = state.into_local();
+                values.push(0.0);
+                Counted(values)
+            })?;
+            Ok(field.local().0.len().saturating_add(shared.0.len()))
+        }
+        let mut vm = VM::new(0u32, MockSerializer);
+        assert_eq!(program(&mut vm).unwrap(), 5);
+        vm.prepare_new_round(InboundMessage::default());
+        assert_eq!(program(&mut vm).unwrap(), 6);
+        // only the updated share state is cloned to be kept for the next round
+        assert_eq!(CLONES.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn incremental_computations_rerun_only_when_dependencies_change() {
+        use core::cell::Cell;
+        let executions = Cell::new(0u32);
+        let program = |vm: &mut VM<u32, MockSerializer>, input: i32| {
+            vm.incremental(&input, |vm| {
+                executions.set(executions.get().saturating_add(1));
+                let field = vm.neighboring(&input).unwrap();
+                field.fold_neighbors(input, |sum, value| sum.saturating_add(*value))
+            })
+            .unwrap()
+        };
+        let path = Path::from("incremental:0/neighboring:0");
+        let neighbor = |value: i32| {
+            let tree = ValueTree::new(Map::from([(
+                path.clone(),
+                MockSerializer.serialize(&value).unwrap(),
+            )]));
+            InboundMessage::new(Map::from([(1u32, tree)]))
+        };
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.set_incremental(true);
+        vm.prepare_new_round(neighbor(10));
+        assert_eq!(program(&mut vm, 1), 11);
+        vm.prepare_new_round(neighbor(10));
+        assert_eq!(program(&mut vm, 1), 11);
+        assert_eq!(executions.get(), 1);
+        // skipped computations still export their values
+        assert_eq!(vm.outbound_at::<i32>(&path), Ok(Some(1)));
+
+        vm.prepare_new_round(neighbor(20));
+        assert_eq!(program(&mut vm, 1), 21);
+        vm.prepare_new_round(neighbor(20));
+        assert_eq!(program(&mut vm, 2), 22);
+        assert_eq!(executions.get(), 3);
+
+        vm.set_incremental(false);
+        vm.prepare_new_round(neighbor(20));
+        assert_eq!(program(&mut vm, 2), 22);
+        assert_eq!(executions.get(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_decoding_matches_sequential_decoding() {
+        let inbound = || {
+            InboundMessage::new(
+                (1..=200u32)
+                    .map(|id| {
+                        let shared = MockSerializer.serialize(&id).unwrap();
+                        let tree = ValueTree::new(Map::from([(Path::from("share:0"), shared)]));
+                        (id, tree)
+                    })
+                    .collect(),
+            )
+        };
+        let program = |vm: &mut VM<u32, MockSerializer>, parallel: bool| {
+            let evolution = |_: &mut VM<u32, MockSerializer>, field: Field<u32, u32>| {
+                field.fold_neighbors(0u32, |sum, value| sum.saturating_add(*value))
+            };
+            if parallel {
+                vm.par_share(&0, evolution).unwrap()
+            } else {
+                vm.share(&0, evolution).unwrap()
+            }
+        };
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(inbound());
+        let sequential = program(&mut vm, false);
+        vm.prepare_new_round(inbound());
+        assert_eq!(program(&mut vm, true), sequential);
+        assert_eq!(sequential, 20_100);
+    }
+
+    #[test]
+    fn neighborhood_sensors_expose_readings() {
+        use crate::rufi::sensors::neighborhood::NeighborReading;
+        let mut vm = VM::new(0u32, MockSerializer);
+        let reading = NeighborReading {
+            range: Some(1.5),
+            lag: Some(Duration::from_millis(10)),
+            vector: None,
+            link: Some(LinkQuality {
+                rssi: Some(-70),
+                ..LinkQuality::default()
+            }),
+        };
+        vm.update_neighborhood(NeighborhoodReadings::new(Map::from([(1u32, reading)])));
+        assert_eq!(vm.nbr_range(), Field::new(0.0, Map::from([(1u32, 1.5)])));
+        assert_eq!(
+            vm.nbr_lag(),
+            Field::new(
+                Duration::ZERO,
+                Map::from([(1u32, Duration::from_millis(10))])
+            )
+        );
+        assert_eq!(vm.nbr_vector(), Field::new((0.0, 0.0), Map::new()));
+        let rssi = vm.nbr_link_quality().map(|quality| quality.rssi);
+        assert_eq!(rssi, Field::new(None, Map::from([(1u32, Some(-70))])));
+    }
+
+    #[test]
+    fn neighboring_should_use_registered_codec() {
+        use crate::rufi::messages::codec::FixedPoint;
+        let serializer = MockSerializer;
+        let path = Path::from("neighboring:0");
+        let codec = FixedPoint::new(100.0);
+        let neighbor_value = ValueCodec::<f64>::encode(&codec, &2.5);
+        let device_1 = ValueTree::new(Map::from([(path.clone(), neighbor_value)]));
+        let inbound = InboundMessage::new(Map::from([(1u32, device_1)]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.register_codec::<f64, _>(Path::from("neighboring:0"), codec);
+        vm.prepare_new_round(inbound);
+        let field = vm.neighboring(&1.234f64).unwrap();
+        assert_eq!(field, Field::new(1.234, Map::from([(1u32, 2.5)])));
+        let to_send = serializer
+            .deserialize::<OutboundMessage<u32>>(vm.get_outbound().unwrap().as_slice())
+            .unwrap();
+        assert_eq!(to_send.at(&path).map(Vec::len), Some(4));
+    }
+
+    #[test]
+    fn neighbor_values_are_decoded_once_per_round() {
+        use core::cell::Cell;
+        use std::rc::Rc;
+
+        struct CountingCodec(Rc<Cell<usize>>);
+        impl ValueCodec<u8> for CountingCodec {
+            fn encode(&self, value: &u8) -> Vec<u8> {
+                vec![*value]
+            }
+
+            fn decode(&self, bytes: &[u8]) -> Option<u8> {
+                self.0.set(self.0.get() + 1);
+                bytes.first().copied()
+            }
+        }
+
+        let decodes = Rc::new(Cell::new(0));
+        let path = Path::from("neighboring:0");
+        let export = || ValueTree::new(Map::from([(path.clone(), vec![7])]));
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.register_codec::<u8, _>(path.clone(), CountingCodec(Rc::clone(&decodes)));
+        for _ in 0..2 {
+            vm.prepare_new_round(InboundMessage::new(Map::from([(1u32, export())])));
+            let first = vm.neighboring(&1u8).unwrap();
+            // re-evaluate the same subcomputation, reading the same path again
+            vm.alignment_stack = AlignmentStack::new();
+            let second = vm.neighboring(&1u8).unwrap();
+            assert_eq!(first, second);
+            assert_eq!(first, Field::new(1, Map::from([(1u32, 7)])));
+        }
+        assert_eq!(decodes.get(), 2);
+    }
+
+    #[test]
+    fn low_priority_exports_are_dropped_first() {
+        use crate::rufi::messages::budget::OverflowPolicy;
+
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::default());
+        vm.neighboring_with_priority(&1u8, Priority::Low).unwrap();
+        vm.neighboring(&2u8).unwrap();
+        let full = vm.get_outbound().unwrap().len();
+        assert!(vm.dropped_paths().is_empty());
+
+        vm.set_message_budget(MessageBudget::new(
+            full - 1,
+            OverflowPolicy::DropLowestPriority,
+        ));
+        let bytes = vm.get_outbound().unwrap();
+        let decoded = OutboundMessage::<u32>::decode(&MockSerializer, &bytes).unwrap();
+        let low = Path::from("neighboring:0");
+        assert!(decoded.at(&low).is_none());
+        assert!(decoded.at(&Path::from("neighboring:1")).is_some());
+        assert_eq!(vm.dropped_paths(), [low]);
+
+        // priorities only apply to the round in which they are assigned
+        vm.prepare_new_round(InboundMessage::default());
+        vm.neighboring(&1u8).unwrap();
+        vm.neighboring(&2u8).unwrap();
+        vm.get_outbound().unwrap();
+        assert_eq!(vm.dropped_paths(), [Path::from("neighboring:1")]);
+    }
 }
@@ -3,10 +3,10 @@ use crate::rufi::messages::path::Path;
 use alloc::collections::BTreeMap as Map;
 
 #[cfg(not(feature = "std"))]
-use alloc::collections::VecDeque;
+use alloc::vec;
 
 #[cfg(not(feature = "std"))]
-use alloc::string::String;
+use alloc::string::{String, ToString};
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
@@ -14,8 +14,8 @@ use alloc::vec::Vec;
 use core::fmt::Display;
 use core::fmt::Formatter;
 use core::num::Saturating;
+#[cfg(feature = "std")]
 use std::collections::HashMap as Map;
-use std::collections::VecDeque;
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct InvocationCoordinate {
@@ -36,35 +36,261 @@ impl Display for InvocationCoordinate {
     }
 }
 
+/// Handle of a path interned by a [`PathInterner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct PathId(usize);
+
+/// A path met while aligning, with the coordinate extending its parent.
+struct Interned {
+    coordinate: InvocationCoordinate,
+    path: Path,
+}
+
+/// Table of the alignment paths met so far.
+///
+/// Extending a path with a coordinate already met costs a lookup, instead of formatting its
+/// tokens and allocating a new [`Path`] on every operator invocation. Programs invoke the same
+/// operators round after round, so the table is kept across rounds.
+pub(crate) struct PathInterner {
+    paths: Vec<Interned>,
+    /// Children of every path by counter, and of the root under `None`.
+    children: Map<(Option<PathId>, u32), Vec<PathId>>,
+}
+impl PathInterner {
+    /// Paths kept before the table is cleared, bounding its memory when programs align on
+    /// ever-changing tokens.
+    const CAPACITY: usize = 4096;
+
+    // `BTreeMap::new` is const, `HashMap::new` is not
+    #[cfg_attr(not(feature = "std"), allow(clippy::missing_const_for_fn))]
+    fn new() -> Self {
+        Self {
+            paths: Vec::new(),
+            children: Map::new(),
+        }
+    }
+
+    fn get(&self, id: PathId) -> Option<&Interned> {
+        self.paths.get(id.0)
+    }
+
+    /// Handle of `parent` extended by the coordinate `token:counter`, interning it if needed.
+    fn child(&mut self, parent: Option<PathId>, counter: u32, token: &str) -> PathId {
+        let siblings = self.children.entry((parent, counter)).or_default();
+        let known = siblings.iter().copied().find(|id| {
+            self.paths
+                .get(id.0)
+                .is_some_and(|interned| interned.coordinate.token == token)
+        });
+        if let Some(id) = known {
+            return id;
+        }
+        let coordinate = InvocationCoordinate::new(counter, token);
+        let path = parent
+            .and_then(|parent| self.paths.get(parent.0))
+            .map_or_else(
+                || Path::new(vec![coordinate.to_string()]),
+                |parent| parent.path.child(&coordinate),
+            );
+        let id = PathId(self.paths.len());
+        self.paths.push(Interned { coordinate, path });
+        siblings.push(id);
+        id
+    }
+
+    const fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    fn clear(&mut self) {
+        self.paths.clear();
+        self.children.clear();
+    }
+}
+
+/// Bounds on the alignment of a round.
+///
+/// Recursive or deeply nested programs fail with
+/// [`AggregateError::AlignmentLimitExceeded`](crate::rufi::aggregate::AggregateError::AlignmentLimitExceeded)
+/// instead of growing the alignment stack and the trace without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentLimits {
+    max_depth: usize,
+    max_paths: usize,
+}
+impl AlignmentLimits {
+    /// No limit at all, the behavior of releases without limits.
+    pub const UNLIMITED: Self = Self {
+        max_depth: usize::MAX,
+        max_paths: usize::MAX,
+    };
+
+    /// Maximum number of nested operator invocations, 512 by default.
+    #[must_use]
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Maximum number of operator invocations in a round, i.e. of distinct paths, 2^20 by
+    /// default.
+    #[must_use]
+    pub const fn with_max_paths(mut self, max_paths: usize) -> Self {
+        self.max_paths = max_paths;
+        self
+    }
+
+    pub const fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub const fn max_paths(&self) -> usize {
+        self.max_paths
+    }
+}
+impl Default for AlignmentLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 512,
+            max_paths: 1 << 20,
+        }
+    }
+}
+
+/// Limit of [`AlignmentLimits`] exceeded by an operator invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentLimit {
+    /// More nested invocations than the given maximum depth.
+    Depth(usize),
+    /// More invocations in the round than the given maximum number of paths.
+    Paths(usize),
+}
+impl Display for AlignmentLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Depth(max) => write!(f, "more than {max} nested operators"),
+            Self::Paths(max) => write!(f, "more than {max} operators in a round"),
+        }
+    }
+}
+
 pub(crate) struct AlignmentStack {
-    stack: VecDeque<InvocationCoordinate>,
-    trace: Map<Path, Saturating<u32>>,
+    stack: Vec<PathId>,
+    /// The root path, borrowed by [`AlignmentStack::path`] outside of any invocation.
+    root: Path,
+    trace: Map<Option<PathId>, Saturating<u32>>,
+    interner: PathInterner,
+    limits: AlignmentLimits,
+    /// Paths aligned in the current round.
+    paths: usize,
+    /// Invocations entered beyond the limits, on top of the stack, which they leave unchanged.
+    overflow: usize,
 }
 impl AlignmentStack {
     pub(crate) fn new() -> Self {
         Self {
-            stack: VecDeque::new(),
+            stack: Vec::new(),
+            root: Path::root(),
             trace: Map::new(),
+            interner: PathInterner::new(),
+            limits: AlignmentLimits::default(),
+            paths: 0,
+            overflow: 0,
+        }
+    }
+
+    pub(crate) const fn set_limits(&mut self, limits: AlignmentLimits) {
+        self.limits = limits;
+    }
+
+    /// Start a new round, keeping the interned paths.
+    pub(crate) fn reset(&mut self) {
+        self.stack.clear();
+        self.trace.clear();
+        self.paths = 0;
+        self.overflow = 0;
+        if self.interner.len() > PathInterner::CAPACITY {
+            self.interner.clear();
         }
     }
 
+    /// Coordinates of the current path, from the outermost.
+    #[cfg(test)]
     pub(crate) fn current_path(&self) -> Vec<InvocationCoordinate> {
-        self.stack.iter().cloned().collect()
+        self.stack
+            .iter()
+            .filter_map(|id| self.interner.get(*id))
+            .map(|interned| interned.coordinate.clone())
+            .collect()
     }
 
-    pub(crate) fn align(&mut self, token: impl Into<String>) {
-        let current_path = Path::new(self.stack.iter().cloned().collect());
+    /// The current path, borrowed from the interned ones.
+    pub(crate) fn path(&self) -> &Path {
+        self.path_id()
+            .and_then(|id| self.interner.get(id))
+            .map_or(&self.root, |interned| &interned.path)
+    }
+
+    /// Handle of the current path, `None` for the root; valid until the next
+    /// [`AlignmentStack::reset`].
+    pub(crate) fn path_id(&self) -> Option<PathId> {
+        self.stack.last().copied()
+    }
+
+    /// Enter the next invocation of `token` at the current path.
+    ///
+    /// # Errors
+    /// Returns the exceeded limit, if any: the invocation is entered without changing the
+    /// current path, and must be left with [`AlignmentStack::unalign`] as usual
+    pub(crate) fn align(&mut self, token: &str) -> Result<(), AlignmentLimit> {
+        self.check_limits()?;
+        let parent = self.stack.last().copied();
         let current_counter = self
             .trace
-            .get(&current_path)
+            .get(&parent)
             .map_or(Saturating(0), |counter| counter + Saturating(1));
-        let invocation_coordinate = InvocationCoordinate::new(current_counter.0, token.into());
-        self.stack.push_back(invocation_coordinate);
-        self.trace.insert(current_path, current_counter);
+        self.trace.insert(parent, current_counter);
+        let id = self.interner.child(parent, current_counter.0, token);
+        self.stack.push(id);
+        Ok(())
+    }
+
+    /// Push a coordinate that does not depend on the operators previously invoked at the current
+    /// path, so that the namespace is aligned across devices regardless of what precedes it.
+    ///
+    /// # Errors
+    /// See [`AlignmentStack::align`]
+    pub(crate) fn align_namespace(&mut self, token: &str) -> Result<(), AlignmentLimit> {
+        self.check_limits()?;
+        let parent = self.stack.last().copied();
+        let id = self.interner.child(parent, 0, token);
+        self.stack.push(id);
+        Ok(())
+    }
+
+    /// Count a new invocation against the limits, entering it as an overflow if exceeded.
+    const fn check_limits(&mut self) -> Result<(), AlignmentLimit> {
+        let exceeded = if self.paths >= self.limits.max_paths {
+            Some(AlignmentLimit::Paths(self.limits.max_paths))
+        } else if self.overflow > 0 || self.stack.len() >= self.limits.max_depth {
+            Some(AlignmentLimit::Depth(self.limits.max_depth))
+        } else {
+            None
+        };
+        if let Some(limit) = exceeded {
+            self.overflow = self.overflow.saturating_add(1);
+            return Err(limit);
+        }
+        self.paths = self.paths.saturating_add(1);
+        Ok(())
     }
 
     pub(crate) fn unalign(&mut self) {
-        self.stack.pop_back();
+        if self.overflow > 0 {
+            self.overflow = self.overflow.saturating_sub(1);
+        } else {
+            self.stack.pop();
+        }
     }
 }
 
@@ -84,7 +310,7 @@ mod tests {
     #[test]
     fn alignment_stack_simple() {
         let mut stack = super::AlignmentStack::new();
-        stack.align("test");
+        stack.align("test").unwrap();
         assert_eq!(stack.current_path().len(), 1);
         let expected = InvocationCoordinate::new(0, "test");
         assert_eq!(stack.current_path().first(), Some(&expected));
@@ -95,8 +321,8 @@ mod tests {
     #[test]
     fn alignment_stack_nested() {
         let mut stack = super::AlignmentStack::new();
-        stack.align("outer");
-        stack.align("inner");
+        stack.align("outer").unwrap();
+        stack.align("inner").unwrap();
         assert_eq!(stack.current_path().len(), 2);
         let expected_outer = InvocationCoordinate::new(0, "outer");
         let expected_inner = InvocationCoordinate::new(0, "inner");
@@ -110,13 +336,74 @@ mod tests {
     #[test]
     fn alignment_stack_same_token() {
         let mut stack = super::AlignmentStack::new();
-        stack.align("test");
+        stack.align("test").unwrap();
         let expected = InvocationCoordinate::new(0, "test");
         assert_eq!(stack.current_path().first(), Some(&expected));
         stack.unalign();
-        stack.align("test");
+        stack.align("test").unwrap();
         let expected_1 = InvocationCoordinate::new(1, "test");
         assert_eq!(stack.current_path().first(), Some(&expected_1));
         stack.unalign();
     }
+
+    #[test]
+    fn alignment_stack_namespace_ignores_previous_invocations() {
+        let mut stack = super::AlignmentStack::new();
+        stack.align("test").unwrap();
+        stack.unalign();
+        stack.align_namespace("program").unwrap();
+        let expected = InvocationCoordinate::new(0, "program");
+        assert_eq!(stack.current_path().first(), Some(&expected));
+        stack.align("test").unwrap();
+        let expected_inner = InvocationCoordinate::new(0, "test");
+        assert_eq!(stack.current_path().get(1), Some(&expected_inner));
+    }
+
+    #[test]
+    fn paths_are_interned_across_rounds() {
+        use crate::rufi::messages::path::Path;
+        let mut stack = super::AlignmentStack::new();
+        stack.align("outer").unwrap();
+        stack.align("inner").unwrap();
+        assert_eq!(stack.path(), &Path::from("outer:0/inner:0"));
+        stack.unalign();
+        stack.align("inner").unwrap();
+        assert_eq!(stack.path(), &Path::from("outer:0/inner:1"));
+        let interned = stack.interner.len();
+        stack.reset();
+        assert_eq!(stack.path(), &Path::root());
+        stack.align("outer").unwrap();
+        stack.align("inner").unwrap();
+        assert_eq!(stack.path(), &Path::from("outer:0/inner:0"));
+        assert_eq!(stack.interner.len(), interned);
+    }
+
+    #[test]
+    fn invocations_beyond_the_limits_leave_the_path_unchanged() {
+        use super::{AlignmentLimit, AlignmentLimits};
+        use crate::rufi::messages::path::Path;
+        let mut stack = super::AlignmentStack::new();
+        stack.set_limits(
+            AlignmentLimits::UNLIMITED
+                .with_max_depth(1)
+                .with_max_paths(3),
+        );
+        stack.align("outer").unwrap();
+        assert_eq!(stack.align("inner"), Err(AlignmentLimit::Depth(1)));
+        assert_eq!(stack.align("deeper"), Err(AlignmentLimit::Depth(1)));
+        assert_eq!(stack.path(), &Path::from("outer:0"));
+        stack.unalign();
+        stack.unalign();
+        assert_eq!(stack.path(), &Path::from("outer:0"));
+        stack.unalign();
+        for token in ["second", "third"] {
+            stack.align(token).unwrap();
+            stack.unalign();
+        }
+        assert_eq!(stack.align("fourth"), Err(AlignmentLimit::Paths(3)));
+        stack.unalign();
+        assert_eq!(stack.path(), &Path::root());
+        stack.reset();
+        stack.align("outer").unwrap();
+    }
 }
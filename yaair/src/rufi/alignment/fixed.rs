@@ -0,0 +1,204 @@
+//! Alignment stack with capacities fixed at compile time, enabled by the `no-alloc` feature.
+//!
+//! Like [`fixed`](crate::rufi::messages::fixed), it is a building block for firmware keeping
+//! its alignment in static memory: the [`VM`](crate::rufi::aggregate::VM) does not use it and
+//! still requires `alloc`.
+
+use crate::rufi::messages::fixed::{CapacityError, PathKey};
+use heapless::index_map::FnvIndexMap;
+use heapless::Vec;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Key and check digest of a path, see [`FixedAlignmentStack::push`].
+type Digests = (PathKey, u64);
+
+/// Allocation-free counterpart of the alignment stack of the VM, holding up to `DEPTH` nested
+/// invocations and up to `TRACE` distinct paths per round.
+///
+/// Paths are identified by their [`PathKey`], an FNV-1a digest of the `token:counter`
+/// coordinates of the stack; `TRACE` must be a power of two. Two paths of the round with the
+/// same key are detected by a second, independent digest and rejected with
+/// [`CapacityError::Collision`].
+#[derive(Debug, Clone)]
+pub struct FixedAlignmentStack<const DEPTH: usize, const TRACE: usize> {
+    stack: Vec<Digests, DEPTH>,
+    trace: FnvIndexMap<PathKey, u32, TRACE>,
+    /// Check digest of every path aligned in the round.
+    aligned: FnvIndexMap<PathKey, u64, TRACE>,
+    /// Invocations that failed to align and are not unaligned yet, so that `unalign` does not
+    /// pop their parent.
+    overflow: usize,
+}
+impl<const DEPTH: usize, const TRACE: usize> FixedAlignmentStack<DEPTH, TRACE> {
+    pub const fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            trace: FnvIndexMap::new(),
+            aligned: FnvIndexMap::new(),
+            overflow: 0,
+        }
+    }
+
+    /// Key of the current path, the root when nothing is aligned.
+    pub fn current_path(&self) -> PathKey {
+        self.current().0
+    }
+
+    /// Enter the next invocation of `token` at the current path.
+    ///
+    /// # Errors
+    /// Returns an error if the stack or the trace is full, or if the new path has the key of
+    /// another path of the round; like the stack of the VM, the failed invocation must still be
+    /// unaligned
+    pub fn align(&mut self, token: &str) -> Result<PathKey, CapacityError> {
+        let parent = self.current();
+        let counter = self
+            .trace
+            .get(&parent.0)
+            .map_or(0, |counter| counter.saturating_add(1));
+        if !self.trace.contains_key(&parent.0) && self.trace.len() >= TRACE {
+            return Err(self.overflow(CapacityError::Trace));
+        }
+        let path = self.push(parent, token, counter)?;
+        // only counted once entered, the room for it has been checked above
+        self.trace.insert(parent.0, counter).ok();
+        Ok(path)
+    }
+
+    /// Enter a namespace, independent from the invocations preceding it at the current path.
+    ///
+    /// # Errors
+    /// See [`FixedAlignmentStack::align`]
+    pub fn align_namespace(&mut self, token: &str) -> Result<PathKey, CapacityError> {
+        self.push(self.current(), token, 0)
+    }
+
+    pub fn unalign(&mut self) {
+        if self.overflow > 0 {
+            self.overflow = self.overflow.saturating_sub(1);
+        } else {
+            self.stack.pop();
+        }
+    }
+
+    /// Forget the invocations of the previous round.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.trace.clear();
+        self.aligned.clear();
+        self.overflow = 0;
+    }
+
+    /// Count a failed invocation, to be unaligned like the entered ones.
+    const fn overflow(&mut self, error: CapacityError) -> CapacityError {
+        self.overflow = self.overflow.saturating_add(1);
+        error
+    }
+
+    fn current(&self) -> Digests {
+        self.stack
+            .last()
+            .copied()
+            .unwrap_or((PathKey(FNV_OFFSET), FNV_OFFSET))
+    }
+
+    /// Enter `parent` extended by `token:counter`, digested with FNV-1a for the key and with
+    /// FNV-1 for the check digest.
+    fn push(
+        &mut self,
+        parent: Digests,
+        token: &str,
+        counter: u32,
+    ) -> Result<PathKey, CapacityError> {
+        if self.overflow > 0 {
+            return Err(self.overflow(CapacityError::Depth));
+        }
+        // the separator keeps ("ab", 1) and ("a", "b1") apart
+        let bytes = token
+            .bytes()
+            .chain([b':'])
+            .chain(counter.to_be_bytes())
+            .chain([b'/']);
+        let key = bytes.clone().fold(parent.0 .0, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        });
+        let check = bytes.fold(parent.1, |hash, byte| {
+            hash.wrapping_mul(FNV_PRIME) ^ u64::from(byte)
+        });
+        let path = PathKey(key);
+        if self
+            .aligned
+            .get(&path)
+            .is_some_and(|aligned| *aligned != check)
+        {
+            return Err(self.overflow(CapacityError::Collision));
+        }
+        if self.stack.push((path, check)).is_err() {
+            return Err(self.overflow(CapacityError::Depth));
+        }
+        if self.aligned.insert(path, check).is_err() {
+            self.stack.pop();
+            return Err(self.overflow(CapacityError::Trace));
+        }
+        Ok(path)
+    }
+}
+impl<const DEPTH: usize, const TRACE: usize> Default for FixedAlignmentStack<DEPTH, TRACE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_depend_on_tokens_counters_and_nesting() {
+        let mut stack: FixedAlignmentStack<2, 4> = FixedAlignmentStack::new();
+        let first = stack.align("neighboring").unwrap();
+        stack.unalign();
+        let second = stack.align("neighboring").unwrap();
+        assert_ne!(first, second);
+        let nested = stack.align("neighboring").unwrap();
+        assert_ne!(nested, first);
+        assert_eq!(stack.align("repeat"), Err(CapacityError::Depth));
+
+        let mut other: FixedAlignmentStack<2, 4> = FixedAlignmentStack::new();
+        assert_eq!(other.align("neighboring"), Ok(first));
+        other.reset();
+        let namespace = other.align_namespace("app").unwrap();
+        other.unalign();
+        assert_eq!(other.align_namespace("app"), Ok(namespace));
+    }
+
+    #[test]
+    fn paths_sharing_a_key_are_rejected() {
+        let mut stack: FixedAlignmentStack<2, 4> = FixedAlignmentStack::new();
+        let key = stack.align("repeat").unwrap();
+        stack.unalign();
+        stack.reset();
+        // another path of the round digested to the same key
+        stack.aligned.insert(key, 0).unwrap();
+        assert_eq!(stack.align("repeat"), Err(CapacityError::Collision));
+        stack.reset();
+        assert_eq!(stack.align("repeat"), Ok(key));
+    }
+
+    #[test]
+    fn failed_invocations_are_not_counted_and_unalign_themselves() {
+        let mut stack: FixedAlignmentStack<2, 4> = FixedAlignmentStack::new();
+        let outer = stack.align("branch").unwrap();
+        let inner = stack.align("share").unwrap();
+        assert_eq!(stack.align("repeat"), Err(CapacityError::Depth));
+        assert_eq!(stack.align("repeat"), Err(CapacityError::Depth));
+        assert!(!stack.trace.contains_key(&inner));
+        stack.unalign();
+        stack.unalign();
+        assert_eq!(stack.current_path(), inner);
+        stack.unalign();
+        assert_eq!(stack.current_path(), outer);
+    }
+}
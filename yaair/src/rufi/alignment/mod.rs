@@ -1 +1,4 @@
 pub mod alignment_stack;
+#[cfg(feature = "no-alloc")]
+pub mod fixed;
+pub mod report;
@@ -0,0 +1,20 @@
+use crate::rufi::messages::path::Path;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Alignment mismatches observed by a VM in the current round, see
+/// [`VM::alignment_report`](crate::rufi::aggregate::VM::alignment_report).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentReport<Id> {
+    /// Paths exported by a neighbor that no operator read, sorted by neighbor and path.
+    pub unread: Vec<(Id, Path)>,
+    /// Paths exported locally that no neighbor exported, sorted.
+    pub unmatched: Vec<Path>,
+}
+impl<Id> AlignmentReport<Id> {
+    /// Whether every value exchanged in the round was matched on both sides.
+    pub const fn is_aligned(&self) -> bool {
+        self.unread.is_empty() && self.unmatched.is_empty()
+    }
+}
@@ -0,0 +1,174 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A SHA-256 digest.
+pub type Hash = [u8; 32];
+
+/// Hash preceding the first entry of a log.
+pub const GENESIS: Hash = [0; 32];
+
+/// Record of a single round.
+///
+/// `hash` covers all the other fields, including the hash of the previous entry: altering or
+/// removing an entry invalidates every following one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub round: u64,
+    /// Digest of the serialized program result, standing for what the device computed.
+    pub result_hash: Hash,
+    /// Digest of the serialized outbound message, i.e. what the device sent.
+    pub outbound_hash: Hash,
+    pub previous_hash: Hash,
+    pub hash: Hash,
+}
+impl AuditEntry {
+    fn new(round: u64, result_hash: Hash, outbound_hash: Hash, previous_hash: Hash) -> Self {
+        let hash = Self::chain(round, result_hash, outbound_hash, previous_hash);
+        Self {
+            round,
+            result_hash,
+            outbound_hash,
+            previous_hash,
+            hash,
+        }
+    }
+
+    fn chain(round: u64, result_hash: Hash, outbound_hash: Hash, previous_hash: Hash) -> Hash {
+        Sha256::new()
+            .chain_update(previous_hash)
+            .chain_update(round.to_be_bytes())
+            .chain_update(result_hash)
+            .chain_update(outbound_hash)
+            .finalize()
+            .into()
+    }
+
+    /// Whether `hash` matches the content of the entry.
+    pub fn is_consistent(&self) -> bool {
+        self.hash
+            == Self::chain(
+                self.round,
+                self.result_hash,
+                self.outbound_hash,
+                self.previous_hash,
+            )
+    }
+}
+
+/// Tamper-evident log of the rounds executed by a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+    head: Hash,
+    next_round: u64,
+}
+impl AuditLog {
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            head: GENESIS,
+            next_round: 0,
+        }
+    }
+
+    /// Append the entry of a round given its serialized result and outbound message.
+    ///
+    /// # Returns
+    /// The hash of the new entry
+    pub fn record(&mut self, result: &[u8], outbound: &[u8]) -> Hash {
+        let entry = AuditEntry::new(
+            self.next_round,
+            Sha256::digest(result).into(),
+            Sha256::digest(outbound).into(),
+            self.head,
+        );
+        self.head = entry.hash;
+        self.next_round = self.next_round.saturating_add(1);
+        self.entries.push(entry);
+        self.head
+    }
+
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Hash of the last recorded entry, [`GENESIS`] if nothing was recorded.
+    pub const fn head(&self) -> Hash {
+        self.head
+    }
+
+    /// Remove the retained entries, e.g. after exporting them; the chain continues from
+    /// [`AuditLog::head`].
+    pub fn take_entries(&mut self) -> Vec<AuditEntry> {
+        core::mem::take(&mut self.entries)
+    }
+
+    /// Verify the retained entries, see [`verify_chain`].
+    pub fn verify(&self) -> bool {
+        self.entries
+            .first()
+            .is_none_or(|first| verify_chain(&self.entries, first.previous_hash))
+    }
+}
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Verify that `entries` form an unbroken chain starting from `previous_hash`.
+pub fn verify_chain(entries: &[AuditEntry], previous_hash: Hash) -> bool {
+    let mut expected_previous = previous_hash;
+    entries.iter().all(|entry| {
+        let valid = entry.previous_hash == expected_previous && entry.is_consistent();
+        expected_previous = entry.hash;
+        valid
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_of(rounds: u8) -> AuditLog {
+        let mut log = AuditLog::new();
+        for round in 0..rounds {
+            log.record(&[round], &[round, round]);
+        }
+        log
+    }
+
+    #[test]
+    fn recorded_entries_form_a_chain() {
+        let log = log_of(3);
+        assert_eq!(log.entries().len(), 3);
+        assert!(log.verify());
+        assert!(verify_chain(log.entries(), GENESIS));
+        assert_eq!(
+            log.entries().last().map(|entry| entry.hash),
+            Some(log.head())
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let mut entries = log_of(3).take_entries();
+        if let Some(entry) = entries.get_mut(1) {
+            entry.outbound_hash = [1; 32];
+        }
+        assert!(!verify_chain(&entries, GENESIS));
+        entries.remove(1);
+        assert!(!verify_chain(&entries, GENESIS));
+    }
+
+    #[test]
+    fn chain_continues_after_taking_entries() {
+        let mut log = log_of(2);
+        let head = log.head();
+        let _ = log.take_entries();
+        log.record(b"result", b"outbound");
+        assert!(verify_chain(log.entries(), head));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::blocks::collect::collect_sum;
+use crate::rufi::blocks::gradient::{broadcast_along_gradient, gradient};
+use crate::rufi::blocks::leader::elect_leader;
+use crate::rufi::data::field::Field;
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// Count the devices in the area where `inside` holds, sharing the count with all of them.
+///
+/// Composes the S, G and C patterns: devices in the area elect a leader within `radius` (see
+/// [`elect_leader`], `key` must be unique), build a gradient towards it, sum one per device
+/// down the gradient and broadcast the total back. Disconnected parts of the area count
+/// themselves separately, and counts heal when devices join, leave or the area is partitioned.
+///
+/// # Returns
+/// The estimated number of devices in the area, zero for devices outside of it
+pub fn count_devices_in_area<Id, A, K>(
+    vm: &mut A,
+    inside: bool,
+    key: &K,
+    radius: f64,
+    metric: &Field<Id, f64>,
+) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    K: Serialize + for<'de> Deserialize<'de> + Clone + PartialOrd + 'static,
+{
+    vm.branch(
+        inside,
+        |vm| {
+            let leader = elect_leader(vm, key, radius, metric)?;
+            let is_leader = leader == *key;
+            let potential = gradient(vm, is_leader, metric)?;
+            let count = collect_sum(vm, potential, 1.0)?;
+            broadcast_along_gradient(vm, is_leader, potential, count)
+        },
+        |_| Ok(0.0),
+    )
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::simulator::simulation::{JoinPolicy, Simulator};
+    use crate::rufi::test_utils::MockSerializer;
+
+    fn counts(simulator: &Simulator<'_, u32, MockSerializer, f64>) -> Vec<f64> {
+        simulator
+            .results()
+            .values()
+            .map(|count| count.round())
+            .collect()
+    }
+
+    #[test]
+    fn counts_heal_after_a_partition() {
+        let program = |id: u32, vm: &mut VM<u32, MockSerializer>| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            count_devices_in_area(vm, id != 5, &id, 10.0, &metric).unwrap()
+        };
+        let mut simulator = Simulator::new(MockSerializer, program);
+        for id in 0..6 {
+            simulator.add_device(id, JoinPolicy::Fresh);
+        }
+        for (previous, id) in (0..6).zip(1..6) {
+            simulator.connect(previous, id);
+        }
+        simulator.run(20).unwrap();
+        assert_eq!(counts(&simulator), vec![5.0, 5.0, 5.0, 5.0, 5.0, 0.0]);
+
+        simulator.partition([vec![0, 1], vec![2, 3, 4, 5]]);
+        simulator.run(30).unwrap();
+        assert_eq!(counts(&simulator), vec![2.0, 2.0, 3.0, 3.0, 3.0, 0.0]);
+    }
+}
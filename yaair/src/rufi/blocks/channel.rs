@@ -0,0 +1,76 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::blocks::gradient::{broadcast_along_gradient, gradient};
+use crate::rufi::data::field::Field;
+use core::hash::Hash;
+use serde::Serialize;
+
+/// Distance between the closest source and the closest target, estimated through `metric` and
+/// shared with every device along the gradient from the sources.
+///
+/// Devices report `f64::MAX` if no target can be reached from the sources.
+pub fn distance_between<Id, A>(
+    vm: &mut A,
+    source: bool,
+    target: bool,
+    metric: &Field<Id, f64>,
+) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    let to_source = gradient(vm, source, metric)?;
+    let to_target = gradient(vm, target, metric)?;
+    broadcast_along_gradient(vm, source, to_source, to_target)
+}
+
+/// Whether the device lies on the shortest path between the closest source and target,
+/// tolerating detours up to `width`.
+pub fn channel<Id, A>(
+    vm: &mut A,
+    source: bool,
+    target: bool,
+    width: f64,
+    metric: &Field<Id, f64>,
+) -> Result<bool, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    let to_source = gradient(vm, source, metric)?;
+    let to_target = gradient(vm, target, metric)?;
+    let between = broadcast_along_gradient(vm, source, to_source, to_target)?;
+    Ok(between < f64::MAX && to_source + to_target <= between + width)
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::{grid, run_rounds};
+
+    #[test]
+    fn distance_between_reaches_every_device() {
+        let results = run_rounds(&grid(4, 3), 12, |id, vm| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            distance_between(vm, id == 0, id == 11, &metric).unwrap()
+        });
+        assert!(results
+            .values()
+            .all(|distance| (distance - 5.0).abs() < f64::EPSILON));
+    }
+
+    #[test]
+    fn channel_follows_the_shortest_path_on_a_grid() {
+        let results = run_rounds(&grid(5, 3), 15, |id, vm| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            channel(vm, id == 5, id == 9, 0.5, &metric).unwrap()
+        });
+        let on_channel: Vec<u32> = results
+            .iter()
+            .filter(|(_, inside)| **inside)
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(on_channel, vec![5, 6, 7, 8, 9]);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// Converge-cast `local` values down a `potential` field (e.g. a gradient) towards its minima.
+///
+/// Every device accumulates its own value with the ones collected by the neighbors having a
+/// higher potential. `accumulate` should be idempotent (e.g. `min`, `max`, logical and/or) since
+/// a value may reach the sink through multiple paths; see [`collect_sum`] for sums.
+pub fn collect<Id, A, V, F>(
+    vm: &mut A,
+    potential: f64,
+    local: V,
+    accumulate: F,
+) -> Result<V, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+    F: Fn(V, &V) -> V,
+{
+    let potentials = vm.neighboring(&potential)?;
+    vm.share(&local, |_, collected| {
+        collected
+            .aligned_map(&potentials, |value, neighbor_potential| {
+                (value.clone(), *neighbor_potential)
+            })
+            .fold_neighbors(local.clone(), |acc, (value, neighbor_potential)| {
+                if *neighbor_potential > potential {
+                    accumulate(acc, value)
+                } else {
+                    acc
+                }
+            })
+    })
+}
+
+/// Maximum of the `local` values of the devices converging to every minimum of `potential`,
+/// see [`collect`].
+pub fn collect_max<Id, A, V>(vm: &mut A, potential: f64, local: V) -> Result<V, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    V: Serialize + for<'de> Deserialize<'de> + Clone + PartialOrd + 'static,
+{
+    collect(vm, potential, local, |acc, value| {
+        if *value > acc {
+            value.clone()
+        } else {
+            acc
+        }
+    })
+}
+
+/// Minimum of the `local` values of the devices converging to every minimum of `potential`,
+/// see [`collect`].
+pub fn collect_min<Id, A, V>(vm: &mut A, potential: f64, local: V) -> Result<V, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    V: Serialize + for<'de> Deserialize<'de> + Clone + PartialOrd + 'static,
+{
+    collect(vm, potential, local, |acc, value| {
+        if *value < acc {
+            value.clone()
+        } else {
+            acc
+        }
+    })
+}
+
+/// Sum the `local` values down a `potential` field towards its minima.
+///
+/// Summation is not idempotent, so every device splits its partial sum among the neighbors with
+/// a lower potential, in proportion to the potential descent towards each of them: values
+/// reach the sink through multiple paths but are counted once.
+pub fn collect_sum<Id, A>(vm: &mut A, potential: f64, local: f64) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    let potentials = vm.neighboring(&potential)?;
+    let descent: f64 = potentials
+        .excluding_self()
+        .map(|(_, neighbor_potential)| potential - neighbor_potential)
+        .filter(|descent| *descent > 0.0)
+        .sum();
+    // every device shares its partial sum with the potential and descent it is split by
+    let (sum, _, _) = vm.share(&(local, potential, descent), |_, partials| {
+        let upstream: f64 = partials
+            .excluding_self()
+            .filter(|(_, (_, upper, total))| *upper > potential && *total > 0.0)
+            .map(|(_, (partial, upper, total))| partial * (upper - potential) / total)
+            .sum();
+        (local + upstream, potential, descent)
+    })?;
+    Ok(sum)
+}
+
+/// Mean of the `local` values collected by [`collect_sum`].
+pub fn collect_mean<Id, A>(vm: &mut A, potential: f64, local: f64) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    let sum = collect_sum(vm, potential, local)?;
+    let count = collect_sum(vm, potential, 1.0)?;
+    Ok(sum / count)
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::blocks::gradient::hop_gradient;
+    use crate::rufi::test_utils::{grid, line, run_rounds};
+
+    #[test]
+    fn collect_max_on_a_line() {
+        let results = run_rounds(&line(4), 8, |id, vm| {
+            let potential = hop_gradient(vm, id == 0).unwrap();
+            collect(vm, potential, id, |a, b| a.max(*b)).unwrap()
+        });
+        assert_eq!(results.get(&0), Some(&3));
+        assert_eq!(results.get(&3), Some(&3));
+    }
+
+    #[test]
+    fn collect_min_on_a_line() {
+        let results = run_rounds(&line(4), 8, |id, vm| {
+            let potential = hop_gradient(vm, id == 3).unwrap();
+            collect_min(vm, potential, id).unwrap()
+        });
+        assert_eq!(results.get(&3), Some(&0));
+    }
+
+    #[test]
+    fn collect_sum_counts_every_device_once_on_a_grid() {
+        let results = run_rounds(&grid(4, 3), 15, |id, vm| {
+            let potential = hop_gradient(vm, id == 0).unwrap();
+            let naive = collect(vm, potential, 1.0, |acc, value| acc + value).unwrap();
+            let sum = collect_sum(vm, potential, 1.0).unwrap();
+            let mean = collect_mean(vm, potential, f64::from(id)).unwrap();
+            (naive, sum, mean)
+        });
+        let (naive, sum, mean) = results.get(&0).copied().unwrap();
+        assert!(naive > 12.0, "multiple paths are counted by the naive sum");
+        assert!((sum - 12.0).abs() < 1e-9, "collected {sum}");
+        assert!((mean - 5.5).abs() < 1e-9, "averaged {mean}");
+    }
+}
@@ -0,0 +1,53 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use core::hash::Hash;
+use serde::Serialize;
+
+/// Agree on the average of the `initial` values by Laplacian averaging with the neighbors.
+///
+/// Every round each device moves towards its neighbors by `alpha` times the sum of the
+/// differences with their values. With symmetric links the sum of the values is preserved, so
+/// connected devices converge to the average of their initial values as long as `alpha` is
+/// below one over the largest number of neighbors; larger steps oscillate or diverge.
+///
+/// # Returns
+/// The current estimate of the average
+// blocks build without `std`, which provides `f64::mul_add`
+#[allow(clippy::suboptimal_flops)]
+pub fn consensus<Id, A>(vm: &mut A, initial: f64, alpha: f64) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    vm.share(&initial, |_, estimates| {
+        let local = *estimates.local();
+        let disagreement = estimates.fold_neighbors(0.0, |sum, estimate| sum + (estimate - local));
+        local + alpha * disagreement
+    })
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::simulator::simulation::{JoinPolicy, Simulator};
+    use crate::rufi::test_utils::MockSerializer;
+
+    #[test]
+    fn a_ring_converges_to_the_average() {
+        let program =
+            |id: u32, vm: &mut VM<u32, MockSerializer>| consensus(vm, f64::from(id), 0.3).unwrap();
+        let mut simulator = Simulator::new(MockSerializer, program);
+        for id in 0..6 {
+            simulator.add_device(id, JoinPolicy::Fresh);
+        }
+        for id in 0..6 {
+            simulator.connect(id, (id + 1) % 6);
+        }
+        simulator.run(100).unwrap();
+        for estimate in simulator.results().values() {
+            assert!((estimate - 2.5).abs() < 1e-6, "estimated {estimate}");
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::rufi::aggregate::Aggregate;
+use core::hash::Hash;
+use serde::Serialize;
+
+/// State of a [`repeat_until`], stored in the state of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Convergence<V> {
+    pub value: V,
+    /// Rounds in which the value evolved.
+    pub rounds: u64,
+    /// Whether the predicate held, freezing the value.
+    pub converged: bool,
+}
+
+/// Like [`Aggregate::repeat`], but stop evolving the state once `predicate` holds on it.
+///
+/// # Returns
+/// The state together with the number of rounds it evolved for and whether it converged
+pub fn repeat_until<Id, A, V>(
+    vm: &mut A,
+    initial: &V,
+    evolution: impl FnOnce(V, &mut A) -> V,
+    predicate: impl FnOnce(&V) -> bool,
+) -> Convergence<V>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    V: Clone + 'static,
+{
+    let start = Convergence {
+        value: initial.clone(),
+        rounds: 0,
+        converged: false,
+    };
+    vm.repeat(&start, |state, vm| {
+        if state.converged {
+            return state;
+        }
+        let value = evolution(state.value, vm);
+        Convergence {
+            converged: predicate(&value),
+            value,
+            rounds: state.rounds.saturating_add(1),
+        }
+    })
+}
+
+/// Number of rounds in which this call site was executed, including the current one.
+pub fn rep_count<Id, A>(vm: &mut A) -> u64
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    vm.repeat(&0, |count: u64, _| count.saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::messages::inbound::InboundMessage;
+    use crate::rufi::test_utils::MockSerializer;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    #[test]
+    fn evolution_stops_once_converged() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        let states: Vec<_> = (0..5)
+            .map(|_| {
+                vm.prepare_new_round(InboundMessage::default());
+                let count = rep_count(&mut vm);
+                (
+                    count,
+                    repeat_until(&mut vm, &1, |value, _| value * 2, |value| *value >= 8),
+                )
+            })
+            .collect();
+        let last = Convergence {
+            value: 8,
+            rounds: 3,
+            converged: true,
+        };
+        assert_eq!(states.get(1).map(|(_, state)| state.value), Some(4));
+        assert_eq!(states.last(), Some(&(5, last)));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::data::field::Field;
+use core::hash::Hash;
+use serde::Serialize;
+
+/// Whether some neighbor detected the event, excluding the local device.
+pub fn any_hood<Id: Ord + Hash + Copy>(detected: &Field<Id, bool>) -> bool {
+    detected.excluding_self().any(|(_, detected)| *detected)
+}
+
+/// Whether all the neighbors detected the event, excluding the local device; isolated devices
+/// vacuously hold.
+pub fn all_hood<Id: Ord + Hash + Copy>(detected: &Field<Id, bool>) -> bool {
+    detected.excluding_self().all(|(_, detected)| *detected)
+}
+
+/// Number of neighbors that detected the event, excluding the local device.
+pub fn count_hood<Id: Ord + Hash + Copy>(detected: &Field<Id, bool>) -> usize {
+    detected
+        .excluding_self()
+        .filter(|(_, detected)| **detected)
+        .count()
+}
+
+/// Whether the device has neighbors and `predicate` holds on all of them, e.g. to tell a device
+/// inside an alarmed region from one on its border.
+pub fn surrounded_by<Id, A>(vm: &mut A, predicate: bool) -> Result<bool, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    let neighbors = vm.neighboring(&predicate)?;
+    Ok(neighbors.size() > 1 && all_hood(&neighbors))
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::{line, run_rounds};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn quantifiers_ignore_the_local_value() {
+        let detected = Field::from((true, BTreeMap::from([(1u32, false), (2, true)])));
+        assert!(any_hood(&detected));
+        assert!(!all_hood(&detected));
+        assert_eq!(count_hood(&detected), 1);
+        let isolated = Field::from((true, BTreeMap::<u32, bool>::new()));
+        assert!(!any_hood(&isolated));
+        assert!(all_hood(&isolated));
+        assert_eq!(count_hood(&isolated), 0);
+    }
+
+    #[test]
+    fn devices_are_surrounded_by_their_alarmed_neighbors() {
+        let results = run_rounds(&line(6), 2, |id, vm| {
+            let alarmed = (1..5).contains(&id);
+            (alarmed, surrounded_by(vm, alarmed).unwrap())
+        });
+        let inside: Vec<_> = results
+            .values()
+            .map(|(alarmed, surrounded)| alarmed & surrounded)
+            .collect();
+        assert_eq!(inside, vec![false, false, true, true, false, false]);
+        // the ends of the line only neighbor alarmed devices
+        assert_eq!(results.get(&0), Some(&(false, true)));
+    }
+}
@@ -0,0 +1,363 @@
+// `f64::mul_add` is only available with `std`, and the blocks must build without it
+#![allow(clippy::suboptimal_flops)]
+
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::blocks::time::delta_time;
+use crate::rufi::data::field::Field;
+use crate::rufi::sensors::neighborhood::NeighborhoodSensors;
+use crate::rufi::time::TimeSensor;
+use core::cmp::Ordering;
+use core::hash::Hash;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Distance from the closest source, estimated through `metric` (adaptive Bellman-Ford).
+///
+/// Devices that cannot reach any source report `f64::MAX`.
+pub fn gradient<Id, A>(
+    vm: &mut A,
+    source: bool,
+    metric: &Field<Id, f64>,
+) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    vm.share(&f64::MAX, |_, distances| {
+        if source {
+            0.0
+        } else {
+            distances
+                .aligned_map(metric, |distance, weight| distance + weight)
+                .fold_neighbors(f64::MAX, |min, distance| min.min(*distance))
+        }
+    })
+}
+
+/// Number of hops to the closest source.
+pub fn hop_gradient<Id, A>(vm: &mut A, source: bool) -> Result<f64, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    vm.share(&f64::MAX, |_, distances| {
+        if source {
+            0.0
+        } else {
+            distances.fold_neighbors(f64::MAX, |min, distance| min.min(distance + 1.0))
+        }
+    })
+}
+
+/// Propagate the `value` of the sources outwards along a `potential` field (e.g. a gradient).
+///
+/// Every device takes the value of its neighbor with the lowest potential below its own, so
+/// values flow from the minima of the field; devices without such a neighbor keep `value`.
+pub fn broadcast_along_gradient<Id, A, V>(
+    vm: &mut A,
+    source: bool,
+    potential: f64,
+    value: V,
+) -> Result<V, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    V: Serialize + for<'de> Deserialize<'de> + Clone + 'static,
+{
+    let potentials = vm.neighboring(&potential)?;
+    vm.share(&value, |_, values| {
+        if source {
+            return value.clone();
+        }
+        values
+            .aligned_map(&potentials, |upstream, neighbor_potential| {
+                (upstream.clone(), *neighbor_potential)
+            })
+            .excluding_self()
+            .filter(|(_, (_, neighbor_potential))| *neighbor_potential < potential)
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .map_or_else(|| value.clone(), |(_, (upstream, _))| upstream.clone())
+    })
+}
+
+/// Distance estimation algorithm, so that blocks can be parametrized on the gradient they use.
+pub trait GradientAlgorithm {
+    /// Distance from the closest source, estimated through `metric`.
+    ///
+    /// Devices that cannot reach any source report `f64::MAX`.
+    ///
+    /// # Errors
+    /// Returns an error if the estimates cannot be shared with the neighbors
+    fn distance<Id, A>(
+        &self,
+        vm: &mut A,
+        source: bool,
+        metric: &Field<Id, f64>,
+    ) -> Result<f64, AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        A: Aggregate<Id> + NeighborhoodSensors<Id> + TimeSensor;
+}
+
+/// Adaptive Bellman-Ford, see [`gradient`].
+///
+/// Converges quickly when distances shrink but counts to infinity when a source disappears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Abf;
+impl GradientAlgorithm for Abf {
+    fn distance<Id, A>(
+        &self,
+        vm: &mut A,
+        source: bool,
+        metric: &Field<Id, f64>,
+    ) -> Result<f64, AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        A: Aggregate<Id> + NeighborhoodSensors<Id> + TimeSensor,
+    {
+        gradient(vm, source, metric)
+    }
+}
+
+/// Constraint and Restoring Force gradient.
+///
+/// A device keeps the estimates of the neighbors constraining it; when none is left it rises at
+/// `rising_speed` (distance per second) until it is constrained again, instead of counting to
+/// infinity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crf {
+    pub rising_speed: f64,
+}
+impl Crf {
+    pub const fn new(rising_speed: f64) -> Self {
+        Self { rising_speed }
+    }
+}
+impl GradientAlgorithm for Crf {
+    fn distance<Id, A>(
+        &self,
+        vm: &mut A,
+        source: bool,
+        metric: &Field<Id, f64>,
+    ) -> Result<f64, AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        A: Aggregate<Id> + NeighborhoodSensors<Id> + TimeSensor,
+    {
+        let elapsed = delta_time(vm).as_secs_f64();
+        let lags = vm.nbr_lag();
+        let (distance, _) = vm.share(&(f64::MAX, 0.0), |_, estimates| {
+            if source {
+                return (0.0, 0.0);
+            }
+            let (current, _) = *estimates.local();
+            let constraint = estimates
+                .aligned_map(metric, |(distance, speed), weight| {
+                    (*distance, *speed, *weight)
+                })
+                .excluding_self()
+                .filter(|(id, (distance, speed, weight))| {
+                    distance + weight + speed * lag_of(&lags, **id) <= current
+                })
+                .map(|(_, (distance, _, weight))| distance + weight)
+                .reduce(f64::min);
+            constraint.map_or_else(
+                || {
+                    let risen = current + self.rising_speed * elapsed;
+                    (risen.min(f64::MAX), self.rising_speed)
+                },
+                |distance| (distance, 0.0),
+            )
+        })?;
+        Ok(distance)
+    }
+}
+
+/// Bounded Information Speed gradient.
+///
+/// Estimates are also bounded from below by the time information takes to travel from the
+/// source, assuming it propagates at `speed` (distance per second) between devices within
+/// `radius`, so that stale estimates are discarded at the speed of information.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bis {
+    pub speed: f64,
+    pub radius: f64,
+}
+impl Bis {
+    pub const fn new(speed: f64, radius: f64) -> Self {
+        Self { speed, radius }
+    }
+}
+impl GradientAlgorithm for Bis {
+    fn distance<Id, A>(
+        &self,
+        vm: &mut A,
+        source: bool,
+        metric: &Field<Id, f64>,
+    ) -> Result<f64, AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        A: Aggregate<Id> + NeighborhoodSensors<Id> + TimeSensor,
+    {
+        let lags = vm.nbr_lag();
+        let (distance, _) = vm.share(&(f64::MAX, f64::MAX), |_, estimates| {
+            if source {
+                return (0.0, 0.0);
+            }
+            let (space, time) = estimates
+                .aligned_map(metric, |(space, time), weight| (space + weight, *time))
+                .excluding_self()
+                .map(|(id, (space, time))| (*space, time + lag_of(&lags, *id)))
+                .fold((f64::MAX, f64::MAX), |best, candidate| {
+                    if candidate.partial_cmp(&best) == Some(Ordering::Less) {
+                        candidate
+                    } else {
+                        best
+                    }
+                });
+            let bound = self.speed * time - self.radius;
+            (space.max(bound).min(f64::MAX), time)
+        })?;
+        Ok(distance)
+    }
+}
+
+/// Flexible gradient, trading accuracy for stability.
+///
+/// Estimates are only updated when the slope towards a neighbor diverges from the metric by
+/// more than `epsilon`, or when they are far off; short links are stretched to `distortion`
+/// times the communication `radius` to damp the jitter of close devices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flex {
+    pub epsilon: f64,
+    pub distortion: f64,
+    pub radius: f64,
+}
+impl Flex {
+    pub const fn new(epsilon: f64, distortion: f64, radius: f64) -> Self {
+        Self {
+            epsilon,
+            distortion,
+            radius,
+        }
+    }
+}
+impl Default for Flex {
+    fn default() -> Self {
+        Self::new(0.5, 0.2, 1.0)
+    }
+}
+impl GradientAlgorithm for Flex {
+    fn distance<Id, A>(
+        &self,
+        vm: &mut A,
+        source: bool,
+        metric: &Field<Id, f64>,
+    ) -> Result<f64, AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        A: Aggregate<Id> + NeighborhoodSensors<Id> + TimeSensor,
+    {
+        let shortest = self.distortion * self.radius;
+        vm.share(&f64::MAX, |_, estimates| {
+            if source {
+                return 0.0;
+            }
+            let current = *estimates.local();
+            let links =
+                estimates.aligned_map(metric, |distance, weight| (*distance, weight.max(shortest)));
+            let constraint = links
+                .excluding_self()
+                .map(|(_, (distance, weight))| distance + weight)
+                .fold(f64::MAX, f64::min);
+            // slope towards the steepest neighbor, with the neighbor estimate and link length
+            let (slope, neighbor, weight) = links
+                .excluding_self()
+                .map(|(_, (distance, weight))| ((current - distance) / weight, *distance, *weight))
+                .fold((0.0, current, shortest), |steepest, candidate| {
+                    if candidate.0 > steepest.0 {
+                        candidate
+                    } else {
+                        steepest
+                    }
+                });
+            let estimate = if self.radius.max(2.0 * constraint) < current {
+                constraint
+            } else if slope > 1.0 + self.epsilon {
+                neighbor + (1.0 + self.epsilon) * weight
+            } else if slope < 1.0 - self.epsilon {
+                neighbor + (1.0 - self.epsilon) * weight
+            } else {
+                current
+            };
+            estimate.min(f64::MAX)
+        })
+    }
+}
+
+/// Seconds since the last message of `id`, zero if unknown.
+fn lag_of<Id: Ord + Hash + Copy>(lags: &Field<Id, Duration>, id: Id) -> f64 {
+    lags.get(&id).map_or(0.0, Duration::as_secs_f64)
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::test_utils::{line, run_rounds, MockSerializer};
+
+    #[test]
+    fn hop_gradient_on_a_line() {
+        let results = run_rounds(&line(4), 5, |id, vm| hop_gradient(vm, id == 0).unwrap());
+        assert_eq!(results.get(&3), Some(&3.0));
+        assert_eq!(results.get(&0), Some(&0.0));
+    }
+
+    #[test]
+    fn gradient_uses_metric() {
+        let results = run_rounds(&line(3), 4, |id, vm| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 2.0);
+            gradient(vm, id == 0, &metric).unwrap()
+        });
+        assert_eq!(results.get(&2), Some(&4.0));
+    }
+
+    fn converges(algorithm: &impl GradientAlgorithm) {
+        let results = run_rounds(&line(4), 8, |id, vm| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            algorithm.distance(vm, id == 0, &metric).unwrap()
+        });
+        assert_eq!(results.get(&0), Some(&0.0));
+        assert_eq!(results.get(&3), Some(&3.0));
+    }
+
+    #[test]
+    fn gradient_algorithms_converge_on_a_line() {
+        converges(&Abf);
+        converges(&Crf::new(1.0));
+        converges(&Bis::new(1.0, 1.0));
+        converges(&Flex::default());
+    }
+
+    #[test]
+    fn crf_rises_faster_than_abf_when_the_source_disappears() {
+        let after_loss = |algorithm: &dyn Fn(&mut VM<u32, MockSerializer>, bool) -> f64| {
+            let results = run_rounds(&line(4), 12, |id, vm| {
+                let round = vm.repeat(&0u32, |round, _| round.saturating_add(1));
+                algorithm(vm, id == 0 && round <= 6)
+            });
+            results.get(&3).copied().unwrap()
+        };
+        let abf = after_loss(&|vm, source| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            Abf.distance(vm, source, &metric).unwrap()
+        });
+        let crf = after_loss(&|vm, source| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            Crf::new(10.0).distance(vm, source, &metric).unwrap()
+        });
+        assert!(crf > abf, "CRF reached {crf}, ABF {abf}");
+    }
+}
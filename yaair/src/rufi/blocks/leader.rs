@@ -0,0 +1,165 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::blocks::gradient::gradient;
+use crate::rufi::data::field::Field;
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// Elect leaders by symmetry breaking: every device follows the candidate with the lowest `key`
+/// within distance `grain`, estimated through `metric`.
+///
+/// Keys must be unique, e.g. the device ids. Each connected region of the network elects its own
+/// leaders, and when a leader leaves its key fades once its distance estimate exceeds `grain`.
+///
+/// # Returns
+/// The key of the leader followed by the device, its own key if it is a leader
+pub fn elect_leader<Id, A, K>(
+    vm: &mut A,
+    key: &K,
+    grain: f64,
+    metric: &Field<Id, f64>,
+) -> Result<K, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    K: Serialize + for<'de> Deserialize<'de> + Clone + PartialOrd + 'static,
+{
+    let (leader, _) = vm.share(&(key.clone(), 0.0), |_, candidates| {
+        candidates
+            .aligned_map(metric, |(leader, distance), weight| {
+                (leader.clone(), distance + weight)
+            })
+            .excluding_self()
+            .filter(|(_, (_, distance))| *distance < grain)
+            .fold((key.clone(), 0.0), |best, (_, candidate)| {
+                if *candidate < best {
+                    candidate.clone()
+                } else {
+                    best
+                }
+            })
+    })?;
+    Ok(leader)
+}
+
+/// Elect leaders roughly `grain` apart by distance competition, the sparse choice (S) of
+/// field calculus.
+///
+/// Unlike [`elect_leader`], leaders measure their distance from each other: devices farther than
+/// `grain` from every leader candidate themselves, those within half a grain follow the lowest
+/// `key` among the leaders reachable through neighbors also within half a grain, and those in
+/// between stay undecided, so that competing leaders settle between half a grain and a grain
+/// apart. Keys must be unique, e.g. the device ids.
+///
+/// # Returns
+/// Whether the device is a leader
+pub fn sparse_choice<Id, A, K>(
+    vm: &mut A,
+    key: &K,
+    grain: f64,
+    metric: &Field<Id, f64>,
+) -> Result<bool, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    K: Serialize + for<'de> Deserialize<'de> + Clone + PartialOrd + 'static,
+{
+    let mut failure = None;
+    let leader = vm.repeat(&Some(key.clone()), |leader, vm| {
+        compete(vm, key, leader.as_ref(), grain, metric).unwrap_or_else(|err| {
+            failure = Some(err);
+            leader
+        })
+    });
+    failure.map_or_else(|| Ok(leader.as_ref() == Some(key)), Err)
+}
+
+/// Leader followed in this round by a device that followed `leader`, `None` if undecided.
+fn compete<Id, A, K>(
+    vm: &mut A,
+    key: &K,
+    leader: Option<&K>,
+    grain: f64,
+    metric: &Field<Id, f64>,
+) -> Result<Option<K>, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    K: Serialize + for<'de> Deserialize<'de> + Clone + PartialOrd + 'static,
+{
+    let distance = gradient(vm, leader == Some(key), metric)?;
+    let neighbors = vm.neighboring(&(leader.cloned(), distance))?;
+    let radius = grain / 2.0;
+    if distance > grain {
+        return Ok(Some(key.clone()));
+    }
+    if distance >= radius {
+        return Ok(None);
+    }
+    Ok(neighbors
+        .aligned_map(metric, |(followed, from_leader), weight| {
+            followed.clone().filter(|_| from_leader + weight < radius)
+        })
+        .excluding_self()
+        .filter_map(|(_, followed)| followed.clone())
+        .chain(leader.cloned())
+        .reduce(|lowest, candidate| {
+            if candidate < lowest {
+                candidate
+            } else {
+                lowest
+            }
+        }))
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::{grid, line, run_rounds};
+
+    #[test]
+    fn leaders_are_elected_within_the_grain() {
+        let results = run_rounds(&line(6), 10, |id, vm| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            elect_leader(vm, &id, 2.5, &metric).unwrap()
+        });
+        assert_eq!(
+            results.values().copied().collect::<Vec<_>>(),
+            vec![0, 0, 0, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn sparse_leaders_are_about_a_grain_apart() {
+        let width = 12;
+        let grain = 4.0;
+        let results = run_rounds(&grid(width, width), 40, |id, vm| {
+            let metric = vm.neighboring(&()).unwrap().map(|()| 1.0);
+            sparse_choice(vm, &id, grain, &metric).unwrap()
+        });
+        let position = |id: u32| (f64::from(id % width), f64::from(id / width));
+        let hops = |a: u32, b: u32| {
+            let ((ax, ay), (bx, by)) = (position(a), position(b));
+            (ax - bx).abs() + (ay - by).abs()
+        };
+        let leaders: Vec<u32> = results
+            .iter()
+            .filter(|(_, leader)| **leader)
+            .map(|(id, _)| *id)
+            .collect();
+        assert!(leaders.len() > 1);
+        for leader in &leaders {
+            for other in leaders.iter().filter(|other| *other != leader) {
+                assert!(hops(*leader, *other) >= grain / 2.0, "{leader} and {other}");
+            }
+        }
+        for id in results.keys() {
+            let closest = leaders
+                .iter()
+                .map(|leader| hops(*id, *leader))
+                .fold(f64::MAX, f64::min);
+            assert!(closest <= grain, "{id} is {closest} hops from the leaders");
+        }
+    }
+}
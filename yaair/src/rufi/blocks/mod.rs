@@ -0,0 +1,11 @@
+pub mod area;
+pub mod channel;
+pub mod collect;
+pub mod consensus;
+pub mod convergence;
+pub mod detection;
+pub mod gradient;
+pub mod leader;
+pub mod quiescence;
+pub mod roles;
+pub mod time;
@@ -0,0 +1,71 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::blocks::collect::collect;
+use crate::rufi::blocks::gradient::hop_gradient;
+use core::hash::Hash;
+use serde::Serialize;
+
+/// Whether `value` has not changed for at least `rounds` consecutive rounds.
+pub fn stable_for<Id, A, V>(vm: &mut A, value: &V, rounds: u32) -> bool
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    V: PartialEq + Clone + 'static,
+{
+    let (_, unchanged) = vm.repeat(&(value.clone(), 0u32), |(previous, unchanged), _| {
+        if previous == *value {
+            (previous, unchanged.saturating_add(1))
+        } else {
+            (value.clone(), 0)
+        }
+    });
+    unchanged >= rounds
+}
+
+/// Detect when the computation of the whole network has stabilized.
+///
+/// Local `stable` flags are converge-cast along a hop-count gradient towards the `root`.
+/// The root learns that the network is quiescent once every reachable device reports stability;
+/// on the other devices the result only covers the sub-network collected through them.
+pub fn quiescence<Id, A>(vm: &mut A, root: bool, stable: bool) -> Result<bool, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+{
+    let potential = hop_gradient(vm, root)?;
+    collect(vm, potential, stable, |all, other| all && *other)
+}
+
+// the tests run on the simulator, which requires `std`
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::{line, run_rounds};
+
+    #[test]
+    fn stable_for_counts_unchanged_rounds() {
+        let results = run_rounds(&line(1), 4, |_, vm| stable_for(vm, &7, 3));
+        assert_eq!(results.get(&0), Some(&true));
+        let unstable = run_rounds(&line(1), 4, |_, vm| {
+            let round = vm.repeat(&0u32, |round, _| round + 1);
+            stable_for(vm, &round, 1)
+        });
+        assert_eq!(unstable.get(&0), Some(&false));
+    }
+
+    #[test]
+    fn root_detects_quiescence() {
+        let results = run_rounds(&line(4), 10, |id, vm| {
+            quiescence(vm, id == 0, true).unwrap()
+        });
+        assert_eq!(results.get(&0), Some(&true));
+    }
+
+    #[test]
+    fn root_detects_unstable_device() {
+        let results = run_rounds(&line(4), 10, |id, vm| {
+            quiescence(vm, id == 0, id != 3).unwrap()
+        });
+        assert_eq!(results.get(&0), Some(&false));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::rufi::aggregate::{Aggregate, AggregateError};
+use crate::rufi::data::field::Field;
+#[cfg(not(feature = "std"))]
+use alloc::format;
+use core::fmt::{Display, Formatter};
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+/// A role a device can play in an aggregate program.
+///
+/// Roles are plain enums whose allowed transitions are declared by [`Role::can_transition_to`];
+/// staying in the same role is always allowed.
+pub trait Role: Copy + Eq + Display + Serialize + for<'de> Deserialize<'de> + 'static {
+    /// Every role, in the same order on all the devices, see [`when_role`].
+    const ROLES: &'static [Self];
+
+    /// Whether a device playing `self` may switch to `next`.
+    fn can_transition_to(&self, next: &Self) -> bool;
+}
+
+/// Roles of a device in a data-dissemination pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FlowRole {
+    Source,
+    Relay,
+    Sink,
+}
+impl Display for FlowRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Source => write!(f, "source"),
+            Self::Relay => write!(f, "relay"),
+            Self::Sink => write!(f, "sink"),
+        }
+    }
+}
+impl Role for FlowRole {
+    const ROLES: &'static [Self] = &[Self::Source, Self::Relay, Self::Sink];
+
+    fn can_transition_to(&self, _next: &Self) -> bool {
+        true
+    }
+}
+
+/// Roles of a device in a clustered network.
+///
+/// Members must become candidates before being promoted to leaders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ClusterRole {
+    Member,
+    Candidate,
+    Leader,
+}
+impl Display for ClusterRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Member => write!(f, "member"),
+            Self::Candidate => write!(f, "candidate"),
+            Self::Leader => write!(f, "leader"),
+        }
+    }
+}
+impl Role for ClusterRole {
+    const ROLES: &'static [Self] = &[Self::Member, Self::Candidate, Self::Leader];
+
+    fn can_transition_to(&self, next: &Self) -> bool {
+        matches!(
+            (self, next),
+            (Self::Member, Self::Candidate)
+                | (Self::Candidate, Self::Member | Self::Leader)
+                | (Self::Leader, Self::Member)
+        )
+    }
+}
+
+/// Keep track of the role of the device across rounds.
+///
+/// `next` proposes the role for the current round given the previous one. Disallowed transitions
+/// leave the device in its previous role and are reported as
+/// [`AggregateError::InvalidRoleTransition`].
+pub fn evolve_role<Id, A, R, F>(vm: &mut A, initial: R, next: F) -> Result<R, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    R: Role,
+    F: FnOnce(R, &mut A) -> R,
+{
+    let mut rejected = None;
+    let role = vm.repeat(&initial, |current, vm| {
+        let candidate = next(current, vm);
+        if candidate == current || current.can_transition_to(&candidate) {
+            candidate
+        } else {
+            rejected = Some(candidate);
+            current
+        }
+    });
+    rejected.map_or(Ok(role), |to| {
+        Err(AggregateError::InvalidRoleTransition(format!(
+            "{role} -> {to}"
+        )))
+    })
+}
+
+/// Share the role of the device with its neighbors.
+pub fn neighbor_roles<Id, A, R>(vm: &mut A, role: R) -> Result<Field<Id, R>, AggregateError>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    R: Role,
+{
+    vm.neighboring(&role)
+}
+
+/// Execute `th` on devices playing `expected`, `el` on the others.
+///
+/// The bodies are aligned on `role`, so their operators only interact with neighbors playing the
+/// same role, even among the devices executing `el`: every device invokes one `branch` per
+/// role in [`Role::ROLES`], executing its body only in the one of its own role.
+pub fn when_role<Id, A, R, V, Th, El>(vm: &mut A, role: R, expected: R, th: Th, el: El) -> V
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id>,
+    R: Role,
+    Th: FnOnce(&mut A) -> V,
+    El: FnOnce(&mut A) -> V,
+{
+    let position = R::ROLES
+        .iter()
+        .position(|candidate| *candidate == role)
+        .unwrap_or(R::ROLES.len());
+    for _ in 0..position {
+        vm.branch(false, |_| (), |_| ());
+    }
+    let result = vm.branch(role == expected, th, el);
+    for _ in position.saturating_add(1)..R::ROLES.len() {
+        vm.branch(false, |_| (), |_| ());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::messages::inbound::InboundMessage;
+    use crate::rufi::test_utils::MockSerializer;
+    #[cfg(feature = "std")]
+    use crate::rufi::test_utils::{line, run_rounds};
+
+    #[test]
+    fn cluster_role_transitions() {
+        assert!(ClusterRole::Member.can_transition_to(&ClusterRole::Candidate));
+        assert!(ClusterRole::Candidate.can_transition_to(&ClusterRole::Leader));
+        assert!(!ClusterRole::Member.can_transition_to(&ClusterRole::Leader));
+    }
+
+    #[test]
+    fn evolve_role_applies_allowed_transitions() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        let first = evolve_role(&mut vm, ClusterRole::Member, |_, _| ClusterRole::Candidate);
+        assert_eq!(first, Ok(ClusterRole::Candidate));
+        vm.prepare_new_round(InboundMessage::default());
+        let second = evolve_role(&mut vm, ClusterRole::Member, |_, _| ClusterRole::Leader);
+        assert_eq!(second, Ok(ClusterRole::Leader));
+    }
+
+    #[test]
+    fn evolve_role_rejects_disallowed_transitions() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        let rejected = evolve_role(&mut vm, ClusterRole::Member, |_, _| ClusterRole::Leader);
+        assert_eq!(
+            rejected,
+            Err(AggregateError::InvalidRoleTransition(
+                "member -> leader".into()
+            ))
+        );
+        vm.prepare_new_round(InboundMessage::default());
+        let kept = evolve_role(&mut vm, ClusterRole::Member, |current, _| current);
+        assert_eq!(kept, Ok(ClusterRole::Member));
+    }
+
+    #[test]
+    fn when_role_selects_branch() {
+        let mut vm = VM::new(0u32, MockSerializer);
+        let result = when_role(&mut vm, FlowRole::Sink, FlowRole::Sink, |_| 1, |_| 0);
+        assert_eq!(result, 1);
+        let field = neighbor_roles(&mut vm, FlowRole::Sink).unwrap();
+        assert_eq!(field.local(), &FlowRole::Sink);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn when_role_separates_every_role() {
+        let roles = [
+            FlowRole::Relay,
+            FlowRole::Relay,
+            FlowRole::Sink,
+            FlowRole::Sink,
+            FlowRole::Source,
+        ];
+        let results = run_rounds(&line(5), 2, |id, vm| {
+            let role = roles.get(usize::try_from(id).unwrap()).copied().unwrap();
+            let same_role = when_role(
+                vm,
+                role,
+                FlowRole::Source,
+                VM::neighbor_count,
+                VM::neighbor_count,
+            );
+            (same_role, vm.neighbor_count())
+        });
+        // relays and sinks both execute `el`, but only count the neighbors in their own role,
+        // while the operators following `when_role` stay aligned across roles
+        assert_eq!(
+            results.values().copied().collect::<Vec<_>>(),
+            vec![(1, 1), (1, 2), (1, 2), (1, 2), (0, 1)]
+        );
+    }
+}
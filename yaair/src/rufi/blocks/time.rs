@@ -0,0 +1,130 @@
+use crate::rufi::aggregate::Aggregate;
+use crate::rufi::time::TimeSensor;
+use core::hash::Hash;
+use core::time::Duration;
+use serde::Serialize;
+
+/// Time elapsed since the previous round, zero on the first round.
+pub fn delta_time<Id, A>(vm: &mut A) -> Duration
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id> + TimeSensor,
+{
+    let now = vm.current_time();
+    let (_, delta) = vm.repeat(&(now, Duration::ZERO), |(previous, _), _| {
+        (now, now.saturating_sub(previous))
+    });
+    delta
+}
+
+/// Count down from `duration`, returning the remaining time (zero once expired).
+pub fn timer<Id, A>(vm: &mut A, duration: Duration) -> Duration
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id> + TimeSensor,
+{
+    vm.repeat(&duration, |remaining, vm| {
+        remaining.saturating_sub(delta_time(vm))
+    })
+}
+
+/// Remember the last `Some` value for `timeout`, then forget it.
+///
+/// Every new `Some` value refreshes the memory and restarts the timeout.
+pub fn limited_memory<Id, A, V>(vm: &mut A, value: Option<V>, timeout: Duration) -> Option<V>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id> + TimeSensor,
+    V: Clone + 'static,
+{
+    let now = vm.current_time();
+    let (remembered, _) = vm.repeat(&(None, now), |(previous, refreshed_at), _| match value {
+        Some(fresh) => (Some(fresh), now),
+        None if now.saturating_sub(refreshed_at) < timeout => (previous, refreshed_at),
+        None => (None, refreshed_at),
+    });
+    remembered
+}
+
+/// Exponentially decaying level: every round `deposit` is added and the accumulated level halves
+/// every `half_life`, like evaporating pheromones.
+#[cfg(feature = "std")]
+pub fn evaporation<Id, A>(vm: &mut A, deposit: f64, half_life: Duration) -> f64
+where
+    Id: Ord + Hash + Copy + Serialize,
+    A: Aggregate<Id> + TimeSensor,
+{
+    vm.repeat(&0.0, |level, vm| {
+        let elapsed = delta_time(vm).as_secs_f64();
+        let decay = 0.5f64.powf(elapsed / half_life.as_secs_f64());
+        level.mul_add(decay, deposit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::messages::inbound::InboundMessage;
+    use crate::rufi::test_utils::MockSerializer;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    fn rounds<V>(
+        count: usize,
+        mut program: impl FnMut(&mut VM<u32, MockSerializer>) -> V,
+    ) -> Vec<V> {
+        let mut vm = VM::new(0u32, MockSerializer);
+        (0..count)
+            .map(|_| {
+                vm.prepare_new_round(InboundMessage::default());
+                program(&mut vm)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delta_time_follows_the_clock() {
+        let deltas = rounds(3, delta_time);
+        assert_eq!(
+            deltas,
+            vec![
+                Duration::ZERO,
+                Duration::from_secs(1),
+                Duration::from_secs(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn timer_expires() {
+        let remaining = rounds(4, |vm| timer(vm, Duration::from_secs(2)));
+        assert_eq!(remaining.last(), Some(&Duration::ZERO));
+        assert_eq!(remaining.get(1), Some(&Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn limited_memory_forgets_after_timeout() {
+        let mut round = 0;
+        let memory = rounds(5, |vm| {
+            round += 1;
+            limited_memory(vm, (round == 1).then_some(42), Duration::from_secs(2))
+        });
+        assert_eq!(memory, vec![Some(42), Some(42), None, None, None]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn evaporation_halves_every_half_life() {
+        let mut round = 0;
+        let levels = rounds(3, |vm| {
+            round += 1;
+            evaporation(
+                vm,
+                if round == 1 { 8.0 } else { 0.0 },
+                Duration::from_secs(1),
+            )
+        });
+        assert_eq!(levels, vec![8.0, 4.0, 2.0]);
+    }
+}
@@ -0,0 +1,394 @@
+use crate::rufi::aggregate::VM;
+use crate::rufi::device::DeviceId;
+use crate::rufi::engine::{BoxedProgram, Engine};
+use crate::rufi::messages::budget::{MessageBudget, OverflowPolicy};
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::metrics::Metrics;
+use crate::rufi::network::Network;
+use crate::rufi::scheduler::{Jittered, Scheduler};
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::hash::Hasher;
+use core::time::Duration;
+
+/// Preset configurations for common classes of devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// Battery-powered microcontrollers on low-bandwidth radios: slow, heavily jittered rounds.
+    Tiny,
+    /// Gateways and single-board computers.
+    #[default]
+    Standard,
+    /// Cloud or edge servers on reliable links: fast rounds, short retention.
+    Server,
+}
+impl Profile {
+    /// Time between two rounds.
+    pub const fn period(self) -> Duration {
+        match self {
+            Self::Tiny => Duration::from_secs(10),
+            Self::Standard => Duration::from_secs(1),
+            Self::Server => Duration::from_millis(100),
+        }
+    }
+
+    /// Upper bound of the random delay added to every period.
+    pub const fn jitter(self) -> Duration {
+        match self {
+            Self::Tiny => Duration::from_secs(1),
+            Self::Standard => Duration::from_millis(100),
+            Self::Server => Duration::ZERO,
+        }
+    }
+
+    /// Maximum size of the outbound message, if the typical link has a strict MTU.
+    pub const fn message_budget(self) -> Option<usize> {
+        match self {
+            // LoRa payload limit at the slowest data rates
+            Self::Tiny => Some(222),
+            Self::Standard | Self::Server => None,
+        }
+    }
+
+    /// Age after which the exports of a silent neighbor are ignored.
+    pub const fn retention(self) -> Duration {
+        match self {
+            Self::Tiny => Duration::from_secs(30),
+            Self::Standard => Duration::from_secs(3),
+            Self::Server => Duration::from_millis(500),
+        }
+    }
+}
+
+/// Configuration that prevented [`EngineBuilder::build`] from creating an engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    MissingId,
+    MissingNetwork,
+    MissingSerializer,
+    MissingProgram,
+    MissingEnvironment,
+    /// A zero retention would discard every neighbor as soon as it is received.
+    ZeroRetention,
+}
+
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MissingId => write!(f, "Missing local id"),
+            Self::MissingNetwork => write!(f, "Missing network"),
+            Self::MissingSerializer => write!(f, "Missing serializer"),
+            Self::MissingProgram => write!(f, "Missing program"),
+            Self::MissingEnvironment => write!(f, "Missing environment"),
+            Self::ZeroRetention => write!(f, "Retention must be greater than zero"),
+        }
+    }
+}
+
+/// Scheduling choice of an [`EngineBuilder`], resolved once the profile and the id are known.
+pub trait IntoScheduler {
+    type Scheduler: Scheduler;
+
+    fn into_scheduler(self, profile: Profile, seed: u64) -> Self::Scheduler;
+}
+
+/// The default scheduling of an [`EngineBuilder`]: the period and jitter of its [`Profile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProfileScheduler;
+impl IntoScheduler for ProfileScheduler {
+    type Scheduler = Jittered;
+
+    fn into_scheduler(self, profile: Profile, seed: u64) -> Jittered {
+        Jittered::new(profile.period(), profile.jitter(), seed)
+    }
+}
+impl<Sch: Scheduler> IntoScheduler for Sch {
+    type Scheduler = Self;
+
+    fn into_scheduler(self, _profile: Profile, _seed: u64) -> Self {
+        self
+    }
+}
+
+/// Engine produced by an [`EngineBuilder`], or the reason it could not be built.
+pub type Built<Id, Out, Env, S, Net, Sch> =
+    Result<Engine<Id, Out, Env, S, Net, <Sch as IntoScheduler>::Scheduler>, BuildError>;
+
+/// Builder for an [`Engine`], starting from the defaults of a [`Profile`].
+///
+/// The id, network, serializer, program and environment are required; scheduling, retention
+/// and message size default to the [`Profile::Standard`] preset.
+pub struct EngineBuilder<Id, Out, Env, S, Net, Sch = ProfileScheduler>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: IntoScheduler,
+{
+    local_id: Option<Id>,
+    network: Option<Net>,
+    environment: Option<Env>,
+    serializer: Option<S>,
+    program: Option<BoxedProgram<Env, Id, S, Out>>,
+    scheduler: Sch,
+    profile: Profile,
+    retention: Option<Duration>,
+    observer: Option<Box<dyn Metrics>>,
+}
+impl<Id, Out, Env, S, Net> EngineBuilder<Id, Out, Env, S, Net>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+{
+    pub const fn new() -> Self {
+        Self {
+            local_id: None,
+            network: None,
+            environment: None,
+            serializer: None,
+            program: None,
+            scheduler: ProfileScheduler,
+            profile: Profile::Standard,
+            retention: None,
+            observer: None,
+        }
+    }
+}
+impl<Id, Out, Env, S, Net> Default for EngineBuilder<Id, Out, Env, S, Net>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<Id, Out, Env, S, Net, Sch> EngineBuilder<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: IntoScheduler,
+{
+    #[must_use]
+    pub const fn id(mut self, local_id: Id) -> Self {
+        self.local_id = Some(local_id);
+        self
+    }
+
+    #[must_use]
+    pub fn network(mut self, network: Net) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    #[must_use]
+    pub fn environment(mut self, environment: Env) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    #[must_use]
+    pub fn serializer(mut self, serializer: S) -> Self {
+        self.serializer = Some(serializer);
+        self
+    }
+
+    #[must_use]
+    pub fn program(mut self, program: impl Fn(&Env, &mut VM<Id, S>) -> Out + 'static) -> Self {
+        self.program = Some(Box::new(program));
+        self
+    }
+
+    /// Replace the jittered scheduling of the profile.
+    pub fn scheduler<Sch2: Scheduler>(
+        self,
+        scheduler: Sch2,
+    ) -> EngineBuilder<Id, Out, Env, S, Net, Sch2> {
+        EngineBuilder {
+            local_id: self.local_id,
+            network: self.network,
+            environment: self.environment,
+            serializer: self.serializer,
+            program: self.program,
+            scheduler,
+            profile: self.profile,
+            retention: self.retention,
+            observer: self.observer,
+        }
+    }
+
+    /// Select the preset used for scheduling, retention and message size.
+    #[must_use]
+    pub const fn profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Replace the retention of the profile, see [`Engine::with_retention`].
+    #[must_use]
+    pub const fn retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Notify `observer` of every completed and failed round, see [`Engine::with_metrics`].
+    #[must_use]
+    pub fn observer(mut self, observer: impl Metrics + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Build the engine; the default jitter is seeded from the local id so that devices
+    /// desynchronize.
+    ///
+    /// # Errors
+    /// Returns the first required part that has not been set, or an invalid retention
+    pub fn build(self) -> Built<Id, Out, Env, S, Net, Sch> {
+        let local_id = self.local_id.ok_or(BuildError::MissingId)?;
+        let network = self.network.ok_or(BuildError::MissingNetwork)?;
+        let serializer = self.serializer.ok_or(BuildError::MissingSerializer)?;
+        let program = self.program.ok_or(BuildError::MissingProgram)?;
+        let environment = self.environment.ok_or(BuildError::MissingEnvironment)?;
+        let retention = self.retention.unwrap_or_else(|| self.profile.retention());
+        if retention.is_zero() {
+            return Err(BuildError::ZeroRetention);
+        }
+        let mut hasher = Fnv1a::default();
+        local_id.hash(&mut hasher);
+        let scheduler = self.scheduler.into_scheduler(self.profile, hasher.finish());
+        let mut engine =
+            Engine::with_boxed_program(local_id, network, environment, serializer, program)
+                .with_scheduler(scheduler)
+                .with_retention(retention);
+        if let Some(max_bytes) = self.profile.message_budget() {
+            engine = engine.with_message_budget(MessageBudget::new(
+                max_bytes,
+                OverflowPolicy::DropLowestPriority,
+            ));
+        }
+        if let Some(observer) = self.observer {
+            engine = engine.with_metrics(observer);
+        }
+        Ok(engine)
+    }
+}
+
+/// FNV-1a, a tiny deterministic hasher available without `std`.
+pub(crate) struct Fnv1a(u64);
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::{Aggregate, VM};
+    use crate::rufi::messages::inbound::InboundMessage;
+    use crate::rufi::messages::path::Path;
+    use crate::rufi::messages::valuetree::ValueTree;
+    use crate::rufi::messages::Map;
+    use crate::rufi::metrics::InMemoryMetrics;
+    use crate::rufi::network::NetworkError;
+    use crate::rufi::scheduler::Periodic;
+    use crate::rufi::sensors::neighborhood::{NeighborReading, NeighborhoodReadings};
+    use crate::rufi::test_utils::MockSerializer;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    /// Network where neighbor 1 is fresh and neighbor 2 has been silent for ten seconds.
+    struct StaleNetwork;
+    impl Network<u32, MockSerializer> for StaleNetwork {
+        fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+            Ok(())
+        }
+
+        fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+            let export =
+                || ValueTree::new(Map::from([(Path::from("neighboring:0"), b"null".to_vec())]));
+            InboundMessage::new(Map::from([(1, export()), (2, export())]))
+        }
+
+        fn sense_neighborhood(&mut self) -> NeighborhoodReadings<u32> {
+            let lag = |secs| NeighborReading {
+                lag: Some(Duration::from_secs(secs)),
+                ..NeighborReading::default()
+            };
+            NeighborhoodReadings::new(Map::from([(1, lag(1)), (2, lag(10))]))
+        }
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn aligned_neighbors(_: &(), vm: &mut VM<u32, MockSerializer>) -> usize {
+        vm.neighboring(&()).unwrap().size()
+    }
+
+    #[test]
+    fn profiles_scale_with_device_class() {
+        assert!(Profile::Tiny.period() > Profile::Standard.period());
+        assert!(Profile::Standard.period() > Profile::Server.period());
+        assert!(Profile::Tiny.retention() > Profile::Server.retention());
+        assert_eq!(Profile::default(), Profile::Standard);
+    }
+
+    fn builder() -> EngineBuilder<u32, usize, (), MockSerializer, StaleNetwork> {
+        EngineBuilder::new()
+            .id(0)
+            .network(StaleNetwork)
+            .environment(())
+            .serializer(MockSerializer)
+            .program(aligned_neighbors)
+    }
+
+    #[test]
+    fn profile_retention_drops_stale_neighbors() {
+        let mut engine = builder().profile(Profile::Standard).build().unwrap();
+        assert_eq!(engine.retention(), Some(Profile::Standard.retention()));
+        assert_eq!(engine.cycle().unwrap().output, 2);
+
+        let mut tiny = builder().profile(Profile::Tiny).build().unwrap();
+        assert_eq!(tiny.cycle().unwrap().output, 3);
+    }
+
+    #[test]
+    fn explicit_settings_override_the_profile() {
+        let mut engine = builder()
+            .profile(Profile::Tiny)
+            .retention(Duration::from_secs(5))
+            .scheduler(Periodic::new(Duration::from_secs(2)))
+            .observer(InMemoryMetrics::new())
+            .build()
+            .unwrap();
+        assert_eq!(engine.scheduler().period(), Duration::from_secs(2));
+        assert_eq!(engine.cycle().unwrap().output, 2);
+        assert_eq!(engine.metrics().map(|metrics| metrics.rounds), Some(1));
+    }
+
+    #[test]
+    fn invalid_configurations_are_rejected() {
+        let missing = EngineBuilder::<_, usize, _, _, StaleNetwork>::new()
+            .id(0u32)
+            .environment(())
+            .serializer(MockSerializer)
+            .program(aligned_neighbors)
+            .build();
+        assert!(matches!(missing, Err(BuildError::MissingNetwork)));
+        let zero = builder().retention(Duration::ZERO).build();
+        assert!(matches!(zero, Err(BuildError::ZeroRetention)));
+    }
+}
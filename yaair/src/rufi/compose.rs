@@ -0,0 +1,80 @@
+//! Combinators assembling programs out of reusable units, e.g. for
+//! [`Engine::new`](crate::rufi::engine::Engine::new).
+
+use crate::rufi::aggregate::VM;
+use crate::rufi::messages::serializer::Serializer;
+use core::hash::Hash;
+use serde::Serialize;
+
+/// Run `first`, then `second` with the output of `first` as its environment.
+///
+/// Both programs share the alignment namespace of the composed one: operators of `second` are
+/// aligned after the ones of `first`.
+pub fn seq<Env, Id, S, Mid, Out>(
+    first: impl Fn(&Env, &mut VM<Id, S>) -> Mid,
+    second: impl Fn(&Mid, &mut VM<Id, S>) -> Out,
+) -> impl Fn(&Env, &mut VM<Id, S>) -> Out
+where
+    Id: Ord + Hash + Copy + Serialize,
+    S: Serializer,
+{
+    move |environment, vm| {
+        let intermediate = first(environment, vm);
+        second(&intermediate, vm)
+    }
+}
+
+/// Run `first` and `second` on the same environment, each aligned under its own coordinate so
+/// that their state and exports are isolated, even when they are the same program.
+pub fn pair<Env, Id, S, A, B>(
+    first: impl Fn(&Env, &mut VM<Id, S>) -> A,
+    second: impl Fn(&Env, &mut VM<Id, S>) -> B,
+) -> impl Fn(&Env, &mut VM<Id, S>) -> (A, B)
+where
+    Id: Ord + Hash + Copy + Serialize,
+    S: Serializer,
+{
+    move |environment, vm| {
+        let a = vm.aligned("pair[first]", |vm| first(environment, vm));
+        let b = vm.aligned("pair[second]", |vm| second(environment, vm));
+        (a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::Aggregate;
+    use crate::rufi::engine::Engine;
+    use crate::rufi::messages::inbound::InboundMessage;
+    use crate::rufi::messages::path::Path;
+    use crate::rufi::network::NoNetwork;
+    use crate::rufi::test_utils::MockSerializer;
+
+    #[test]
+    fn composed_programs_run_on_the_engine() {
+        let counter = |step: &u32, vm: &mut VM<u32, MockSerializer>| {
+            vm.repeat(&0, |count: u32, _| count.saturating_add(*step))
+        };
+        let program = pair(seq(counter, counter), counter);
+        let mut engine = Engine::new(0u32, NoNetwork, 1, MockSerializer, program);
+        assert_eq!(engine.cycle().unwrap().output, (1, 1));
+        assert_eq!(engine.cycle().unwrap().output, (3, 2));
+    }
+
+    #[test]
+    fn paired_programs_are_isolated() {
+        let exchange = |(): &(), vm: &mut VM<u32, MockSerializer>| vm.neighboring(&1u8).unwrap();
+        let program = pair(exchange, exchange);
+        let mut vm = VM::new(0u32, MockSerializer);
+        vm.prepare_new_round(InboundMessage::default());
+        program(&(), &mut vm);
+        let outbound = vm.outbound();
+        assert!(outbound
+            .at(&Path::from("pair[first]:0/neighboring:0"))
+            .is_some());
+        assert!(outbound
+            .at(&Path::from("pair[second]:1/neighboring:0"))
+            .is_some());
+    }
+}
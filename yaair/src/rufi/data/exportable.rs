@@ -0,0 +1,15 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Values that can be exchanged with neighbors through `neighboring` and `share`.
+///
+/// Implemented for every type meeting the bounds of those operators; derive it with
+/// `yaair_macros::Exportable` to check every field of a composite value where it is declared,
+/// rather than where it is first shared.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be exchanged with neighbors",
+    label = "not exportable",
+    note = "values exchanged by `neighboring` and `share` must implement `Serialize`, `Deserialize` and `Clone`, and cannot borrow"
+)]
+pub trait Exportable: Serialize + DeserializeOwned + Clone + 'static {}
+impl<V: Serialize + DeserializeOwned + Clone + 'static> Exportable for V {}
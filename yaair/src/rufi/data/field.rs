@@ -1,12 +1,30 @@
 #[cfg(not(feature = "std"))]
+use alloc::collections::btree_map::{IntoIter as MapIntoIter, Iter as MapIter};
+#[cfg(not(feature = "std"))]
 use alloc::collections::BTreeMap as Map;
+#[cfg(not(feature = "std"))]
+use alloc::collections::{BTreeMap, BTreeSet};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 use core::hash::Hash;
 use core::num::Saturating;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::hash_map::{IntoIter as MapIntoIter, Iter as MapIter};
+#[cfg(feature = "std")]
 use std::collections::HashMap as Map;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Debug, PartialEq, Eq)]
+/// Values of the local device and of its aligned neighbors.
+///
+/// Fields can be compared, cloned and serialized, so that programs can return or export them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Field<D: Ord + Hash + Copy, V> {
+    #[serde(rename = "local")]
     default: V,
+    #[serde(rename = "neighbors")]
     overrides: Map<D, V>,
 }
 
@@ -19,10 +37,83 @@ impl<D: Ord + Hash + Copy, V> Field<D, V> {
         &self.default
     }
 
+    /// Consume the field, keeping only the local value.
+    pub fn into_local(self) -> V {
+        self.default
+    }
+
     pub fn size(&self) -> usize {
         (Saturating(self.overrides.len()) + Saturating(1)).0
     }
 
+    /// Iterate over all the entries: the local one first, with no id, then the neighbors.
+    pub fn iter(&self) -> Iter<'_, D, V> {
+        Iter {
+            local: Some(&self.default),
+            neighbors: self.overrides.iter(),
+        }
+    }
+
+    /// Ids of the neighbors.
+    pub fn ids(&self) -> impl Iterator<Item = &D> + '_ {
+        self.overrides.keys()
+    }
+
+    /// All the values, the local one first.
+    pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Value of the neighbor `id`, `None` if it is not aligned.
+    pub fn get(&self, id: &D) -> Option<&V> {
+        self.overrides.get(id)
+    }
+
+    /// Whether the neighbor `id` is aligned.
+    pub fn contains(&self, id: &D) -> bool {
+        self.overrides.contains_key(id)
+    }
+
+    /// Value of the neighbor `id`, or the local value if it is not aligned.
+    pub fn get_or_default(&self, id: &D) -> &V {
+        self.get(id).unwrap_or(&self.default)
+    }
+
+    /// Build a field from the values of a map holding the local one under `local_id`.
+    ///
+    /// # Returns
+    /// `None` if the map has no value for `local_id`
+    pub fn from_map(local_id: D, mut values: BTreeMap<D, V>) -> Option<Self> {
+        let local = values.remove(&local_id)?;
+        Some(Self::new(local, values.into_iter().collect()))
+    }
+
+    /// Values of the neighbors by id, without the local one.
+    pub fn to_map(&self) -> BTreeMap<D, V>
+    where
+        V: Clone,
+    {
+        self.overrides
+            .iter()
+            .map(|(id, value)| (*id, value.clone()))
+            .collect()
+    }
+
+    /// Values of the local device, under `local_id`, and of the neighbors by id.
+    pub fn to_map_with_local(&self, local_id: D) -> BTreeMap<D, V>
+    where
+        V: Clone,
+    {
+        let mut values = self.to_map();
+        values.insert(local_id, self.default.clone());
+        values
+    }
+
+    /// Iterate over the neighbor entries only.
+    pub fn excluding_self(&self) -> impl Iterator<Item = (&D, &V)> + '_ {
+        self.overrides.iter()
+    }
+
     pub fn aligned_map<O, V2, F>(&self, other: &Field<D, V2>, transform: F) -> Field<D, O>
     where
         O: Clone,
@@ -38,6 +129,80 @@ impl<D: Ord + Hash + Copy, V> Field<D, V> {
         )
     }
 
+    /// Pair the values of `self` and `other`, keeping only the neighbors aligned in both.
+    pub fn aligned_zip<V2>(&self, other: &Field<D, V2>) -> Field<D, (V, V2)>
+    where
+        V: Clone,
+        V2: Clone,
+    {
+        self.aligned_map(other, |value, other| (value.clone(), other.clone()))
+    }
+
+    /// Combine the values of three fields, keeping only the neighbors aligned in all of them.
+    pub fn aligned_map3<O, V2, V3, F>(
+        &self,
+        second: &Field<D, V2>,
+        third: &Field<D, V3>,
+        transform: F,
+    ) -> Field<D, O>
+    where
+        O: Clone,
+        F: Fn(&V, &V2, &V3) -> O,
+    {
+        Field::new(
+            transform(&self.default, &second.default, &third.default),
+            self.overrides
+                .iter()
+                .filter_map(|(id, value)| {
+                    let second = second.overrides.get(id)?;
+                    let third = third.overrides.get(id)?;
+                    Some((*id, transform(value, second, third)))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn map<O, F>(&self, transform: F) -> Field<D, O>
+    where
+        F: Fn(&V) -> O,
+    {
+        Field::new(
+            transform(&self.default),
+            self.overrides
+                .iter()
+                .map(|(k, v)| (*k, transform(v)))
+                .collect(),
+        )
+    }
+
+    /// Project the field on `devices`, dropping the values of the other neighbors.
+    ///
+    /// Fields computed before entering a `branch` still hold the neighbors that took the other
+    /// branch; restrict them to [`Aggregate::aligned_devices`](crate::rufi::aggregate::Aggregate::aligned_devices)
+    /// to combine them with fields computed inside it.
+    pub fn restricted_to(&self, devices: &BTreeSet<D>) -> Self
+    where
+        V: Clone,
+    {
+        Self {
+            default: self.default.clone(),
+            overrides: self
+                .overrides
+                .iter()
+                .filter(|(id, _)| devices.contains(id))
+                .map(|(id, value)| (*id, value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Fold the values of the neighbors, excluding the local one.
+    pub fn fold_neighbors<A, F>(&self, init: A, fold: F) -> A
+    where
+        F: FnMut(A, &V) -> A,
+    {
+        self.overrides.values().fold(init, fold)
+    }
+
     pub fn min(&self) -> &V
     where
         V: Ord + Clone,
@@ -45,12 +210,263 @@ impl<D: Ord + Hash + Copy, V> Field<D, V> {
         self.overrides.values().min().unwrap_or(&self.default)
     }
 
-    pub fn min_by(&self, mut compare: impl FnMut(&V, &V) -> core::cmp::Ordering) -> &V {
+    pub fn min_by(&self, mut compare: impl FnMut(&V, &V) -> Ordering) -> &V {
         self.overrides
             .values()
             .min_by(|a, b| compare(a, b))
             .unwrap_or(&self.default)
     }
+
+    pub fn max_by(&self, mut compare: impl FnMut(&V, &V) -> Ordering) -> &V {
+        self.overrides
+            .values()
+            .max_by(|a, b| compare(a, b))
+            .unwrap_or(&self.default)
+    }
+
+    /// Minimum of the neighbor values under a partial order, such as `f64`.
+    ///
+    /// Values not comparable with themselves (NaN) are ignored; falls back to the local value
+    /// if no neighbor is left.
+    pub fn partial_min(&self) -> &V
+    where
+        V: PartialOrd,
+    {
+        self.arg_min()
+            .and_then(|id| self.overrides.get(&id))
+            .unwrap_or(&self.default)
+    }
+
+    /// Maximum of the neighbor values under a partial order, see [`Field::partial_min`].
+    pub fn partial_max(&self) -> &V
+    where
+        V: PartialOrd,
+    {
+        self.arg_max()
+            .and_then(|id| self.overrides.get(&id))
+            .unwrap_or(&self.default)
+    }
+
+    /// Id of the neighbor with the minimum value, ignoring NaN; ties go to the lowest id.
+    pub fn arg_min(&self) -> Option<D>
+    where
+        V: PartialOrd,
+    {
+        self.comparable_neighbors()
+            .min_by(|(a_id, a), (b_id, b)| compare_partial(a, b).then(a_id.cmp(b_id)))
+            .map(|(id, _)| *id)
+    }
+
+    /// Id of the neighbor with the maximum value, ignoring NaN; ties go to the lowest id.
+    pub fn arg_max(&self) -> Option<D>
+    where
+        V: PartialOrd,
+    {
+        self.comparable_neighbors()
+            .max_by(|(a_id, a), (b_id, b)| compare_partial(a, b).then(b_id.cmp(a_id)))
+            .map(|(id, _)| *id)
+    }
+
+    fn comparable_neighbors(&self) -> impl Iterator<Item = (&D, &V)> + '_
+    where
+        V: PartialOrd,
+    {
+        self.overrides
+            .iter()
+            .filter(|(_, value)| value.partial_cmp(value).is_some())
+    }
+}
+
+impl<D: Ord + Hash + Copy> Field<D, f64> {
+    /// Mean of all the values, the local one included.
+    pub fn mean(&self) -> f64 {
+        let (sum, count) = self
+            .values()
+            .fold((0.0, 0.0), |(sum, count), value| (sum + value, count + 1.0));
+        sum / count
+    }
+
+    /// Mean of the values weighted by `weights`, ignoring the neighbors without a weight.
+    ///
+    /// # Returns
+    /// `None` if the weights sum to zero
+    pub fn weighted_mean(&self, weights: &Self) -> Option<f64> {
+        let (sum, total) = self
+            .aligned_zip(weights)
+            .values()
+            .fold((0.0, 0.0), |(sum, total), (value, weight)| {
+                (sum + value * weight, total + weight)
+            });
+        (total != 0.0).then(|| sum / total)
+    }
+
+    /// Population variance of all the values, the local one included.
+    // `f64::mul_add` is only available with `std`
+    #[allow(clippy::suboptimal_flops)]
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        let (squares, count) = self.values().fold((0.0, 0.0), |(squares, count), value| {
+            let deviation = value - mean;
+            (squares + deviation * deviation, count + 1.0)
+        });
+        squares / count
+    }
+
+    /// Median of all the values, the local one included; the mean of the two middle values
+    /// when their number is even.
+    pub fn median(&self) -> f64 {
+        let mut values: Vec<f64> = self.values().copied().collect();
+        values.sort_by(f64::total_cmp);
+        let middle = values.len() / 2;
+        let upper = values.get(middle).copied().unwrap_or(self.default);
+        if values.len().is_multiple_of(2) {
+            let lower = middle
+                .checked_sub(1)
+                .and_then(|lower| values.get(lower))
+                .copied()
+                .unwrap_or(upper);
+            f64::midpoint(lower, upper)
+        } else {
+            upper
+        }
+    }
+}
+
+impl<D: Ord + Hash + Copy, V> Field<D, Option<V>> {
+    /// Drop the neighbors without a value.
+    ///
+    /// # Returns
+    /// `None` if the local value is missing
+    pub fn flatten(self) -> Option<Field<D, V>> {
+        Some(Field::new(
+            self.default?,
+            self.overrides
+                .into_iter()
+                .filter_map(|(id, value)| Some((id, value?)))
+                .collect(),
+        ))
+    }
+
+    /// The field of the values, `None` if any of them is missing, local or neighbor.
+    pub fn transpose(self) -> Option<Field<D, V>> {
+        Some(Field::new(
+            self.default?,
+            self.overrides
+                .into_iter()
+                .map(|(id, value)| Some((id, value?)))
+                .collect::<Option<_>>()?,
+        ))
+    }
+}
+
+impl<D: Ord + Hash + Copy, V, E> Field<D, Result<V, E>> {
+    /// Drop the neighbors whose value is an error, e.g. data that could not be validated.
+    ///
+    /// # Errors
+    /// Returns the local error, if any
+    pub fn flatten(self) -> Result<Field<D, V>, E> {
+        Ok(Field::new(
+            self.default?,
+            self.overrides
+                .into_iter()
+                .filter_map(|(id, value)| Some((id, value.ok()?)))
+                .collect(),
+        ))
+    }
+
+    /// The field of the values, failing if any of them is an error, local or neighbor.
+    ///
+    /// # Errors
+    /// Returns the local error, or else the error of the neighbor with the lowest id
+    pub fn transpose(self) -> Result<Field<D, V>, E> {
+        let local = self.default?;
+        let neighbors: BTreeMap<D, Result<V, E>> = self.overrides.into_iter().collect();
+        Ok(Field::new(
+            local,
+            neighbors
+                .into_iter()
+                .map(|(id, value)| Ok((id, value?)))
+                .collect::<Result<_, E>>()?,
+        ))
+    }
+}
+
+/// A field holding the default local value and no neighbors.
+impl<D: Ord + Hash + Copy, V: Default> Default for Field<D, V> {
+    fn default() -> Self {
+        Self::new(V::default(), Map::new())
+    }
+}
+
+/// A field with the given local value and neighbor values.
+impl<D: Ord + Hash + Copy, V> From<(V, BTreeMap<D, V>)> for Field<D, V> {
+    fn from((local, neighbors): (V, BTreeMap<D, V>)) -> Self {
+        Self::new(local, neighbors.into_iter().collect())
+    }
+}
+
+/// The neighbor values of a field, dropping the local one.
+impl<D: Ord + Hash + Copy, V> From<Field<D, V>> for BTreeMap<D, V> {
+    fn from(field: Field<D, V>) -> Self {
+        field.overrides.into_iter().collect()
+    }
+}
+
+fn compare_partial<V: PartialOrd>(a: &V, b: &V) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+/// Borrowing iterator over the entries of a [`Field`], see [`Field::iter`].
+pub struct Iter<'a, D, V> {
+    local: Option<&'a V>,
+    neighbors: MapIter<'a, D, V>,
+}
+impl<'a, D, V> Iterator for Iter<'a, D, V> {
+    type Item = (Option<&'a D>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.local.take().map_or_else(
+            || self.neighbors.next().map(|(id, value)| (Some(id), value)),
+            |local| Some((None, local)),
+        )
+    }
+}
+
+/// Owning iterator over the entries of a [`Field`]: the local one first, with no id.
+pub struct IntoIter<D, V> {
+    local: Option<V>,
+    neighbors: MapIntoIter<D, V>,
+}
+impl<D, V> Iterator for IntoIter<D, V> {
+    type Item = (Option<D>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.local.take().map_or_else(
+            || self.neighbors.next().map(|(id, value)| (Some(id), value)),
+            |local| Some((None, local)),
+        )
+    }
+}
+
+impl<D: Ord + Hash + Copy, V> IntoIterator for Field<D, V> {
+    type Item = (Option<D>, V);
+    type IntoIter = IntoIter<D, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            local: Some(self.default),
+            neighbors: self.overrides.into_iter(),
+        }
+    }
+}
+
+impl<'a, D: Ord + Hash + Copy, V> IntoIterator for &'a Field<D, V> {
+    type Item = (Option<&'a D>, &'a V);
+    type IntoIter = Iter<'a, D, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 #[cfg(test)]
@@ -134,6 +550,13 @@ mod tests {
         assert_eq!(result.overrides.get(&2), Some(&"c30".to_string()));
     }
 
+    #[test]
+    fn test_map_and_fold_neighbors() {
+        let field = make_field(1, vec![(1, 2), (2, 3)]).map(|v| v * 10);
+        assert_eq!(field.local(), &10);
+        assert_eq!(field.fold_neighbors(0, |acc, v| acc + v), 50);
+    }
+
     #[test]
     fn test_empty_overrides() {
         let f1: Field<i32, i32> = make_field(1, vec![]);
@@ -143,4 +566,134 @@ mod tests {
         assert_eq!(result.local(), &2);
         assert!(result.overrides.is_empty());
     }
+
+    #[test]
+    fn test_iterators_yield_local_first() {
+        let field = make_field(0, vec![(1, 10)]);
+        assert_eq!(
+            field.iter().collect::<Vec<_>>(),
+            vec![(None, &0), (Some(&1), &10)]
+        );
+        assert_eq!(field.ids().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(field.values().sum::<i32>(), 10);
+        assert_eq!(field.excluding_self().collect::<Vec<_>>(), vec![(&1, &10)]);
+        assert_eq!((&field).into_iter().count(), field.size());
+        assert_eq!(
+            field.into_iter().collect::<Vec<_>>(),
+            vec![(None, 0), (Some(1), 10)]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_partial_extremes_ignore_nan() {
+        let field = make_field(5.0, vec![(1, f64::NAN), (2, 3.0), (3, 7.0), (4, 3.0)]);
+        assert_eq!(field.partial_min(), &3.0);
+        assert_eq!(field.partial_max(), &7.0);
+        assert_eq!(field.arg_min(), Some(2));
+        assert_eq!(field.arg_max(), Some(3));
+        // a total order, on the other hand, ranks NaN above every number
+        assert!(field.max_by(f64::total_cmp).is_nan());
+
+        let isolated = make_field(5.0, vec![(1, f64::NAN)]);
+        assert_eq!(isolated.partial_min(), &5.0);
+        assert_eq!(isolated.arg_min(), None);
+    }
+
+    #[test]
+    fn test_restricted_to_keeps_only_given_devices() {
+        let field = make_field(0, vec![(1, 10), (2, 20), (3, 30)]);
+        let restricted = field.restricted_to(&BTreeSet::from([1, 3, 4]));
+        assert_eq!(restricted, make_field(0, vec![(1, 10), (3, 30)]));
+    }
+
+    #[test]
+    fn test_fields_are_comparable_and_serializable() {
+        let field = make_field(1u8, vec![(2u32, 3u8), (4, 5)]);
+        let json = serde_json::to_string(&field).unwrap();
+        let decoded: Field<u32, u8> = serde_json::from_str(&json).unwrap();
+        let copy = field.clone();
+        assert_eq!(decoded, field);
+        assert_eq!(copy, field);
+        assert_ne!(decoded, make_field(1u8, vec![(2u32, 3u8)]));
+        assert_eq!(Field::<u32, u8>::default(), make_field(0u8, Vec::new()));
+    }
+
+    #[test]
+    fn test_neighbors_can_be_read_by_id() {
+        let field = make_field(0u8, vec![(1u32, 10u8), (2, 20)]);
+        assert_eq!(field.get(&2), Some(&20));
+        assert_eq!(field.get(&3), None);
+        assert!(field.contains(&1));
+        assert!(!field.contains(&3));
+        assert_eq!(field.get_or_default(&1), &10);
+        assert_eq!(field.get_or_default(&3), &0);
+    }
+
+    #[test]
+    fn test_aligned_zip_and_map3_keep_common_neighbors() {
+        let distances = make_field(0.0, vec![(1u32, 1.0), (2, 2.0), (3, 3.0)]);
+        let lags = make_field(0u8, vec![(1u32, 5u8), (2, 6)]);
+        let values = make_field('a', vec![(2u32, 'b'), (3, 'c')]);
+        assert_eq!(
+            distances.aligned_zip(&lags),
+            make_field((0.0, 0u8), vec![(1u32, (1.0, 5u8)), (2, (2.0, 6))])
+        );
+        let combined = distances.aligned_map3(&lags, &values, |distance, lag, value| {
+            format!("{distance}/{lag}/{value}")
+        });
+        assert_eq!(
+            combined,
+            make_field("0/0/a".to_string(), vec![(2u32, "2/6/b".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_conversions_to_and_from_maps() {
+        let field = make_field(0u8, vec![(1u32, 10u8), (2, 20)]);
+        let neighbors = BTreeMap::from([(1u32, 10u8), (2, 20)]);
+        assert_eq!(field.to_map(), neighbors);
+        let with_local = field.to_map_with_local(7);
+        assert_eq!(with_local.get(&7), Some(&0));
+        assert_eq!(Field::from_map(7, with_local), Some(field.clone()));
+        assert_eq!(Field::from_map(3, neighbors.clone()), None);
+        assert_eq!(Field::from((0u8, neighbors.clone())), field);
+        assert_eq!(BTreeMap::from(field), neighbors);
+    }
+
+    #[test]
+    fn test_missing_values_are_flattened_or_transposed() {
+        let readings = make_field(Some(0u8), vec![(1u32, Some(10u8)), (2, None)]);
+        assert_eq!(
+            readings.clone().flatten(),
+            Some(make_field(0, vec![(1, 10)]))
+        );
+        assert_eq!(readings.transpose(), None);
+        assert_eq!(
+            make_field(None::<u8>, vec![(1u32, Some(10))]).flatten(),
+            None
+        );
+
+        let parsed = make_field(Ok(0u8), vec![(1u32, Ok(10)), (2, Err("b")), (3, Err("c"))]);
+        assert_eq!(parsed.clone().flatten(), Ok(make_field(0, vec![(1, 10)])));
+        assert_eq!(parsed.transpose(), Err("b"));
+        let valid = make_field(Ok::<u8, &str>(0), vec![(1u32, Ok(10))]);
+        assert_eq!(valid.transpose(), Ok(make_field(0, vec![(1, 10)])));
+        assert_eq!(
+            make_field(Err::<u8, _>("a"), vec![(1u32, Err("b"))]).flatten(),
+            Err("a")
+        );
+    }
+
+    #[test]
+    fn test_statistics_include_the_local_value() {
+        let readings = make_field(2.0, vec![(1u32, 4.0), (2, 4.0), (3, 6.0)]);
+        assert!((readings.mean() - 4.0).abs() < f64::EPSILON);
+        assert!((readings.variance() - 2.0).abs() < f64::EPSILON);
+        let weights = make_field(1.0, vec![(1u32, 3.0), (3, 0.0)]);
+        assert_eq!(readings.weighted_mean(&weights), Some(3.5));
+        assert_eq!(readings.weighted_mean(&make_field(0.0, vec![])), None);
+        assert!((readings.median() - 4.0).abs() < f64::EPSILON);
+        assert!((make_field(3.0, vec![(1u32, 1.0), (2, 2.0)]).median() - 2.0).abs() < f64::EPSILON);
+    }
 }
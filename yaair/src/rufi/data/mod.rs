@@ -1,2 +1,4 @@
+pub mod exportable;
 pub mod field;
+pub mod snapshot;
 pub mod state;
@@ -0,0 +1,130 @@
+use crate::rufi::messages::outbound::OutboundMessage;
+use crate::rufi::messages::path::Path;
+use crate::rufi::messages::serializer::Serializer;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::hash::Hash;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Value of the [`State`](crate::rufi::data::state::State) of a device, serialized into a
+/// [`DeviceSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    /// Path of the value, kept as tokens since they may contain the `/` separator.
+    pub path: Path,
+    /// Name of the type of the value, as registered with
+    /// [`VM::register_snapshot_type`](crate::rufi::aggregate::VM::register_snapshot_type).
+    pub type_name: String,
+    pub value: Vec<u8>,
+}
+
+/// Transferable copy of the state and last exports of a device.
+///
+/// Taken with [`VM::snapshot`](crate::rufi::aggregate::VM::snapshot), it lets a replacement
+/// device resume where the original one left off instead of starting from the initial values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceSnapshot<Id: Ord + Hash + Copy> {
+    /// Device the snapshot was taken from.
+    pub device: Id,
+    pub entries: Vec<SnapshotEntry>,
+    /// Outbound message of the last round of the device.
+    pub outbound: OutboundMessage<Id>,
+    /// Paths of the values left out because their type is not registered.
+    pub skipped: Vec<Path>,
+}
+
+/// Decoder of the snapshot values of a registered type.
+type Decode<S> = fn(&[u8], &S) -> Option<Box<dyn Any>>;
+
+/// Conversions of the values of a type registered for snapshots.
+pub(crate) struct SnapshotType<S> {
+    pub(crate) type_id: TypeId,
+    pub(crate) name: &'static str,
+    pub(crate) encode: fn(&dyn Any, &S) -> Option<Vec<u8>>,
+    pub(crate) decode: Decode<S>,
+}
+impl<S: Serializer> SnapshotType<S> {
+    pub(crate) fn of<V: Serialize + DeserializeOwned + 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<V>(),
+            name: core::any::type_name::<V>(),
+            encode: |value, serializer| serializer.serialize(value.downcast_ref::<V>()?).ok(),
+            decode: |bytes, serializer| {
+                let value: Box<dyn Any> = Box::new(serializer.deserialize::<V>(bytes).ok()?);
+                Some(value)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rufi::aggregate::{Aggregate, VM};
+    use crate::rufi::messages::inbound::InboundMessage;
+    use crate::rufi::messages::serializer::Serializer;
+    use crate::rufi::test_utils::MockSerializer;
+
+    fn program(vm: &mut VM<u32, MockSerializer>) -> (u32, f64) {
+        let rounds = vm.repeat(&0u32, |count: u32, _| count.saturating_add(1));
+        let total = vm.share(&0.5f64, |_, field| field.local() * 2.0).unwrap();
+        (rounds, total)
+    }
+
+    #[test]
+    fn replacement_devices_resume_from_the_snapshot() {
+        let mut old = VM::new(1u32, MockSerializer);
+        old.register_snapshot_type::<u32>();
+        for _ in 0..3 {
+            old.prepare_new_round(InboundMessage::default());
+            program(&mut old);
+        }
+        let taken = old.snapshot();
+        // the type of the share state is not registered
+        assert_eq!(taken.skipped.len(), 1);
+        let bytes = MockSerializer.serialize(&taken).unwrap();
+
+        let mut replacement = VM::new(2u32, MockSerializer);
+        replacement.register_snapshot_type::<u32>();
+        let outbound = replacement
+            .import_snapshot(MockSerializer.deserialize(&bytes).unwrap())
+            .unwrap();
+        assert_eq!(outbound.sender, 2);
+        replacement.prepare_new_round(InboundMessage::default());
+        assert_eq!(program(&mut replacement), (4, 1.0));
+
+        let mut unaware = VM::new(3u32, MockSerializer);
+        assert!(unaware
+            .import_snapshot(MockSerializer.deserialize(&bytes).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn paths_with_separators_survive_the_round_trip() {
+        let counter = |vm: &mut VM<u32, MockSerializer>| {
+            vm.namespace("zone/north", |vm| {
+                vm.repeat(&0u32, |count: u32, _| count.saturating_add(1))
+            })
+        };
+        let mut old = VM::new(1u32, MockSerializer);
+        old.register_snapshot_type::<u32>();
+        for _ in 0..2 {
+            old.prepare_new_round(InboundMessage::default());
+            counter(&mut old);
+        }
+        let bytes = MockSerializer.serialize(&old.snapshot()).unwrap();
+
+        let mut replacement = VM::new(2u32, MockSerializer);
+        replacement.register_snapshot_type::<u32>();
+        replacement
+            .import_snapshot(MockSerializer.deserialize(&bytes).unwrap())
+            .unwrap();
+        replacement.prepare_new_round(InboundMessage::default());
+        assert_eq!(counter(&mut replacement), 3);
+    }
+}
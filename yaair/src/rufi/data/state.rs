@@ -1,50 +1,251 @@
 use crate::rufi::messages::path::Path;
 #[cfg(not(feature = "std"))]
 use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 
 #[cfg(not(feature = "std"))]
 use alloc::collections::BTreeMap as Map;
 
+#[cfg(feature = "std")]
 use std::collections::HashMap as Map;
 
-use core::any::Any;
+use core::any::{Any, TypeId};
+use core::fmt::{self, Display};
+
+/// Values replaced since the last commit, in insertion order, see [`State::rollback`].
+type Journal = Vec<(Path, Option<Box<dyn Any>>)>;
+
+/// Type and name of the values at a path, with the operator that first wrote them.
+type RegisteredType = (TypeId, &'static str, &'static str);
+
+/// Access to a path of the [`State`] with a type different from the registered one, see
+/// [`State::set_type_registry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub path: Path,
+    /// Type of the values stored at the path.
+    pub expected: &'static str,
+    /// Type of the offending access.
+    pub found: &'static str,
+    /// Operator that first wrote the path.
+    pub registered_by: &'static str,
+    /// Operator of the offending access.
+    pub operator: &'static str,
+}
+impl Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at path {} expected '{}' (written by {}) but found '{}'",
+            self.operator, self.path, self.expected, self.registered_by, self.found
+        )
+    }
+}
 
 #[derive(Debug)]
 pub struct State {
-    last_state: Map<Path, Box<dyn Any>>,
+    values: Map<Path, Box<dyn Any>>,
+    journal: Option<Journal>,
+    types: Option<Map<Path, RegisteredType>>,
 }
 impl State {
     pub fn new() -> Self {
-        Self {
-            last_state: Map::new(),
-        }
+        Self::from_snapshot(Map::new())
     }
 
-    pub fn from_snapshot(snapshot: Map<Path, Box<dyn Any>>) -> Self {
+    pub const fn from_snapshot(snapshot: Map<Path, Box<dyn Any>>) -> Self {
         Self {
-            last_state: snapshot,
+            values: snapshot,
+            journal: None,
+            types: None,
         }
     }
 
     pub fn insert<V: Any>(&mut self, path: Path, value: V) {
-        self.last_state.insert(path, Box::new(value));
+        match self.journal.as_mut() {
+            Some(journal) => {
+                let previous = self.values.insert(path.clone(), Box::new(value));
+                journal.push((path, previous));
+            }
+            None => {
+                self.values.insert(path, Box::new(value));
+            }
+        }
+    }
+
+    /// Like [`State::insert`], overwriting a value of the same type in place instead of
+    /// allocating the path and the value again, as operators do round after round.
+    pub fn update<V: Any>(&mut self, path: &Path, value: V) {
+        if self.journal.is_none() {
+            if let Some(slot) = self
+                .values
+                .get_mut(path)
+                .and_then(|slot| slot.downcast_mut::<V>())
+            {
+                *slot = value;
+                return;
+            }
+        }
+        self.insert(path.clone(), value);
+    }
+
+    /// Like [`State::insert`], for a value already boxed.
+    pub fn insert_boxed(&mut self, path: Path, value: Box<dyn Any>) {
+        let previous = self.values.insert(path.clone(), value);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push((path, previous));
+        }
+    }
+
+    /// Stored values, in no particular order.
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, &dyn Any)> + '_ {
+        self.values
+            .iter()
+            .map(|(path, value)| (path, value.as_ref()))
+    }
+
+    /// Keep the values replaced by [`State::insert`] until the next commit, so that they can
+    /// be restored by [`State::rollback`].
+    pub fn set_journaling(&mut self, enabled: bool) {
+        self.journal = enabled.then(Vec::new);
+    }
+
+    /// Forget the values replaced so far, making the current state the rollback point.
+    pub fn commit(&mut self) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.clear();
+        }
     }
 
+    /// Restore the state of the last commit; does nothing without journaling.
+    pub fn rollback(&mut self) {
+        let Some(journal) = self.journal.as_mut() else {
+            return;
+        };
+        while let Some((path, previous)) = journal.pop() {
+            match previous {
+                Some(value) => self.values.insert(path, value),
+                None => self.values.remove(&path),
+            };
+        }
+    }
+
+    /// Record the type of the values of every path on their first access by an operator, so
+    /// that later accesses with another type are reported by [`State::register`] instead of
+    /// panicking in [`State::get`].
+    pub fn set_type_registry(&mut self, enabled: bool) {
+        self.types = enabled.then(Map::new);
+    }
+
+    /// Check that `operator` accesses `path` with the registered type, registering `V` if the
+    /// path is new; does nothing without the registry.
+    ///
+    /// # Errors
+    /// Returns the [`TypeMismatch`] if `V` is not the registered type, or the type of a value
+    /// stored before the registry was enabled
+    pub fn register<V: Any>(
+        &mut self,
+        path: &Path,
+        operator: &'static str,
+    ) -> Result<(), TypeMismatch> {
+        let Some(types) = self.types.as_mut() else {
+            return Ok(());
+        };
+        let found = core::any::type_name::<V>();
+        let mismatch = |expected, registered_by| TypeMismatch {
+            path: path.clone(),
+            expected,
+            found,
+            registered_by,
+            operator,
+        };
+        if let Some((type_id, expected, registered_by)) = types.get(path) {
+            return if *type_id == TypeId::of::<V>() {
+                Ok(())
+            } else {
+                Err(mismatch(expected, registered_by))
+            };
+        }
+        if self.values.get(path).is_some_and(|value| !value.is::<V>()) {
+            return Err(mismatch("<unknown>", "<unknown>"));
+        }
+        types.insert(path.clone(), (TypeId::of::<V>(), found, operator));
+        Ok(())
+    }
+
+    /// Move the value at `path` out of the state, cloning it instead while journaling so that
+    /// it can still be restored.
+    ///
+    /// # Panics
+    /// If the value at `path` is not of type `V`, see [`State::try_take`]
+    pub fn take<V: Any + Clone>(&mut self, path: &Path) -> Option<V> {
+        self.try_take(path)
+            .unwrap_or_else(|mismatch| mismatch_panic(&mismatch))
+    }
+
+    /// Like [`State::take`], reporting a value of another type instead of panicking.
+    ///
+    /// # Errors
+    /// Returns the [`TypeMismatch`] if the value at `path` is not of type `V`
+    pub fn try_take<V: Any + Clone>(&mut self, path: &Path) -> Result<Option<V>, TypeMismatch> {
+        if self.try_get::<V>(path)?.is_none() || self.journal.is_some() {
+            return Ok(self.try_get::<V>(path)?.cloned());
+        }
+        Ok(self
+            .values
+            .remove(path)
+            .and_then(|value| value.downcast::<V>().ok())
+            .map(|value| *value))
+    }
+
+    /// # Panics
+    /// If the value at `path` is not of type `V`, see [`State::try_get`]
     pub fn get<V: Any>(&self, path: &Path) -> Option<&V> {
-        self.last_state.get(path).and_then(|value| {
-            value.downcast_ref::<V>().or_else(|| {
-                panic!(
-                    "Type mismatch in repeat state at path {:?}. \
-                    Expected type '{}' but found different type in stored state. \
-                    This usually indicates the same alignment path is being used \
-                    for different value types across iterations.",
-                    path,
-                    core::any::type_name::<V>()
-                )
-            })
+        self.try_get(path)
+            .unwrap_or_else(|mismatch| mismatch_panic(&mismatch))
+    }
+
+    /// Like [`State::get`], reporting a value of another type instead of panicking.
+    ///
+    /// # Errors
+    /// Returns the [`TypeMismatch`] if the value at `path` is not of type `V`
+    pub fn try_get<V: Any>(&self, path: &Path) -> Result<Option<&V>, TypeMismatch> {
+        let Some(value) = self.values.get(path) else {
+            return Ok(None);
+        };
+        value.downcast_ref::<V>().map(Some).ok_or_else(|| {
+            let (expected, registered_by) = self
+                .types
+                .as_ref()
+                .and_then(|types| types.get(path))
+                .map_or(
+                    ("<unknown>", "<unknown>"),
+                    |(_, expected, registered_by)| (*expected, *registered_by),
+                );
+            TypeMismatch {
+                path: path.clone(),
+                expected,
+                found: core::any::type_name::<V>(),
+                registered_by,
+                operator: "<unknown>",
+            }
         })
     }
 }
+
+// only the accessors kept for compatibility panic, the VM uses the fallible ones
+#[allow(clippy::panic)]
+fn mismatch_panic(mismatch: &TypeMismatch) -> ! {
+    panic!(
+        "Type mismatch in repeat state at path {:?}. \
+        Expected type '{}' but found different type in stored state. \
+        This usually indicates the same alignment path is being used \
+        for different value types across iterations.",
+        mismatch.path, mismatch.found
+    )
+}
+
 impl Default for State {
     fn default() -> Self {
         Self::new()
@@ -72,8 +273,8 @@ mod tests {
     fn test_new_and_default() {
         let s1 = State::new();
         let s2 = State::default();
-        assert_eq!(s1.last_state.len(), 0);
-        assert_eq!(s2.last_state.len(), 0);
+        assert_eq!(s1.values.len(), 0);
+        assert_eq!(s2.values.len(), 0);
     }
 
     #[test]
@@ -95,6 +296,21 @@ mod tests {
         let _ = state.get::<u32>(&path);
     }
 
+    #[test]
+    fn test_registry_reports_type_mismatches() {
+        let mut state = State::new();
+        state.set_type_registry(true);
+        let path = make_path(8);
+        assert_eq!(state.register::<u32>(&path, "repeat"), Ok(()));
+        state.insert(path.clone(), 1u32);
+        assert_eq!(state.register::<u32>(&path, "repeat"), Ok(()));
+        let mismatch = state.register::<f32>(&path, "share").unwrap_err();
+        assert_eq!(mismatch.expected, "u32");
+        assert_eq!(mismatch.found, "f32");
+        assert_eq!(mismatch.registered_by, "repeat");
+        assert_eq!(mismatch.operator, "share");
+    }
+
     #[test]
     fn test_get_none_for_missing_path() {
         let state = State::new();
@@ -102,6 +318,32 @@ mod tests {
         assert_eq!(state.get::<u32>(&path), None);
     }
 
+    #[test]
+    fn test_take_keeps_journaled_values() {
+        let mut state = State::new();
+        state.insert(make_path(7), 1u8);
+        assert_eq!(state.take::<u8>(&make_path(7)), Some(1u8));
+        assert_eq!(state.get::<u8>(&make_path(7)), None);
+        state.set_journaling(true);
+        state.insert(make_path(7), 2u8);
+        assert_eq!(state.take::<u8>(&make_path(7)), Some(2u8));
+        assert_eq!(state.get::<u8>(&make_path(7)), Some(&2u8));
+    }
+
+    #[test]
+    fn test_rollback_restores_the_last_commit() {
+        let mut state = State::new();
+        state.set_journaling(true);
+        state.insert(make_path(5), 1u8);
+        state.commit();
+        state.insert(make_path(5), 2u8);
+        state.insert(make_path(5), 3u8);
+        state.insert(make_path(6), 4u8);
+        state.rollback();
+        assert_eq!(state.get::<u8>(&make_path(5)), Some(&1u8));
+        assert_eq!(state.get::<u8>(&make_path(6)), None);
+    }
+
     #[test]
     fn test_from_snapshot() {
         let path = make_path(4);
@@ -110,4 +352,18 @@ mod tests {
         let state = State::from_snapshot(snapshot);
         assert_eq!(state.get::<u8>(&path), Some(&99u8));
     }
+
+    #[test]
+    fn test_try_accessors_report_type_mismatches() {
+        let mut state = State::new();
+        let path = make_path(9);
+        assert_eq!(state.try_get::<u32>(&path), Ok(None));
+        state.insert(path.clone(), PI);
+        let mismatch = state.try_get::<u32>(&path).unwrap_err();
+        assert_eq!(mismatch.found, "u32");
+        assert_eq!(mismatch.expected, "<unknown>");
+        assert_eq!(state.try_take::<u32>(&path), Err(mismatch));
+        assert_eq!(state.try_take::<f32>(&path), Ok(Some(PI)));
+        assert_eq!(state.try_get::<f32>(&path), Ok(None));
+    }
 }
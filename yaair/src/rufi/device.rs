@@ -0,0 +1,171 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use core::fmt::{self, Display};
+use core::hash::Hash;
+use core::str::FromStr;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Bounds required on device identifiers by the [`Engine`](crate::rufi::engine::Engine) and the
+/// [`Network`](crate::rufi::network::Network) implementations; every type satisfying them
+/// implements it.
+pub trait DeviceId: Ord + Hash + Copy + Serialize + DeserializeOwned {}
+impl<T> DeviceId for T where T: Ord + Hash + Copy + Serialize + DeserializeOwned {}
+
+/// A string that is not a valid [`MacId`] or [`UuidId`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseIdError;
+impl Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid device id")
+    }
+}
+
+/// Device identified by its MAC address, written as `aa:bb:cc:dd:ee:ff`.
+///
+/// Human-readable formats serialize it as a string, the others as its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MacId(pub [u8; 6]);
+impl Display for MacId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for MacId {
+    type Err = ParseIdError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 6];
+        let mut groups = value.split(':');
+        for byte in &mut bytes {
+            *byte = groups.next().and_then(hex_byte).ok_or(ParseIdError)?;
+        }
+        groups.next().map_or(Ok(Self(bytes)), |_| Err(ParseIdError))
+    }
+}
+
+/// Device identified by a UUID, written in the hyphenated form.
+///
+/// Human-readable formats serialize it as a string, the others as its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UuidId(pub [u8; 16]);
+impl Display for UuidId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if matches!(index, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for UuidId {
+    type Err = ParseIdError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0; 16];
+        let mut remaining = bytes.iter_mut();
+        let mut groups = value.split('-');
+        for length in [8, 4, 4, 4, 12] {
+            let group = groups
+                .next()
+                .filter(|group| group.len() == length)
+                .ok_or(ParseIdError)?;
+            for digits in group.as_bytes().chunks(2) {
+                let byte = core::str::from_utf8(digits).ok().and_then(hex_byte);
+                *remaining.next().ok_or(ParseIdError)? = byte.ok_or(ParseIdError)?;
+            }
+        }
+        groups.next().map_or(Ok(Self(bytes)), |_| Err(ParseIdError))
+    }
+}
+
+/// Parse a byte written as exactly two hexadecimal digits.
+fn hex_byte(digits: &str) -> Option<u8> {
+    if digits.len() != 2 || !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(digits, 16).ok()
+}
+
+impl Serialize for MacId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+impl<'de> Deserialize<'de> for MacId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(serde::de::Error::custom)
+        } else {
+            <[u8; 6]>::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+impl Serialize for UuidId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+impl<'de> Deserialize<'de> for UuidId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(serde::de::Error::custom)
+        } else {
+            <[u8; 16]>::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+
+    #[test]
+    fn mac_ids_round_trip_as_strings() {
+        let id: MacId = "00:1a:2B:3c:4d:ff".parse().unwrap();
+        assert_eq!(id, MacId([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0xff]));
+        assert_eq!(id.to_string(), "00:1a:2b:3c:4d:ff");
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "\"00:1a:2b:3c:4d:ff\"");
+        assert_eq!(serde_json::from_str::<MacId>(&json).unwrap(), id);
+        assert!("00:1a:2b:3c:4d".parse::<MacId>().is_err());
+        assert!("00:1a:2b:3c:4d:ff:00".parse::<MacId>().is_err());
+        assert!("00:1a:2b:3c:4d:+f".parse::<MacId>().is_err());
+    }
+
+    #[test]
+    fn uuid_ids_round_trip_as_strings() {
+        let text = "123e4567-e89b-12d3-a456-426614174000";
+        let id: UuidId = text.parse().unwrap();
+        assert_eq!(id.0.first(), Some(&0x12));
+        assert_eq!(id.to_string(), text);
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<UuidId>(&json).unwrap(), id);
+        assert!("123e4567e89b-12d3-a456-426614174000"
+            .parse::<UuidId>()
+            .is_err());
+    }
+}
@@ -0,0 +1,48 @@
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeSet;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
+
+/// Source of the current neighbors of the device, decoupled from the [`Network`] carrying the
+/// exports.
+///
+/// An [`Engine`] with a discovery only uses the messages of the discovered neighbors, and
+/// reports the neighbors that joined or left to programs through
+/// [`VM::joined_neighbors`] and [`VM::left_neighbors`].
+///
+/// [`Network`]: crate::rufi::network::Network
+/// [`Engine`]: crate::rufi::engine::Engine
+/// [`VM::joined_neighbors`]: crate::rufi::aggregate::VM::joined_neighbors
+/// [`VM::left_neighbors`]: crate::rufi::aggregate::VM::left_neighbors
+pub trait Discovery<Id: Ord + Copy> {
+    /// Neighbors of the device at the beginning of a round, `now` being the time of the
+    /// previous round.
+    fn discover(&mut self, now: Duration) -> BTreeSet<Id>;
+}
+
+/// Fixed set of neighbors, e.g. from a deployment plan.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StaticNeighbors<Id: Ord + Copy> {
+    neighbors: BTreeSet<Id>,
+}
+impl<Id: Ord + Copy> StaticNeighbors<Id> {
+    pub fn new(neighbors: impl IntoIterator<Item = Id>) -> Self {
+        Self {
+            neighbors: neighbors.into_iter().collect(),
+        }
+    }
+
+    pub fn insert(&mut self, neighbor: Id) {
+        self.neighbors.insert(neighbor);
+    }
+
+    pub fn remove(&mut self, neighbor: &Id) {
+        self.neighbors.remove(neighbor);
+    }
+}
+impl<Id: Ord + Copy> Discovery<Id> for StaticNeighbors<Id> {
+    fn discover(&mut self, _now: Duration) -> BTreeSet<Id> {
+        self.neighbors.clone()
+    }
+}
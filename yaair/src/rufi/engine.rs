@@ -1,112 +1,1502 @@
 use crate::rufi::aggregate::{AggregateError, VM};
+use crate::rufi::alignment::alignment_stack::AlignmentLimits;
+use crate::rufi::builder::Fnv1a;
+use crate::rufi::data::snapshot::DeviceSnapshot;
+use crate::rufi::device::DeviceId;
+use crate::rufi::discovery::Discovery;
+use crate::rufi::messages::budget::MessageBudget;
+use crate::rufi::messages::inbound::InboundMessage;
+use crate::rufi::messages::outbound::OutboundMessage;
 use crate::rufi::messages::serializer::Serializer;
-use crate::rufi::network::Network;
-use core::hash::Hash;
-use serde::Serialize;
+use crate::rufi::metrics::{Metrics, MetricsSnapshot, RoundMetrics};
+use crate::rufi::network::{Network, NetworkError};
+use crate::rufi::replay::Recorder;
+use crate::rufi::scheduler::{Periodic, Scheduler};
+use crate::rufi::sensors::neighborhood::NeighborhoodReadings;
+use crate::rufi::sensors::typed::TypedEnv;
+use crate::rufi::store::StateStore;
+#[cfg(feature = "stream")]
+use crate::rufi::stream::EngineStream;
+#[cfg(all(feature = "stream", feature = "std"))]
+use crate::rufi::time::SystemClock;
+use crate::rufi::time::{Clock, TimeSensor};
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeSet;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::BTreeSet;
 
-pub struct Engine<Id, Out, Env, S, Net>
+/// Wall clock timing the rounds.
+///
+/// It is only available with `std` and outside of `wasm32-unknown-unknown`, where reading the
+/// time panics; elsewhere no instant is ever taken and nothing is measured.
+mod clock {
+    #[cfg(all(
+        feature = "std",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    ))]
+    mod wall {
+        use core::time::Duration;
+        pub use std::time::Instant;
+
+        // same signature as on the platforms without a wall clock
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn now() -> Option<Instant> {
+            Some(Instant::now())
+        }
+
+        pub fn elapsed(started: Option<Instant>) -> Option<Duration> {
+            started.map(|started| started.elapsed())
+        }
+    }
+
+    #[cfg(not(all(
+        feature = "std",
+        not(all(target_arch = "wasm32", target_os = "unknown"))
+    )))]
+    mod wall {
+        use core::time::Duration;
+
+        /// Instants of a platform without a wall clock, which cannot be taken.
+        #[derive(Debug, Clone, Copy)]
+        pub enum Instant {}
+
+        pub const fn now() -> Option<Instant> {
+            None
+        }
+
+        pub const fn elapsed(_started: Option<Instant>) -> Option<Duration> {
+            None
+        }
+    }
+
+    /// `now` is the current instant and `elapsed` the time since an instant, both `None` without
+    /// a wall clock.
+    pub use wall::{elapsed, now, Instant};
+}
+
+/// State of the delta export mode, see [`Engine::with_delta_export`].
+struct DeltaExport<Id: Ord + Hash + Copy> {
+    full_every: u64,
+    sequence: u64,
+    previous: Option<OutboundMessage<Id>>,
+}
+
+/// Discovery of the neighbors and the neighbors it found in the previous round, see
+/// [`Engine::with_discovery`].
+struct DiscoveryState<Id> {
+    source: Box<dyn Discovery<Id>>,
+    known: BTreeSet<Id>,
+}
+
+/// Metrics sink of the engine with the measurements of the round in progress.
+struct MetricsState<Id> {
+    sink: Box<dyn Metrics>,
+    neighbors: BTreeSet<Id>,
+    started: Option<clock::Instant>,
+}
+
+/// What the [`Engine`] does when a round fails, either because an operator raised an error
+/// (see [`VM::round_error`]) or because the outbound message could not be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Leave the round as executed: operator errors are only visible in the program result and
+    /// whatever was exported before the failure is sent.
+    #[default]
+    Propagate,
+    /// Undo the round, restoring the state of the previous one, and send nothing.
+    SkipRound,
+    /// Undo the round like [`OnError::SkipRound`] and send the outbound message of the last
+    /// successful round again, so that neighbors do not consider the device gone.
+    ResendLast,
+}
+
+/// How the [`Engine`] retries the messages the network failed to send because of a transient
+/// [`NetworkError`], see [`Engine::with_retry_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries of a message before dropping it.
+    pub max_retries: u8,
+    /// Delay before the first retry, doubled at every further retry.
+    pub backoff: Duration,
+}
+
+/// A message waiting to be sent again, see [`RetryPolicy`].
+struct PendingSend {
+    message: Vec<u8>,
+    retries: u8,
+    /// Time of the next retry, set by the first [`Engine::tick`] after the failure.
+    retry_at: Option<Duration>,
+}
+
+/// Outcome of a round executed by [`Engine::cycle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundReport<Out> {
+    /// Result of the program.
+    pub output: Out,
+    /// Size of the serialized outbound message handed to the network.
+    pub outbound_size: usize,
+    /// Neighbors whose messages were used in the round.
+    pub neighbors: usize,
+    /// Time of the round, as sampled from the clock of the engine.
+    pub time: Duration,
+    /// Wall time spent in the round; `None` without `std` and on `wasm32-unknown-unknown`,
+    /// where no monotonic clock is available.
+    pub elapsed: Option<Duration>,
+    /// Why the network could not send the outbound message, `None` if it was sent.
+    pub send_error: Option<NetworkError>,
+}
+
+/// An aggregate program run by the [`Engine`] at every round.
+pub type Program<Env, Id, S, Out> = fn(&Env, &mut VM<Id, S>) -> Out;
+
+/// A program stored by the [`Engine`]: any closure, e.g. built with the
+/// [`compose`](crate::rufi::compose) combinators, besides plain [`Program`]s.
+pub type BoxedProgram<Env, Id, S, Out> = Box<dyn Fn(&Env, &mut VM<Id, S>) -> Out>;
+
+pub struct Engine<Id, Out, Env, S, Net, Sch = Periodic>
 where
-    Id: Ord + Hash + Copy + Serialize + for<'de> serde::Deserialize<'de>,
+    Id: DeviceId,
     S: Serializer,
     Net: Network<Id, S>,
+    Sch: Scheduler,
 {
     local_id: Id,
     network: Net,
-    program: fn(&Env, &mut VM<Id, S>) -> Out,
+    program: BoxedProgram<Env, Id, S, Out>,
+    named_programs: Vec<(String, BoxedProgram<Env, Id, S, Out>)>,
+    named_results: Vec<(String, Out)>,
     vm: VM<Id, S>,
     environment: Env,
+    scheduler: Sch,
+    retention: Option<Duration>,
+    delta: Option<DeltaExport<Id>>,
+    metrics: Option<MetricsState<Id>>,
+    discovery: Option<DiscoveryState<Id>>,
+    on_error: OnError,
+    last_outbound: Option<Vec<u8>>,
+    store: Option<Box<dyn StateStore<Id>>>,
+    /// Whether [`Engine::shutdown`] was called.
+    stopped: bool,
+    retry: Option<RetryPolicy>,
+    pending: Option<PendingSend>,
+    /// Measurements of the round in progress, observed by the scheduler and the metrics.
+    round: RoundMetrics,
+    /// Digest of the last outbound message, to tell whether the exports are changing.
+    outbound_digest: Option<u64>,
 }
 impl<Id, Out, Env, S, Net> Engine<Id, Out, Env, S, Net>
 where
-    Id: Ord + Hash + Copy + Serialize + for<'de> serde::Deserialize<'de>,
+    Id: DeviceId,
     S: Serializer,
     Net: Network<Id, S>,
 {
+    /// Create a new engine scheduled with the default [`Periodic`] policy.
     pub fn new(
         local_id: Id,
         network: Net,
         environment: Env,
         serializer: S,
-        program: fn(&Env, &mut VM<Id, S>) -> Out,
+        program: impl Fn(&Env, &mut VM<Id, S>) -> Out + 'static,
+    ) -> Self {
+        Self::with_boxed_program(
+            local_id,
+            network,
+            environment,
+            serializer,
+            Box::new(program),
+        )
+    }
+
+    /// Like [`Engine::new`], for programs already boxed.
+    pub(crate) fn with_boxed_program(
+        local_id: Id,
+        network: Net,
+        environment: Env,
+        serializer: S,
+        program: BoxedProgram<Env, Id, S, Out>,
     ) -> Self {
         Self {
             local_id,
             network,
             program,
+            named_programs: Vec::new(),
+            named_results: Vec::new(),
             environment,
             vm: VM::new(local_id, serializer),
+            scheduler: Periodic::default(),
+            retention: None,
+            delta: None,
+            metrics: None,
+            discovery: None,
+            on_error: OnError::default(),
+            last_outbound: None,
+            store: None,
+            stopped: false,
+            retry: None,
+            pending: None,
+            round: RoundMetrics::default(),
+            outbound_digest: None,
+        }
+    }
+}
+impl<Id, Out, S, Net> Engine<Id, Out, TypedEnv, S, Net>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+{
+    /// Create a new engine like [`Engine::new`], checking that every entry required by the
+    /// environment is set before any round is executed.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::MissingEnvironment`] listing the missing entries
+    pub fn try_new(
+        local_id: Id,
+        network: Net,
+        environment: TypedEnv,
+        serializer: S,
+        program: impl Fn(&TypedEnv, &mut VM<Id, S>) -> Out + 'static,
+    ) -> Result<Self, AggregateError> {
+        environment.validate()?;
+        Ok(Self::new(
+            local_id,
+            network,
+            environment,
+            serializer,
+            program,
+        ))
+    }
+}
+impl<Id, Out, Env, S, Net, Sch> Engine<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+{
+    /// Replace the scheduling policy of this engine.
+    pub fn with_scheduler<Sch2: Scheduler>(
+        self,
+        scheduler: Sch2,
+    ) -> Engine<Id, Out, Env, S, Net, Sch2> {
+        Engine {
+            local_id: self.local_id,
+            network: self.network,
+            program: self.program,
+            named_programs: self.named_programs,
+            named_results: self.named_results,
+            vm: self.vm,
+            environment: self.environment,
+            scheduler,
+            retention: self.retention,
+            delta: self.delta,
+            metrics: self.metrics,
+            discovery: self.discovery,
+            on_error: self.on_error,
+            last_outbound: self.last_outbound,
+            store: self.store,
+            stopped: self.stopped,
+            retry: self.retry,
+            pending: self.pending,
+            round: self.round,
+            outbound_digest: self.outbound_digest,
         }
     }
 
+    /// Ignore the exports of neighbors whose last message is older than `retention`.
+    ///
+    /// The age of a message is the `lag` reported by [`Network::sense_neighborhood`]; neighbors
+    /// without a lag reading are always kept.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub const fn retention(&self) -> Option<Duration> {
+        self.retention
+    }
+
+    /// Limit the size of the outbound message sent at every round.
+    #[must_use]
+    pub fn with_message_budget(mut self, budget: MessageBudget) -> Self {
+        self.vm.set_message_budget(budget);
+        self
+    }
+
+    /// Only consider the neighbors that also heard from this device, see
+    /// [`VM::set_symmetric_links`].
+    #[must_use]
+    pub const fn with_symmetric_links(mut self) -> Self {
+        self.vm.set_symmetric_links(true);
+        self
+    }
+
+    /// Bound the alignment of every round, see [`VM::set_alignment_limits`].
+    #[must_use]
+    pub const fn with_alignment_limits(mut self, limits: AlignmentLimits) -> Self {
+        self.vm.set_alignment_limits(limits);
+        self
+    }
+
+    /// Include the state values of type `V` in the snapshots of the device, see
+    /// [`VM::register_snapshot_type`].
+    #[must_use]
+    pub fn with_snapshot_type<V>(mut self) -> Self
+    where
+        V: serde::Serialize + serde::de::DeserializeOwned + 'static,
+    {
+        self.vm.register_snapshot_type::<V>();
+        self
+    }
+
+    /// Take a transferable copy of the state and the last exports of the device, see
+    /// [`VM::snapshot`].
+    pub fn snapshot(&self) -> DeviceSnapshot<Id> {
+        self.vm.snapshot()
+    }
+
+    /// Warm start the device from the `snapshot` of the device it replaces, sending its last
+    /// exports right away on behalf of this device; see [`VM::import_snapshot`].
+    ///
+    /// # Errors
+    /// Returns an error if the snapshot cannot be imported or its exports serialized
+    pub fn warm_start(&mut self, snapshot: DeviceSnapshot<Id>) -> Result<(), AggregateError> {
+        let outbound = self.vm.import_snapshot(snapshot)?;
+        let serialized = self.vm.encode_outbound(&outbound)?;
+        self.transmit(serialized)
+            .map_err(AggregateError::NetworkError)
+    }
+
+    /// Persist the state of the device to `store` when the engine shuts down.
+    ///
+    /// Only the state values of the types registered with [`Engine::with_snapshot_type`] are
+    /// persisted.
+    #[must_use]
+    pub fn with_state_store(mut self, store: impl StateStore<Id> + 'static) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    /// Leave the network gracefully: send a [`VM::leave_message`] so that neighbors drop the
+    /// device right away, persist the state to the [`StateStore`], if any, and stop the
+    /// scheduling of rounds by [`Engine::tick`] and [`Engine::run`].
+    ///
+    /// # Errors
+    /// Returns an error if the leave message cannot be serialized or sent, or the state
+    /// persisted; the state is persisted even if the leave message is not sent
+    pub fn shutdown(&mut self) -> Result<(), AggregateError> {
+        self.stopped = true;
+        self.pending = None;
+        let farewell = self.vm.leave_message();
+        let sent = self.vm.encode_outbound(&farewell).and_then(|serialized| {
+            self.network
+                .prepare_outbound(serialized)
+                .map_err(AggregateError::NetworkError)
+        });
+        let snapshot = self.vm.snapshot();
+        if let Some(store) = self.store.as_mut() {
+            store.save(&snapshot)?;
+        }
+        sent
+    }
+
+    /// Whether the engine was shut down, see [`Engine::shutdown`].
+    pub const fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Retry the messages the network fails to send because of a transient [`NetworkError`]
+    /// at the following [`Engine::tick`]s, backing off as prescribed by `policy`.
+    ///
+    /// Only the last message is retried: the one of a new round replaces it.
+    #[must_use]
+    pub const fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Transmit only the paths whose value changed since the previous round.
+    ///
+    /// A full message is still sent every `full_every` rounds, so that neighbors that missed a
+    /// delta (and discard the following ones, see [`OutboundMessage::resolve`]) can recover.
+    #[must_use]
+    pub fn with_delta_export(mut self, full_every: u64) -> Self {
+        self.delta = Some(DeltaExport {
+            full_every,
+            sequence: 0,
+            previous: None,
+        });
+        self
+    }
+
+    /// Select how failed rounds are handled; see [`OnError`].
+    #[must_use]
+    pub fn with_error_policy(mut self, on_error: OnError) -> Self {
+        self.vm.set_rollback(on_error != OnError::Propagate);
+        self.on_error = on_error;
+        self
+    }
+
+    /// Report the measurements of every round executed through [`Engine::cycle`] to `metrics`.
+    ///
+    /// Rounds driven by [`Engine::step_with`] bypass the network and are not measured.
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Some(MetricsState {
+            sink: Box::new(metrics),
+            neighbors: BTreeSet::new(),
+            started: None,
+        });
+        self
+    }
+
+    /// Maintain the neighbor set with `discovery`: messages of undiscovered devices are ignored.
+    ///
+    /// Programs observe the changes of the neighbor set through [`VM::joined_neighbors`] and
+    /// [`VM::left_neighbors`].
+    #[must_use]
+    pub fn with_discovery(mut self, discovery: impl Discovery<Id> + 'static) -> Self {
+        self.discovery = Some(DiscoveryState {
+            source: Box::new(discovery),
+            known: BTreeSet::new(),
+        });
+        self
+    }
+
+    /// Neighbors found by the discovery in the last round, if any.
+    pub fn discovered_neighbors(&self) -> Option<&BTreeSet<Id>> {
+        self.discovery.as_ref().map(|discovery| &discovery.known)
+    }
+
+    /// Snapshot of the measurements collected so far, if metrics are enabled.
+    pub fn metrics(&self) -> Option<MetricsSnapshot> {
+        self.metrics.as_ref().map(|metrics| metrics.sink.snapshot())
+    }
+
+    /// Register a program executed in the same rounds as the main one, replacing any program
+    /// with the same name.
+    ///
+    /// Each named program runs in its own alignment namespace, so its state is isolated and its
+    /// exports are nested under `name` in the outbound message.
+    pub fn add_program(
+        &mut self,
+        name: impl Into<String>,
+        program: impl Fn(&Env, &mut VM<Id, S>) -> Out + 'static,
+    ) {
+        let name = name.into();
+        self.named_programs
+            .retain(|(existing, _)| *existing != name);
+        self.named_results.retain(|(existing, _)| *existing != name);
+        self.named_programs.push((name, Box::new(program)));
+    }
+
+    /// Result of the named program `name` in the last round.
+    pub fn program_result(&self, name: &str) -> Option<&Out> {
+        self.named_results
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, result)| result)
+    }
+
     pub const fn get_local_id(&self) -> Id {
         self.local_id
     }
 
-    pub fn cycle(&mut self) -> Result<Out, AggregateError> {
-        let inbound = self.network.prepare_inbound();
-        let result = (self.program)(&self.environment, &mut self.vm);
-        let serialized_outbound = self.vm.get_outbound()?;
-        self.network.prepare_outbound(serialized_outbound);
-        self.vm.prepare_new_round(inbound);
-        Ok(result)
+    pub const fn environment(&self) -> &Env {
+        &self.environment
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::rufi::messages::inbound::InboundMessage;
-    #[cfg(not(feature = "std"))]
-    use alloc::vec::Vec;
-    use core::fmt::{self, Display};
+    /// Mutable access to the environment, e.g. to update sensors or collect actuations between rounds.
+    pub const fn environment_mut(&mut self) -> &mut Env {
+        &mut self.environment
+    }
+
+    pub const fn scheduler(&self) -> &Sch {
+        &self.scheduler
+    }
+
+    /// Mutable access to the scheduler, e.g. to fire an [`ExternalTrigger`](crate::rufi::scheduler::ExternalTrigger).
+    pub const fn scheduler_mut(&mut self) -> &mut Sch {
+        &mut self.scheduler
+    }
+
+    /// Execute a round unconditionally, regardless of the scheduling policy.
+    pub fn cycle(&mut self) -> Result<RoundReport<Out>, AggregateError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("cycle").entered();
+        let started = clock::now();
+        let inbound = self.receive();
+        let (output, serialized_outbound) = self.execute(inbound)?;
+        Ok(self.complete(started, output, serialized_outbound))
+    }
+
+    /// Send the outbound message of a round started at `started`, reporting its outcome.
+    fn complete(
+        &mut self,
+        started: Option<clock::Instant>,
+        output: Out,
+        serialized_outbound: Vec<u8>,
+    ) -> RoundReport<Out> {
+        // the VM drops the leaving and asymmetric neighbors from the received ones
+        let neighbors = self.vm.neighbors().count();
+        self.round.neighbors = neighbors;
+        let outbound_size = serialized_outbound.len();
+        let send_error = self.send(serialized_outbound);
+        RoundReport {
+            output,
+            outbound_size,
+            neighbors,
+            time: self.vm.current_time(),
+            elapsed: clock::elapsed(started),
+            send_error,
+        }
+    }
+
+    /// Collect the inbound message and the neighborhood readings from the network, dropping
+    /// neighbors older than the retention.
+    fn receive(&mut self) -> InboundMessage<Id> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("receive", neighbors = tracing::field::Empty).entered();
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.started = clock::now();
+        }
+        let mut inbound = self.network.prepare_inbound();
+        let readings = self.network.sense_neighborhood();
+        if let Some(retention) = self.retention {
+            inbound.retain(|id| {
+                readings
+                    .get(id)
+                    .and_then(|reading| reading.lag)
+                    .is_none_or(|lag| lag <= retention)
+            });
+        }
+        self.vm.update_neighborhood(readings);
+        if let Some(discovery) = self.discovery.as_mut() {
+            let neighbors = discovery.source.discover(self.vm.current_time());
+            inbound.retain(|id| neighbors.contains(id));
+            let joined = neighbors.difference(&discovery.known).copied().collect();
+            let left = discovery.known.difference(&neighbors).copied().collect();
+            self.vm.update_membership(joined, left);
+            discovery.known = neighbors;
+        }
+        #[cfg(feature = "tracing")]
+        span.record("neighbors", inbound.len());
+        self.round = RoundMetrics {
+            bytes_in: inbound.payload_size(),
+            neighbors: inbound.len(),
+            ..RoundMetrics::default()
+        };
+        if let Some(metrics) = self.metrics.as_mut() {
+            let neighbors: BTreeSet<Id> = inbound.neighbors().collect();
+            self.round.joined = neighbors.difference(&metrics.neighbors).count();
+            self.round.left = metrics.neighbors.difference(&neighbors).count();
+            metrics.neighbors = neighbors;
+        }
+        inbound
+    }
+
+    /// Execute a round of a cycle, reporting failures to the metrics and recovering from them
+    /// as prescribed by the error policy.
+    fn execute(&mut self, inbound: InboundMessage<Id>) -> Result<(Out, Vec<u8>), AggregateError> {
+        let executed = self.step_with(inbound);
+        if let Err(error) = &executed {
+            if let Some(metrics) = self.metrics.as_mut() {
+                metrics.sink.round_failed(error);
+            }
+            match self.on_error {
+                OnError::Propagate => {}
+                OnError::SkipRound => self.vm.rollback_round(),
+                OnError::ResendLast => {
+                    self.vm.rollback_round();
+                    if let Some(last_outbound) = self.last_outbound.clone() {
+                        // a failed resend is retried like a regular send
+                        let _ = self.transmit(last_outbound);
+                    }
+                }
+            }
+        }
+        executed
+    }
 
-    // Dummy Serializer
-    #[derive(Clone, Copy)]
-    struct DummySerializer;
-    #[derive(Debug)]
-    struct DummyError;
-    impl Display for DummyError {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "DummyError")
+    /// Hand the serialized outbound message of the round to the network.
+    ///
+    /// # Returns
+    /// Why the network could not send the message, if it failed
+    fn send(&mut self, serialized_outbound: Vec<u8>) -> Option<NetworkError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("send", bytes = serialized_outbound.len()).entered();
+        self.round.bytes_out = serialized_outbound.len();
+        let mut hasher = Fnv1a::default();
+        hasher.write(&serialized_outbound);
+        let digest = hasher.finish();
+        self.round.changed = self.outbound_digest.replace(digest) != Some(digest);
+        if self.on_error == OnError::ResendLast {
+            self.last_outbound = Some(serialized_outbound.clone());
+        }
+        let sent = self.transmit(serialized_outbound);
+        self.round.send_failed = sent.is_err();
+        if let Some(metrics) = self.metrics.as_mut() {
+            self.round.latency = clock::elapsed(metrics.started.take());
+            metrics.sink.round_completed(&self.round);
+        }
+        sent.err()
+    }
+
+    /// Hand `message` to the network, keeping it to be retried if the send fails because of a
+    /// transient error and a [`RetryPolicy`] is set.
+    fn transmit(&mut self, message: Vec<u8>) -> Result<(), NetworkError> {
+        let copy = self.retry.is_some().then(|| message.clone());
+        let sent = self.network.prepare_outbound(message);
+        self.pending = copy
+            .filter(|_| sent.as_ref().is_err_and(NetworkError::is_transient))
+            .map(|copy| PendingSend {
+                message: copy,
+                retries: 0,
+                retry_at: None,
+            });
+        sent
+    }
+
+    /// Send the pending message again if its backoff elapsed at `now`.
+    fn retry_pending(&mut self, now: Duration) {
+        let (Some(policy), Some(waiting)) = (self.retry, self.pending.as_mut()) else {
+            return;
+        };
+        let retry_at = *waiting
+            .retry_at
+            .get_or_insert_with(|| now.saturating_add(policy.backoff));
+        if now < retry_at {
+            return;
+        }
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let sent = self.network.prepare_outbound(pending.message.clone());
+        let retries = pending.retries.saturating_add(1);
+        if sent.is_err_and(|error| error.is_transient()) && retries < policy.max_retries {
+            let backoff = policy
+                .backoff
+                .saturating_mul(2u32.saturating_pow(u32::from(retries)));
+            self.pending = Some(PendingSend {
+                message: pending.message,
+                retries,
+                retry_at: Some(now.saturating_add(backoff)),
+            });
         }
     }
-    impl Serializer for DummySerializer {
-        type Error = DummyError;
-        fn serialize<T: serde::Serialize>(&self, _value: &T) -> Result<Vec<u8>, Self::Error> {
-            Ok(Vec::new())
+
+    /// Replace the clock providing the time of every round to the program.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.vm.set_clock(clock);
+    }
+
+    /// Update the neighborhood readings used by the next rounds.
+    ///
+    /// Only needed when driving the engine through [`Engine::step_with`]; otherwise readings are
+    /// collected from the network at every cycle.
+    pub fn update_neighborhood(&mut self, readings: NeighborhoodReadings<Id>) {
+        self.vm.update_neighborhood(readings);
+    }
+
+    /// Execute a round on a host-supplied inbound message, bypassing the [`Network`].
+    ///
+    /// Intended for hosts that already own the transport (actors, game loops): the serialized
+    /// outbound message is returned to the caller instead of being handed to the network.
+    ///
+    /// # Returns
+    /// The program result together with the serialized outbound message
+    ///
+    /// # Errors
+    /// Unless the error policy is [`OnError::Propagate`], the first error raised by an operator
+    /// fails the round
+    pub fn step_with(
+        &mut self,
+        inbound: InboundMessage<Id>,
+    ) -> Result<(Out, Vec<u8>), AggregateError> {
+        self.vm.prepare_new_round(inbound);
+        let result = (self.program)(&self.environment, &mut self.vm);
+        self.named_results.clear();
+        for (name, program) in &self.named_programs {
+            let named_result = self
+                .vm
+                .namespace(name.as_str(), |vm| program(&self.environment, vm));
+            self.named_results.push((name.clone(), named_result));
         }
-        fn deserialize<T: for<'de> serde::Deserialize<'de>>(
-            &self,
-            _value: &[u8],
-        ) -> Result<T, Self::Error> {
-            Err(DummyError)
+        if self.on_error != OnError::Propagate {
+            if let Some(error) = self.vm.round_error() {
+                return Err(error.clone());
+            }
         }
+        let serialized_outbound = self.encode_outbound()?;
+        Ok((result, serialized_outbound))
+    }
+
+    /// Serialize the outbound message of the round, as a delta if enabled.
+    fn encode_outbound(&mut self) -> Result<Vec<u8>, AggregateError> {
+        let Some(delta) = self.delta.as_mut() else {
+            return self.vm.get_outbound();
+        };
+        let mut current = self.vm.outbound().clone();
+        current.set_sequence(delta.sequence);
+        let full_round = delta
+            .sequence
+            .checked_rem(delta.full_every)
+            .is_none_or(|remainder| remainder == 0);
+        let serialized = match &delta.previous {
+            Some(previous) if !full_round => self.vm.encode_outbound(&current.delta_from(previous)),
+            _ => self.vm.encode_outbound(&current),
+        }?;
+        delta.sequence = delta.sequence.wrapping_add(1);
+        delta.previous = Some(current);
+        Ok(serialized)
     }
 
+    /// Execute a round only if the scheduler considers it due at `now`.
+    ///
+    /// # Returns
+    /// `None` if no round was due or the engine was shut down, the round result otherwise
+    pub fn tick(&mut self, now: Duration) -> Option<Result<RoundReport<Out>, AggregateError>> {
+        if self.stopped {
+            return None;
+        }
+        self.retry_pending(now);
+        if !self
+            .scheduler
+            .is_due(now, self.network.has_pending_inbound())
+        {
+            return None;
+        }
+        let result = self.cycle();
+        if result.is_ok() {
+            self.scheduler.observe(&self.round);
+        }
+        self.scheduler.round_executed(now);
+        Some(result)
+    }
+
+    /// Drive the engine with the wall clock, sleeping between rounds as suggested by the scheduler.
+    ///
+    /// `on_round` is invoked with the result of every round; returning `false` stops the loop.
+    /// Nothing runs once the engine was shut down.
+    /// Schedulers without a time-based wakeup are polled every `poll_interval`.
+    ///
+    /// Browsers cannot block: on `wasm32-unknown-unknown` drive [`Engine::cycle`] from the
+    /// JavaScript event loop instead.
+    #[cfg(feature = "std")]
+    pub fn run<F>(&mut self, poll_interval: Duration, mut on_round: F)
+    where
+        F: FnMut(Result<RoundReport<Out>, AggregateError>) -> bool,
+    {
+        let epoch = std::time::Instant::now();
+        while !self.stopped {
+            let now = epoch.elapsed();
+            if let Some(result) = self.tick(now) {
+                if !on_round(result) {
+                    return;
+                }
+            }
+            let after_round = epoch.elapsed();
+            let wait = self
+                .scheduler
+                .next_wakeup(after_round)
+                .map_or(poll_interval, |wakeup| {
+                    wakeup.saturating_sub(after_round).min(poll_interval)
+                });
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Turn the engine into an asynchronous [`Stream`](futures_core::Stream) of round results,
+    /// waiting between rounds like [`Engine::run`] without blocking the caller.
+    ///
+    /// Waits are served by a helper thread, so that the stream can be consumed by any async
+    /// runtime; use [`Engine::into_stream_with`] to rely on the timers of the runtime instead.
+    #[cfg(all(feature = "stream", feature = "std"))]
+    pub fn into_stream(self, poll_interval: Duration) -> EngineStream<Id, Out, Env, S, Net, Sch> {
+        EngineStream::new(
+            self,
+            poll_interval,
+            SystemClock::new(),
+            crate::rufi::stream::ThreadSleep::new,
+        )
+    }
+
+    /// Turn the engine into an asynchronous [`Stream`](futures_core::Stream) of round results,
+    /// reading the time from `clock` and awaiting the futures returned by `sleep` between rounds.
+    #[cfg(feature = "stream")]
+    pub fn into_stream_with<F, Fut>(
+        self,
+        poll_interval: Duration,
+        clock: impl Clock + 'static,
+        sleep: F,
+    ) -> EngineStream<Id, Out, Env, S, Net, Sch>
+    where
+        F: FnMut(Duration) -> Fut + 'static,
+        Fut: core::future::Future<Output = ()> + 'static,
+    {
+        EngineStream::new(self, poll_interval, clock, sleep)
+    }
+}
+
+impl<Id, Out, Env, S, Net, Sch> Engine<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    Env: Clone,
+    Out: Clone,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+{
+    /// Execute a round like [`Engine::cycle`], recording its inputs and result in `recorder` so
+    /// that it can be re-executed by a [`Replayer`](crate::rufi::replay::Replayer).
+    pub fn cycle_recorded(
+        &mut self,
+        recorder: &mut Recorder<Id, Env, Out>,
+    ) -> Result<RoundReport<Out>, AggregateError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("cycle").entered();
+        let started = clock::now();
+        let inbound = self.receive();
+        let environment = self.environment.clone();
+        let (result, serialized_outbound) = self.execute(inbound.clone())?;
+        recorder.record(
+            self.vm.current_time(),
+            inbound,
+            self.vm.neighborhood().clone(),
+            environment,
+            result.clone(),
+        );
+        Ok(self.complete(started, result, serialized_outbound))
+    }
+}
+
+#[cfg(feature = "audit")]
+impl<Id, Out, Env, S, Net, Sch> Engine<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    Out: serde::Serialize,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+{
+    /// Execute a round like [`Engine::cycle`], recording its result and outbound message in `log`.
+    pub fn cycle_audited(
+        &mut self,
+        log: &mut crate::rufi::audit::AuditLog,
+    ) -> Result<RoundReport<Out>, AggregateError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("cycle").entered();
+        let started = clock::now();
+        let inbound = self.receive();
+        let (result, serialized_outbound) = self.execute(inbound)?;
+        log.record(&self.vm.serialize_value(&result)?, &serialized_outbound);
+        Ok(self.complete(started, result, serialized_outbound))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::Aggregate;
+    use crate::rufi::messages::path::Path;
+    use crate::rufi::messages::valuetree::ValueTree;
+    use crate::rufi::messages::Map;
+    use crate::rufi::network::NoNetwork;
+    use crate::rufi::scheduler::ExternalTrigger;
+    use crate::rufi::test_utils::MockSerializer;
+    use core::cell::Cell;
+    use std::rc::Rc;
+
     // Dummy Network
     struct DummyNetwork;
     impl<Id, S> Network<Id, S> for DummyNetwork
     where
-        Id: Ord + Hash + Copy + Serialize + for<'de> serde::Deserialize<'de>,
+        Id: DeviceId,
         S: Serializer,
     {
-        fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) {}
+        fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+            Ok(())
+        }
 
         fn prepare_inbound(&mut self) -> InboundMessage<Id> {
             InboundMessage::default()
         }
     }
 
+    /// Network whose sends always fail because it has no connection.
+    struct DisconnectedNetwork;
+    impl Network<u32, MockSerializer> for DisconnectedNetwork {
+        fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+            Err(NetworkError::Disconnected)
+        }
+
+        fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+            InboundMessage::default()
+        }
+    }
+
     #[test]
     fn test_new_and_get_local_id() {
-        let engine = Engine::new(1u32, DummyNetwork, (), DummySerializer, |_env, _vm| 42u8);
+        let engine = Engine::new(1u32, DummyNetwork, (), MockSerializer, |_env, _vm| 42u8);
         assert_eq!(engine.get_local_id(), 1u32);
     }
 
     #[test]
     fn test_cycle() {
-        let mut engine = Engine::new(2u32, DummyNetwork, (), DummySerializer, |_env, _vm| 99u8);
-        let result = engine.cycle();
-        assert_eq!(result, Ok(99u8));
+        let mut engine = Engine::new(2u32, DummyNetwork, (), MockSerializer, |_env, _vm| 99u8);
+        let report = engine.cycle().unwrap();
+        assert_eq!(report.output, 99u8);
+        assert_eq!(report.neighbors, 0);
+        assert_eq!(report.time, Duration::from_secs(1));
+        // only measured on the platforms with a wall clock
+        assert_eq!(report.elapsed.is_some(), clock::now().is_some());
+    }
+
+    #[test]
+    fn test_cycle_reports_the_neighbors_used_by_the_round() {
+        struct Leaving;
+        impl Network<u32, MockSerializer> for Leaving {
+            fn prepare_outbound(&mut self, _message: Vec<u8>) -> Result<(), NetworkError> {
+                Ok(())
+            }
+
+            fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+                let leaving = VM::new(1u32, MockSerializer).leave_message();
+                InboundMessage::new(Map::from([
+                    (1, ValueTree::from(leaving)),
+                    (2, ValueTree::empty()),
+                ]))
+            }
+        }
+        let mut engine = Engine::new(0u32, Leaving, (), MockSerializer, |_env, _vm| ());
+        assert_eq!(engine.cycle().unwrap().neighbors, 1);
+    }
+
+    /// Network where neighbor 1 exports an undecodable value while `faulty` is set.
+    struct FlakyNetwork {
+        faulty: Rc<Cell<bool>>,
+        sent: Rc<Cell<usize>>,
+    }
+    impl Network<u32, MockSerializer> for FlakyNetwork {
+        fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+            self.sent.set(self.sent.get().saturating_add(1));
+            Ok(())
+        }
+
+        fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+            if !self.faulty.get() {
+                return InboundMessage::default();
+            }
+            let export = ValueTree::new(Map::from([(
+                Path::from("repeat:0/neighboring:0"),
+                b"x".to_vec(),
+            )]));
+            InboundMessage::new(Map::from([(1, export)]))
+        }
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn counting(_env: &(), vm: &mut VM<u32, MockSerializer>) -> Result<u8, AggregateError> {
+        let mut failure = None;
+        let count = vm.repeat(&0u8, |count, vm| {
+            failure = vm.neighboring(&count).err();
+            count.saturating_add(1)
+        });
+        failure.map_or(Ok(count), Err)
+    }
+
+    /// Results of three rounds of `counting`, the second one failing, and the messages sent.
+    fn flaky_rounds(on_error: OnError) -> (Vec<Option<u8>>, usize) {
+        let faulty = Rc::new(Cell::new(false));
+        let sent = Rc::new(Cell::new(0));
+        let network = FlakyNetwork {
+            faulty: Rc::clone(&faulty),
+            sent: Rc::clone(&sent),
+        };
+        let mut engine =
+            Engine::new(7u32, network, (), MockSerializer, counting).with_error_policy(on_error);
+        let results = [false, true, false]
+            .into_iter()
+            .map(|fault| {
+                faulty.set(fault);
+                engine.cycle().and_then(|report| report.output).ok()
+            })
+            .collect();
+        (results, sent.get())
+    }
+
+    #[test]
+    fn test_error_policies() {
+        // the failed round still counts and sends its partial export
+        assert_eq!(
+            flaky_rounds(OnError::Propagate),
+            (vec![Some(1), None, Some(3)], 3)
+        );
+        assert_eq!(
+            flaky_rounds(OnError::SkipRound),
+            (vec![Some(1), None, Some(2)], 2)
+        );
+        assert_eq!(
+            flaky_rounds(OnError::ResendLast),
+            (vec![Some(1), None, Some(2)], 3)
+        );
+    }
+
+    #[test]
+    fn test_tick_follows_scheduler() {
+        let mut engine = Engine::new(3u32, DummyNetwork, (), MockSerializer, |_env, _vm| 7u8)
+            .with_scheduler(Periodic::new(Duration::from_secs(2)));
+        assert_eq!(
+            engine
+                .tick(Duration::from_secs(0))
+                .map(|round| round.map(|report| report.output)),
+            Some(Ok(7u8))
+        );
+        assert_eq!(engine.tick(Duration::from_secs(1)), None);
+        assert_eq!(
+            engine
+                .tick(Duration::from_secs(2))
+                .map(|round| round.map(|report| report.output)),
+            Some(Ok(7u8))
+        );
+    }
+
+    #[test]
+    fn test_tick_with_external_trigger() {
+        let mut engine = Engine::new(4u32, DummyNetwork, (), MockSerializer, |_env, _vm| 1u8)
+            .with_scheduler(ExternalTrigger::new());
+        assert_eq!(engine.tick(Duration::from_secs(0)), None);
+        engine.scheduler_mut().trigger();
+        assert_eq!(
+            engine
+                .tick(Duration::from_secs(0))
+                .map(|round| round.map(|report| report.output)),
+            Some(Ok(1u8))
+        );
+        assert_eq!(engine.tick(Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn test_step_with_returns_outbound() {
+        let mut engine = Engine::new(5u32, NoNetwork, (), MockSerializer, |_env, _vm| 3u8);
+        let (result, outbound) = engine.step_with(InboundMessage::default()).unwrap();
+        assert_eq!(result, 3u8);
+        let message = OutboundMessage::<u32>::decode(&MockSerializer, &outbound).unwrap();
+        assert!(message.is_empty());
+    }
+
+    #[test]
+    fn test_program_reads_sensors_and_writes_actuators() {
+        use crate::rufi::sensors::local::{Actuators, InMemoryContext, Sensors};
+        let context = InMemoryContext::new().with_sensor("source", true);
+        let mut engine = Engine::new(6u32, NoNetwork, context, MockSerializer, |env, _vm| {
+            let source = env.sense::<bool>("source").unwrap_or(false);
+            env.actuate("led", source);
+            source
+        });
+        assert_eq!(engine.cycle().map(|report| report.output), Ok(true));
+        assert_eq!(engine.environment().actuation::<bool>("led"), Some(true));
+        engine.environment_mut().set_sensor("source", false);
+        assert_eq!(engine.cycle().map(|report| report.output), Ok(false));
+    }
+
+    #[test]
+    fn test_typed_environment_is_validated_at_startup() {
+        use crate::rufi::sensors::typed::EnvKey;
+        struct Source;
+        impl EnvKey for Source {
+            type Value = bool;
+
+            fn name() -> &'static str {
+                "source"
+            }
+        }
+        let program: Program<TypedEnv, u32, MockSerializer, bool> =
+            |env, _vm| env.get::<Source>().copied().unwrap_or_default();
+        let missing = Engine::try_new(
+            8u32,
+            NoNetwork,
+            TypedEnv::new().require::<Source>(),
+            MockSerializer,
+            program,
+        );
+        assert_eq!(
+            missing.err(),
+            Some(AggregateError::MissingEnvironment(vec!["source".into()]))
+        );
+        let environment = TypedEnv::new().require::<Source>().with::<Source>(true);
+        let mut engine =
+            Engine::try_new(8u32, NoNetwork, environment, MockSerializer, program).unwrap();
+        assert_eq!(engine.cycle().map(|report| report.output), Ok(true));
+    }
+
+    #[test]
+    fn test_named_programs_are_isolated() {
+        use crate::rufi::messages::outbound::OutboundMessage;
+        let mut engine = Engine::new(7u32, NoNetwork, (), MockSerializer, |_env, vm| {
+            vm.repeat(&0u32, |count, _| count + 1)
+        });
+        engine.add_program("doubler", |_env, vm| vm.repeat(&1, |value, _| value * 2));
+        engine.add_program("shared", |_env, vm| {
+            vm.share(&7, |_, field| *field.local()).unwrap()
+        });
+        engine.cycle().unwrap();
+        let (result, outbound) = engine.step_with(InboundMessage::default()).unwrap();
+        assert_eq!(result, 2);
+        assert_eq!(engine.program_result("doubler"), Some(&4));
+        assert_eq!(engine.program_result("missing"), None);
+        let outbound: OutboundMessage<u32> = MockSerializer.deserialize(&outbound).unwrap();
+        assert!(outbound.at(&Path::from("shared:0/share:0")).is_some());
+    }
+
+    #[test]
+    fn test_delta_export_sends_only_changes() {
+        let mut engine = Engine::new(3u32, NoNetwork, (), MockSerializer, |_env, vm| {
+            let rounds = vm.repeat(&0u32, |count, _| count + 1);
+            vm.neighboring(&7u8).unwrap();
+            vm.neighboring(&rounds).unwrap();
+        })
+        .with_delta_export(3);
+        let decode = |running: &mut Engine<_, _, _, _, _>| {
+            let ((), bytes) = running.step_with(InboundMessage::default()).unwrap();
+            MockSerializer
+                .deserialize::<OutboundMessage<u32>>(&bytes)
+                .unwrap()
+        };
+        let first = decode(&mut engine);
+        let second = decode(&mut engine);
+        assert!(!first.is_delta());
+        assert!(second.is_delta());
+        assert!(second.at(&Path::from("neighboring:1")).is_none());
+        assert!(second.at(&Path::from("neighboring:2")).is_some());
+
+        let base = ValueTree::from(first.clone());
+        let merged = second
+            .clone()
+            .resolve(Some((first.sequence(), &base)))
+            .unwrap();
+        assert_eq!(
+            merged.get(&Path::from("neighboring:1")),
+            Some(b"7".as_slice())
+        );
+        assert_eq!(
+            merged.get(&Path::from("neighboring:2")),
+            Some(b"2".as_slice())
+        );
+        assert!(second.resolve(None).is_none());
+        assert!(decode(&mut engine).is_delta());
+        assert!(!decode(&mut engine).is_delta());
+    }
+
+    #[test]
+    fn test_metrics_track_traffic_and_churn() {
+        use crate::rufi::messages::budget::OverflowPolicy;
+        use crate::rufi::metrics::InMemoryMetrics;
+
+        /// Neighbors 1 and 2 in the first round, then only neighbor 2.
+        struct ChurnNetwork(u32);
+        impl Network<u32, MockSerializer> for ChurnNetwork {
+            fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+                Ok(())
+            }
+
+            fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+                let export = || ValueTree::new(Map::from([(Path::from("share:0"), b"1".to_vec())]));
+                self.0 = self.0.saturating_add(1);
+                let neighbors = if self.0 == 1 { vec![1, 2] } else { vec![2] };
+                InboundMessage::new(neighbors.into_iter().map(|id| (id, export())).collect())
+            }
+        }
+
+        let program: Program<(), u32, MockSerializer, u8> =
+            |_env, vm| vm.share(&0u8, |_, field| *field.local()).unwrap();
+        let mut engine = Engine::new(0u32, ChurnNetwork(0), (), MockSerializer, program)
+            .with_metrics(InMemoryMetrics::new());
+        engine.cycle().unwrap();
+        engine.cycle().unwrap();
+        let snapshot = engine.metrics().unwrap();
+        assert_eq!(snapshot.rounds, 2);
+        assert_eq!(snapshot.bytes_in, 3);
+        assert!(snapshot.bytes_out > 0);
+        assert_eq!(snapshot.neighbors, 1);
+        assert_eq!((snapshot.joined, snapshot.left), (2, 1));
+        assert_eq!(snapshot.last_latency.is_some(), clock::now().is_some());
+
+        let mut failing = Engine::new(0u32, ChurnNetwork(0), (), MockSerializer, program)
+            .with_message_budget(MessageBudget::new(1, OverflowPolicy::Error))
+            .with_metrics(InMemoryMetrics::new());
+        assert!(failing.cycle().is_err());
+        assert_eq!(failing.metrics().map(|failures| failures.errors), Some(1));
+        assert!(Engine::new(0u32, NoNetwork, (), MockSerializer, program)
+            .metrics()
+            .is_none());
+    }
+
+    #[test]
+    fn discovery_selects_the_neighbors() {
+        use crate::rufi::discovery::StaticNeighbors;
+
+        struct Chatty;
+        impl Network<u32, MockSerializer> for Chatty {
+            fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+                Ok(())
+            }
+
+            fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+                InboundMessage::new(Map::from([
+                    (2, ValueTree::empty()),
+                    (3, ValueTree::empty()),
+                ]))
+            }
+        }
+
+        let program: Program<(), u32, MockSerializer, Vec<u32>> =
+            |_env, vm| vm.joined_neighbors().iter().copied().collect();
+        let mut engine = Engine::new(0u32, Chatty, (), MockSerializer, program)
+            .with_discovery(StaticNeighbors::new([1, 2]));
+        let report = engine.cycle().unwrap();
+        assert_eq!((report.neighbors, report.output), (1, vec![1, 2]));
+        assert_eq!(engine.cycle().unwrap().output, Vec::<u32>::new());
+        assert_eq!(engine.discovered_neighbors(), Some(&BTreeSet::from([1, 2])));
+    }
+
+    #[test]
+    fn test_transient_send_failures_are_retried_with_backoff() {
+        /// Network congested for the first `congested` sends, counting the attempts.
+        struct Congested {
+            congested: usize,
+            attempts: Rc<Cell<usize>>,
+        }
+        impl Network<u32, MockSerializer> for Congested {
+            fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+                let attempts = self.attempts.get().saturating_add(1);
+                self.attempts.set(attempts);
+                if attempts <= self.congested {
+                    Err(NetworkError::Congested)
+                } else {
+                    Ok(())
+                }
+            }
+
+            fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+                InboundMessage::default()
+            }
+        }
+
+        let attempts = Rc::new(Cell::new(0));
+        let network = Congested {
+            congested: 2,
+            attempts: Rc::clone(&attempts),
+        };
+        let mut engine = Engine::new(1u32, network, (), MockSerializer, |_env, _vm| 0u8)
+            .with_scheduler(ExternalTrigger::new())
+            .with_retry_policy(RetryPolicy {
+                max_retries: 3,
+                backoff: Duration::from_secs(1),
+            });
+        let report = engine.cycle().unwrap();
+        assert_eq!(report.send_error, Some(NetworkError::Congested));
+        // the backoff starts at the first tick, then doubles after the failed retry
+        for (now, expected_attempts) in [(0, 1), (1, 2), (2, 2), (3, 3), (10, 3)] {
+            assert!(engine.tick(Duration::from_secs(now)).is_none());
+            assert_eq!(attempts.get(), expected_attempts);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_announces_leave_and_persists_state() {
+        use crate::rufi::messages::budget::OverflowPolicy;
+        use crate::rufi::store::{MemoryStore, StateStore};
+        use std::cell::RefCell;
+
+        struct Recording(Rc<RefCell<Vec<Vec<u8>>>>);
+        impl Network<u32, MockSerializer> for Recording {
+            fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+                self.0.borrow_mut().push(outbound_message);
+                Ok(())
+            }
+
+            fn prepare_inbound(&mut self) -> InboundMessage<u32> {
+                InboundMessage::default()
+            }
+        }
+        struct Shared(Rc<RefCell<MemoryStore<u32>>>);
+        impl StateStore<u32> for Shared {
+            fn save(&mut self, snapshot: &DeviceSnapshot<u32>) -> Result<(), AggregateError> {
+                self.0.borrow_mut().save(snapshot)
+            }
+
+            fn load(&mut self) -> Result<Option<DeviceSnapshot<u32>>, AggregateError> {
+                self.0.borrow_mut().load()
+            }
+        }
+
+        let store = Rc::new(RefCell::new(MemoryStore::new()));
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new(
+            1u32,
+            Recording(Rc::clone(&sent)),
+            (),
+            MockSerializer,
+            |_env, vm| vm.repeat(&0u8, |count, _| count.saturating_add(1)),
+        )
+        .with_snapshot_type::<u8>()
+        .with_state_store(Shared(Rc::clone(&store)));
+        engine.cycle().unwrap();
+        engine.shutdown().unwrap();
+        assert!(engine.is_stopped());
+        assert!(engine.tick(Duration::from_secs(5)).is_none());
+        let saved = store.borrow_mut().load().unwrap();
+        assert_eq!(saved.map(|snapshot| snapshot.entries.len()), Some(1));
+
+        let farewell = sent.borrow().last().cloned().unwrap();
+        let message = OutboundMessage::<u32>::decode(&MockSerializer, &farewell).unwrap();
+        let inbound = InboundMessage::new(Map::from([(1u32, ValueTree::from(message))]));
+        let mut neighbor = VM::new(2u32, MockSerializer);
+        neighbor.prepare_new_round(inbound);
+        assert_eq!(neighbor.neighboring(&0u8).unwrap().size(), 1);
+
+        // the state is persisted even if the leave message does not fit the budget
+        let unsent = Rc::new(RefCell::new(MemoryStore::new()));
+        let mut over_budget = Engine::new(
+            1u32,
+            Recording(Rc::clone(&sent)),
+            (),
+            MockSerializer,
+            |_env, vm| vm.repeat(&0u8, |count, _| count.saturating_add(1)),
+        )
+        .with_message_budget(MessageBudget::new(1, OverflowPolicy::Error))
+        .with_state_store(Shared(Rc::clone(&unsent)));
+        assert!(matches!(
+            over_budget.shutdown(),
+            Err(AggregateError::MessageBudgetExceeded(_))
+        ));
+        assert!(unsent.borrow_mut().load().unwrap().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_cycle_is_traced() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        /// Collects the name of every span, followed by its `operator` field if any.
+        struct Spans(Arc<Mutex<Vec<String>>>);
+        impl Visit for Spans {
+            fn record_debug(&mut self, _field: &Field, _value: &dyn core::fmt::Debug) {}
+
+            fn record_str(&mut self, field: &Field, value: &str) {
+                if field.name() == "operator" {
+                    self.0.lock().unwrap().push(value.to_owned());
+                }
+            }
+        }
+        impl tracing::Subscriber for Spans {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name().to_owned());
+                let id = Id::from_u64(u64::try_from(names.len()).unwrap());
+                drop(names);
+                span.record(&mut Self(Arc::clone(&self.0)));
+                id
+            }
+
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+            fn event(&self, _event: &Event<'_>) {}
+
+            fn enter(&self, _span: &Id) {}
+
+            fn exit(&self, _span: &Id) {}
+        }
+
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::new(9u32, NoNetwork, (), MockSerializer, |_env, vm| {
+            vm.branch(
+                true,
+                |vm| vm.neighboring(&1u8).unwrap(),
+                |vm| vm.neighboring(&0u8).unwrap(),
+            )
+        });
+        tracing::subscriber::with_default(Spans(Arc::clone(&spans)), || {
+            engine.cycle().unwrap();
+        });
+        assert_eq!(
+            *spans.lock().unwrap(),
+            [
+                "cycle",
+                "receive",
+                "operator",
+                "branch",
+                "operator",
+                "neighboring",
+                "send"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "audit")]
+    fn test_cycle_audited_records_rounds() {
+        use crate::rufi::audit::AuditLog;
+        let mut log = AuditLog::new();
+        let mut engine = Engine::new(8u32, NoNetwork, (), MockSerializer, |_env, _vm| 5u8);
+        for _ in 0..2 {
+            let report = engine.cycle_audited(&mut log).unwrap();
+            assert_eq!((report.output, report.send_error), (5u8, None));
+        }
+        assert_eq!(log.entries().len(), 2);
+        assert!(log.verify());
+    }
+
+    #[test]
+    #[cfg(feature = "audit")]
+    fn test_cycle_audited_reports_send_errors() {
+        use crate::rufi::audit::AuditLog;
+        let mut log = AuditLog::new();
+        let mut engine = Engine::new(
+            8u32,
+            DisconnectedNetwork,
+            (),
+            MockSerializer,
+            |_env, _vm| 5u8,
+        );
+        let report = engine.cycle_audited(&mut log).unwrap();
+        assert_eq!(report.send_error, Some(NetworkError::Disconnected));
+        assert_eq!(log.entries().len(), 1);
+    }
+
+    #[test]
+    fn test_cycle_recorded_reports_send_errors() {
+        use crate::rufi::replay::Recorder;
+        let mut recorder = Recorder::new();
+        let mut engine = Engine::new(
+            9u32,
+            DisconnectedNetwork,
+            (),
+            MockSerializer,
+            |_env, _vm| 6u8,
+        );
+        let report = engine.cycle_recorded(&mut recorder).unwrap();
+        assert_eq!(report.output, 6u8);
+        assert_eq!(report.send_error, Some(NetworkError::Disconnected));
+        assert_eq!(recorder.rounds().len(), 1);
     }
 }
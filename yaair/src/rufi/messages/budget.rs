@@ -0,0 +1,247 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::messages::outbound::OutboundMessage;
+use crate::rufi::messages::path::Path;
+use crate::rufi::messages::serializer::Serializer;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::format;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::hash::Hash;
+use serde::Serialize;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// Importance of an exported path when the message has to be pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// What to do when the serialized outbound message exceeds the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Fail the round with [`AggregateError::MessageBudgetExceeded`].
+    #[default]
+    Error,
+    /// Drop paths starting from the lowest priority; ties are broken by dropping the most
+    /// recently appended path first.
+    DropLowestPriority,
+    /// Drop the most recently appended paths, keeping the longest prefix of the round that fits.
+    Truncate,
+}
+
+/// Maximum size of the serialized outbound message, for links with a strict MTU (LoRa, BLE).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageBudget {
+    max_bytes: usize,
+    policy: OverflowPolicy,
+    priorities: Vec<(Path, Priority)>,
+}
+impl MessageBudget {
+    pub const fn new(max_bytes: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            max_bytes,
+            policy,
+            priorities: Vec::new(),
+        }
+    }
+
+    /// Assign `priority` to the paths under `prefix`; the longest matching prefix wins.
+    #[must_use]
+    pub fn with_priority(mut self, prefix: Path, priority: Priority) -> Self {
+        self.priorities.push((prefix, priority));
+        self
+    }
+
+    pub const fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub const fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    pub fn priority_of(&self, path: &Path) -> Priority {
+        self.priorities
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or_else(Priority::default, |(_, priority)| *priority)
+    }
+
+    /// Serialize `message`, pruning it according to the policy if it exceeds the budget.
+    ///
+    /// # Errors
+    /// Returns an error if the message cannot be serialized, or if it does not fit and the
+    /// policy is [`OverflowPolicy::Error`] or pruning every path is not enough
+    pub fn fit<Id, S>(
+        &self,
+        message: &OutboundMessage<Id>,
+        serializer: &S,
+    ) -> Result<Vec<u8>, AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        S: Serializer,
+    {
+        self.fit_tagged(message, serializer, &BTreeMap::new())
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Like [`MessageBudget::fit`], with the priorities of `tagged` paths overriding the ones
+    /// configured for their prefixes.
+    ///
+    /// # Returns
+    /// The serialized message and the paths dropped to fit it, in the order they were dropped
+    ///
+    /// # Errors
+    /// See [`MessageBudget::fit`]
+    pub fn fit_tagged<Id, S>(
+        &self,
+        message: &OutboundMessage<Id>,
+        serializer: &S,
+        tagged: &BTreeMap<Path, Priority>,
+    ) -> Result<(Vec<u8>, Vec<Path>), AggregateError>
+    where
+        Id: Ord + Hash + Copy + Serialize,
+        S: Serializer,
+    {
+        let encode = |candidate: &OutboundMessage<Id>| {
+            serializer.serialize(candidate).map_err(|err| {
+                AggregateError::SerializationError(format!(
+                    "Failed to serialize outbound message: {err}",
+                ))
+            })
+        };
+        let full = encode(message)?;
+        if full.len() <= self.max_bytes {
+            return Ok((full, Vec::new()));
+        }
+        let mut victims: Vec<Path> = message.appended_paths().collect();
+        if self.policy == OverflowPolicy::DropLowestPriority {
+            // stable sort: within the same priority, later paths are dropped first
+            victims.sort_by_key(|path| {
+                let priority = tagged
+                    .get(path)
+                    .copied()
+                    .unwrap_or_else(|| self.priority_of(path));
+                core::cmp::Reverse(priority)
+            });
+        }
+        let mut pruned = message.clone();
+        let mut dropped = Vec::new();
+        let mut size = full.len();
+        if self.policy != OverflowPolicy::Error {
+            while let Some(victim) = victims.pop() {
+                pruned.remove(&victim);
+                dropped.push(victim);
+                let bytes = encode(&pruned)?;
+                if bytes.len() <= self.max_bytes {
+                    return Ok((bytes, dropped));
+                }
+                size = bytes.len();
+            }
+        }
+        Err(AggregateError::MessageBudgetExceeded(format!(
+            "outbound message of {size} bytes exceeds the budget of {} bytes",
+            self.max_bytes,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::MockSerializer;
+
+    fn message() -> OutboundMessage<u32> {
+        let mut message = OutboundMessage::empty(0);
+        message.append(&Path::from("a:0"), vec![1; 8]);
+        message.append(&Path::from("b:0"), vec![2; 8]);
+        message.append(&Path::from("c:0"), vec![3; 8]);
+        message
+    }
+
+    fn size_without(paths: &[&str]) -> usize {
+        let mut pruned = message();
+        for path in paths {
+            pruned.remove(&Path::from(*path));
+        }
+        MockSerializer.serialize(&pruned).unwrap().len()
+    }
+
+    fn kept(bytes: &[u8]) -> Vec<bool> {
+        let decoded: OutboundMessage<u32> = MockSerializer.deserialize(bytes).unwrap();
+        ["a:0", "b:0", "c:0"]
+            .iter()
+            .map(|path| decoded.at(&Path::from(*path)).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn messages_within_budget_are_untouched() {
+        let budget = MessageBudget::new(size_without(&[]), OverflowPolicy::Error);
+        assert_eq!(
+            kept(&budget.fit(&message(), &MockSerializer).unwrap()),
+            [true; 3]
+        );
+    }
+
+    #[test]
+    fn error_policy_rejects_oversized_messages() {
+        let budget = MessageBudget::new(size_without(&["c:0"]), OverflowPolicy::Error);
+        assert!(matches!(
+            budget.fit(&message(), &MockSerializer),
+            Err(AggregateError::MessageBudgetExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn truncate_drops_latest_paths() {
+        let budget = MessageBudget::new(size_without(&["c:0"]), OverflowPolicy::Truncate);
+        let fitted = budget.fit(&message(), &MockSerializer).unwrap();
+        assert_eq!(kept(&fitted), [true, true, false]);
+    }
+
+    #[test]
+    fn lowest_priority_paths_are_dropped_first() {
+        let budget = MessageBudget::new(size_without(&["a:0"]), OverflowPolicy::DropLowestPriority)
+            .with_priority(Path::from("a:0"), Priority::Low)
+            .with_priority(Path::from("c:0"), Priority::High);
+        let fitted = budget.fit(&message(), &MockSerializer).unwrap();
+        assert_eq!(kept(&fitted), [false, true, true]);
+
+        let tight = MessageBudget::new(
+            size_without(&["a:0", "b:0"]),
+            OverflowPolicy::DropLowestPriority,
+        )
+        .with_priority(Path::from("a:0"), Priority::Low);
+        // among paths of the same priority, the latest is dropped first
+        let pruned = tight.fit(&message(), &MockSerializer).unwrap();
+        assert_eq!(kept(&pruned), [false, true, false]);
+    }
+
+    #[test]
+    fn paths_with_separators_in_their_tokens_are_dropped() {
+        let zone = Path::new(vec!["align[zone/north]:0", "neighboring:0"]);
+        let mut message = message();
+        message.append(&zone, vec![4; 8]);
+        let budget = MessageBudget::new(size_without(&[]), OverflowPolicy::DropLowestPriority)
+            .with_priority(zone.clone(), Priority::Low);
+        let (bytes, dropped) = budget
+            .fit_tagged(&message, &MockSerializer, &BTreeMap::new())
+            .unwrap();
+        assert_eq!(dropped, vec![zone]);
+        assert_eq!(kept(&bytes), [true; 3]);
+    }
+
+    #[test]
+    fn pruning_everything_may_not_be_enough() {
+        let budget = MessageBudget::new(1, OverflowPolicy::Truncate);
+        assert!(budget.fit(&message(), &MockSerializer).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::rufi::messages::path::Path;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::any::Any;
+
+/// Specialized wire encoding for values of type `V`, bypassing the general-purpose serializer.
+///
+/// Codecs are stateless: each value must be decodable on its own, since messages can be lost.
+pub trait ValueCodec<V> {
+    fn encode(&self, value: &V) -> Vec<u8>;
+
+    /// Decode a value previously produced by [`ValueCodec::encode`], `None` if malformed.
+    fn decode(&self, bytes: &[u8]) -> Option<V>;
+}
+
+/// Codecs registered for path prefixes.
+///
+/// A value exported at a path is handled by the codec registered for the longest matching prefix
+/// whose value type matches; values without a codec go through the VM serializer.
+#[derive(Default)]
+pub struct CodecRegistry {
+    codecs: Vec<(Path, Box<dyn Any>)>,
+}
+impl CodecRegistry {
+    pub const fn new() -> Self {
+        Self { codecs: Vec::new() }
+    }
+
+    /// Register `codec` for values of type `V` exported under `prefix`.
+    pub fn register<V, C>(&mut self, prefix: Path, codec: C)
+    where
+        V: 'static,
+        C: ValueCodec<V> + 'static,
+    {
+        let codec: Box<dyn ValueCodec<V>> = Box::new(codec);
+        self.codecs.push((prefix, Box::new(codec)));
+    }
+
+    /// Register `codec` for values of type `V` exported anywhere; codecs registered for a
+    /// prefix take precedence.
+    pub fn register_for_type<V, C>(&mut self, codec: C)
+    where
+        V: 'static,
+        C: ValueCodec<V> + 'static,
+    {
+        self.register(Path::new(Vec::<&str>::new()), codec);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codecs.is_empty()
+    }
+
+    /// Find the codec handling values of type `V` at `path`.
+    pub fn find<V: 'static>(&self, path: &Path) -> Option<&dyn ValueCodec<V>> {
+        self.codecs
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix))
+            .filter_map(|(prefix, codec)| {
+                codec
+                    .downcast_ref::<Box<dyn ValueCodec<V>>>()
+                    .map(|codec| (prefix.len(), codec.as_ref()))
+            })
+            .max_by_key(|(length, _)| *length)
+            .map(|(_, codec)| codec)
+    }
+}
+
+/// Packs floating point values as 32-bit fixed-point integers with the given `scale`.
+///
+/// With `scale = 1000.0` coordinates are transmitted with millimeter precision in 4 bytes each;
+/// values outside the representable range saturate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedPoint {
+    scale: f64,
+}
+impl FixedPoint {
+    pub const fn new(scale: f64) -> Self {
+        Self { scale }
+    }
+
+    #[allow(clippy::as_conversions)] // float to int casts saturate, which is the intended behavior
+    fn pack(self, value: f64) -> [u8; 4] {
+        // the cast truncates, so this rounds half away from zero without `f64::round` (std only)
+        let scaled = value * self.scale;
+        ((scaled + 0.5_f64.copysign(scaled)) as i32).to_le_bytes()
+    }
+
+    fn unpack(self, bytes: &[u8]) -> Option<f64> {
+        let raw = i32::from_le_bytes(bytes.try_into().ok()?);
+        Some(f64::from(raw) / self.scale)
+    }
+}
+impl ValueCodec<f64> for FixedPoint {
+    fn encode(&self, value: &f64) -> Vec<u8> {
+        self.pack(*value).to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<f64> {
+        self.unpack(bytes)
+    }
+}
+impl ValueCodec<(f64, f64)> for FixedPoint {
+    fn encode(&self, value: &(f64, f64)) -> Vec<u8> {
+        let mut bytes = self.pack(value.0).to_vec();
+        bytes.extend_from_slice(&self.pack(value.1));
+        bytes
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<(f64, f64)> {
+        let (x, y) = bytes.split_at_checked(4)?;
+        Some((self.unpack(x)?, self.unpack(y)?))
+    }
+}
+
+/// Packs `f32` values as IEEE 754 half precision floats in 2 bytes each, rounding to nearest.
+///
+/// Half precision keeps about 3 significant digits up to 65504; larger values become infinite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Half;
+impl Half {
+    fn pack(value: f32) -> [u8; 2] {
+        let bits = value.to_bits();
+        let sign = (bits >> 16) & 0x8000;
+        let exponent = (bits >> 23) & 0xff;
+        let mantissa = bits & 0x007f_ffff;
+        let half = match exponent {
+            0xff if mantissa != 0 => sign | 0x7e00,
+            143.. => sign | 0x7c00,
+            113.. => {
+                let half = sign | (exponent.wrapping_sub(112) << 10) | (mantissa >> 13);
+                // a carry out of the mantissa correctly bumps the exponent
+                half.wrapping_add(u32::from(rounds_up(mantissa, 13, half)))
+            }
+            102.. => {
+                let shift = 126_u32.wrapping_sub(exponent);
+                let mantissa = mantissa | 0x0080_0000;
+                let half = mantissa >> shift;
+                sign | half.wrapping_add(u32::from(rounds_up(mantissa, shift, half)))
+            }
+            _ => sign,
+        };
+        u16::try_from(half).unwrap_or(u16::MAX).to_le_bytes()
+    }
+
+    fn unpack(bytes: &[u8]) -> Option<f32> {
+        let half = u32::from(u16::from_le_bytes(bytes.try_into().ok()?));
+        let sign = (half & 0x8000) << 16;
+        let exponent = (half >> 10) & 0x1f;
+        let mantissa = half & 0x03ff;
+        let bits = match exponent {
+            0 => {
+                // subnormal: mantissa * 2^-24, exact in single precision
+                let magnitude = f32::from(u16::try_from(mantissa).ok()?) / 16_777_216.0;
+                sign | magnitude.to_bits()
+            }
+            0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+            _ => sign | (exponent.wrapping_add(112) << 23) | (mantissa << 13),
+        };
+        Some(f32::from_bits(bits))
+    }
+}
+
+/// Whether dropping the lowest `shift` bits of `mantissa`, leaving `kept`, should round up to
+/// the nearest value, ties to even.
+const fn rounds_up(mantissa: u32, shift: u32, kept: u32) -> bool {
+    let halfway = 1 << shift.wrapping_sub(1);
+    let remainder = mantissa & (1_u32 << shift).wrapping_sub(1);
+    remainder > halfway || (remainder == halfway && kept & 1 == 1)
+}
+impl ValueCodec<f32> for Half {
+    fn encode(&self, value: &f32) -> Vec<u8> {
+        Self::pack(*value).to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<f32> {
+        Self::unpack(bytes)
+    }
+}
+impl ValueCodec<Vec<f32>> for Half {
+    fn encode(&self, value: &Vec<f32>) -> Vec<u8> {
+        value.iter().flat_map(|value| Self::pack(*value)).collect()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<Vec<f32>> {
+        let chunks = bytes.chunks_exact(2);
+        if !chunks.remainder().is_empty() {
+            return None;
+        }
+        chunks.map(Self::unpack).collect()
+    }
+}
+
+/// Variable-length (LEB128) encoding for unsigned counters: small values take a single byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Varint;
+impl ValueCodec<u64> for Varint {
+    fn encode(&self, value: &u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut rest = *value;
+        loop {
+            let [low, ..] = rest.to_le_bytes();
+            let byte = low & 0x7f;
+            rest >>= 7;
+            if rest == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        let mut value = 0u64;
+        for (index, byte) in bytes.iter().enumerate() {
+            let shift = u32::try_from(index).ok()?.checked_mul(7)?;
+            let chunk = u64::from(byte & 0x7f).checked_shl(shift)?;
+            value |= chunk;
+            if byte & 0x80 == 0 {
+                return (index.checked_add(1)? == bytes.len()).then_some(value);
+            }
+        }
+        None
+    }
+}
+impl ValueCodec<u32> for Varint {
+    fn encode(&self, value: &u32) -> Vec<u8> {
+        <Self as ValueCodec<u64>>::encode(self, &u64::from(*value))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u32> {
+        <Self as ValueCodec<u64>>::decode(self, bytes).and_then(|value| u32::try_from(value).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_point_round_trip() {
+        let codec = FixedPoint::new(1000.0);
+        let bytes = ValueCodec::<(f64, f64)>::encode(&codec, &(1.2345, -7.5));
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(
+            ValueCodec::<(f64, f64)>::decode(&codec, &bytes),
+            Some((1.235, -7.5))
+        );
+    }
+
+    #[test]
+    fn half_round_trip() {
+        // the smallest subnormal half precision float is 2^-24
+        let values = [0.0f32, -1.5, 0.1, 65504.0, 5.960_464_5e-8];
+        for value in values {
+            let bytes = ValueCodec::<f32>::encode(&Half, &value);
+            assert_eq!(bytes.len(), 2);
+            let decoded = ValueCodec::<f32>::decode(&Half, &bytes).unwrap();
+            assert!((decoded - value).abs() <= value.abs() / 1000.0);
+        }
+        let overflow = ValueCodec::<f32>::encode(&Half, &1e6);
+        assert_eq!(
+            ValueCodec::<f32>::decode(&Half, &overflow),
+            Some(f32::INFINITY)
+        );
+        let nan = ValueCodec::<f32>::encode(&Half, &f32::NAN);
+        assert!(ValueCodec::<f32>::decode(&Half, &nan).unwrap().is_nan());
+
+        let readings = vec![20.5f32, 21.25, -3.0];
+        let bytes = ValueCodec::<Vec<f32>>::encode(&Half, &readings);
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(
+            ValueCodec::<Vec<f32>>::decode(&Half, &bytes),
+            Some(readings)
+        );
+        assert_eq!(ValueCodec::<Vec<f32>>::decode(&Half, &[0; 3]), None);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for value in [0u64, 1, 127, 128, 300, u64::MAX] {
+            let bytes = ValueCodec::<u64>::encode(&Varint, &value);
+            assert_eq!(ValueCodec::<u64>::decode(&Varint, &bytes), Some(value));
+        }
+        assert_eq!(ValueCodec::<u64>::encode(&Varint, &5).len(), 1);
+        assert_eq!(ValueCodec::<u64>::decode(&Varint, &[0x80]), None);
+    }
+
+    #[test]
+    fn registry_selects_longest_prefix_with_matching_type() {
+        let mut registry = CodecRegistry::new();
+        registry.register::<u64, _>(Path::from("share:0"), Varint);
+        registry.register::<f64, _>(Path::from("share:0/neighboring:0"), FixedPoint::new(10.0));
+        let path = Path::from("share:0/neighboring:0");
+        assert!(registry.find::<f64>(&path).is_some());
+        assert!(registry.find::<u64>(&path).is_some());
+        assert!(registry.find::<u32>(&path).is_none());
+        assert!(registry.find::<f64>(&Path::from("repeat:0")).is_none());
+        registry.register_for_type::<f32, _>(Half);
+        assert!(registry.find::<f32>(&Path::from("repeat:0")).is_some());
+    }
+}
@@ -0,0 +1,211 @@
+//! Messages with capacities fixed at compile time, enabled by the `no-alloc` feature.
+//!
+//! They are storage types for firmware exchanging values from static buffers, to be filled by
+//! the application: the [`VM`](crate::rufi::aggregate::VM) keeps using
+//! [`InboundMessage`](crate::rufi::messages::inbound::InboundMessage) and
+//! [`OutboundMessage`](crate::rufi::messages::outbound::OutboundMessage), and the crate still
+//! requires `alloc`.
+
+use core::hash::Hash;
+use heapless::index_map::FnvIndexMap;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// A capacity fixed at compile time has been exceeded, or two paths cannot be told apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityError {
+    /// The alignment stack is deeper than its capacity.
+    Depth,
+    /// More operators have been invoked at the same path than the trace can count.
+    Trace,
+    /// More paths have been exported than the message can hold.
+    Paths,
+    /// An exported value is larger than a slot of the message.
+    Value,
+    /// More neighbors have sent a message than the inbound message can hold.
+    Neighbors,
+    /// Two paths aligned in the same round have the same [`PathKey`].
+    Collision,
+}
+
+impl core::fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Depth => write!(f, "alignment stack capacity exceeded"),
+            Self::Trace => write!(f, "alignment trace capacity exceeded"),
+            Self::Paths => write!(f, "exported paths capacity exceeded"),
+            Self::Value => write!(f, "exported value larger than its slot"),
+            Self::Neighbors => write!(f, "neighbors capacity exceeded"),
+            Self::Collision => write!(f, "two paths have the same key"),
+        }
+    }
+}
+
+/// Fixed-size digest of an alignment path, see
+/// [`FixedAlignmentStack`](crate::rufi::alignment::fixed::FixedAlignmentStack).
+///
+/// Devices only compare paths for equality, so exchanging digests instead of the tokens keeps
+/// messages small and avoids allocating strings. Distinct paths may share a key: the stack
+/// rejects the collisions among the paths of a device, but a neighbor path colliding with a
+/// local one is aligned with it. With 64-bit keys, the odds are about `n² / 2^65` for `n`
+/// distinct paths in the network, below one in 10^13 for a thousand paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PathKey(pub u64);
+
+/// Allocation-free counterpart of [`OutboundMessage`](crate::rufi::messages::outbound::OutboundMessage)
+/// holding up to `PATHS` values of at most `BYTES` bytes each.
+///
+/// Values are keyed by [`PathKey`] instead of the tokens of the path, so devices using it only
+/// communicate with each other, not with devices exchanging [`OutboundMessage`]s.
+/// `PATHS` must be a power of two.
+///
+/// [`OutboundMessage`]: crate::rufi::messages::outbound::OutboundMessage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixedOutbound<Id: Hash + Eq, const PATHS: usize, const BYTES: usize> {
+    pub sender: Id,
+    underlying: FnvIndexMap<PathKey, Vec<u8, BYTES>, PATHS>,
+}
+impl<Id: Hash + Eq, const PATHS: usize, const BYTES: usize> FixedOutbound<Id, PATHS, BYTES> {
+    pub const fn empty(sender: Id) -> Self {
+        Self {
+            sender,
+            underlying: FnvIndexMap::new(),
+        }
+    }
+
+    /// Export `value` at `path`, replacing any previous value.
+    ///
+    /// # Errors
+    /// Returns an error if `value` does not fit a slot or no slot is left
+    pub fn append(&mut self, path: PathKey, value: &[u8]) -> Result<(), CapacityError> {
+        let value = Vec::from_slice(value).map_err(|_| CapacityError::Value)?;
+        self.underlying
+            .insert(path, value)
+            .map(|_| ())
+            .map_err(|_| CapacityError::Paths)
+    }
+
+    pub fn at(&self, path: PathKey) -> Option<&[u8]> {
+        self.underlying.get(&path).map(Vec::as_slice)
+    }
+
+    pub fn len(&self) -> usize {
+        self.underlying.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.underlying.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.underlying.clear();
+    }
+}
+
+/// Allocation-free counterpart of [`InboundMessage`](crate::rufi::messages::inbound::InboundMessage)
+/// holding the last message of up to `NEIGHBORS` neighbors.
+///
+/// `NEIGHBORS` and `PATHS` must be powers of two.
+#[derive(Debug, Clone)]
+pub struct FixedInbound<
+    Id: Hash + Eq,
+    const NEIGHBORS: usize,
+    const PATHS: usize,
+    const BYTES: usize,
+> {
+    underlying: FnvIndexMap<Id, FixedOutbound<Id, PATHS, BYTES>, NEIGHBORS>,
+}
+impl<Id, const NEIGHBORS: usize, const PATHS: usize, const BYTES: usize>
+    FixedInbound<Id, NEIGHBORS, PATHS, BYTES>
+where
+    Id: Hash + Eq + Copy,
+{
+    pub const fn new() -> Self {
+        Self {
+            underlying: FnvIndexMap::new(),
+        }
+    }
+
+    /// Store `message` as the last message of its sender.
+    ///
+    /// # Errors
+    /// Returns an error if the sender is new and no slot is left
+    pub fn receive(
+        &mut self,
+        message: FixedOutbound<Id, PATHS, BYTES>,
+    ) -> Result<(), CapacityError> {
+        self.underlying
+            .insert(message.sender, message)
+            .map(|_| ())
+            .map_err(|_| CapacityError::Neighbors)
+    }
+
+    pub fn get(&self, id: &Id) -> Option<&FixedOutbound<Id, PATHS, BYTES>> {
+        self.underlying.get(id)
+    }
+
+    /// Keep only the messages of the neighbors for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Id) -> bool) {
+        self.underlying.retain(|id, _| keep(id));
+    }
+
+    /// Payloads exported at `path` by every neighbor.
+    pub fn get_at_path(&self, path: PathKey) -> impl Iterator<Item = (Id, &[u8])> + '_ {
+        self.underlying
+            .iter()
+            .filter_map(move |(id, message)| message.at(path).map(|value| (*id, value)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.underlying.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.underlying.is_empty()
+    }
+}
+impl<Id, const NEIGHBORS: usize, const PATHS: usize, const BYTES: usize> Default
+    for FixedInbound<Id, NEIGHBORS, PATHS, BYTES>
+where
+    Id: Hash + Eq + Copy,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outbound_enforces_capacities() {
+        let mut message: FixedOutbound<u8, 2, 4> = FixedOutbound::empty(1);
+        assert_eq!(
+            message.append(PathKey(1), &[1, 2, 3, 4, 5]),
+            Err(CapacityError::Value)
+        );
+        assert_eq!(message.append(PathKey(1), &[1]), Ok(()));
+        assert_eq!(message.append(PathKey(2), &[2]), Ok(()));
+        assert_eq!(message.append(PathKey(1), &[3]), Ok(()));
+        assert_eq!(message.append(PathKey(3), &[4]), Err(CapacityError::Paths));
+        assert_eq!(message.at(PathKey(1)), Some([3].as_slice()));
+    }
+
+    #[test]
+    fn inbound_collects_values_at_path() {
+        let mut inbound: FixedInbound<u8, 2, 2, 4> = FixedInbound::new();
+        for id in 1..=2 {
+            let mut message = FixedOutbound::empty(id);
+            message.append(PathKey(7), &[id]).unwrap();
+            inbound.receive(message).unwrap();
+        }
+        assert_eq!(
+            inbound.receive(FixedOutbound::empty(3)),
+            Err(CapacityError::Neighbors)
+        );
+        let mut values: std::vec::Vec<(u8, &[u8])> = inbound.get_at_path(PathKey(7)).collect();
+        values.sort_unstable();
+        assert_eq!(values, [(1, [1].as_slice()), (2, [2].as_slice())]);
+    }
+}
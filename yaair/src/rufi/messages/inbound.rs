@@ -1,31 +1,113 @@
+use crate::rufi::messages::metadata::Metadata;
 use crate::rufi::messages::path::Path;
 use crate::rufi::messages::valuetree::ValueTree;
+use crate::rufi::messages::{Map, Set};
 #[cfg(not(feature = "std"))]
-use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
-
+use alloc::collections::BTreeMap;
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::sync::Arc;
 use core::hash::Hash;
-use std::collections::{HashMap as Map, HashSet as Set};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
-#[derive(Debug)]
+/// Trees received by a device in a round, shared so that the same export can be delivered to
+/// many receivers without copying it.
+#[derive(Debug, Clone)]
 pub struct InboundMessage<Id: Ord + Hash + Copy> {
-    underlying: Map<Id, ValueTree>,
+    underlying: Map<Id, Arc<ValueTree>>,
 }
 impl<Id: Ord + Hash + Copy> InboundMessage<Id> {
-    pub const fn new(underlying: Map<Id, ValueTree>) -> Self {
+    pub fn new(underlying: Map<Id, ValueTree>) -> Self {
+        let underlying = underlying
+            .into_iter()
+            .map(|(id, tree)| (id, Arc::new(tree)))
+            .collect();
+        Self { underlying }
+    }
+
+    /// Like [`InboundMessage::new`], with trees that may be delivered to other devices as well,
+    /// e.g. the exports of devices hosted in the same process.
+    pub const fn shared(underlying: Map<Id, Arc<ValueTree>>) -> Self {
         Self { underlying }
     }
 
+    /// Number of neighbors that sent a message.
+    pub fn len(&self) -> usize {
+        self.underlying.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.underlying.is_empty()
+    }
+
+    /// Ids of the neighbors that sent a message.
+    pub fn neighbors(&self) -> impl Iterator<Item = Id> + '_ {
+        self.underlying.keys().copied()
+    }
+
+    /// Total size of the values received from all the neighbors, see [`ValueTree::payload_size`].
+    pub fn payload_size(&self) -> usize {
+        self.underlying
+            .values()
+            .fold(0, |size, tree| size.saturating_add(tree.payload_size()))
+    }
+
+    /// Size of the values received at every path, summed over the neighbors and sorted by
+    /// path, the counterpart of [`OutboundMessage::path_sizes`].
+    ///
+    /// [`OutboundMessage::path_sizes`]: crate::rufi::messages::outbound::OutboundMessage::path_sizes
+    pub fn path_sizes(&self) -> impl Iterator<Item = (Path, usize)> {
+        let mut sizes: BTreeMap<Path, usize> = BTreeMap::new();
+        for (path, size) in self.underlying.values().flat_map(|tree| tree.path_sizes()) {
+            let total = sizes.entry(path.clone()).or_default();
+            *total = total.saturating_add(size);
+        }
+        sizes.into_iter()
+    }
+
     pub fn get(&self, id: &Id) -> Option<&ValueTree> {
-        self.underlying.get(id)
+        self.underlying.get(id).map(AsRef::as_ref)
     }
 
-    pub fn get_at_path(&self, path: &Path) -> Map<Id, Vec<u8>> {
+    /// Keep only the messages of the neighbors for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Id) -> bool) {
+        self.underlying.retain(|id, _| keep(id));
+    }
+
+    /// Payloads exported at `path` by every neighbor, borrowed from the received trees.
+    pub fn get_at_path<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = (Id, &'a [u8])> + 'a {
         self.underlying
             .iter()
             .filter_map(|(id, value_tree)| value_tree.get(path).map(|value| (*id, value)))
-            .collect()
+    }
+
+    /// Neighbors that exported some value at `prefix` or below it.
+    pub fn devices_under<'a>(&'a self, prefix: &'a Path) -> impl Iterator<Item = Id> + 'a {
+        self.underlying
+            .iter()
+            .filter(|(_, value_tree)| value_tree.contains_prefix(prefix))
+            .map(|(id, _)| *id)
+    }
+
+    /// Messages restricted to the values exported at `prefix` or below it, see
+    /// [`ValueTree::get_subtree`]; neighbors that exported nothing there are left out.
+    pub fn get_subtree(&self, prefix: &Path) -> Self {
+        let underlying = self
+            .underlying
+            .iter()
+            .map(|(id, value_tree)| (*id, Arc::new(value_tree.get_subtree(prefix))))
+            .filter(|(_, subtree)| !subtree.is_empty())
+            .collect();
+        Self { underlying }
+    }
+
+    /// Headers attached by the neighbors to their messages, see [`Metadata`].
+    pub fn metadata(&self) -> impl Iterator<Item = (Id, &Metadata)> + '_ {
+        self.underlying
+            .iter()
+            .filter_map(|(id, value_tree)| Some((*id, value_tree.metadata()?)))
     }
 
     pub fn devices_at_path(&self, path: &Path) -> Set<Id> {
@@ -40,6 +122,12 @@ impl<Id: Ord + Hash + Copy> InboundMessage<Id> {
             })
             .collect()
     }
+
+    /// Neighbors that exported some value at `prefix` or below it, see
+    /// [`InboundMessage::devices_under`].
+    pub fn devices_at_prefix(&self, prefix: &Path) -> Set<Id> {
+        self.devices_under(prefix).collect()
+    }
 }
 impl<Id: Ord + Hash + Copy> Default for InboundMessage<Id> {
     fn default() -> Self {
@@ -48,3 +136,81 @@ impl<Id: Ord + Hash + Copy> Default for InboundMessage<Id> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    #[test]
+    fn get_at_path_borrows_payloads() {
+        let path = Path::from("share:0");
+        let tree = ValueTree::new(Map::from([(path.clone(), vec![1, 2, 3])]));
+        let inbound = InboundMessage::new(Map::from([(7u32, tree)]));
+        let stored = inbound
+            .get(&7)
+            .and_then(|received| received.get(&path))
+            .unwrap();
+        let (id, payload) = inbound.get_at_path(&path).next().unwrap();
+        assert_eq!(id, 7);
+        assert!(core::ptr::eq(payload, stored));
+        assert_eq!(inbound.get_at_path(&Path::from("share:1")).count(), 0);
+    }
+
+    #[test]
+    fn shared_trees_are_not_copied() {
+        let tree = Arc::new(ValueTree::new(Map::from([(
+            Path::from("share:0"),
+            vec![1],
+        )])));
+        let first = InboundMessage::shared(Map::from([(7u32, Arc::clone(&tree))]));
+        let second = InboundMessage::shared(Map::from([(7u32, Arc::clone(&tree))]));
+        assert!(core::ptr::eq(
+            first.get(&7).unwrap(),
+            second.get(&7).unwrap()
+        ));
+        assert_eq!(Arc::strong_count(&tree), 3);
+    }
+
+    #[test]
+    fn path_sizes_are_summed_over_the_neighbors() {
+        let (share, count) = (Path::from("share:0"), Path::from("share:0/presence:0"));
+        let first = ValueTree::new(Map::from([
+            (share.clone(), vec![1, 2]),
+            (count.clone(), vec![]),
+        ]));
+        let second = ValueTree::new(Map::from([(share.clone(), vec![3, 4, 5])]));
+        let inbound = InboundMessage::new(Map::from([(1u32, first), (2u32, second)]));
+        assert_eq!(
+            inbound.path_sizes().collect::<Vec<_>>(),
+            vec![(share, 5), (count, 0)]
+        );
+        assert_eq!(inbound.payload_size(), 5);
+    }
+
+    #[test]
+    fn subtrees_keep_the_exports_under_a_prefix() {
+        let first = ValueTree::new(Map::from([
+            (Path::from("program:a/share:0"), vec![1]),
+            (Path::from("program:b/share:0"), vec![2]),
+        ]));
+        let second = ValueTree::new(Map::from([(Path::from("program:b/share:0"), vec![3])]));
+        let inbound = InboundMessage::new(Map::from([(1u32, first), (2u32, second)]));
+        let prefix = Path::from("program:a");
+
+        let subtree = inbound.get_subtree(&prefix);
+        assert_eq!(subtree.neighbors().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(
+            subtree
+                .get(&1)
+                .and_then(|tree| tree.get(&Path::from("share:0"))),
+            Some(&[1][..])
+        );
+        assert_eq!(inbound.devices_at_prefix(&prefix), Set::from([1]));
+        assert_eq!(
+            inbound.devices_at_prefix(&Path::from("program:b")),
+            Set::from([1, 2])
+        );
+    }
+}
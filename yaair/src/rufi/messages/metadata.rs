@@ -0,0 +1,15 @@
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Header describing the round in which a message was produced, see [`VM::set_metadata`].
+///
+/// [`VM::set_metadata`]: crate::rufi::aggregate::VM::set_metadata
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Time of the round according to the clock of the sender, logical by default.
+    pub timestamp: Duration,
+    /// Number of the round, increasing by one at every round of the sender.
+    pub sequence: u64,
+    /// Position of the sender, if known.
+    pub position: Option<(f64, f64)>,
+}
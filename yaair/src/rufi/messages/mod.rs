@@ -1,5 +1,27 @@
+pub mod budget;
+pub mod codec;
+#[cfg(feature = "no-alloc")]
+pub mod fixed;
 pub mod inbound;
+pub mod metadata;
 pub mod outbound;
 pub mod path;
 pub mod serializer;
+#[cfg(feature = "structured")]
+pub mod structured;
+pub mod tree;
 pub mod valuetree;
+
+/// Map of the message types: a `HashMap` with the `std` feature, a `BTreeMap` without it.
+#[cfg(feature = "std")]
+pub type Map<K, V> = std::collections::HashMap<K, V>;
+/// Map of the message types: a `HashMap` with the `std` feature, a `BTreeMap` without it.
+#[cfg(not(feature = "std"))]
+pub type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// Set of the message types, backed like [`Map`].
+#[cfg(feature = "std")]
+pub type Set<T> = std::collections::HashSet<T>;
+/// Set of the message types, backed like [`Map`].
+#[cfg(not(feature = "std"))]
+pub type Set<T> = alloc::collections::BTreeSet<T>;
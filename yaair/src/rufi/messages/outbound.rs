@@ -1,36 +1,430 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::messages::metadata::Metadata;
 use crate::rufi::messages::path::Path;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::messages::tree::ExportTree;
+use crate::rufi::messages::valuetree::ValueTree;
 #[cfg(not(feature = "std"))]
-use alloc::collections::BTreeMap as Map;
-
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::format;
 #[cfg(not(feature = "std"))]
-use alloc::string::{String, ToString};
+use alloc::string::String;
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
 use core::hash::Hash;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap as Map;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// Version of the wire format of the [`OutboundMessage`]s produced by this crate.
+///
+/// Version 3 exports an [`ExportTree`], versions 1 and 2 a flat map from paths to values.
+pub const WIRE_VERSION: u32 = 3;
+
+/// Version of the messages sent before the format was versioned, which carry no version field.
+pub const LEGACY_WIRE_VERSION: u32 = 1;
 
-#[derive(Debug, Serialize, Deserialize)]
+const fn legacy_version() -> u32 {
+    LEGACY_WIRE_VERSION
+}
+
+/// Values exported by a device in a round.
+///
+/// Values are kept in an [`ExportTree`] sorted by path, so that the same exports are always
+/// serialized to the same bytes, with or without `std`: receivers and signatures can compare
+/// messages byte by byte.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutboundMessage<Id: Ord + Hash + Copy> {
     pub sender: Id,
-    underlying: Map<String, Vec<u8>>,
+    underlying: ExportTree,
+    #[serde(default)]
+    sequence: u64,
+    #[serde(default)]
+    delta: Option<Delta>,
+    #[serde(default)]
+    metadata: Option<Metadata>,
+    /// Wire format version, see [`WIRE_VERSION`].
+    #[serde(default = "legacy_version")]
+    version: u32,
+    /// Remaining hops of the paths exported with a limit, see [`OutboundMessage::relay`].
+    #[serde(default, with = "hop_limits")]
+    hop_limits: BTreeMap<Path, u8>,
+    /// Relays the message went through since it was sent.
+    #[serde(default)]
+    relays: u8,
+    /// Paths in the order they were appended, used to prune the message; not transmitted.
+    #[serde(skip)]
+    appended: Vec<Path>,
+}
+
+/// Marks a message carrying only the paths changed since the message `base` of the same sender.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Delta {
+    base: u64,
+    /// Paths whose value disappeared, kept as tokens since they may contain the `/` separator.
+    removed: Vec<Path>,
+    /// Roots of the subtrees that disappeared as a whole.
+    #[serde(default)]
+    pruned: Vec<Path>,
 }
+
+/// Layout of the [`Delta`] of versions 1 and 2, listing the removed paths joined by `/`.
+#[derive(Deserialize)]
+struct LegacyDelta {
+    base: u64,
+    removed: Vec<String>,
+}
+impl From<LegacyDelta> for Delta {
+    fn from(legacy: LegacyDelta) -> Self {
+        Self {
+            base: legacy.base,
+            removed: legacy
+                .removed
+                .iter()
+                .map(|path| Path::from(path.as_str()))
+                .collect(),
+            pruned: Vec::new(),
+        }
+    }
+}
+
+/// Layout of the messages of versions 1 and 2, exporting a flat map from paths to values.
+#[derive(Deserialize)]
+struct LegacyMessage<Id> {
+    sender: Id,
+    underlying: BTreeMap<String, Vec<u8>>,
+    #[serde(default)]
+    sequence: u64,
+    #[serde(default)]
+    delta: Option<LegacyDelta>,
+    #[serde(default)]
+    metadata: Option<Metadata>,
+    #[serde(default = "legacy_version")]
+    version: u32,
+    #[serde(default)]
+    hop_limits: BTreeMap<String, u8>,
+    #[serde(default)]
+    relays: u8,
+}
+impl<Id: Ord + Hash + Copy> From<LegacyMessage<Id>> for OutboundMessage<Id> {
+    fn from(legacy: LegacyMessage<Id>) -> Self {
+        let mut underlying = ExportTree::new();
+        for (path, value) in legacy.underlying {
+            underlying.insert(&Path::from(path.as_str()), value);
+        }
+        Self {
+            sender: legacy.sender,
+            underlying,
+            sequence: legacy.sequence,
+            delta: legacy.delta.map(Delta::from),
+            metadata: legacy.metadata,
+            version: WIRE_VERSION,
+            hop_limits: legacy
+                .hop_limits
+                .iter()
+                .map(|(path, hops)| (Path::from(path.as_str()), *hops))
+                .collect(),
+            relays: legacy.relays,
+            appended: Vec::new(),
+        }
+    }
+}
+
 impl<Id: Ord + Hash + Copy> OutboundMessage<Id> {
-    pub fn empty(sender: Id) -> Self {
+    pub const fn empty(sender: Id) -> Self {
         Self {
             sender,
-            underlying: Map::new(),
+            underlying: ExportTree::new(),
+            sequence: 0,
+            delta: None,
+            metadata: None,
+            version: WIRE_VERSION,
+            hop_limits: BTreeMap::new(),
+            relays: 0,
+            appended: Vec::new(),
+        }
+    }
+
+    /// Decode a message received from a neighbor, migrating it to the current wire format.
+    ///
+    /// Messages without a version field are [`LEGACY_WIRE_VERSION`] messages: fields added
+    /// since then take their default value, and the flat map of values of versions 1 and 2 is
+    /// rebuilt as a tree, so devices running older releases keep communicating while a fleet is
+    /// upgraded. Messages of newer versions are rejected, since their fields could be misread.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::IncompatibleVersion`] if the message has an unknown version, or
+    /// an error if `bytes` cannot be deserialized
+    pub fn decode<S: Serializer>(serializer: &S, bytes: &[u8]) -> Result<Self, AggregateError>
+    where
+        Id: for<'de> Deserialize<'de>,
+    {
+        let decoded = serializer.deserialize::<Self>(bytes).map_err(|err| {
+            AggregateError::DeserializationError(format!("Failed to decode message: {err}"))
+        });
+        match decoded {
+            Ok(message) if message.version == WIRE_VERSION => Ok(message),
+            Ok(message) if message.version > WIRE_VERSION => {
+                Err(AggregateError::IncompatibleVersion(message.version))
+            }
+            current => serializer
+                .deserialize::<LegacyMessage<Id>>(bytes)
+                .ok()
+                .filter(|legacy| legacy.version < WIRE_VERSION)
+                .map(Self::from)
+                .map_or_else(
+                    || {
+                        current.and_then(|message| {
+                            Err(AggregateError::IncompatibleVersion(message.version))
+                        })
+                    },
+                    Ok,
+                ),
+        }
+    }
+
+    /// Wire format version of the message, [`WIRE_VERSION`] once decoded.
+    pub const fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Empty the message for a new round, keeping the allocated capacity.
+    pub fn reset(&mut self) {
+        self.underlying.clear();
+        self.appended.clear();
+        self.sequence = 0;
+        self.delta = None;
+        self.metadata = None;
+        self.hop_limits.clear();
+        self.relays = 0;
+    }
+
+    /// Attach a header describing the round to the message, see [`Metadata`].
+    pub const fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = Some(metadata);
+    }
+
+    pub const fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Number the message, so that receivers can tell whether they missed the base of a delta.
+    pub const fn set_sequence(&mut self, sequence: u64) {
+        self.sequence = sequence;
+    }
+
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub const fn is_delta(&self) -> bool {
+        self.delta.is_some()
+    }
+
+    /// Build a delta carrying only the paths of `self` that are new or changed with respect to
+    /// `previous`, together with the paths that disappeared.
+    ///
+    /// Subtrees left unchanged are skipped as a whole, and subtrees that disappeared are listed
+    /// by their root only.
+    #[must_use]
+    pub fn delta_from(&self, previous: &Self) -> Self {
+        let removed = self.underlying.removed_since(&previous.underlying);
+        Self {
+            sender: self.sender,
+            underlying: self.underlying.changed_since(&previous.underlying),
+            appended: self
+                .appended
+                .iter()
+                .filter(|path| previous.underlying.get(path) != self.underlying.get(path))
+                .cloned()
+                .collect(),
+            sequence: self.sequence,
+            delta: Some(Delta {
+                base: previous.sequence,
+                removed: removed.values,
+                pruned: removed.subtrees,
+            }),
+            metadata: self.metadata,
+            version: self.version,
+            hop_limits: self.hop_limits.clone(),
+            relays: self.relays,
         }
     }
 
+    /// Rebuild the full tree of the sender given the `last` tree received from it and its sequence.
+    ///
+    /// # Returns
+    /// `None` if the message is a delta whose base is not `last`, i.e. a message has been lost
+    pub fn resolve(self, last: Option<(u64, &ValueTree)>) -> Option<ValueTree> {
+        let Some(delta) = self.delta else {
+            return Some(
+                tree_of(self.underlying)
+                    .with_metadata(self.metadata)
+                    .with_hops(self.relays.saturating_add(1)),
+            );
+        };
+        let (_, tree) = last.filter(|(sequence, _)| *sequence == delta.base)?;
+        let mut merged = tree.clone();
+        for prefix in &delta.pruned {
+            merged.prune(prefix);
+        }
+        for path in &delta.removed {
+            merged.remove(path);
+        }
+        for (path, value) in self.underlying.into_leaves() {
+            merged.insert(path, value);
+        }
+        Some(
+            merged
+                .with_metadata(self.metadata)
+                .with_hops(self.relays.saturating_add(1)),
+        )
+    }
+
     pub fn append(&mut self, path: &Path, value: Vec<u8>) {
-        self.underlying.insert(path.to_string(), value);
+        if self.underlying.insert(path, value).is_none() {
+            self.appended.push(path.clone());
+        }
+    }
+
+    /// Remove the value exported at `path`, returning it.
+    pub fn remove(&mut self, path: &Path) -> Option<Vec<u8>> {
+        let removed = self.underlying.remove(path);
+        self.appended.retain(|appended| appended != path);
+        self.hop_limits.remove(path);
+        removed
+    }
+
+    /// Remove every value exported at `prefix` or below it, e.g. the exports of a program that
+    /// neighbors do not run, returning them with paths relative to `prefix`.
+    pub fn prune(&mut self, prefix: &Path) -> Option<ExportTree> {
+        let pruned = self.underlying.prune(prefix)?;
+        self.appended.retain(|path| !path.starts_with(prefix));
+        self.hop_limits.retain(|path, _| !path.starts_with(prefix));
+        Some(pruned)
+    }
+
+    /// Values exported at `prefix` or below it, with paths relative to `prefix`.
+    pub fn subtree(&self, prefix: &Path) -> Option<&ExportTree> {
+        self.underlying.subtree(prefix)
+    }
+
+    /// Let the value exported at `path` travel at most `hops` hops: relays stop forwarding it
+    /// once the limit is reached. A limit of 0 or 1 keeps it to the direct neighbors.
+    pub fn set_hop_limit(&mut self, path: &Path, hops: u8) {
+        self.hop_limits.insert(path.clone(), hops);
+    }
+
+    /// Hops the value exported at `path` can still travel, `None` if unlimited.
+    pub fn hop_limit(&self, path: &Path) -> Option<u8> {
+        self.hop_limits.get(path).copied()
+    }
+
+    /// Relays the message went through since it was sent.
+    pub const fn relays(&self) -> u8 {
+        self.relays
+    }
+
+    /// Prepare a received message to be forwarded by a relay, e.g. a node of a mesh network:
+    /// count one more hop and drop the values that reached their hop limit.
+    ///
+    /// # Returns
+    /// Whether some value is left to forward
+    pub fn relay(&mut self) -> bool {
+        let exhausted: Vec<Path> = self
+            .hop_limits
+            .iter()
+            .filter(|(_, hops)| **hops <= 1)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in exhausted {
+            self.remove(&path);
+        }
+        for hops in self.hop_limits.values_mut() {
+            *hops = hops.saturating_sub(1);
+        }
+        self.relays = self.relays.saturating_add(1);
+        !self.underlying.is_empty()
+    }
+
+    /// Exported paths, in the order they were appended in this round.
+    ///
+    /// Empty for messages decoded from the wire, since the order is not transmitted.
+    pub fn appended_paths(&self) -> impl Iterator<Item = Path> + '_ {
+        self.appended.iter().cloned()
+    }
+
+    /// Last path appended in this round, see [`OutboundMessage::appended_paths`].
+    pub fn last_appended(&self) -> Option<Path> {
+        self.appended.last().cloned()
     }
 
     pub fn at(&self, path: &Path) -> Option<&Vec<u8>> {
-        self.underlying.get(&path.to_string())
+        self.underlying.get(path)
+    }
+
+    /// Number of exported values.
+    pub fn len(&self) -> usize {
+        self.underlying.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.underlying.is_empty()
+    }
+
+    /// Total size of the exported values, excluding the paths and the encoding of the message.
+    pub fn size_bytes(&self) -> usize {
+        self.path_sizes()
+            .fold(0, |size, (_, value)| size.saturating_add(value))
+    }
+
+    /// Size of the value exported at every path, sorted by path, e.g. to find the operators
+    /// dominating the bandwidth.
+    pub fn path_sizes(&self) -> impl Iterator<Item = (Path, usize)> + '_ {
+        self.underlying
+            .leaves()
+            .map(|(path, value)| (path, value.len()))
+    }
+}
+
+fn tree_of(exports: ExportTree) -> ValueTree {
+    ValueTree::new(exports.into_leaves().into_iter().collect())
+}
+
+impl<Id: Ord + Hash + Copy> From<OutboundMessage<Id>> for ValueTree {
+    fn from(message: OutboundMessage<Id>) -> Self {
+        tree_of(message.underlying)
+            .with_metadata(message.metadata)
+            .with_hops(message.relays.saturating_add(1))
+    }
+}
+
+/// Hop limits are sent as a list of pairs, since formats like JSON only accept string keys and
+/// joining the tokens of a path would break those containing the `/` separator.
+mod hop_limits {
+    use crate::rufi::messages::path::Path;
+    #[cfg(not(feature = "std"))]
+    use alloc::collections::BTreeMap;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+    use serde::{Deserialize, Deserializer, Serializer};
+    #[cfg(feature = "std")]
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        limits: &BTreeMap<Path, u8>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(limits)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Path, u8>, D::Error> {
+        Vec::<(Path, u8)>::deserialize(deserializer).map(|pairs| pairs.into_iter().collect())
     }
 }
 
@@ -57,3 +451,195 @@ impl<Id: Ord + Hash + Copy> OutboundMessage<Id> {
 //             .and_then(|value| value.downcast_ref::<V>())
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::MockSerializer;
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+
+    #[test]
+    fn legacy_messages_are_migrated_to_the_current_version() {
+        let unversioned = br#"{"sender":3,"underlying":{"share:0/neighboring:0":[1]}}"#;
+        let flat = br#"{"sender":3,"underlying":{"share:0":[2]},"version":2,"relays":1}"#;
+        for (bytes, path, value) in [
+            (unversioned.as_slice(), "share:0/neighboring:0", 1),
+            (flat.as_slice(), "share:0", 2),
+        ] {
+            let decoded = OutboundMessage::<u32>::decode(&MockSerializer, bytes).unwrap();
+            assert_eq!(decoded.version(), WIRE_VERSION);
+            assert_eq!(decoded.at(&Path::from(path)), Some(&vec![value]));
+        }
+    }
+
+    #[test]
+    fn messages_of_unknown_versions_are_rejected() {
+        let newer = WIRE_VERSION.saturating_add(1);
+        let mut message = OutboundMessage::empty(3u32);
+        message.append(&Path::from("neighboring:0"), vec![1]);
+        message.version = newer;
+        let encoded = serde_json::to_vec(&message).unwrap();
+        let decoded = OutboundMessage::<u32>::decode(&MockSerializer, &encoded);
+        assert_eq!(
+            decoded.err(),
+            Some(AggregateError::IncompatibleVersion(newer))
+        );
+    }
+
+    #[test]
+    fn sizes_are_reported_per_path() {
+        let mut message = OutboundMessage::empty(0u32);
+        assert!(message.is_empty());
+        message.append(&Path::from("share:0"), vec![1; 8]);
+        message.append(&Path::from("branch[true]:1/neighboring:0"), vec![2; 3]);
+        message.append(&Path::from("share:0"), vec![3; 4]);
+        assert_eq!(message.len(), 2);
+        assert_eq!(message.size_bytes(), 7);
+        assert_eq!(
+            message.path_sizes().collect::<Vec<_>>(),
+            vec![
+                (Path::from("branch[true]:1/neighboring:0"), 3),
+                (Path::from("share:0"), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn relays_drop_values_beyond_their_hop_limit() {
+        let mut message = OutboundMessage::empty(0u32);
+        let local = Path::from("neighboring:0");
+        let regional = Path::from("neighboring:1");
+        message.append(&local, vec![1]);
+        message.append(&regional, vec![2]);
+        message.set_hop_limit(&local, 1);
+        message.set_hop_limit(&regional, 3);
+        assert_eq!(ValueTree::from(message.clone()).hops(), 1);
+
+        assert!(message.relay());
+        assert_eq!(message.at(&local), None);
+        assert_eq!(message.hop_limit(&regional), Some(2));
+        assert!(message.relay());
+        assert_eq!(ValueTree::from(message.clone()).hops(), 3);
+        assert!(!message.relay());
+        assert_eq!(message.relays(), 3);
+    }
+
+    #[test]
+    fn hop_limits_survive_separators_in_their_tokens() {
+        let serializer = MockSerializer;
+        let mut message = OutboundMessage::empty(0u32);
+        let zone = Path::new(vec![
+            "align[zone/north]:0".to_string(),
+            "neighboring:0".to_string(),
+        ]);
+        message.append(&zone, vec![1]);
+        message.append(&Path::from("neighboring:1"), vec![2]);
+        message.set_hop_limit(&zone, 1);
+
+        let bytes = serializer.serialize(&message).unwrap();
+        let mut received = OutboundMessage::<u32>::decode(&serializer, &bytes).unwrap();
+        assert_eq!(received.hop_limit(&zone), Some(1));
+        assert!(received.relay());
+        assert_eq!(received.at(&zone), None);
+        assert_eq!(received.hop_limit(&zone), None);
+    }
+
+    #[test]
+    fn serialization_does_not_depend_on_the_export_order() {
+        let paths = [
+            "share:0",
+            "branch[true]:1/neighboring:0",
+            "neighboring:0",
+            "a",
+        ];
+        let mut forward = OutboundMessage::empty(0u32);
+        let mut backward = OutboundMessage::empty(0u32);
+        for (value, path) in paths.iter().enumerate() {
+            forward.append(&Path::from(*path), vec![u8::try_from(value).unwrap()]);
+        }
+        for (value, path) in paths.iter().enumerate().rev() {
+            backward.append(&Path::from(*path), vec![u8::try_from(value).unwrap()]);
+        }
+        let forward = serde_json::to_string(&forward).unwrap();
+        assert_eq!(forward, serde_json::to_string(&backward).unwrap());
+        let positions: Vec<usize> = ["\"a\"", "\"branch", "\"neighboring", "\"share"]
+            .iter()
+            .filter_map(|path| forward.find(path))
+            .collect();
+        assert_eq!(positions.len(), 4);
+        assert!(positions.is_sorted());
+    }
+
+    #[test]
+    fn shared_prefixes_are_encoded_once() {
+        let prefix = "branch[true]:1/share:0/alignedMap[leader]:2";
+        let mut message = OutboundMessage::empty(0u32);
+        let mut flat = BTreeMap::new();
+        for index in 0..8 {
+            let path = format!("{prefix}/neighboring:{index}");
+            message.append(&Path::from(path.as_str()), vec![index]);
+            flat.insert(path, vec![index]);
+        }
+        let tree = serde_json::to_vec(&message).unwrap();
+        let legacy =
+            serde_json::to_vec(&serde_json::json!({ "sender": 0, "underlying": flat })).unwrap();
+        assert!(tree.len() < legacy.len());
+        assert_eq!(
+            OutboundMessage::<u32>::decode(&MockSerializer, &legacy)
+                .unwrap()
+                .subtree(&Path::from(prefix)),
+            message.subtree(&Path::from(prefix))
+        );
+    }
+
+    #[test]
+    fn deltas_prune_removed_subtrees() {
+        let mut previous = OutboundMessage::empty(0u32);
+        previous.append(&Path::from("branch[true]:0/neighboring:0"), vec![1]);
+        previous.append(&Path::from("branch[true]:0/neighboring:1"), vec![2]);
+        previous.append(&Path::from("repeat:1"), vec![3]);
+        let mut current = previous.clone();
+        current.set_sequence(1);
+        assert!(current.prune(&Path::from("branch[true]:0")).is_some());
+        current.append(&Path::from("branch[false]:0/neighboring:0"), vec![4]);
+
+        let delta = current.delta_from(&previous);
+        assert_eq!(delta.underlying.len(), 1);
+        assert_eq!(
+            delta.delta.as_ref().map(|delta| delta.pruned.clone()),
+            Some(vec![Path::from("branch[true]:0")])
+        );
+        let last = ValueTree::from(previous.clone());
+        let resolved = delta.resolve(Some((previous.sequence(), &last))).unwrap();
+        assert_eq!(
+            resolved.get(&Path::from("branch[true]:0/neighboring:1")),
+            None
+        );
+        assert_eq!(resolved.get(&Path::from("repeat:1")), Some([3].as_slice()));
+        assert_eq!(
+            resolved.get(&Path::from("branch[false]:0/neighboring:0")),
+            Some([4].as_slice())
+        );
+    }
+
+    #[test]
+    fn deltas_remove_paths_with_separators_in_their_tokens() {
+        let zone = Path::new(vec!["align[zone/north]:0", "neighboring:0"]);
+        let mut previous = OutboundMessage::empty(0u32);
+        previous.append(&zone, vec![1]);
+        previous.append(&Path::from("repeat:1"), vec![2]);
+        let mut current = previous.clone();
+        current.set_sequence(1);
+        current.remove(&zone);
+
+        let bytes = MockSerializer
+            .serialize(&current.delta_from(&previous))
+            .unwrap();
+        let delta = OutboundMessage::<u32>::decode(&MockSerializer, &bytes).unwrap();
+        let last = ValueTree::from(previous.clone());
+        let resolved = delta.resolve(Some((previous.sequence(), &last))).unwrap();
+        assert_eq!(resolved.get(&zone), None);
+        assert_eq!(resolved.get(&Path::from("repeat:1")), Some([2].as_slice()));
+    }
+}
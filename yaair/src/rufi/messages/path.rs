@@ -18,6 +18,45 @@ impl Path {
             tokens: tokens.into_iter().map(|t| t.to_string()).collect(),
         }
     }
+
+    /// The empty path, prefix of every other path.
+    pub const fn root() -> Self {
+        Self { tokens: Vec::new() }
+    }
+
+    pub const fn tokens(&self) -> &[String] {
+        self.tokens.as_slice()
+    }
+
+    /// Number of tokens in the path.
+    pub const fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// This path extended with `token`.
+    pub fn child(&self, token: &impl ToString) -> Self {
+        let mut tokens = self.tokens.clone();
+        tokens.push(token.to_string());
+        Self { tokens }
+    }
+
+    /// Whether `prefix` is a prefix of this path, token by token.
+    pub fn starts_with(&self, prefix: &Self) -> bool {
+        self.tokens.starts_with(&prefix.tokens)
+    }
+
+    /// This path relative to `prefix`, `None` if `prefix` is not a prefix of it.
+    pub fn strip_prefix(&self, prefix: &Self) -> Option<Self> {
+        self.tokens
+            .strip_prefix(prefix.tokens.as_slice())
+            .map(|tokens| Self {
+                tokens: tokens.to_vec(),
+            })
+    }
 }
 impl Display for Path {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
@@ -36,9 +75,7 @@ impl From<&str> for Path {
 mod tests {
     use super::*;
 
-    #[cfg(not(feature = "std"))]
-    use alloc::collections::BTreeSet as Set;
-    use std::collections::HashSet as Set;
+    use crate::rufi::messages::Set;
 
     fn make_path(tokens: &[&str]) -> Path {
         Path::new(tokens.to_vec())
@@ -58,6 +95,25 @@ mod tests {
         assert!(!set.contains(&p3));
     }
 
+    #[test]
+    fn test_path_starts_with() {
+        let path = make_path(&["a", "b", "c"]);
+        assert!(path.starts_with(&make_path(&["a", "b"])));
+        assert!(!path.starts_with(&make_path(&["a", "c"])));
+        assert!(!make_path(&["a"]).starts_with(&path));
+    }
+
+    #[test]
+    fn test_path_strip_prefix() {
+        let path = make_path(&["a", "b", "c"]);
+        assert_eq!(
+            path.strip_prefix(&make_path(&["a"])),
+            Some(make_path(&["b", "c"]))
+        );
+        assert_eq!(path.strip_prefix(&Path::root()), Some(path.clone()));
+        assert_eq!(path.strip_prefix(&make_path(&["b"])), None);
+    }
+
     #[test]
     fn test_path_ordering() {
         let p1 = make_path(&["a"]);
@@ -8,5 +8,6 @@ pub trait Serializer {
     type Error: Display;
 
     fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error>;
-    fn deserialize<T: for<'de> Deserialize<'de>>(&self, value: &[u8]) -> Result<T, Self::Error>;
+    /// Deserialize a value borrowing from `value`, so that payloads can be read in place.
+    fn deserialize<'de, T: Deserialize<'de>>(&self, value: &'de [u8]) -> Result<T, Self::Error>;
 }
@@ -0,0 +1,121 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::messages::path::Path;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::messages::valuetree::ValueTree;
+use crate::rufi::messages::Map;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_value::Value;
+
+/// [`ValueTree`] whose leaves are decoded into self-describing [`Value`]s, so that debugging
+/// tools and simulator front-ends can inspect exports without knowing their Rust types.
+///
+/// Decoding needs a self-describing wire format (e.g. JSON): formats like postcard cannot be
+/// decoded without the type of every value, and keep using the byte representation.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StructuredTree {
+    underlying: Map<Path, Value>,
+}
+impl StructuredTree {
+    /// Decode every leaf of `tree` with `serializer`.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::DeserializationError`] if a leaf cannot be decoded without its
+    /// type, e.g. because the format is not self-describing
+    pub fn decode<S: Serializer>(tree: &ValueTree, serializer: &S) -> Result<Self, AggregateError> {
+        tree.entries_under(&Path::root())
+            .map(|(path, bytes)| {
+                serializer
+                    .deserialize::<Value>(bytes)
+                    .map(|value| (path.clone(), value))
+                    .map_err(|err| AggregateError::DeserializationError(format!("{path}: {err}")))
+            })
+            .collect::<Result<_, _>>()
+            .map(|underlying| Self { underlying })
+    }
+
+    /// Encode every leaf with `serializer`, e.g. to inject hand-written exports in a simulation.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::SerializationError`] if a leaf cannot be encoded
+    pub fn encode<S: Serializer>(&self, serializer: &S) -> Result<ValueTree, AggregateError> {
+        self.underlying
+            .iter()
+            .map(|(path, value)| {
+                serializer
+                    .serialize(value)
+                    .map(|bytes| (path.clone(), bytes))
+                    .map_err(|err| AggregateError::SerializationError(format!("{path}: {err}")))
+            })
+            .collect::<Result<_, _>>()
+            .map(ValueTree::new)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Value> {
+        self.underlying.get(path)
+    }
+
+    /// The value at `path` converted to `T`, `None` if missing or of a different shape.
+    pub fn get_as<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        self.get(path)?.clone().deserialize_into().ok()
+    }
+
+    /// Store `value` at `path`.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::SerializationError`] if `value` cannot be represented
+    pub fn insert<T: Serialize>(&mut self, path: Path, value: &T) -> Result<(), AggregateError> {
+        let value = serde_value::to_value(value)
+            .map_err(|err| AggregateError::SerializationError(err.to_string()))?;
+        self.underlying.insert(path, value);
+        Ok(())
+    }
+
+    /// Values in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &Value)> + '_ {
+        self.underlying.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.underlying.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.underlying.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::test_utils::MockSerializer;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn leaves_round_trip_through_structured_values() {
+        let (distance, label) = (Path::from("share:0"), Path::from("branch:1/neighboring:0"));
+        let mut tree = ValueTree::empty();
+        tree.insert(distance.clone(), MockSerializer.serialize(&2.5f64).unwrap());
+        tree.insert(
+            label.clone(),
+            MockSerializer.serialize(&("leader", 3u32)).unwrap(),
+        );
+
+        let structured = StructuredTree::decode(&tree, &MockSerializer).unwrap();
+        assert_eq!(structured.len(), 2);
+        assert_eq!(structured.get(&distance), Some(&Value::F64(2.5)));
+        assert_eq!(
+            structured.get_as::<(String, u32)>(&label),
+            Some(("leader".to_owned(), 3))
+        );
+        assert_eq!(structured.get_as::<BTreeMap<String, u32>>(&label), None);
+
+        let encoded = structured.encode(&MockSerializer).unwrap();
+        assert_eq!(encoded.get(&distance), tree.get(&distance));
+        assert!(StructuredTree::decode(
+            &ValueTree::new(Map::from([(distance, b"{".to_vec())])),
+            &MockSerializer
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::rufi::messages::path::Path;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// Exported values arranged by path token, so that the prefix shared by the values of a deeply
+/// nested alignment stack is encoded once.
+///
+/// Children are kept sorted by token, so that the same exports always serialize to the same
+/// bytes; empty branches are pruned as values are removed.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ExportTree {
+    #[serde(rename = "v")]
+    value: Option<Vec<u8>>,
+    #[serde(rename = "c")]
+    children: BTreeMap<String, Self>,
+}
+
+/// Paths of a previous tree missing from the current one, see [`ExportTree::removed_since`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Removed {
+    /// Paths whose value was removed, while some value below them is still exported.
+    pub values: Vec<Path>,
+    /// Roots of the subtrees removed as a whole.
+    pub subtrees: Vec<Path>,
+}
+
+impl ExportTree {
+    pub const fn new() -> Self {
+        Self {
+            value: None,
+            children: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Vec<u8>> {
+        self.subtree(path)?.value.as_ref()
+    }
+
+    /// Values exported at `prefix` or below it, with paths relative to `prefix`.
+    pub fn subtree(&self, prefix: &Path) -> Option<&Self> {
+        prefix
+            .tokens()
+            .iter()
+            .try_fold(self, |node, token| node.children.get(token))
+    }
+
+    /// Store `value` at `path`, returning the value it replaces.
+    pub fn insert(&mut self, path: &Path, value: Vec<u8>) -> Option<Vec<u8>> {
+        let node = path.tokens().iter().fold(self, |node, token| {
+            node.children.entry(token.clone()).or_default()
+        });
+        node.value.replace(value)
+    }
+
+    /// Remove the value exported at `path`, keeping the values below it.
+    pub fn remove(&mut self, path: &Path) -> Option<Vec<u8>> {
+        self.take(path.tokens(), |node| node.value.take())
+    }
+
+    /// Detach the subtree rooted at `prefix`, e.g. to drop the exports of a branch at once.
+    pub fn prune(&mut self, prefix: &Path) -> Option<Self> {
+        self.take(prefix.tokens(), |node| Some(core::mem::take(node)))
+            .filter(|pruned| !pruned.is_empty())
+    }
+
+    /// Apply `take` to the node at `tokens`, then remove the branches left empty.
+    fn take<T>(
+        &mut self,
+        tokens: &[String],
+        take: impl FnOnce(&mut Self) -> Option<T>,
+    ) -> Option<T> {
+        let Some((first, rest)) = tokens.split_first() else {
+            return take(self);
+        };
+        let child = self.children.get_mut(first)?;
+        let taken = child.take(rest, take);
+        if child.is_empty() {
+            self.children.remove(first);
+        }
+        taken
+    }
+
+    /// Number of exported values.
+    pub fn len(&self) -> usize {
+        self.children
+            .values()
+            .fold(usize::from(self.value.is_some()), |len, child| {
+                len.saturating_add(child.len())
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_none() && self.children.values().all(Self::is_empty)
+    }
+
+    pub fn clear(&mut self) {
+        self.value = None;
+        self.children.clear();
+    }
+
+    /// Exported values, sorted by path.
+    pub fn leaves(&self) -> impl Iterator<Item = (Path, &[u8])> + '_ {
+        let mut leaves = Vec::new();
+        self.collect_leaves(&Path::root(), &mut leaves);
+        leaves.into_iter()
+    }
+
+    fn collect_leaves<'a>(&'a self, at: &Path, leaves: &mut Vec<(Path, &'a [u8])>) {
+        if let Some(value) = &self.value {
+            leaves.push((at.clone(), value.as_slice()));
+        }
+        for (token, child) in &self.children {
+            child.collect_leaves(&at.child(token), leaves);
+        }
+    }
+
+    /// Exported values, sorted by path, consuming the tree.
+    pub fn into_leaves(self) -> Vec<(Path, Vec<u8>)> {
+        let mut leaves = Vec::new();
+        self.collect_owned_leaves(&Path::root(), &mut leaves);
+        leaves
+    }
+
+    fn collect_owned_leaves(self, at: &Path, leaves: &mut Vec<(Path, Vec<u8>)>) {
+        if let Some(value) = self.value {
+            leaves.push((at.clone(), value));
+        }
+        for (token, child) in self.children {
+            child.collect_owned_leaves(&at.child(&token), leaves);
+        }
+    }
+
+    /// Values that are new or changed with respect to `previous`; subtrees equal to the ones of
+    /// `previous` are skipped as a whole.
+    #[must_use]
+    pub fn changed_since(&self, previous: &Self) -> Self {
+        if self == previous {
+            return Self::new();
+        }
+        Self {
+            value: self.value.clone().filter(|_| self.value != previous.value),
+            children: self
+                .children
+                .iter()
+                .map(|(token, child)| {
+                    let changed = previous
+                        .children
+                        .get(token)
+                        .map_or_else(|| child.clone(), |old| child.changed_since(old));
+                    (token.clone(), changed)
+                })
+                .filter(|(_, changed)| !changed.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Paths of `previous` missing from this tree, listing removed subtrees by their root only.
+    pub fn removed_since(&self, previous: &Self) -> Removed {
+        let mut removed = Removed::default();
+        self.collect_removed(previous, &Path::root(), &mut removed);
+        removed
+    }
+
+    fn collect_removed(&self, previous: &Self, at: &Path, removed: &mut Removed) {
+        if self.value.is_none() && previous.value.is_some() {
+            removed.values.push(at.clone());
+        }
+        for (token, old) in &previous.children {
+            match self.children.get(token) {
+                Some(child) if !child.is_empty() => {
+                    child.collect_removed(old, &at.child(token), removed);
+                }
+                _ if old.is_empty() => {}
+                _ => removed.subtrees.push(at.child(token)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+
+    #[test]
+    fn removing_values_prunes_empty_branches() {
+        let mut tree = ExportTree::new();
+        tree.insert(&Path::from("branch:0/share:0"), vec![1]);
+        tree.insert(&Path::from("branch:0/share:0/neighboring:0"), vec![2]);
+        tree.insert(&Path::from("repeat:1"), vec![3]);
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&Path::from("branch:0")), None);
+
+        assert_eq!(tree.remove(&Path::from("branch:0/share:0")), Some(vec![1]));
+        assert_eq!(
+            tree.get(&Path::from("branch:0/share:0/neighboring:0")),
+            Some(&vec![2])
+        );
+        assert_eq!(
+            tree.remove(&Path::from("branch:0/share:0/neighboring:0")),
+            Some(vec![2])
+        );
+        assert!(tree.subtree(&Path::from("branch:0")).is_none());
+
+        let pruned = tree.prune(&Path::from("repeat:1")).unwrap();
+        assert_eq!(pruned.get(&Path::root()), Some(&vec![3]));
+        assert!(tree.is_empty());
+        assert!(tree.prune(&Path::from("repeat:1")).is_none());
+    }
+
+    #[test]
+    fn leaves_are_sorted_by_path() {
+        let mut tree = ExportTree::new();
+        for path in ["share:0", "branch:1/neighboring:0", "branch:1", "a"] {
+            tree.insert(&Path::from(path), path.as_bytes().to_vec());
+        }
+        let paths: Vec<String> = tree.leaves().map(|(path, _)| path.to_string()).collect();
+        assert_eq!(
+            paths,
+            ["a", "branch:1", "branch:1/neighboring:0", "share:0"]
+        );
+        let owned: Vec<Path> = tree
+            .clone()
+            .into_leaves()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        let mut sorted = owned.clone();
+        sorted.sort();
+        assert_eq!(owned, sorted);
+    }
+
+    #[test]
+    fn differences_are_reported_by_subtree() {
+        let mut previous = ExportTree::new();
+        previous.insert(&Path::from("branch:0"), vec![0]);
+        previous.insert(&Path::from("branch:0/share:0"), vec![1]);
+        previous.insert(&Path::from("branch:1/share:0/neighboring:0"), vec![2]);
+        previous.insert(&Path::from("branch:1/share:0/neighboring:1"), vec![3]);
+        previous.insert(&Path::from("repeat:0"), vec![4]);
+        let mut current = previous.clone();
+        current.remove(&Path::from("branch:0"));
+        current.prune(&Path::from("branch:1"));
+        current.insert(&Path::from("repeat:0"), vec![5]);
+
+        let changed = current.changed_since(&previous);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed.get(&Path::from("repeat:0")), Some(&vec![5]));
+        assert_eq!(
+            current.removed_since(&previous),
+            Removed {
+                values: vec![Path::from("branch:0")],
+                subtrees: vec![Path::from("branch:1")],
+            }
+        );
+        assert!(current.changed_since(&current).is_empty());
+    }
+}
@@ -1,35 +1,130 @@
+use crate::rufi::messages::metadata::Metadata;
 use crate::rufi::messages::path::Path;
-
-#[cfg(not(feature = "std"))]
-use alloc::collections::BTreeMap as Map;
+use crate::rufi::messages::Map;
 
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-use std::collections::HashMap as Map;
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ValueTree {
     underlying: Map<Path, Vec<u8>>,
+    metadata: Option<Metadata>,
+    /// Hops travelled by the message the tree was received with, 1 if received from the sender.
+    hops: u8,
 }
 
 impl ValueTree {
+    // `BTreeMap::new` is const, `HashMap::new` is not
+    #[cfg_attr(not(feature = "std"), allow(clippy::missing_const_for_fn))]
     pub fn empty() -> Self {
         Self {
             underlying: Map::new(),
+            metadata: None,
+            hops: 1,
         }
     }
 
     pub const fn new(underlying: Map<Path, Vec<u8>>) -> Self {
-        Self { underlying }
+        Self {
+            underlying,
+            metadata: None,
+            hops: 1,
+        }
+    }
+
+    /// Attach the header of the message the tree was received with.
+    #[must_use]
+    pub const fn with_metadata(mut self, metadata: Option<Metadata>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Header of the message the tree was received with, if the sender attached one.
+    pub const fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Set the hops travelled by the message the tree was received with.
+    #[must_use]
+    pub const fn with_hops(mut self, hops: u8) -> Self {
+        self.hops = hops;
+        self
+    }
+
+    /// Hops travelled by the message the tree was received with, see
+    /// [`OutboundMessage::relay`](crate::rufi::messages::outbound::OutboundMessage::relay).
+    pub const fn hops(&self) -> u8 {
+        self.hops
     }
 
     pub fn contains_key(&self, path: &Path) -> bool {
         self.underlying.contains_key(path)
     }
 
-    pub fn get(&self, path: &Path) -> Option<Vec<u8>> {
-        self.underlying.get(path).cloned()
+    /// Whether some value is exported at `prefix` or below it.
+    pub fn contains_prefix(&self, prefix: &Path) -> bool {
+        self.underlying.keys().any(|path| path.starts_with(prefix))
+    }
+
+    /// Total size of the exported values, excluding the paths.
+    pub fn payload_size(&self) -> usize {
+        self.underlying
+            .values()
+            .fold(0, |size, value| size.saturating_add(value.len()))
+    }
+
+    /// Size of the value exported at every path, in no particular order.
+    pub fn path_sizes(&self) -> impl Iterator<Item = (&Path, usize)> + '_ {
+        self.underlying
+            .iter()
+            .map(|(path, value)| (path, value.len()))
+    }
+
+    /// Values exported at `prefix` or below it, in no particular order.
+    pub fn entries_under<'a>(
+        &'a self,
+        prefix: &'a Path,
+    ) -> impl Iterator<Item = (&'a Path, &'a [u8])> + 'a {
+        self.underlying
+            .iter()
+            .filter(|(path, _)| path.starts_with(prefix))
+            .map(|(path, value)| (path, value.as_slice()))
+    }
+
+    /// Values exported at `prefix` or below it, with paths relative to `prefix`, e.g. the
+    /// exports of a single program of a multi-program engine.
+    pub fn get_subtree(&self, prefix: &Path) -> Self {
+        let underlying = self
+            .underlying
+            .iter()
+            .filter_map(|(path, value)| Some((path.strip_prefix(prefix)?, value.clone())))
+            .collect();
+        Self {
+            underlying,
+            metadata: self.metadata,
+            hops: self.hops,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.underlying.is_empty()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        self.underlying.get(path).map(Vec::as_slice)
+    }
+
+    pub fn insert(&mut self, path: Path, value: Vec<u8>) {
+        self.underlying.insert(path, value);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<Vec<u8>> {
+        self.underlying.remove(path)
+    }
+
+    /// Remove every value exported at `prefix` or below it.
+    pub fn prune(&mut self, prefix: &Path) {
+        self.underlying.retain(|path, _| !path.starts_with(prefix));
     }
 
     // pub fn insert<T>(&mut self, path: Path, value: T)
@@ -0,0 +1,209 @@
+use crate::rufi::aggregate::AggregateError;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(all(feature = "prometheus", not(feature = "std")))]
+use alloc::string::String;
+use core::time::Duration;
+
+/// Measurements of a single round executed by the [`Engine`](crate::rufi::engine::Engine).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RoundMetrics {
+    /// Size of the payloads received from the neighbors.
+    pub bytes_in: usize,
+    /// Size of the serialized outbound message.
+    pub bytes_out: usize,
+    /// Neighbors whose messages were used in the round.
+    pub neighbors: usize,
+    /// Neighbors that were not part of the previous round.
+    pub joined: usize,
+    /// Neighbors of the previous round that are no longer present.
+    pub left: usize,
+    /// Wall time spent in the round, from receiving to sending; `None` without `std` and on
+    /// `wasm32-unknown-unknown`, where no monotonic clock is available.
+    pub latency: Option<Duration>,
+    /// Whether the network failed to send the outbound message.
+    pub send_failed: bool,
+    /// Whether the outbound message differs from the one of the previous round, i.e. the values
+    /// exported to the neighbors are changing.
+    pub changed: bool,
+}
+
+/// Sink for the measurements taken by the engine at every round.
+pub trait Metrics {
+    /// Called after every round that produced an outbound message.
+    fn round_completed(&mut self, round: &RoundMetrics);
+
+    /// Called when a round fails with `error`.
+    fn round_failed(&mut self, error: &AggregateError);
+
+    /// Aggregated view of the measurements collected so far.
+    fn snapshot(&self) -> MetricsSnapshot;
+}
+
+impl<M: Metrics + ?Sized> Metrics for Box<M> {
+    fn round_completed(&mut self, round: &RoundMetrics) {
+        (**self).round_completed(round);
+    }
+
+    fn round_failed(&mut self, error: &AggregateError) {
+        (**self).round_failed(error);
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
+        (**self).snapshot()
+    }
+}
+
+/// Totals and last values of the measurements of an engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetricsSnapshot {
+    pub rounds: u64,
+    pub errors: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Neighbors in the last round.
+    pub neighbors: usize,
+    pub joined: u64,
+    pub left: u64,
+    pub last_latency: Option<Duration>,
+}
+impl MetricsSnapshot {
+    /// Render the snapshot in the Prometheus text exposition format, naming every metric after
+    /// `prefix`.
+    #[cfg(feature = "prometheus")]
+    pub fn to_prometheus(&self, prefix: &str) -> String {
+        use core::fmt::Write;
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: &dyn core::fmt::Display| {
+            // writing to a String never fails
+            let _ = write!(
+                text,
+                "# HELP {prefix}_{name} {help}\n# TYPE {prefix}_{name} {kind}\n{prefix}_{name} {value}\n",
+            );
+        };
+        metric("rounds_total", "counter", "Rounds executed.", &self.rounds);
+        metric("errors_total", "counter", "Rounds failed.", &self.errors);
+        metric(
+            "received_bytes_total",
+            "counter",
+            "Payload bytes received from neighbors.",
+            &self.bytes_in,
+        );
+        metric(
+            "sent_bytes_total",
+            "counter",
+            "Bytes of outbound messages sent.",
+            &self.bytes_out,
+        );
+        metric(
+            "neighbors",
+            "gauge",
+            "Neighbors in the last round.",
+            &self.neighbors,
+        );
+        metric(
+            "neighbors_joined_total",
+            "counter",
+            "Neighbors appeared since the previous round.",
+            &self.joined,
+        );
+        metric(
+            "neighbors_left_total",
+            "counter",
+            "Neighbors disappeared since the previous round.",
+            &self.left,
+        );
+        if let Some(latency) = self.last_latency {
+            metric(
+                "round_latency_seconds",
+                "gauge",
+                "Wall time spent in the last round.",
+                &latency.as_secs_f64(),
+            );
+        }
+        text
+    }
+}
+
+/// [`Metrics`] keeping running totals in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InMemoryMetrics {
+    snapshot: MetricsSnapshot,
+}
+impl InMemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Metrics for InMemoryMetrics {
+    fn round_completed(&mut self, round: &RoundMetrics) {
+        let total = &mut self.snapshot;
+        let widen = |value: usize| u64::try_from(value).unwrap_or(u64::MAX);
+        total.rounds = total.rounds.saturating_add(1);
+        total.bytes_in = total.bytes_in.saturating_add(widen(round.bytes_in));
+        total.bytes_out = total.bytes_out.saturating_add(widen(round.bytes_out));
+        total.neighbors = round.neighbors;
+        total.joined = total.joined.saturating_add(widen(round.joined));
+        total.left = total.left.saturating_add(widen(round.left));
+        total.last_latency = round.latency;
+    }
+
+    fn round_failed(&mut self, _error: &AggregateError) {
+        self.snapshot.errors = self.snapshot.errors.saturating_add(1);
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
+        self.snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+
+    #[test]
+    fn in_memory_metrics_accumulate_rounds() {
+        let mut metrics = InMemoryMetrics::new();
+        let round = RoundMetrics {
+            bytes_in: 10,
+            bytes_out: 4,
+            neighbors: 2,
+            joined: 2,
+            left: 0,
+            latency: None,
+            send_failed: false,
+            changed: true,
+        };
+        metrics.round_completed(&round);
+        metrics.round_completed(&RoundMetrics {
+            neighbors: 1,
+            left: 1,
+            ..round
+        });
+        metrics.round_failed(&AggregateError::SerializationError(String::new()));
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.rounds, 2);
+        assert_eq!(snapshot.errors, 1);
+        assert_eq!(snapshot.bytes_in, 20);
+        assert_eq!(snapshot.neighbors, 1);
+        assert_eq!((snapshot.joined, snapshot.left), (4, 1));
+    }
+
+    #[test]
+    #[cfg(feature = "prometheus")]
+    fn snapshots_render_as_prometheus_text() {
+        let snapshot = MetricsSnapshot {
+            rounds: 3,
+            last_latency: Some(Duration::from_millis(250)),
+            ..MetricsSnapshot::default()
+        };
+        let text = snapshot.to_prometheus("yaair");
+        assert!(text.contains("# TYPE yaair_rounds_total counter\nyaair_rounds_total 3\n"));
+        assert!(text.contains("yaair_round_latency_seconds 0.25\n"));
+        assert!(!MetricsSnapshot::default()
+            .to_prometheus("yaair")
+            .contains("latency"));
+    }
+}
@@ -1,6 +1,27 @@
 pub mod aggregate;
 pub mod alignment;
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod blocks;
+pub mod builder;
+pub mod compose;
 pub mod data;
+pub mod device;
+pub mod discovery;
 pub mod engine;
 pub mod messages;
+pub mod metrics;
+pub mod multi;
 pub mod network;
+pub mod replay;
+pub mod scheduler;
+pub mod sensors;
+#[cfg(feature = "std")]
+pub mod simulator;
+pub mod store;
+#[cfg(feature = "stream")]
+pub mod stream;
+pub mod time;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
@@ -0,0 +1,371 @@
+use crate::rufi::aggregate::{AggregateError, VM};
+use crate::rufi::device::DeviceId;
+use crate::rufi::engine::Program;
+use crate::rufi::messages::inbound::InboundMessage;
+use crate::rufi::messages::outbound::OutboundMessage;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::messages::valuetree::ValueTree;
+use crate::rufi::messages::Map;
+use crate::rufi::network::Network;
+use crate::rufi::scheduler::{Periodic, Scheduler};
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+/// How hosted devices receive the exports of the other hosted devices, see
+/// [`MultiDeviceEngine::with_routing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LocalRouting {
+    /// Hand over the exported trees in memory, shared by all the receivers, without encoding
+    /// nor decoding the messages; the values in the trees are still serialized by the
+    /// operators exporting them.
+    #[default]
+    InMemory,
+    /// Encode and decode the messages like the ones of remote devices, e.g. to check that the
+    /// whole message round-trips through the serializer.
+    Serialized,
+}
+
+/// A logical device hosted by a [`MultiDeviceEngine`].
+struct Hosted<Id: DeviceId, Env, S: Serializer, Sch> {
+    vm: VM<Id, S>,
+    environment: Env,
+    scheduler: Sch,
+    /// Exports produced by the other devices when this one last executed a round.
+    seen: u64,
+}
+
+/// Host of many logical devices in a single process, e.g. a gateway emulating its leaf devices.
+///
+/// Every device runs the same program on its own environment and is scheduled independently.
+/// Exports are routed in memory between hosted devices, which by default are all neighbors of
+/// each other: every export is shared by its receivers instead of copied for each of them. An
+/// optional uplink exchanges them with remote devices as well.
+pub struct MultiDeviceEngine<Id, Out, Env, S, Sch = Periodic>
+where
+    Id: DeviceId,
+    S: Serializer + Clone,
+    Sch: Scheduler + Clone,
+{
+    serializer: S,
+    program: Program<Env, Id, S, Out>,
+    scheduler: Sch,
+    devices: BTreeMap<Id, Hosted<Id, Env, S, Sch>>,
+    exports: BTreeMap<Id, Arc<ValueTree>>,
+    remote: BTreeMap<Id, Arc<ValueTree>>,
+    results: BTreeMap<Id, Out>,
+    generation: u64,
+    routing: LocalRouting,
+    relation: Option<Box<dyn Fn(Id, Id) -> bool>>,
+    uplink: Option<Box<dyn Network<Id, S>>>,
+}
+impl<Id, Out, Env, S> MultiDeviceEngine<Id, Out, Env, S>
+where
+    Id: DeviceId,
+    S: Serializer + Clone,
+{
+    /// Create an engine without devices, scheduled with the default [`Periodic`] policy.
+    pub fn new(serializer: S, program: Program<Env, Id, S, Out>) -> Self {
+        Self {
+            serializer,
+            program,
+            scheduler: Periodic::default(),
+            devices: BTreeMap::new(),
+            exports: BTreeMap::new(),
+            remote: BTreeMap::new(),
+            results: BTreeMap::new(),
+            generation: 0,
+            routing: LocalRouting::default(),
+            relation: None,
+            uplink: None,
+        }
+    }
+}
+impl<Id, Out, Env, S, Sch> MultiDeviceEngine<Id, Out, Env, S, Sch>
+where
+    Id: DeviceId,
+    S: Serializer + Clone,
+    Sch: Scheduler + Clone,
+{
+    /// Schedule every device, including the ones already added, with a copy of `scheduler`.
+    pub fn with_scheduler<Sch2: Scheduler + Clone>(
+        self,
+        scheduler: Sch2,
+    ) -> MultiDeviceEngine<Id, Out, Env, S, Sch2> {
+        let devices = self
+            .devices
+            .into_iter()
+            .map(|(id, device)| {
+                let hosted = Hosted {
+                    vm: device.vm,
+                    environment: device.environment,
+                    scheduler: scheduler.clone(),
+                    seen: device.seen,
+                };
+                (id, hosted)
+            })
+            .collect();
+        MultiDeviceEngine {
+            serializer: self.serializer,
+            program: self.program,
+            scheduler,
+            devices,
+            exports: self.exports,
+            remote: self.remote,
+            results: self.results,
+            generation: self.generation,
+            routing: self.routing,
+            relation: self.relation,
+            uplink: self.uplink,
+        }
+    }
+
+    /// Select how exports are routed between hosted devices.
+    #[must_use]
+    pub const fn with_routing(mut self, routing: LocalRouting) -> Self {
+        self.routing = routing;
+        self
+    }
+
+    /// Only let hosted devices `a` and `b` be neighbors if `relation(a, b)` holds.
+    #[must_use]
+    pub fn with_neighbor_relation(mut self, relation: impl Fn(Id, Id) -> bool + 'static) -> Self {
+        self.relation = Some(Box::new(relation));
+        self
+    }
+
+    /// Send the exports of every hosted device through `uplink` and deliver the messages it
+    /// receives to all of them.
+    #[must_use]
+    pub fn with_uplink(mut self, uplink: impl Network<Id, S> + 'static) -> Self {
+        self.uplink = Some(Box::new(uplink));
+        self
+    }
+
+    /// Host a new device, taking part in the rounds from the next one.
+    ///
+    /// # Returns
+    /// `false` if a device with the same id is already hosted
+    pub fn add_device(&mut self, id: Id, environment: Env) -> bool {
+        if self.devices.contains_key(&id) {
+            return false;
+        }
+        let hosted = Hosted {
+            vm: VM::new(id, self.serializer.clone()),
+            environment,
+            scheduler: self.scheduler.clone(),
+            seen: 0,
+        };
+        self.devices.insert(id, hosted);
+        true
+    }
+
+    /// Stop hosting a device, forgetting its exports.
+    ///
+    /// # Returns
+    /// The environment of the device, or `None` if it is not hosted
+    pub fn remove_device(&mut self, id: Id) -> Option<Env> {
+        self.exports.remove(&id);
+        self.results.remove(&id);
+        self.devices.remove(&id).map(|device| device.environment)
+    }
+
+    /// Ids of the hosted devices, in ascending order.
+    pub fn devices(&self) -> impl Iterator<Item = Id> + '_ {
+        self.devices.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub fn environment(&self, id: Id) -> Option<&Env> {
+        self.devices.get(&id).map(|device| &device.environment)
+    }
+
+    /// Mutable access to the environment of a device, e.g. to update its sensors.
+    pub fn environment_mut(&mut self, id: Id) -> Option<&mut Env> {
+        self.devices
+            .get_mut(&id)
+            .map(|device| &mut device.environment)
+    }
+
+    /// Result of the last round executed by a device.
+    pub fn result(&self, id: Id) -> Option<&Out> {
+        self.results.get(&id)
+    }
+
+    /// Execute a round on every device, regardless of the scheduling policy.
+    ///
+    /// # Errors
+    /// Returns the first error raised while exporting the values of a device or sending them
+    /// through the uplink
+    pub fn cycle(&mut self) -> Result<(), AggregateError> {
+        self.poll_uplink();
+        let ids: Vec<Id> = self.devices.keys().copied().collect();
+        ids.into_iter().try_for_each(|id| self.execute(id))
+    }
+
+    /// Execute a round on the devices whose scheduler considers it due at `now`.
+    ///
+    /// # Returns
+    /// The number of rounds executed
+    ///
+    /// # Errors
+    /// Returns the first error raised while exporting the values of a device or sending them
+    /// through the uplink
+    pub fn tick(&mut self, now: Duration) -> Result<usize, AggregateError> {
+        let remote_pending = self.poll_uplink();
+        let generation = self.generation;
+        let due: Vec<Id> = self
+            .devices
+            .iter_mut()
+            .filter_map(|(id, device)| {
+                let pending = remote_pending || device.seen < generation;
+                device.scheduler.is_due(now, pending).then_some(*id)
+            })
+            .collect();
+        for id in &due {
+            self.execute(*id)?;
+            if let Some(device) = self.devices.get_mut(id) {
+                device.scheduler.round_executed(now);
+            }
+        }
+        Ok(due.len())
+    }
+
+    /// Earliest time at which a device should be polled again, see [`Scheduler::next_wakeup`].
+    pub fn next_wakeup(&self, now: Duration) -> Option<Duration> {
+        self.devices
+            .values()
+            .filter_map(|device| device.scheduler.next_wakeup(now))
+            .min()
+    }
+
+    /// Collect the messages of the remote devices from the uplink.
+    ///
+    /// # Returns
+    /// Whether the uplink received new messages
+    fn poll_uplink(&mut self) -> bool {
+        let Some(uplink) = self.uplink.as_mut() else {
+            return false;
+        };
+        let pending = uplink.has_pending_inbound();
+        let inbound = uplink.prepare_inbound();
+        self.remote = inbound
+            .neighbors()
+            .filter(|id| !self.devices.contains_key(id))
+            .filter_map(|id| Some((id, Arc::new(inbound.get(&id)?.clone()))))
+            .collect();
+        pending
+    }
+
+    /// Execute a round on the device `id` and route its exports.
+    fn execute(&mut self, id: Id) -> Result<(), AggregateError> {
+        let inbound: Map<Id, Arc<ValueTree>> = self
+            .exports
+            .iter()
+            .filter(|(other, _)| {
+                **other != id
+                    && self
+                        .relation
+                        .as_ref()
+                        .is_none_or(|relation| relation(id, **other))
+            })
+            .chain(&self.remote)
+            .map(|(other, tree)| (*other, Arc::clone(tree)))
+            .collect();
+        let Some(device) = self.devices.get_mut(&id) else {
+            return Ok(());
+        };
+        device.vm.prepare_new_round(InboundMessage::shared(inbound));
+        let result = (self.program)(&device.environment, &mut device.vm);
+        let serialized = if self.routing == LocalRouting::Serialized || self.uplink.is_some() {
+            Some(device.vm.get_outbound()?)
+        } else {
+            None
+        };
+        let export = match (&serialized, self.routing) {
+            (Some(bytes), LocalRouting::Serialized) => {
+                ValueTree::from(OutboundMessage::<Id>::decode(&self.serializer, bytes)?)
+            }
+            (Some(_) | None, LocalRouting::InMemory | LocalRouting::Serialized) => {
+                ValueTree::from(device.vm.outbound().clone())
+            }
+        };
+        self.generation = self.generation.saturating_add(1);
+        device.seen = self.generation;
+        self.exports.insert(id, Arc::new(export));
+        self.results.insert(id, result);
+        match (self.uplink.as_mut(), serialized) {
+            (Some(uplink), Some(bytes)) => uplink
+                .prepare_outbound(bytes)
+                .map_err(AggregateError::NetworkError),
+            (Some(_) | None, _) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::blocks::gradient::hop_gradient;
+    use crate::rufi::test_utils::MockSerializer;
+
+    fn line(routing: LocalRouting) -> MultiDeviceEngine<u32, f64, bool, MockSerializer> {
+        let mut engine = MultiDeviceEngine::new(MockSerializer, |source: &bool, vm| {
+            hop_gradient(vm, *source).unwrap()
+        })
+        .with_routing(routing)
+        .with_neighbor_relation(|a: u32, b| a.abs_diff(b) == 1);
+        for id in 0..4 {
+            engine.add_device(id, id == 0);
+        }
+        engine
+    }
+
+    #[test]
+    fn hosted_devices_exchange_exports() {
+        for routing in [LocalRouting::InMemory, LocalRouting::Serialized] {
+            let mut engine = line(routing);
+            for _ in 0..4 {
+                engine.cycle().unwrap();
+            }
+            assert_eq!(engine.result(3), Some(&3.0));
+            *engine.environment_mut(3).unwrap() = true;
+            engine.cycle().unwrap();
+            assert_eq!(engine.result(3), Some(&0.0));
+            // devices run in ascending order: 2 sees the new export of 3 in the next cycle
+            assert_eq!(engine.result(2), Some(&2.0));
+            engine.cycle().unwrap();
+            assert_eq!(engine.result(2), Some(&1.0));
+        }
+    }
+
+    #[test]
+    fn devices_are_scheduled_independently() {
+        let mut engine = line(LocalRouting::InMemory);
+        assert_eq!(engine.tick(Duration::ZERO), Ok(4));
+        assert_eq!(engine.tick(Duration::from_millis(500)), Ok(0));
+        assert_eq!(
+            engine.next_wakeup(Duration::from_millis(500)),
+            Some(Duration::from_secs(1))
+        );
+        assert!(engine.remove_device(3).is_some());
+        assert_eq!(engine.tick(Duration::from_secs(1)), Ok(3));
+    }
+}
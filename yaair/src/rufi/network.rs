@@ -1,11 +1,111 @@
+use crate::rufi::device::DeviceId;
 use crate::rufi::messages::inbound::InboundMessage;
 use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::sensors::neighborhood::NeighborhoodReadings;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
-use core::hash::Hash;
-use serde::{Deserialize, Serialize};
+use core::fmt::{self, Display};
+
+/// Why a [`Network`] could not send a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    /// The transport cannot accept the message right now, e.g. its buffers are full.
+    Congested,
+    /// The transport lost its connection to the neighbors.
+    Disconnected,
+    /// The message of the given size exceeds what the transport can carry.
+    TooLarge(usize),
+    Other(String),
+}
+impl NetworkError {
+    /// Whether sending the same message again later may succeed.
+    pub const fn is_transient(&self) -> bool {
+        matches!(self, Self::Congested | Self::Disconnected)
+    }
+}
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Congested => write!(f, "Network congested"),
+            Self::Disconnected => write!(f, "Network disconnected"),
+            Self::TooLarge(size) => write!(f, "Message of {size} bytes too large"),
+            Self::Other(msg) => write!(f, "Network error: {msg}"),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl From<std::io::Error> for NetworkError {
+    fn from(err: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let kind = err.kind();
+        if matches!(kind, ErrorKind::WouldBlock | ErrorKind::TimedOut) {
+            Self::Congested
+        } else if matches!(
+            kind,
+            ErrorKind::NotConnected
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::AddrNotAvailable
+                | ErrorKind::NetworkUnreachable
+                | ErrorKind::HostUnreachable
+                | ErrorKind::NetworkDown
+        ) {
+            Self::Disconnected
+        } else {
+            Self::Other(err.to_string())
+        }
+    }
+}
 
-pub trait Network<Id: Ord + Hash + Copy + Serialize + for<'de> Deserialize<'de>, S: Serializer> {
-    fn prepare_outbound(&mut self, outbound_message: Vec<u8>);
+pub trait Network<Id: DeviceId, S: Serializer> {
+    /// Send the serialized outbound message of the round to the neighbors.
+    ///
+    /// # Errors
+    /// Returns the [`NetworkError`] preventing the message from being sent; transient ones are
+    /// retried by the [`Engine`](crate::rufi::engine::Engine) if configured, see
+    /// [`Engine::with_retry_policy`](crate::rufi::engine::Engine::with_retry_policy)
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError>;
     fn prepare_inbound(&mut self) -> InboundMessage<Id>;
+
+    /// Whether new messages have been received since the last call to `prepare_inbound`.
+    ///
+    /// Used by reactive schedulers; networks that cannot tell report `true`.
+    fn has_pending_inbound(&self) -> bool {
+        true
+    }
+
+    /// Per-neighbor metadata (range, lag, direction, link quality) measured by the transport.
+    ///
+    /// Networks that cannot measure anything report no readings.
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        NeighborhoodReadings::default()
+    }
+}
+
+/// A network that neither sends nor receives anything.
+///
+/// Useful together with [`Engine::step_with`](crate::rufi::engine::Engine::step_with),
+/// when the host exchanges messages on its own.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoNetwork;
+impl<Id, S> Network<Id, S> for NoNetwork
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, _outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        Ok(())
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        InboundMessage::default()
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,195 @@
+use crate::rufi::aggregate::VM;
+use crate::rufi::engine::Program;
+use crate::rufi::messages::inbound::InboundMessage;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::sensors::neighborhood::NeighborhoodReadings;
+use crate::rufi::time::Clock;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::time::Duration;
+use serde::Serialize;
+
+/// Everything a round of the main program depends on, together with its result.
+#[derive(Debug, Clone)]
+pub struct RecordedRound<Id: Ord + Hash + Copy, Env, Out> {
+    pub round: u64,
+    /// Time of the round, as returned by the clock of the device.
+    pub time: Duration,
+    /// Inbound message the round was executed on, after the retention filter.
+    pub inbound: InboundMessage<Id>,
+    pub readings: NeighborhoodReadings<Id>,
+    /// Snapshot of the environment taken before the round.
+    pub environment: Env,
+    pub result: Out,
+}
+
+/// In-memory recording of the rounds executed by a device, see
+/// [`Engine::cycle_recorded`](crate::rufi::engine::Engine::cycle_recorded).
+///
+/// The recording must start from the first round of the device: replaying it from the middle
+/// would start from an empty state.
+#[derive(Debug, Clone)]
+pub struct Recorder<Id: Ord + Hash + Copy, Env, Out> {
+    rounds: Vec<RecordedRound<Id, Env, Out>>,
+}
+impl<Id: Ord + Hash + Copy, Env, Out> Recorder<Id, Env, Out> {
+    pub const fn new() -> Self {
+        Self { rounds: Vec::new() }
+    }
+
+    /// Append a round, numbered after the ones already recorded.
+    pub fn record(
+        &mut self,
+        time: Duration,
+        inbound: InboundMessage<Id>,
+        readings: NeighborhoodReadings<Id>,
+        environment: Env,
+        result: Out,
+    ) {
+        self.rounds.push(RecordedRound {
+            round: u64::try_from(self.rounds.len()).unwrap_or(u64::MAX),
+            time,
+            inbound,
+            readings,
+            environment,
+            result,
+        });
+    }
+
+    pub fn rounds(&self) -> &[RecordedRound<Id, Env, Out>] {
+        &self.rounds
+    }
+
+    pub fn into_rounds(self) -> Vec<RecordedRound<Id, Env, Out>> {
+        self.rounds
+    }
+}
+impl<Id: Ord + Hash + Copy, Env, Out> Default for Recorder<Id, Env, Out> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A round whose replayed result differs from the recorded one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence<Out> {
+    pub round: u64,
+    pub recorded: Out,
+    pub replayed: Out,
+}
+
+/// Re-executes a program on a recording, feeding it the recorded inputs.
+pub struct Replayer<Id, Out, Env, S>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    S: Serializer,
+{
+    vm: VM<Id, S>,
+    program: Program<Env, Id, S, Out>,
+}
+impl<Id, Out, Env, S> Replayer<Id, Out, Env, S>
+where
+    Id: Ord + Hash + Copy + Serialize,
+    S: Serializer,
+{
+    /// Create a replayer for the device `local_id`, starting from an empty state.
+    pub fn new(local_id: Id, serializer: S, program: Program<Env, Id, S, Out>) -> Self {
+        Self {
+            vm: VM::new(local_id, serializer),
+            program,
+        }
+    }
+
+    /// Re-execute `round` on top of the rounds replayed so far.
+    pub fn step(&mut self, round: &RecordedRound<Id, Env, Out>) -> Out {
+        self.vm.set_clock(Fixed(round.time));
+        self.vm.update_neighborhood(round.readings.clone());
+        self.vm.prepare_new_round(round.inbound.clone());
+        (self.program)(&round.environment, &mut self.vm)
+    }
+
+    /// Replay `rounds` in order, stopping at the first result that differs from the recorded one.
+    pub fn first_divergence(
+        &mut self,
+        rounds: &[RecordedRound<Id, Env, Out>],
+    ) -> Option<Divergence<Out>>
+    where
+        Out: PartialEq + Clone,
+    {
+        for round in rounds {
+            let replayed = self.step(round);
+            if replayed != round.result {
+                return Some(Divergence {
+                    round: round.round,
+                    recorded: round.result.clone(),
+                    replayed,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Clock stuck at the time of the round being replayed.
+struct Fixed(Duration);
+impl Clock for Fixed {
+    fn now(&mut self) -> Duration {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::Aggregate;
+    use crate::rufi::engine::Engine;
+    use crate::rufi::network::NoNetwork;
+    use crate::rufi::test_utils::MockSerializer;
+    use crate::rufi::time::TimeSensor;
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn program(threshold: &u64, vm: &mut VM<u32, MockSerializer>) -> (u64, bool) {
+        let rounds = vm.repeat(&0u64, |count, _| count.saturating_add(1));
+        (rounds, vm.current_time().as_secs() >= *threshold)
+    }
+
+    #[test]
+    fn replay_reproduces_recorded_rounds() {
+        let mut recorder = Recorder::new();
+        let mut engine = Engine::new(1u32, NoNetwork, 2u64, MockSerializer, program);
+        for threshold in [2, 0, 5] {
+            *engine.environment_mut() = threshold;
+            engine.cycle_recorded(&mut recorder).unwrap();
+        }
+        assert_eq!(recorder.rounds().len(), 3);
+        assert_eq!(
+            recorder.rounds().get(1).map(|round| round.result),
+            Some((2, true))
+        );
+
+        let mut replayer = Replayer::new(1u32, MockSerializer, program);
+        assert_eq!(replayer.first_divergence(recorder.rounds()), None);
+    }
+
+    #[test]
+    fn replay_reports_the_first_divergence() {
+        let mut recorder = Recorder::new();
+        let mut engine = Engine::new(1u32, NoNetwork, 0u64, MockSerializer, program);
+        engine.cycle_recorded(&mut recorder).unwrap();
+        engine.cycle_recorded(&mut recorder).unwrap();
+        let mut rounds = recorder.into_rounds();
+        if let Some(round) = rounds.get_mut(1) {
+            round.environment = 10;
+        }
+        let mut replayer = Replayer::new(1u32, MockSerializer, program);
+        assert_eq!(
+            replayer.first_divergence(&rounds),
+            Some(Divergence {
+                round: 1,
+                recorded: (2, true),
+                replayed: (2, false),
+            })
+        );
+    }
+}
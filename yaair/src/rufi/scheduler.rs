@@ -0,0 +1,357 @@
+use crate::rufi::metrics::RoundMetrics;
+use core::time::Duration;
+
+/// Policy deciding when the [`Engine`](crate::rufi::engine::Engine) should execute a round.
+///
+/// Time is expressed as a [`Duration`] elapsed since an arbitrary epoch chosen by the host,
+/// so that the same policies can be driven by `std::time::Instant` or by a hardware tick counter.
+pub trait Scheduler {
+    /// Returns `true` if a round should be executed at `now`.
+    ///
+    /// # Arguments
+    /// * `now` - The current time, relative to the host epoch
+    /// * `inbound_pending` - Whether the network has received new messages since the last round
+    fn is_due(&mut self, now: Duration, inbound_pending: bool) -> bool;
+
+    /// Notifies the scheduler that a round has been executed at `now`.
+    fn round_executed(&mut self, now: Duration);
+
+    /// Returns the time at which the scheduler should be polled again, if known.
+    ///
+    /// Hosts can use this hint to sleep between polls; `None` means that the next round
+    /// depends on an event (new message, external trigger) rather than on time.
+    fn next_wakeup(&self, now: Duration) -> Option<Duration>;
+
+    /// Notifies the scheduler of the measurements of a round that completed successfully,
+    /// before [`Scheduler::round_executed`]. Policies that do not adapt ignore them.
+    fn observe(&mut self, _round: &RoundMetrics) {}
+}
+
+/// Executes a round every `period`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Periodic {
+    period: Duration,
+    next: Option<Duration>,
+}
+impl Periodic {
+    pub const fn new(period: Duration) -> Self {
+        Self { period, next: None }
+    }
+
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+}
+impl Default for Periodic {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+impl Scheduler for Periodic {
+    fn is_due(&mut self, now: Duration, _inbound_pending: bool) -> bool {
+        self.next.is_none_or(|next| now >= next)
+    }
+
+    fn round_executed(&mut self, now: Duration) {
+        self.next = Some(now.saturating_add(self.period));
+    }
+
+    fn next_wakeup(&self, now: Duration) -> Option<Duration> {
+        Some(self.next.unwrap_or(now))
+    }
+}
+
+/// Executes a round every `period` plus a pseudo-random delay in `[0, jitter]`.
+///
+/// Jitter desynchronizes devices that were started at the same time, avoiding collisions
+/// on shared media. The pseudo-random sequence is a xorshift generator seeded by the caller,
+/// so runs are reproducible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jittered {
+    period: Duration,
+    jitter: Duration,
+    state: u64,
+    next: Option<Duration>,
+}
+impl Jittered {
+    pub const fn new(period: Duration, jitter: Duration, seed: u64) -> Self {
+        Self {
+            period,
+            jitter,
+            // xorshift must never be seeded with zero
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+            next: None,
+        }
+    }
+
+    const fn next_random(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    fn next_jitter(&mut self) -> Duration {
+        let jitter_nanos = u64::try_from(self.jitter.as_nanos()).unwrap_or(u64::MAX);
+        let random = self.next_random();
+        let offset = random
+            .checked_rem(jitter_nanos.saturating_add(1))
+            .unwrap_or(random);
+        Duration::from_nanos(offset)
+    }
+}
+impl Scheduler for Jittered {
+    fn is_due(&mut self, now: Duration, _inbound_pending: bool) -> bool {
+        self.next.is_none_or(|next| now >= next)
+    }
+
+    fn round_executed(&mut self, now: Duration) {
+        let jitter = self.next_jitter();
+        self.next = Some(now.saturating_add(self.period).saturating_add(jitter));
+    }
+
+    fn next_wakeup(&self, now: Duration) -> Option<Duration> {
+        Some(self.next.unwrap_or(now))
+    }
+}
+
+/// Executes a round as soon as new messages are available from the network.
+///
+/// `min_interval` bounds the round rate under heavy traffic, while the optional `max_interval`
+/// guarantees that a round is executed (and the device keeps advertising its exports) even
+/// when no message is received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reactive {
+    min_interval: Duration,
+    max_interval: Option<Duration>,
+    last: Option<Duration>,
+}
+impl Reactive {
+    pub const fn new(min_interval: Duration, max_interval: Option<Duration>) -> Self {
+        Self {
+            min_interval,
+            max_interval,
+            last: None,
+        }
+    }
+}
+impl Scheduler for Reactive {
+    fn is_due(&mut self, now: Duration, inbound_pending: bool) -> bool {
+        let Some(last) = self.last else {
+            return true;
+        };
+        let elapsed = now.saturating_sub(last);
+        let timed_out = self.max_interval.is_some_and(|max| elapsed >= max);
+        timed_out || (inbound_pending && elapsed >= self.min_interval)
+    }
+
+    fn round_executed(&mut self, now: Duration) {
+        self.last = Some(now);
+    }
+
+    fn next_wakeup(&self, now: Duration) -> Option<Duration> {
+        self.last.map_or(Some(now), |last| {
+            self.max_interval.map(|max| last.saturating_add(max))
+        })
+    }
+}
+
+/// Executes a round every `period`, adapting it to the network conditions within
+/// `[min_period, max_period]`.
+///
+/// The period doubles when the outbound message could not be sent or the inbound traffic exceeds
+/// the configured limit, signs of a congested medium. Otherwise it halves when the exports are
+/// changing, to track fast-moving values, and grows by `min_period` when they are stable, saving
+/// bandwidth and energy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adaptive {
+    min_period: Duration,
+    max_period: Duration,
+    period: Duration,
+    inbound_limit: Option<usize>,
+    next: Option<Duration>,
+}
+impl Adaptive {
+    /// Starts at `min_period`; `max_period` is raised to `min_period` if lower.
+    pub fn new(min_period: Duration, max_period: Duration) -> Self {
+        Self {
+            min_period,
+            max_period: max_period.max(min_period),
+            period: min_period,
+            inbound_limit: None,
+            next: None,
+        }
+    }
+
+    /// Consider the medium congested when more than `bytes` are received in a round.
+    #[must_use]
+    pub const fn with_inbound_limit(mut self, bytes: usize) -> Self {
+        self.inbound_limit = Some(bytes);
+        self
+    }
+
+    /// The period currently used between rounds.
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+}
+impl Scheduler for Adaptive {
+    fn is_due(&mut self, now: Duration, _inbound_pending: bool) -> bool {
+        self.next.is_none_or(|next| now >= next)
+    }
+
+    fn round_executed(&mut self, now: Duration) {
+        self.next = Some(now.saturating_add(self.period));
+    }
+
+    fn next_wakeup(&self, now: Duration) -> Option<Duration> {
+        Some(self.next.unwrap_or(now))
+    }
+
+    fn observe(&mut self, round: &RoundMetrics) {
+        let congested = round.send_failed
+            || self
+                .inbound_limit
+                .is_some_and(|limit| round.bytes_in > limit);
+        self.period = if congested {
+            self.period.saturating_mul(2)
+        } else if round.changed {
+            self.period.checked_div(2).unwrap_or(self.min_period)
+        } else {
+            self.period.saturating_add(self.min_period)
+        }
+        .clamp(self.min_period, self.max_period);
+    }
+}
+
+/// Executes a round only when explicitly triggered by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExternalTrigger {
+    triggered: bool,
+}
+impl ExternalTrigger {
+    pub const fn new() -> Self {
+        Self { triggered: false }
+    }
+
+    /// Requests the execution of a round at the next poll.
+    pub const fn trigger(&mut self) {
+        self.triggered = true;
+    }
+}
+impl Scheduler for ExternalTrigger {
+    fn is_due(&mut self, _now: Duration, _inbound_pending: bool) -> bool {
+        self.triggered
+    }
+
+    fn round_executed(&mut self, _now: Duration) {
+        self.triggered = false;
+    }
+
+    fn next_wakeup(&self, now: Duration) -> Option<Duration> {
+        self.triggered.then_some(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn periodic_fires_immediately_then_every_period() {
+        let mut scheduler = Periodic::new(secs(2));
+        assert!(scheduler.is_due(secs(0), false));
+        scheduler.round_executed(secs(0));
+        assert!(!scheduler.is_due(secs(1), true));
+        assert!(scheduler.is_due(secs(2), false));
+        assert_eq!(scheduler.next_wakeup(secs(1)), Some(secs(2)));
+    }
+
+    #[test]
+    fn jittered_stays_within_bounds() {
+        let mut scheduler = Jittered::new(secs(1), Duration::from_millis(100), 42);
+        let mut now = secs(0);
+        for _ in 0..100 {
+            scheduler.round_executed(now);
+            let next = scheduler.next_wakeup(now).unwrap();
+            assert!(next >= now + secs(1));
+            assert!(next <= now + secs(1) + Duration::from_millis(100));
+            now = next;
+        }
+    }
+
+    #[test]
+    fn jittered_is_reproducible_with_same_seed() {
+        let mut a = Jittered::new(secs(1), secs(1), 7);
+        let mut b = Jittered::new(secs(1), secs(1), 7);
+        a.round_executed(secs(0));
+        b.round_executed(secs(0));
+        assert_eq!(a.next_wakeup(secs(0)), b.next_wakeup(secs(0)));
+    }
+
+    #[test]
+    fn reactive_fires_on_new_messages_only() {
+        let mut scheduler = Reactive::new(Duration::from_millis(100), Some(secs(5)));
+        assert!(scheduler.is_due(secs(0), false));
+        scheduler.round_executed(secs(0));
+        assert!(!scheduler.is_due(secs(1), false));
+        assert!(!scheduler.is_due(Duration::from_millis(50), true));
+        assert!(scheduler.is_due(secs(1), true));
+        assert!(scheduler.is_due(secs(5), false));
+    }
+
+    #[test]
+    fn adaptive_backs_off_on_congestion_and_speeds_up_on_changes() {
+        let mut scheduler = Adaptive::new(secs(1), secs(8)).with_inbound_limit(100);
+        let stable = RoundMetrics::default();
+        let congested = RoundMetrics {
+            send_failed: true,
+            ..stable
+        };
+        let changing = RoundMetrics {
+            changed: true,
+            ..stable
+        };
+        scheduler.observe(&congested);
+        scheduler.observe(&RoundMetrics {
+            bytes_in: 200,
+            ..changing
+        });
+        assert_eq!(scheduler.period(), secs(4));
+        scheduler.round_executed(secs(0));
+        assert!(!scheduler.is_due(secs(3), true));
+        assert!(scheduler.is_due(secs(4), false));
+        for _ in 0..3 {
+            scheduler.observe(&congested);
+        }
+        assert_eq!(scheduler.period(), secs(8));
+        scheduler.observe(&changing);
+        assert_eq!(scheduler.period(), secs(4));
+        scheduler.observe(&stable);
+        assert_eq!(scheduler.period(), secs(5));
+        for _ in 0..5 {
+            scheduler.observe(&changing);
+        }
+        assert_eq!(scheduler.period(), secs(1));
+    }
+
+    #[test]
+    fn external_trigger_fires_once_per_trigger() {
+        let mut scheduler = ExternalTrigger::new();
+        assert!(!scheduler.is_due(secs(0), true));
+        scheduler.trigger();
+        assert!(scheduler.is_due(secs(0), false));
+        scheduler.round_executed(secs(0));
+        assert!(!scheduler.is_due(secs(1), false));
+    }
+}
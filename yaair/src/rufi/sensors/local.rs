@@ -0,0 +1,106 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use core::any::Any;
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+/// Read access to the local sensors of a device.
+pub trait Sensors {
+    /// Read the current value of the sensor `name`.
+    ///
+    /// # Returns
+    /// `None` if the sensor does not exist or holds a value of a different type
+    fn sense<T: Any + Clone>(&self, name: &str) -> Option<T>;
+}
+
+/// Write access to the local actuators of a device.
+///
+/// Programs only receive a shared reference to their environment, hence actuations are buffered
+/// during the round and read by the host once the round is over.
+pub trait Actuators {
+    /// Set the actuator `name` to `value`, replacing any previous write in the same round.
+    fn actuate<T: Any>(&self, name: &str, value: T);
+}
+
+/// In-memory sensors and actuators, suitable for tests and simulations.
+#[derive(Debug, Default)]
+pub struct InMemoryContext {
+    sensors: Map<String, Box<dyn Any>>,
+    actuations: RefCell<Map<String, Box<dyn Any>>>,
+}
+impl InMemoryContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of the sensor `name`, returning the updated context.
+    pub fn with_sensor<T: Any>(mut self, name: impl Into<String>, value: T) -> Self {
+        self.set_sensor(name, value);
+        self
+    }
+
+    /// Set the value of the sensor `name`.
+    pub fn set_sensor<T: Any>(&mut self, name: impl Into<String>, value: T) {
+        self.sensors.insert(name.into(), Box::new(value));
+    }
+
+    /// Read back the last value written to the actuator `name`.
+    pub fn actuation<T: Any + Clone>(&self, name: &str) -> Option<T> {
+        self.actuations
+            .borrow()
+            .get(name)
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Remove and return all the actuations written since the last drain.
+    pub fn drain_actuations(&mut self) -> Map<String, Box<dyn Any>> {
+        core::mem::take(self.actuations.get_mut())
+    }
+}
+impl Sensors for InMemoryContext {
+    fn sense<T: Any + Clone>(&self, name: &str) -> Option<T> {
+        self.sensors
+            .get(name)
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+}
+impl Actuators for InMemoryContext {
+    fn actuate<T: Any>(&self, name: &str, value: T) {
+        self.actuations
+            .borrow_mut()
+            .insert(name.into(), Box::new(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sense_returns_typed_values() {
+        let context = InMemoryContext::new()
+            .with_sensor("temperature", 21.5f64)
+            .with_sensor("source", true);
+        assert_eq!(context.sense::<f64>("temperature"), Some(21.5));
+        assert_eq!(context.sense::<bool>("source"), Some(true));
+        assert_eq!(context.sense::<u32>("temperature"), None);
+        assert_eq!(context.sense::<f64>("humidity"), None);
+    }
+
+    #[test]
+    fn actuations_are_buffered_until_drained() {
+        let mut context = InMemoryContext::new();
+        context.actuate("led", false);
+        context.actuate("led", true);
+        assert_eq!(context.actuation::<bool>("led"), Some(true));
+        assert_eq!(context.drain_actuations().len(), 1);
+        assert_eq!(context.actuation::<bool>("led"), None);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod local;
+pub mod neighborhood;
+pub mod typed;
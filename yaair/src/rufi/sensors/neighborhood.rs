@@ -0,0 +1,130 @@
+use crate::rufi::data::field::Field;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+use core::hash::Hash;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+/// Metadata about a single neighbor, as perceived by the network layer.
+///
+/// Every entry is optional since not all transports can measure everything.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NeighborReading {
+    /// Estimated distance to the neighbor.
+    pub range: Option<f64>,
+    /// Age of the last message received from the neighbor.
+    pub lag: Option<Duration>,
+    /// Relative position `(x, y)` of the neighbor with respect to the local device.
+    pub vector: Option<(f64, f64)>,
+    /// Quality of the radio link with the neighbor.
+    pub link: Option<LinkQuality>,
+}
+
+/// Quality of the link with a neighbor, as reported by the radio.
+///
+/// Every entry is optional since radios report different indicators.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkQuality {
+    /// Received signal strength, in dBm.
+    pub rssi: Option<i16>,
+    /// Signal-to-noise ratio, in dB.
+    pub snr: Option<f32>,
+    /// Fraction of the messages of the neighbor received recently, between 0 and 1.
+    pub delivery_ratio: Option<f64>,
+}
+impl LinkQuality {
+    /// Expected number of transmissions for a message to get through the link (ETX), a common
+    /// gradient metric; `None` without a positive delivery ratio.
+    pub fn expected_transmissions(&self) -> Option<f64> {
+        self.delivery_ratio
+            .filter(|ratio| *ratio > 0.0)
+            .map(f64::recip)
+    }
+}
+
+/// Readings for all the current neighbors, indexed by device id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeighborhoodReadings<Id: Ord + Hash + Copy> {
+    readings: Map<Id, NeighborReading>,
+}
+impl<Id: Ord + Hash + Copy> NeighborhoodReadings<Id> {
+    pub const fn new(readings: Map<Id, NeighborReading>) -> Self {
+        Self { readings }
+    }
+
+    pub fn get(&self, id: &Id) -> Option<&NeighborReading> {
+        self.readings.get(id)
+    }
+
+    pub fn insert(&mut self, id: Id, reading: NeighborReading) {
+        self.readings.insert(id, reading);
+    }
+
+    /// Build a field from the readings, keeping only the neighbors for which `select` yields a value.
+    pub fn to_field<V, F>(&self, local: V, select: F) -> Field<Id, V>
+    where
+        F: Fn(&NeighborReading) -> Option<V>,
+    {
+        Field::new(
+            local,
+            self.readings
+                .iter()
+                .filter_map(|(id, reading)| select(reading).map(|value| (*id, value)))
+                .collect(),
+        )
+    }
+}
+impl<Id: Ord + Hash + Copy> Default for NeighborhoodReadings<Id> {
+    fn default() -> Self {
+        Self::new(Map::new())
+    }
+}
+
+/// Sensors perceiving the neighborhood of the device, exposed to aggregate programs as fields.
+///
+/// Neighbors without the corresponding reading are not part of the returned fields.
+pub trait NeighborhoodSensors<Id: Ord + Hash + Copy> {
+    /// Distance of each neighbor, `0.0` for the local device.
+    fn nbr_range(&self) -> Field<Id, f64>;
+
+    /// Age of the last message received from each neighbor, zero for the local device.
+    fn nbr_lag(&self) -> Field<Id, Duration>;
+
+    /// Relative position of each neighbor, the origin for the local device.
+    fn nbr_vector(&self) -> Field<Id, (f64, f64)>;
+
+    /// Quality of the link with each neighbor, no indicator for the local device.
+    fn nbr_link_quality(&self) -> Field<Id, LinkQuality>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_field_skips_missing_readings() {
+        let mut readings = NeighborhoodReadings::default();
+        readings.insert(
+            1u32,
+            NeighborReading {
+                range: Some(2.0),
+                ..NeighborReading::default()
+            },
+        );
+        readings.insert(2u32, NeighborReading::default());
+        let field = readings.to_field(0.0, |reading| reading.range);
+        assert_eq!(field, Field::new(0.0, Map::from([(1u32, 2.0)])));
+    }
+
+    #[test]
+    fn expected_transmissions_need_a_positive_delivery_ratio() {
+        let quality = |delivery_ratio| LinkQuality {
+            delivery_ratio,
+            ..LinkQuality::default()
+        };
+        assert_eq!(quality(Some(0.5)).expected_transmissions(), Some(2.0));
+        assert_eq!(quality(Some(0.0)).expected_transmissions(), None);
+        assert_eq!(quality(None).expected_transmissions(), None);
+    }
+}
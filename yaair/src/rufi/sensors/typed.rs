@@ -0,0 +1,146 @@
+use crate::rufi::aggregate::AggregateError;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+/// Key of a [`TypedEnv`] entry, usually a unit struct:
+///
+/// ```
+/// use yaair::rufi::sensors::typed::EnvKey;
+///
+/// struct Distances;
+/// impl EnvKey for Distances {
+///     type Value = Vec<f64>;
+/// }
+/// ```
+pub trait EnvKey: 'static {
+    /// Type of the value stored under the key.
+    type Value: Any;
+
+    /// Name of the key in the errors about missing entries.
+    fn name() -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+/// Environment whose entries are looked up by type rather than by name, so that a program
+/// cannot read a value of the wrong type.
+///
+/// Programs declare the entries they need with [`TypedEnv::require`]; an
+/// [`Engine`](crate::rufi::engine::Engine) created with
+/// [`Engine::try_new`](crate::rufi::engine::Engine::try_new) refuses to start if any of them
+/// is missing.
+#[derive(Debug, Default)]
+pub struct TypedEnv {
+    entries: Map<TypeId, Box<dyn Any>>,
+    required: Vec<(TypeId, &'static str)>,
+}
+impl TypedEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare that the entry `K` must be present, returning the updated environment.
+    #[must_use]
+    pub fn require<K: EnvKey>(mut self) -> Self {
+        let key = TypeId::of::<K>();
+        if !self.required.iter().any(|(required, _)| *required == key) {
+            self.required.push((key, K::name()));
+        }
+        self
+    }
+
+    /// Set the entry `K`, returning the updated environment.
+    #[must_use]
+    pub fn with<K: EnvKey>(mut self, value: K::Value) -> Self {
+        self.insert::<K>(value);
+        self
+    }
+
+    /// Set the entry `K`, replacing its previous value.
+    pub fn insert<K: EnvKey>(&mut self, value: K::Value) {
+        self.entries.insert(TypeId::of::<K>(), Box::new(value));
+    }
+
+    /// Value of the entry `K`; always present for validated required entries.
+    pub fn get<K: EnvKey>(&self) -> Option<&K::Value> {
+        self.entries
+            .get(&TypeId::of::<K>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Mutable access to the value of the entry `K`.
+    pub fn get_mut<K: EnvKey>(&mut self) -> Option<&mut K::Value> {
+        self.entries
+            .get_mut(&TypeId::of::<K>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Names of the required entries that are not set, in declaration order.
+    pub fn missing(&self) -> Vec<&'static str> {
+        self.required
+            .iter()
+            .filter(|(key, _)| !self.entries.contains_key(key))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    /// Check that every required entry is set.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::MissingEnvironment`] listing the missing entries
+    pub fn validate(&self) -> Result<(), AggregateError> {
+        let missing = self.missing();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(AggregateError::MissingEnvironment(
+                missing.into_iter().map(String::from).collect(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Distances;
+    impl EnvKey for Distances {
+        type Value = Vec<f64>;
+    }
+
+    struct Source;
+    impl EnvKey for Source {
+        type Value = bool;
+
+        fn name() -> &'static str {
+            "source"
+        }
+    }
+
+    #[test]
+    fn missing_required_entries_are_listed() {
+        let mut env = TypedEnv::new().require::<Distances>().require::<Source>();
+        assert_eq!(env.missing().len(), 2);
+        assert!(env.missing().first().unwrap().ends_with("Distances"));
+        env.insert::<Source>(true);
+        let error = env.validate().unwrap_err();
+        assert!(matches!(
+            &error,
+            AggregateError::MissingEnvironment(missing) if missing.len() == 1
+        ));
+        env.insert::<Distances>(vec![1.0]);
+        assert_eq!(env.validate(), Ok(()));
+        assert_eq!(env.get::<Distances>(), Some(&vec![1.0]));
+        assert_eq!(env.get::<Source>(), Some(&true));
+    }
+}
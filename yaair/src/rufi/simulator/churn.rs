@@ -0,0 +1,25 @@
+use crate::rufi::simulator::simulation::JoinPolicy;
+
+/// Change to the set of devices or to their links, applied by the
+/// [`Simulator`](crate::rufi::simulator::simulation::Simulator) between rounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChurnEvent<Id> {
+    Join(Id, JoinPolicy),
+    Leave(Id),
+    Connect(Id, Id),
+    Disconnect(Id, Id),
+    /// Split the network: devices in different groups cannot communicate, while devices in
+    /// no group form one more group together.
+    Partition(Vec<Vec<Id>>),
+    /// Remove the current partition.
+    Heal,
+}
+
+/// Number of rounds taken by the network to satisfy a condition after a churn event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    /// Round in which the last churn event was applied.
+    pub churn_round: u64,
+    /// Rounds executed from the event until the condition held.
+    pub rounds: u64,
+}
@@ -0,0 +1,203 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::device::DeviceId;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::simulator::simulation::Simulator;
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::{self, Write};
+
+/// Output and metrics of a device at the end of a simulated round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Record<Id, Out> {
+    /// Index of the round, starting from zero.
+    pub round: u64,
+    pub device: Id,
+    pub output: Out,
+    /// Neighbors of the device at the end of the round.
+    pub neighbors: usize,
+    /// Size in bytes of the export produced in the round.
+    pub outbound_size: usize,
+}
+
+/// Collects the [`Record`]s of a simulation, round after round, and dumps them as CSV or
+/// JSON lines for offline analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recorder<Id, Out> {
+    records: Vec<Record<Id, Out>>,
+}
+impl<Id, Out> Recorder<Id, Out> {
+    pub const fn new() -> Self {
+        Self {
+            records: Vec::new(),
+        }
+    }
+
+    pub fn records(&self) -> &[Record<Id, Out>] {
+        &self.records
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Record the results of the last round executed by `simulator`.
+    pub fn record<S>(&mut self, simulator: &Simulator<'_, Id, S, Out>)
+    where
+        Id: DeviceId,
+        S: Serializer + Clone,
+        Out: Clone,
+    {
+        let Some(round) = simulator.round().checked_sub(1) else {
+            return;
+        };
+        self.records
+            .extend(simulator.results().iter().map(|(id, output)| Record {
+                round,
+                device: *id,
+                output: output.clone(),
+                neighbors: simulator.neighbors(*id).len(),
+                outbound_size: simulator.outbound_size(*id).unwrap_or(0),
+            }));
+    }
+
+    /// Execute `rounds` rounds of `simulator`, recording every one of them.
+    ///
+    /// # Errors
+    /// Returns the first error raised by [`Simulator::step`]
+    pub fn run<S>(
+        &mut self,
+        simulator: &mut Simulator<'_, Id, S, Out>,
+        rounds: usize,
+    ) -> Result<(), AggregateError>
+    where
+        Id: DeviceId,
+        S: Serializer + Clone,
+        Out: Clone,
+    {
+        (0..rounds).try_for_each(|_| {
+            simulator.step()?;
+            self.record(simulator);
+            Ok(())
+        })
+    }
+
+    /// Write the records as CSV, with a header, one row per device and round.
+    ///
+    /// # Errors
+    /// Returns the error of `writer`
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()>
+    where
+        Id: Display,
+        Out: Display,
+    {
+        writeln!(writer, "round,device,output,neighbors,outbound_size")?;
+        for record in &self.records {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                record.round,
+                csv_field(&record.device),
+                csv_field(&record.output),
+                record.neighbors,
+                record.outbound_size
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the records as JSON lines, one object per device and round.
+    ///
+    /// # Errors
+    /// Returns the error of `writer`, or of the serialization of ids and outputs
+    pub fn write_json_lines(&self, mut writer: impl Write) -> io::Result<()>
+    where
+        Id: Serialize,
+        Out: Serialize,
+    {
+        for record in &self.records {
+            serde_json::to_writer(&mut writer, record)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+impl<Id, Out> Default for Recorder<Id, Out> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Format `value` as a CSV field, quoting it if needed.
+fn csv_field(value: &impl Display) -> String {
+    let text = value.to_string();
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::{Aggregate, VM};
+    use crate::rufi::simulator::simulation::JoinPolicy;
+    use crate::rufi::test_utils::MockSerializer;
+
+    fn pair() -> Simulator<'static, u32, MockSerializer, String> {
+        let program = |id: u32, vm: &mut VM<u32, MockSerializer>| {
+            let size = vm.neighboring(&id).unwrap().size();
+            format!("{id},{size}")
+        };
+        let mut simulator = Simulator::new(MockSerializer, program);
+        simulator.add_device(0, JoinPolicy::Fresh);
+        simulator.add_device(1, JoinPolicy::Fresh);
+        simulator.connect(0, 1);
+        simulator
+    }
+
+    #[test]
+    fn records_are_written_as_csv() {
+        let mut simulator = pair();
+        let mut recorder = Recorder::new();
+        recorder.record(&simulator);
+        assert!(recorder.records().is_empty());
+        recorder.run(&mut simulator, 2).unwrap();
+        assert_eq!(recorder.records().len(), 4);
+
+        let mut csv = Vec::new();
+        recorder.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("round,device,output,neighbors,outbound_size")
+        );
+        assert!(lines
+            .next()
+            .is_some_and(|row| row.starts_with("0,0,\"0,1\",1,")));
+        assert!(lines
+            .nth(2)
+            .is_some_and(|row| row.starts_with("1,1,\"1,2\",1,")));
+    }
+
+    #[test]
+    fn records_are_written_as_json_lines() {
+        let mut simulator = pair();
+        let mut recorder = Recorder::new();
+        recorder.run(&mut simulator, 1).unwrap();
+        let mut json = Vec::new();
+        recorder.write_json_lines(&mut json).unwrap();
+        let rows: Vec<serde_json::Value> = String::from_utf8(json)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row["round"] == 0));
+        assert_eq!(
+            rows.last().map(|row| row["output"].clone()),
+            Some("1,1".into())
+        );
+    }
+}
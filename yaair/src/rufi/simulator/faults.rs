@@ -0,0 +1,167 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Faults affecting the messages sent over a directed link, applied independently to every
+/// message.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkFaults {
+    /// Probability that a message is lost.
+    pub drop: f64,
+    /// Probability that a message is delivered a second time, one round after the original.
+    pub duplicate: f64,
+    /// Probability that a message is held back by one round, arriving after the next one.
+    pub reorder: f64,
+    /// Rounds every message spends in flight before being delivered.
+    pub delay: u64,
+}
+impl LinkFaults {
+    /// A link delivering every message in the round after it was sent.
+    pub const fn reliable() -> Self {
+        Self {
+            drop: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            delay: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_drop(mut self, probability: f64) -> Self {
+        self.drop = probability;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_duplicate(mut self, probability: f64) -> Self {
+        self.duplicate = probability;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_reorder(mut self, probability: f64) -> Self {
+        self.reorder = probability;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_delay(mut self, rounds: u64) -> Self {
+        self.delay = rounds;
+        self
+    }
+}
+
+/// Delivery of a copy of a message, as scheduled by a [`FaultModel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delivery {
+    /// Round in which the copy is delivered.
+    pub round: u64,
+    /// Whether the copy overtakes the messages delivered in the same round, i.e. it is a
+    /// duplicate or a reordered message that replaces a fresher one.
+    pub late: bool,
+}
+
+/// Faults injected by the [`Simulator`](crate::rufi::simulator::simulation::Simulator) in the
+/// links between devices.
+///
+/// Every link suffers the default faults unless overridden; faults are drawn from a xorshift
+/// generator seeded by the caller, so runs are reproducible.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultModel<Id: Ord + Copy> {
+    default: LinkFaults,
+    links: BTreeMap<(Id, Id), LinkFaults>,
+    state: u64,
+}
+impl<Id: Ord + Copy> FaultModel<Id> {
+    pub const fn new(default: LinkFaults, seed: u64) -> Self {
+        Self {
+            default,
+            links: BTreeMap::new(),
+            // xorshift must never be seeded with zero
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Override the faults of the messages sent by `from` to `to`.
+    #[must_use]
+    pub fn with_link(mut self, from: Id, to: Id, faults: LinkFaults) -> Self {
+        self.links.insert((from, to), faults);
+        self
+    }
+
+    /// Faults of the messages sent by `from` to `to`.
+    pub fn faults(&self, from: Id, to: Id) -> LinkFaults {
+        self.links.get(&(from, to)).copied().unwrap_or(self.default)
+    }
+
+    /// Decide the fate of a message sent by `from` to `to` at the end of round `sent`.
+    ///
+    /// # Returns
+    /// The deliveries of the message, none if it is lost
+    pub fn transmit(&mut self, from: Id, to: Id, sent: u64) -> Vec<Delivery> {
+        let faults = self.faults(from, to);
+        if self.happens(faults.drop) {
+            return Vec::new();
+        }
+        let reordered = self.happens(faults.reorder);
+        let round = sent
+            .saturating_add(1)
+            .saturating_add(faults.delay)
+            .saturating_add(u64::from(reordered));
+        let mut deliveries = vec![Delivery {
+            round,
+            late: reordered,
+        }];
+        if self.happens(faults.duplicate) {
+            deliveries.push(Delivery {
+                round: round.saturating_add(1),
+                late: true,
+            });
+        }
+        deliveries
+    }
+
+    /// Draw an event of the given probability.
+    fn happens(&mut self, probability: f64) -> bool {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        let sample = u32::try_from(x >> 32).unwrap_or(u32::MAX);
+        f64::from(sample) / f64::from(u32::MAX) < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_overrides_take_precedence() {
+        let mut model = FaultModel::new(LinkFaults::reliable().with_delay(2), 7).with_link(
+            0u32,
+            1,
+            LinkFaults::reliable().with_drop(1.0),
+        );
+        assert!(model.transmit(0, 1, 0).is_empty());
+        let expected = Delivery {
+            round: 3,
+            late: false,
+        };
+        assert_eq!(model.transmit(1, 0, 0), vec![expected]);
+    }
+
+    #[test]
+    fn drop_probability_is_respected_on_average() {
+        let mut model = FaultModel::new(LinkFaults::reliable().with_drop(0.25), 42);
+        let lost = (0..10_000)
+            .filter(|round| model.transmit(0u32, 1, *round).is_empty())
+            .count();
+        assert!((2_000..3_000).contains(&lost), "lost {lost} messages");
+    }
+}
@@ -0,0 +1,230 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::device::DeviceId;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::simulator::simulation::Simulator;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io::{self, Write};
+
+/// Device, output and position of a node of the graph.
+type Node<Id> = (Id, String, Option<(f64, f64)>);
+
+/// Format of the topology snapshots written by [`write_graph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT, positioned devices are pinned with `pos` for `neato -n`.
+    Dot,
+    /// GraphML, readable by Gephi and yEd.
+    GraphMl,
+}
+impl GraphFormat {
+    /// Conventional extension of the files in this format.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::GraphMl => "graphml",
+        }
+    }
+}
+
+/// Write the topology of `simulator` in its last round as a graph, labelling every device
+/// with its output and placing it at its position, if any.
+///
+/// # Errors
+/// Returns the error of `writer`
+pub fn write_graph<Id, S, Out>(
+    simulator: &Simulator<'_, Id, S, Out>,
+    format: GraphFormat,
+    mut writer: impl Write,
+) -> io::Result<()>
+where
+    Id: DeviceId + Display,
+    S: Serializer + Clone,
+    Out: Display,
+{
+    let round = simulator.round().saturating_sub(1);
+    let nodes: Vec<Node<Id>> = simulator
+        .devices()
+        .map(|id| {
+            let output = simulator
+                .result(id)
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            (id, output, simulator.position(id))
+        })
+        .collect();
+    let edges: BTreeSet<(Id, Id)> = nodes
+        .iter()
+        .flat_map(|(id, _, _)| {
+            simulator
+                .neighbors(*id)
+                .into_iter()
+                .map(move |(neighbor, _)| (*id.min(&neighbor), *id.max(&neighbor)))
+        })
+        .collect();
+    match format {
+        GraphFormat::Dot => {
+            writeln!(writer, "graph \"round {round}\" {{")?;
+            for (id, output, position) in &nodes {
+                write!(
+                    writer,
+                    "  \"{}\" [label=\"{}\"",
+                    dot_escape(id),
+                    dot_escape(output)
+                )?;
+                if let Some((x, y)) = position {
+                    write!(writer, ", pos=\"{x},{y}!\"")?;
+                }
+                writeln!(writer, "];")?;
+            }
+            for (a, b) in &edges {
+                writeln!(writer, "  \"{}\" -- \"{}\";", dot_escape(a), dot_escape(b))?;
+            }
+            writeln!(writer, "}}")
+        }
+        GraphFormat::GraphMl => {
+            writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(
+                writer,
+                r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+            )?;
+            writeln!(
+                writer,
+                r#"  <key id="output" for="node" attr.name="output" attr.type="string"/>"#
+            )?;
+            writeln!(
+                writer,
+                r#"  <key id="x" for="node" attr.name="x" attr.type="double"/>"#
+            )?;
+            writeln!(
+                writer,
+                r#"  <key id="y" for="node" attr.name="y" attr.type="double"/>"#
+            )?;
+            writeln!(
+                writer,
+                r#"  <graph id="round-{round}" edgedefault="undirected">"#
+            )?;
+            for (id, output, position) in &nodes {
+                writeln!(writer, r#"    <node id="{}">"#, xml_escape(id))?;
+                writeln!(
+                    writer,
+                    r#"      <data key="output">{}</data>"#,
+                    xml_escape(output)
+                )?;
+                if let Some((x, y)) = position {
+                    writeln!(writer, r#"      <data key="x">{x}</data>"#)?;
+                    writeln!(writer, r#"      <data key="y">{y}</data>"#)?;
+                }
+                writeln!(writer, "    </node>")?;
+            }
+            for (a, b) in &edges {
+                writeln!(
+                    writer,
+                    r#"    <edge source="{}" target="{}"/>"#,
+                    xml_escape(a),
+                    xml_escape(b)
+                )?;
+            }
+            writeln!(writer, "  </graph>")?;
+            writeln!(writer, "</graphml>")
+        }
+    }
+}
+
+/// Execute `rounds` rounds of `simulator`, writing a snapshot after every round to the writer
+/// returned by `open` for that round, e.g. a file named after the round.
+///
+/// # Errors
+/// Returns the first error raised by [`Simulator::step`] or by the writers
+pub fn run_with_snapshots<Id, S, Out, W: Write>(
+    simulator: &mut Simulator<'_, Id, S, Out>,
+    rounds: usize,
+    format: GraphFormat,
+    mut open: impl FnMut(u64) -> io::Result<W>,
+) -> Result<(), AggregateError>
+where
+    Id: DeviceId + Display,
+    S: Serializer + Clone,
+    Out: Display,
+{
+    (0..rounds).try_for_each(|_| {
+        simulator.step()?;
+        let round = simulator.round().saturating_sub(1);
+        open(round)
+            .and_then(|writer| write_graph(simulator, format, writer))
+            .map_err(|err| {
+                AggregateError::SerializationError(format!(
+                    "Failed to write the snapshot of round {round}: {err}"
+                ))
+            })
+    })
+}
+
+fn dot_escape(value: &impl Display) -> String {
+    value.to_string().replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(value: &impl Display) -> String {
+    value
+        .to_string()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::{Aggregate, VM};
+    use crate::rufi::simulator::simulation::JoinPolicy;
+    use crate::rufi::test_utils::MockSerializer;
+
+    fn line() -> Simulator<'static, u32, MockSerializer, String> {
+        let program = |_: u32, vm: &mut VM<u32, MockSerializer>| {
+            let size = vm.neighboring(&0u8).unwrap().size();
+            format!("\"{size}\"")
+        };
+        let mut simulator = Simulator::new(MockSerializer, program);
+        simulator.add_device_at(0, (0.0, 0.0), JoinPolicy::Fresh);
+        simulator.add_device(1, JoinPolicy::Fresh);
+        simulator.add_device(2, JoinPolicy::Fresh);
+        simulator.connect(0, 1);
+        simulator.connect(1, 2);
+        simulator
+    }
+
+    fn snapshot(format: GraphFormat) -> String {
+        let mut simulator = line();
+        let mut rounds = Vec::new();
+        run_with_snapshots(&mut simulator, 2, format, |round| {
+            rounds.push(round);
+            Ok(io::sink())
+        })
+        .unwrap();
+        assert_eq!(rounds, vec![0, 1]);
+        let mut buffer = Vec::new();
+        write_graph(&simulator, format, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn topology_is_written_as_dot() {
+        let last = snapshot(GraphFormat::Dot);
+        assert!(last.starts_with("graph \"round 1\" {"));
+        assert!(last.contains(r#""0" [label="\"2\"", pos="0,0!"];"#));
+        assert!(last.contains(r#""1" [label="\"3\""];"#));
+        assert!(last.contains(r#""0" -- "1";"#));
+        assert!(last.contains(r#""1" -- "2";"#));
+        assert!(!last.contains(r#""1" -- "0";"#));
+    }
+
+    #[test]
+    fn topology_is_written_as_graphml() {
+        let last = snapshot(GraphFormat::GraphMl);
+        assert!(last.contains(r#"<graph id="round-1" edgedefault="undirected">"#));
+        assert!(last.contains(r#"<data key="output">&quot;3&quot;</data>"#));
+        assert!(last.contains(r#"<data key="x">0</data>"#));
+        assert_eq!(last.matches("<edge ").count(), 2);
+    }
+}
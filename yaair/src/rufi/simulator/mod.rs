@@ -0,0 +1,9 @@
+pub mod churn;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod faults;
+pub mod graph;
+#[cfg(feature = "scenario")]
+pub mod scenario;
+pub mod simulation;
+pub mod topology;
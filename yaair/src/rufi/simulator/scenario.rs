@@ -0,0 +1,280 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::simulator::faults::{FaultModel, LinkFaults};
+use crate::rufi::simulator::simulation::{JoinPolicy, SimProgram, Simulator};
+use serde::Deserialize;
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+/// Errors raised while loading or running a [`Scenario`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioError {
+    /// The description is not valid YAML or does not match the expected structure.
+    Parse(String),
+    /// The description is well-formed but inconsistent, e.g. with a negative round rate.
+    Invalid(String),
+    /// No program is registered under the name required by the scenario.
+    UnknownProgram(String),
+    /// A round of the simulation failed.
+    Simulation(AggregateError),
+}
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(msg) => write!(f, "Invalid scenario description: {msg}"),
+            Self::Invalid(msg) => write!(f, "Invalid scenario: {msg}"),
+            Self::UnknownProgram(name) => write!(f, "Unknown program: {name}"),
+            Self::Simulation(err) => write!(f, "Simulation failed: {err}"),
+        }
+    }
+}
+
+/// Placement of the devices of a [`Scenario`], numbered from zero.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "layout", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Deployment {
+    /// `width` x `height` devices, device `y * width + x` being at `(x, y) * spacing`.
+    Grid {
+        width: u32,
+        height: u32,
+        spacing: f64,
+    },
+    /// `count` devices along the x axis.
+    Line { count: u32, spacing: f64 },
+    /// `count` devices placed uniformly at random in a `width` x `height` rectangle.
+    Random {
+        count: u32,
+        width: f64,
+        height: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    /// One device for every position.
+    Explicit { positions: Vec<(f64, f64)> },
+}
+impl Deployment {
+    /// Position of every device, indexed by id.
+    pub fn positions(&self) -> Vec<(f64, f64)> {
+        match self {
+            Self::Grid {
+                width,
+                height,
+                spacing,
+            } => (0..*height)
+                .flat_map(|y| {
+                    (0..*width).map(move |x| (f64::from(x) * spacing, f64::from(y) * spacing))
+                })
+                .collect(),
+            Self::Line { count, spacing } => {
+                (0..*count).map(|x| (f64::from(x) * spacing, 0.0)).collect()
+            }
+            Self::Random {
+                count,
+                width,
+                height,
+                seed,
+            } => {
+                // xorshift must never be seeded with zero
+                let mut state = if *seed == 0 {
+                    0x9E37_79B9_7F4A_7C15
+                } else {
+                    *seed
+                };
+                let mut sample = move || {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    f64::from(u32::try_from(state >> 32).unwrap_or(u32::MAX)) / f64::from(u32::MAX)
+                };
+                (0..*count)
+                    .map(|_| (sample() * width, sample() * height))
+                    .collect()
+            }
+            Self::Explicit { positions } => positions.clone(),
+        }
+    }
+}
+
+/// Faults of every link of a [`Scenario`], see [`FaultModel`].
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultSpec {
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub links: LinkFaults,
+}
+
+/// Declarative description of a simulation, in the spirit of Alchemist.
+///
+/// ```yaml
+/// program: gradient
+/// devices: { layout: grid, width: 10, height: 10, spacing: 1.0 }
+/// communication_range: 1.5
+/// round_rate: 2.0
+/// rounds: 50
+/// faults: { seed: 7, links: { drop: 0.1 } }
+/// ```
+///
+/// Programs cannot be described in YAML: they are looked up by name when the simulation is
+/// built.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Name of the program run by every device.
+    pub program: String,
+    pub devices: Deployment,
+    /// Links between devices, in addition to the ones within the communication range.
+    #[serde(default)]
+    pub links: Vec<(u32, u32)>,
+    #[serde(default)]
+    pub communication_range: Option<f64>,
+    /// Rounds per second of simulated time, one by default.
+    #[serde(default)]
+    pub round_rate: Option<f64>,
+    /// Rounds executed by [`Scenario::run`].
+    #[serde(default)]
+    pub rounds: usize,
+    #[serde(default)]
+    pub faults: Option<FaultSpec>,
+}
+impl Scenario {
+    /// Parse a scenario from its YAML description.
+    ///
+    /// # Errors
+    /// Returns [`ScenarioError::Parse`] if the description is malformed
+    pub fn from_yaml(yaml: &str) -> Result<Self, ScenarioError> {
+        serde_yaml::from_str(yaml).map_err(|err| ScenarioError::Parse(err.to_string()))
+    }
+
+    /// Build the simulation described by the scenario, with the program returned by `resolve`
+    /// for the name in the scenario.
+    ///
+    /// # Errors
+    /// Returns an error if the program is unknown or the scenario is inconsistent
+    pub fn build<'p, S, Out>(
+        &self,
+        serializer: S,
+        resolve: impl FnOnce(&str) -> Option<SimProgram<'p, u32, S, Out>>,
+    ) -> Result<Simulator<'p, u32, S, Out>, ScenarioError>
+    where
+        S: Serializer + Clone + 'p,
+        Out: 'p,
+    {
+        let program = resolve(&self.program)
+            .ok_or_else(|| ScenarioError::UnknownProgram(self.program.clone()))?;
+        let mut simulator = Simulator::new(serializer, program);
+        if let Some(rate) = self.round_rate {
+            let duration = Duration::try_from_secs_f64(rate.recip())
+                .ok()
+                .filter(|_| rate > 0.0)
+                .ok_or_else(|| ScenarioError::Invalid(format!("round rate {rate}")))?;
+            simulator = simulator.with_round_duration(duration);
+        }
+        if let Some(range) = self.communication_range {
+            simulator = simulator.with_communication_range(range);
+        }
+        if let Some(faults) = self.faults {
+            simulator = simulator.with_fault_model(FaultModel::new(faults.links, faults.seed));
+        }
+        for (index, position) in self.devices.positions().into_iter().enumerate() {
+            let id = u32::try_from(index)
+                .map_err(|_| ScenarioError::Invalid("too many devices".into()))?;
+            simulator.add_device_at(id, position, JoinPolicy::Fresh);
+        }
+        for (a, b) in &self.links {
+            if !simulator.contains(*a) || !simulator.contains(*b) {
+                return Err(ScenarioError::Invalid(format!(
+                    "link between unknown devices {a} and {b}"
+                )));
+            }
+            simulator.connect(*a, *b);
+        }
+        Ok(simulator)
+    }
+
+    /// Build the simulation, see [`Scenario::build`], and execute its rounds.
+    ///
+    /// # Errors
+    /// Returns an error if the simulation cannot be built or a round fails
+    pub fn run<'p, S, Out>(
+        &self,
+        serializer: S,
+        resolve: impl FnOnce(&str) -> Option<SimProgram<'p, u32, S, Out>>,
+    ) -> Result<Simulator<'p, u32, S, Out>, ScenarioError>
+    where
+        S: Serializer + Clone + 'p,
+        Out: 'p,
+    {
+        let mut simulator = self.build(serializer, resolve)?;
+        simulator
+            .run(self.rounds)
+            .map_err(ScenarioError::Simulation)?;
+        Ok(simulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::VM;
+    use crate::rufi::blocks::gradient::hop_gradient;
+    use crate::rufi::test_utils::MockSerializer;
+
+    const SCENARIO: &str = "
+program: gradient
+devices: { layout: line, count: 4, spacing: 1.0 }
+communication_range: 1.5
+round_rate: 4.0
+rounds: 6
+";
+
+    fn programs(name: &str) -> Option<SimProgram<'static, u32, MockSerializer, f64>> {
+        (name == "gradient").then(|| -> SimProgram<'_, _, _, _> {
+            Box::new(|id: u32, vm: &mut VM<u32, MockSerializer>| hop_gradient(vm, id == 0).unwrap())
+        })
+    }
+
+    #[test]
+    fn scenarios_build_and_run_simulations() {
+        let scenario = Scenario::from_yaml(SCENARIO).unwrap();
+        let simulator = scenario.run(MockSerializer, programs).unwrap();
+        assert_eq!(simulator.round(), 6);
+        assert_eq!(simulator.position(3), Some((3.0, 0.0)));
+        assert_eq!(simulator.result(3), Some(&3.0));
+    }
+
+    #[test]
+    fn inconsistent_scenarios_are_rejected() {
+        assert!(matches!(
+            Scenario::from_yaml("program: gradient\ndevices: { layout: ring }"),
+            Err(ScenarioError::Parse(_))
+        ));
+        let unknown = Scenario::from_yaml(&SCENARIO.replace("program: gradient", "program: x"))
+            .unwrap()
+            .build(MockSerializer, programs);
+        assert_eq!(
+            unknown.err(),
+            Some(ScenarioError::UnknownProgram("x".into()))
+        );
+        let negative = Scenario::from_yaml(&SCENARIO.replace("4.0", "-1.0"))
+            .unwrap()
+            .build(MockSerializer, programs);
+        assert!(matches!(negative, Err(ScenarioError::Invalid(_))));
+    }
+
+    #[test]
+    fn random_deployments_are_reproducible() {
+        let deployment = Deployment::Random {
+            count: 5,
+            width: 10.0,
+            height: 2.0,
+            seed: 3,
+        };
+        let positions = deployment.positions();
+        assert_eq!(positions, deployment.positions());
+        assert!(positions
+            .iter()
+            .all(|(x, y)| (0.0..=10.0).contains(x) && (0.0..=2.0).contains(y)));
+    }
+}
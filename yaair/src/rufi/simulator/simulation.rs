@@ -0,0 +1,700 @@
+use crate::rufi::aggregate::{AggregateError, VM};
+use crate::rufi::device::DeviceId;
+use crate::rufi::messages::inbound::InboundMessage;
+use crate::rufi::messages::outbound::OutboundMessage;
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::messages::valuetree::ValueTree;
+use crate::rufi::sensors::neighborhood::{NeighborReading, NeighborhoodReadings};
+use crate::rufi::simulator::churn::{ChurnEvent, Recovery};
+use crate::rufi::simulator::faults::FaultModel;
+use crate::rufi::simulator::topology::Topology;
+use crate::rufi::time::TickClock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// Aggregate program executed by every simulated device.
+pub type SimProgram<'p, Id, S, Out> = Box<dyn Fn(Id, &mut VM<Id, S>) -> Out + 'p>;
+
+/// How a device joining the simulation initializes its state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinPolicy {
+    /// Start from an empty state.
+    #[default]
+    Fresh,
+    /// Resume the state the device had when it was removed, if any.
+    Restore,
+}
+
+/// How the devices of the [`Simulator`] execute within a round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Execution {
+    /// Every device executes on the exports produced in the previous round, as if all of them
+    /// fired at the same time.
+    #[default]
+    Lockstep,
+    /// Devices fire one at a time, in an order shuffled at every round by a xorshift generator
+    /// seeded with `seed`, each on the latest exports of its neighbors. Every device fires
+    /// exactly once per round, so the schedule is fair.
+    FairAsync { seed: u64 },
+}
+
+struct Device<Id: Ord + Hash + Copy + Serialize, S: Serializer> {
+    vm: VM<Id, S>,
+    position: Option<(f64, f64)>,
+    outbound: Option<Vec<u8>>,
+}
+
+/// Copy of an export travelling over a faulty link.
+struct InFlight {
+    round: u64,
+    late: bool,
+    tree: ValueTree,
+}
+
+/// In-process simulator running an aggregate program on a set of devices in rounds.
+///
+/// By default rounds are synchronous: in every round each device receives the exports produced
+/// by its neighbors in the previous round; see [`Execution`] for a fair asynchronous schedule. Two devices are neighbors if they are linked in the [`Topology`] or, when a
+/// communication range is configured, if their positions are within that range.
+/// Devices can join and leave between rounds, modelling open systems, and the network can be
+/// partitioned and healed; [`ChurnEvent`]s can also be scheduled for a given round.
+/// A [`FaultModel`] can make links lose, duplicate, reorder and delay exports.
+pub struct Simulator<'p, Id: Ord + Hash + Copy + Serialize, S: Serializer + Clone, Out> {
+    serializer: S,
+    program: SimProgram<'p, Id, S, Out>,
+    devices: BTreeMap<Id, Device<Id, S>>,
+    departed: BTreeMap<Id, VM<Id, S>>,
+    topology: Topology<Id>,
+    communication_range: Option<f64>,
+    round_duration: Option<Duration>,
+    results: BTreeMap<Id, Out>,
+    round: u64,
+    faults: Option<FaultModel<Id>>,
+    in_flight: BTreeMap<(Id, Id), Vec<InFlight>>,
+    partition: BTreeMap<Id, usize>,
+    scheduled: BTreeMap<u64, Vec<ChurnEvent<Id>>>,
+    last_churn: Option<u64>,
+    execution: Execution,
+    /// State of the generator shuffling the devices under [`Execution::FairAsync`].
+    shuffle: u64,
+}
+
+impl<'p, Id, S, Out> Simulator<'p, Id, S, Out>
+where
+    Id: DeviceId,
+    S: Serializer + Clone,
+{
+    pub fn new(serializer: S, program: impl Fn(Id, &mut VM<Id, S>) -> Out + 'p) -> Self {
+        Self {
+            serializer,
+            program: Box::new(program),
+            devices: BTreeMap::new(),
+            departed: BTreeMap::new(),
+            topology: Topology::new(),
+            communication_range: None,
+            round_duration: None,
+            results: BTreeMap::new(),
+            round: 0,
+            faults: None,
+            in_flight: BTreeMap::new(),
+            partition: BTreeMap::new(),
+            scheduled: BTreeMap::new(),
+            last_churn: None,
+            execution: Execution::Lockstep,
+            shuffle: 0,
+        }
+    }
+
+    /// Execute the rounds according to `execution`, see [`Simulator::set_execution`].
+    #[must_use]
+    pub const fn with_execution(mut self, execution: Execution) -> Self {
+        self.set_execution(execution);
+        self
+    }
+
+    /// Execute the next rounds according to `execution`, e.g. to compare the behavior of an
+    /// algorithm under both schedules in the same run.
+    pub const fn set_execution(&mut self, execution: Execution) {
+        self.execution = execution;
+        if let Execution::FairAsync { seed } = execution {
+            // xorshift must never be seeded with zero
+            self.shuffle = if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            };
+        }
+    }
+
+    pub const fn execution(&self) -> Execution {
+        self.execution
+    }
+
+    /// Deliver exports through links suffering the faults of `model`.
+    #[must_use]
+    pub fn with_fault_model(mut self, model: FaultModel<Id>) -> Self {
+        self.faults = Some(model);
+        self
+    }
+
+    pub const fn fault_model(&self) -> Option<&FaultModel<Id>> {
+        self.faults.as_ref()
+    }
+
+    /// Link every pair of positioned devices closer than `range`, in addition to the topology.
+    #[must_use]
+    pub const fn with_communication_range(mut self, range: f64) -> Self {
+        self.communication_range = Some(range);
+        self
+    }
+
+    /// Advance the clock of the devices added from now on by `duration` at every round,
+    /// instead of one second.
+    #[must_use]
+    pub const fn with_round_duration(mut self, duration: Duration) -> Self {
+        self.round_duration = Some(duration);
+        self
+    }
+
+    /// Add a device to the simulation; it takes part in the rounds from the next one.
+    ///
+    /// # Returns
+    /// `false` if a device with the same id is already running
+    pub fn add_device(&mut self, id: Id, policy: JoinPolicy) -> bool {
+        if self.devices.contains_key(&id) {
+            return false;
+        }
+        let parked = self.departed.remove(&id);
+        let vm = if let (JoinPolicy::Restore, Some(vm)) = (policy, parked) {
+            vm
+        } else {
+            let mut vm = VM::new(id, self.serializer.clone());
+            if let Some(duration) = self.round_duration {
+                vm.set_clock(TickClock::new(duration));
+            }
+            vm
+        };
+        self.devices.insert(
+            id,
+            Device {
+                vm,
+                position: None,
+                outbound: None,
+            },
+        );
+        true
+    }
+
+    /// Add a device placed at `position`, see [`Simulator::add_device`].
+    pub fn add_device_at(&mut self, id: Id, position: (f64, f64), policy: JoinPolicy) -> bool {
+        let added = self.add_device(id, policy);
+        if added {
+            self.set_position(id, position);
+        }
+        added
+    }
+
+    /// Remove a device and all its links; its exports are no longer delivered.
+    ///
+    /// The state of the device is kept aside so that it can rejoin with [`JoinPolicy::Restore`].
+    ///
+    /// # Returns
+    /// `false` if no such device is running
+    pub fn remove_device(&mut self, id: Id) -> bool {
+        let Some(device) = self.devices.remove(&id) else {
+            return false;
+        };
+        self.departed.insert(id, device.vm);
+        self.topology.isolate(id);
+        self.in_flight
+            .retain(|(from, to), _| *from != id && *to != id);
+        self.results.remove(&id);
+        true
+    }
+
+    pub fn set_position(&mut self, id: Id, position: (f64, f64)) {
+        if let Some(device) = self.devices.get_mut(&id) {
+            device.position = Some(position);
+        }
+    }
+
+    pub fn position(&self, id: Id) -> Option<(f64, f64)> {
+        self.devices.get(&id).and_then(|device| device.position)
+    }
+
+    /// Link two running devices.
+    pub fn connect(&mut self, a: Id, b: Id) {
+        if self.devices.contains_key(&a) && self.devices.contains_key(&b) {
+            self.topology.connect(a, b);
+        }
+    }
+
+    pub fn disconnect(&mut self, a: Id, b: Id) {
+        self.topology.disconnect(a, b);
+    }
+
+    /// Split the network in `groups`: devices in different groups cannot communicate, whatever
+    /// their links or positions. Devices in no group form one more group together.
+    ///
+    /// Replaces the current partition, if any.
+    pub fn partition<G: IntoIterator<Item = Id>>(&mut self, groups: impl IntoIterator<Item = G>) {
+        self.partition = groups
+            .into_iter()
+            .zip(1..)
+            .flat_map(|(group, label)| group.into_iter().map(move |id| (id, label)))
+            .collect();
+    }
+
+    /// Remove the current partition, restoring the links and ranges of the devices.
+    pub fn heal(&mut self) {
+        self.partition.clear();
+    }
+
+    pub fn is_partitioned(&self) -> bool {
+        !self.partition.is_empty()
+    }
+
+    fn same_side(&self, a: Id, b: Id) -> bool {
+        self.partition.get(&a) == self.partition.get(&b)
+    }
+
+    /// Apply `event` right away.
+    pub fn apply(&mut self, event: ChurnEvent<Id>) {
+        match event {
+            ChurnEvent::Join(id, policy) => {
+                self.add_device(id, policy);
+            }
+            ChurnEvent::Leave(id) => {
+                self.remove_device(id);
+            }
+            ChurnEvent::Connect(a, b) => self.connect(a, b),
+            ChurnEvent::Disconnect(a, b) => self.disconnect(a, b),
+            ChurnEvent::Partition(groups) => self.partition(groups),
+            ChurnEvent::Heal => self.heal(),
+        }
+        self.last_churn = Some(self.round);
+    }
+
+    /// Apply `event` before executing the round with index `round`, i.e. once `round` rounds
+    /// have been executed; events scheduled in the past are applied before the next round.
+    pub fn schedule(&mut self, round: u64, event: ChurnEvent<Id>) {
+        self.scheduled.entry(round).or_default().push(event);
+    }
+
+    /// Round in which the last churn event was applied, if any.
+    pub const fn last_churn(&self) -> Option<u64> {
+        self.last_churn
+    }
+
+    /// Execute rounds until `condition` holds on the simulator, for at most `max_rounds`.
+    ///
+    /// Useful to measure how long an aggregate algorithm takes to recover after churn: the
+    /// condition is checked after every round.
+    ///
+    /// # Returns
+    /// The rounds executed since the last churn event when the condition first held, `None`
+    /// if it never held
+    ///
+    /// # Errors
+    /// Returns the first error raised by [`Simulator::step`]
+    pub fn run_until(
+        &mut self,
+        max_rounds: usize,
+        mut condition: impl FnMut(&Self) -> bool,
+    ) -> Result<Option<Recovery>, AggregateError> {
+        for _ in 0..max_rounds {
+            self.step()?;
+            if condition(self) {
+                let churn_round = self.last_churn.unwrap_or(0);
+                return Ok(Some(Recovery {
+                    churn_round,
+                    rounds: self.round.saturating_sub(churn_round),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub const fn topology(&self) -> &Topology<Id> {
+        &self.topology
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.devices.contains_key(&id)
+    }
+
+    /// Ids of the running devices, in ascending order.
+    pub fn devices(&self) -> impl Iterator<Item = Id> + '_ {
+        self.devices.keys().copied()
+    }
+
+    /// Number of rounds executed so far.
+    pub const fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Result of the last round executed by every running device.
+    pub const fn results(&self) -> &BTreeMap<Id, Out> {
+        &self.results
+    }
+
+    pub fn result(&self, id: Id) -> Option<&Out> {
+        self.results.get(&id)
+    }
+
+    /// Consume the simulator, returning the results of the last round.
+    pub fn into_results(self) -> BTreeMap<Id, Out> {
+        self.results
+    }
+
+    /// Size in bytes of the last export of `id`.
+    pub fn outbound_size(&self, id: Id) -> Option<usize> {
+        self.devices
+            .get(&id)
+            .and_then(|device| device.outbound.as_ref())
+            .map(Vec::len)
+    }
+
+    /// Neighbors of `id` with the readings derived from their positions.
+    pub fn neighbors(&self, id: Id) -> Vec<(Id, NeighborReading)> {
+        let Some(device) = self.devices.get(&id) else {
+            return Vec::new();
+        };
+        self.devices
+            .iter()
+            .filter(|(other, _)| **other != id)
+            .filter_map(|(other, neighbor)| {
+                let reading = reading_between(device.position, neighbor.position);
+                let in_range = self
+                    .communication_range
+                    .zip(reading.range)
+                    .is_some_and(|(max, range)| range <= max);
+                let linked = in_range || self.topology.are_connected(id, *other);
+                (linked && self.same_side(id, *other)).then_some((*other, reading))
+            })
+            .collect()
+    }
+
+    /// Execute one round on every running device, according to the [`Execution`] mode.
+    ///
+    /// # Errors
+    /// Returns an error if an export cannot be serialized or decoded
+    pub fn step(&mut self) -> Result<(), AggregateError> {
+        let pending = self.scheduled.split_off(&self.round.saturating_add(1));
+        let due = std::mem::replace(&mut self.scheduled, pending);
+        due.into_values()
+            .flatten()
+            .for_each(|event| self.apply(event));
+        let mut exports = self.decode_exports()?;
+        for id in self.firing_order() {
+            self.execute(id, &exports)?;
+            if self.execution != Execution::Lockstep {
+                if let Some(bytes) = self
+                    .devices
+                    .get(&id)
+                    .and_then(|device| device.outbound.as_ref())
+                {
+                    exports.insert(id, self.decode_export(bytes)?);
+                }
+            }
+        }
+        let round = self.round;
+        for queue in self.in_flight.values_mut() {
+            queue.retain(|message| message.round > round);
+        }
+        self.round = self.round.saturating_add(1);
+        Ok(())
+    }
+
+    /// Ids of the running devices in the order they fire in the current round.
+    fn firing_order(&mut self) -> Vec<Id> {
+        let mut ids: Vec<Id> = self.devices.keys().copied().collect();
+        if self.execution == Execution::Lockstep {
+            return ids;
+        }
+        // Fisher-Yates shuffle
+        for index in (1..ids.len()).rev() {
+            let mut x = self.shuffle;
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            self.shuffle = x;
+            let other = u64::try_from(index.saturating_add(1))
+                .ok()
+                .and_then(|bound| x.checked_rem(bound))
+                .and_then(|other| usize::try_from(other).ok())
+                .unwrap_or(index);
+            ids.swap(index, other);
+        }
+        ids
+    }
+
+    /// Execute a round on the device `id`, on the exports of its neighbors in `exports`.
+    fn execute(&mut self, id: Id, exports: &BTreeMap<Id, ValueTree>) -> Result<(), AggregateError> {
+        let neighbors = self.neighbors(id);
+        let inbound = neighbors
+            .iter()
+            .filter_map(|(neighbor, _)| {
+                self.deliver(exports, *neighbor, id)
+                    .map(|tree| (*neighbor, tree))
+            })
+            .collect();
+        let Some(device) = self.devices.get_mut(&id) else {
+            return Ok(());
+        };
+        let readings = neighbors.into_iter().collect();
+        device.vm.prepare_new_round(InboundMessage::new(inbound));
+        device
+            .vm
+            .update_neighborhood(NeighborhoodReadings::new(readings));
+        let result = (self.program)(id, &mut device.vm);
+        device.outbound = Some(device.vm.get_outbound()?);
+        self.results.insert(id, result);
+        Ok(())
+    }
+
+    /// Execute `rounds` synchronous rounds.
+    ///
+    /// # Errors
+    /// Returns the first error raised by [`Simulator::step`]
+    pub fn run(&mut self, rounds: usize) -> Result<(), AggregateError> {
+        (0..rounds).try_for_each(|_| self.step())
+    }
+
+    /// Export of `from` received by `to` in the current round, after the link faults.
+    ///
+    /// When several copies arrive in the same round the last one wins, and duplicated or
+    /// reordered copies arrive after the fresh ones, replacing them.
+    fn deliver(
+        &mut self,
+        exports: &BTreeMap<Id, ValueTree>,
+        from: Id,
+        to: Id,
+    ) -> Option<ValueTree> {
+        let Some(faults) = self.faults.as_mut() else {
+            return exports.get(&from).cloned();
+        };
+        let round = self.round;
+        let queue = self.in_flight.entry((from, to)).or_default();
+        if let Some(tree) = exports.get(&from) {
+            // exports were sent at the end of the previous round
+            for delivery in faults.transmit(from, to, round.saturating_sub(1)) {
+                queue.push(InFlight {
+                    round: delivery.round,
+                    late: delivery.late,
+                    tree: tree.clone(),
+                });
+            }
+        }
+        let (arrived, pending): (Vec<_>, Vec<_>) = core::mem::take(queue)
+            .into_iter()
+            .partition(|message| message.round <= round);
+        *queue = pending;
+        arrived
+            .into_iter()
+            .filter(|message| message.round == round)
+            .max_by_key(|message| message.late)
+            .map(|message| message.tree)
+    }
+
+    fn decode_exports(&self) -> Result<BTreeMap<Id, ValueTree>, AggregateError> {
+        self.devices
+            .iter()
+            .filter_map(|(id, device)| device.outbound.as_ref().map(|bytes| (*id, bytes)))
+            .map(|(id, bytes)| Ok((id, self.decode_export(bytes)?)))
+            .collect()
+    }
+
+    fn decode_export(&self, bytes: &[u8]) -> Result<ValueTree, AggregateError> {
+        let message: OutboundMessage<Id> = self.serializer.deserialize(bytes).map_err(|err| {
+            AggregateError::DeserializationError(format!(
+                "Failed to decode the export of a simulated device: {err}",
+            ))
+        })?;
+        Ok(ValueTree::from(message))
+    }
+}
+
+fn reading_between(local: Option<(f64, f64)>, other: Option<(f64, f64)>) -> NeighborReading {
+    let vector = local.zip(other).map(|((x, y), (ox, oy))| (ox - x, oy - y));
+    NeighborReading {
+        range: vector.map(|(dx, dy)| dx.hypot(dy)),
+        lag: None,
+        vector,
+        link: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::Aggregate;
+    use crate::rufi::blocks::convergence::rep_count;
+    use crate::rufi::blocks::gradient::hop_gradient;
+    use crate::rufi::sensors::neighborhood::NeighborhoodSensors;
+    use crate::rufi::simulator::churn::ChurnEvent;
+    use crate::rufi::simulator::faults::LinkFaults;
+    use crate::rufi::test_utils::{line, run_rounds_with, MockSerializer};
+
+    fn hop_distance(id: u32, vm: &mut VM<u32, MockSerializer>) -> f64 {
+        hop_gradient(vm, id == 0).unwrap()
+    }
+
+    fn chain(n: u32) -> Simulator<'static, u32, MockSerializer, f64> {
+        let mut simulator = Simulator::new(MockSerializer, hop_distance);
+        for id in 0..n {
+            simulator.add_device(id, JoinPolicy::Fresh);
+        }
+        for (previous, id) in (0..n).zip(1..n) {
+            simulator.connect(previous, id);
+        }
+        simulator
+    }
+
+    #[test]
+    fn joining_device_converges_with_the_network() {
+        let mut simulator = chain(3);
+        simulator.run(5).unwrap();
+        assert_eq!(simulator.result(2), Some(&2.0));
+
+        simulator.add_device(3, JoinPolicy::Fresh);
+        simulator.connect(2, 3);
+        simulator.step().unwrap();
+        assert_eq!(simulator.result(3), Some(&3.0));
+        assert_eq!(simulator.result(0), Some(&0.0));
+    }
+
+    #[test]
+    fn removing_a_device_cuts_its_links() {
+        let mut simulator = chain(3);
+        simulator.run(5).unwrap();
+        assert!(simulator.remove_device(1));
+        assert!(!simulator.remove_device(1));
+        simulator.run(3).unwrap();
+        assert_eq!(simulator.result(1), None);
+        assert_eq!(simulator.result(2), Some(&f64::MAX));
+        assert_eq!(simulator.topology().links().count(), 0);
+    }
+
+    #[test]
+    fn rejoining_device_restores_or_clears_its_state() {
+        let counter = |_: u32, vm: &mut VM<u32, MockSerializer>| vm.repeat(&0u32, |n, _| n + 1);
+        let mut simulator = Simulator::new(MockSerializer, counter);
+        simulator.add_device(0, JoinPolicy::Fresh);
+        simulator.run(3).unwrap();
+        simulator.remove_device(0);
+        simulator.add_device(0, JoinPolicy::Restore);
+        simulator.step().unwrap();
+        assert_eq!(simulator.result(0), Some(&4));
+
+        simulator.remove_device(0);
+        simulator.add_device(0, JoinPolicy::Fresh);
+        simulator.step().unwrap();
+        assert_eq!(simulator.result(0), Some(&1));
+    }
+
+    #[test]
+    fn communication_range_links_close_devices() {
+        let ranges = |_: u32, vm: &mut VM<u32, MockSerializer>| {
+            vm.nbr_range()
+                .fold_neighbors(0.0, |total, range| total + range)
+        };
+        let mut simulator = Simulator::new(MockSerializer, ranges).with_communication_range(1.5);
+        simulator.add_device_at(0, (0.0, 0.0), JoinPolicy::Fresh);
+        simulator.add_device_at(1, (1.0, 0.0), JoinPolicy::Fresh);
+        simulator.add_device_at(2, (3.0, 0.0), JoinPolicy::Fresh);
+        simulator.step().unwrap();
+        assert_eq!(simulator.result(0), Some(&1.0));
+        assert_eq!(simulator.result(2), Some(&0.0));
+        assert_eq!(simulator.neighbors(2).len(), 0);
+    }
+
+    /// Round seen by device 1 in the last export of device 0, after `rounds` rounds.
+    fn round_heard(faults: LinkFaults, rounds: usize) -> Option<u32> {
+        let program = |_: u32, vm: &mut VM<u32, MockSerializer>| {
+            let round = vm.repeat(&0u32, |n, _| n.saturating_add(1));
+            vm.neighboring(&round)
+                .unwrap()
+                .fold_neighbors(None, |heard: Option<u32>, other| heard.max(Some(*other)))
+        };
+        let model = FaultModel::new(LinkFaults::reliable(), 1).with_link(0, 1, faults);
+        let mut simulator = Simulator::new(MockSerializer, program).with_fault_model(model);
+        simulator.add_device(0, JoinPolicy::Fresh);
+        simulator.add_device(1, JoinPolicy::Fresh);
+        simulator.connect(0, 1);
+        simulator.run(rounds).unwrap();
+        simulator.result(1).copied().flatten()
+    }
+
+    #[test]
+    fn faulty_links_lose_delay_and_replay_exports() {
+        assert_eq!(round_heard(LinkFaults::reliable(), 6), Some(5));
+        assert_eq!(round_heard(LinkFaults::reliable().with_drop(1.0), 6), None);
+        assert_eq!(
+            round_heard(LinkFaults::reliable().with_delay(2), 6),
+            Some(3)
+        );
+        assert_eq!(
+            round_heard(LinkFaults::reliable().with_reorder(1.0), 6),
+            Some(4)
+        );
+        assert_eq!(
+            round_heard(LinkFaults::reliable().with_duplicate(1.0), 6),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn fair_async_devices_see_exports_of_the_same_round() {
+        let topology = line(2);
+        let heard = |_: u32, vm: &mut VM<u32, MockSerializer>| {
+            let neighbors = vm.neighboring(&()).unwrap().ids().count();
+            (neighbors, rep_count(vm))
+        };
+        let lockstep = run_rounds_with(&topology, 1, Execution::Lockstep, heard);
+        assert_eq!(lockstep.values().map(|(n, _)| n).sum::<usize>(), 0);
+        for seed in 0..4 {
+            let fair = run_rounds_with(&topology, 1, Execution::FairAsync { seed }, heard);
+            // the device firing second hears the first one
+            assert_eq!(fair.values().map(|(n, _)| n).sum::<usize>(), 1);
+        }
+        let fair = run_rounds_with(&line(5), 7, Execution::FairAsync { seed: 3 }, heard);
+        assert!(fair.values().all(|(_, rounds)| *rounds == 7));
+    }
+
+    #[test]
+    fn partitions_isolate_groups_until_healed() {
+        let mut simulator = chain(4);
+        simulator.run(5).unwrap();
+        simulator.partition([vec![0, 1], vec![2, 3]]);
+        assert!(simulator.is_partitioned());
+        assert!(simulator.neighbors(2).iter().all(|(id, _)| *id == 3));
+        simulator.run(5).unwrap();
+        assert_eq!(simulator.result(1), Some(&1.0));
+        // the cut-off side counts to infinity
+        assert!(simulator.result(3).is_some_and(|distance| *distance > 3.0));
+        simulator.heal();
+        simulator.run(3).unwrap();
+        assert_eq!(simulator.result(3), Some(&3.0));
+    }
+
+    #[test]
+    fn recovery_is_measured_from_the_last_churn_event() {
+        let mut simulator = chain(4);
+        simulator.schedule(3, ChurnEvent::Partition(vec![vec![0]]));
+        simulator.schedule(6, ChurnEvent::Heal);
+        simulator.run(6).unwrap();
+        assert!(simulator.result(3).is_some_and(|distance| *distance > 3.0));
+        assert_eq!(simulator.last_churn(), Some(3));
+
+        let recovery = simulator
+            .run_until(10, |simulator| simulator.result(3) == Some(&3.0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(recovery.churn_round, 6);
+        assert_eq!(recovery.rounds, 3);
+        assert_eq!(simulator.run_until(2, |_| false), Ok(None));
+    }
+}
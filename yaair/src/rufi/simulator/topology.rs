@@ -0,0 +1,80 @@
+use std::collections::BTreeSet;
+
+/// Undirected communication links between simulated devices.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Topology<Id: Ord + Copy> {
+    links: BTreeSet<(Id, Id)>,
+}
+impl<Id: Ord + Copy> Topology<Id> {
+    pub const fn new() -> Self {
+        Self {
+            links: BTreeSet::new(),
+        }
+    }
+
+    fn key(a: Id, b: Id) -> (Id, Id) {
+        if a <= b {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    /// Link `a` and `b`; self-links are ignored.
+    pub fn connect(&mut self, a: Id, b: Id) {
+        if a != b {
+            self.links.insert(Self::key(a, b));
+        }
+    }
+
+    pub fn disconnect(&mut self, a: Id, b: Id) {
+        self.links.remove(&Self::key(a, b));
+    }
+
+    pub fn are_connected(&self, a: Id, b: Id) -> bool {
+        self.links.contains(&Self::key(a, b))
+    }
+
+    /// Remove all the links of `id`.
+    pub fn isolate(&mut self, id: Id) {
+        self.links.retain(|(a, b)| *a != id && *b != id);
+    }
+
+    pub fn clear(&mut self) {
+        self.links.clear();
+    }
+
+    /// Devices linked to `id`, in ascending order.
+    pub fn neighbors(&self, id: Id) -> impl Iterator<Item = Id> + '_ {
+        self.links.iter().filter_map(move |(a, b)| {
+            if *a == id {
+                Some(*b)
+            } else if *b == id {
+                Some(*a)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = (Id, Id)> + '_ {
+        self.links.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_are_undirected() {
+        let mut topology = Topology::new();
+        topology.connect(2u32, 1);
+        topology.connect(1, 3);
+        topology.connect(1, 1);
+        assert!(topology.are_connected(1, 2));
+        assert_eq!(topology.neighbors(1).collect::<Vec<_>>(), vec![2, 3]);
+        topology.isolate(1);
+        assert_eq!(topology.links().count(), 0);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::data::snapshot::DeviceSnapshot;
+use crate::rufi::device::DeviceId;
+
+/// Persistent storage of the state of a device, e.g. a flash partition or a file, written when
+/// the [`Engine`](crate::rufi::engine::Engine) shuts down.
+pub trait StateStore<Id: DeviceId> {
+    /// Persist `snapshot`, replacing the one saved before.
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::StorageError`] if the snapshot cannot be written
+    fn save(&mut self, snapshot: &DeviceSnapshot<Id>) -> Result<(), AggregateError>;
+
+    /// The last snapshot saved, to warm start the device with
+    /// [`Engine::warm_start`](crate::rufi::engine::Engine::warm_start).
+    ///
+    /// # Errors
+    /// Returns [`AggregateError::StorageError`] if the stored snapshot cannot be read
+    fn load(&mut self) -> Result<Option<DeviceSnapshot<Id>>, AggregateError>;
+}
+
+/// A store keeping the last snapshot in memory, e.g. for tests or to hand it over to another
+/// device in the same process.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore<Id: DeviceId> {
+    snapshot: Option<DeviceSnapshot<Id>>,
+}
+impl<Id: DeviceId> MemoryStore<Id> {
+    pub const fn new() -> Self {
+        Self { snapshot: None }
+    }
+
+    /// The last snapshot saved, without taking it.
+    pub const fn snapshot(&self) -> Option<&DeviceSnapshot<Id>> {
+        self.snapshot.as_ref()
+    }
+}
+impl<Id: DeviceId> StateStore<Id> for MemoryStore<Id> {
+    fn save(&mut self, snapshot: &DeviceSnapshot<Id>) -> Result<(), AggregateError> {
+        self.snapshot = Some(snapshot.clone());
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Option<DeviceSnapshot<Id>>, AggregateError> {
+        Ok(self.snapshot.clone())
+    }
+}
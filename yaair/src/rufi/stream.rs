@@ -0,0 +1,236 @@
+use crate::rufi::aggregate::AggregateError;
+use crate::rufi::device::DeviceId;
+use crate::rufi::engine::{Engine, RoundReport};
+use crate::rufi::messages::serializer::Serializer;
+use crate::rufi::network::Network;
+use crate::rufi::scheduler::Scheduler;
+use crate::rufi::time::Clock;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use futures_core::Stream;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex, PoisonError};
+#[cfg(feature = "std")]
+use std::task::Waker;
+
+/// A wait between two rounds, created by the sleep function of an [`EngineStream`].
+type Sleep = Pin<Box<dyn Future<Output = ()>>>;
+
+/// [`Stream`] of the results of the rounds executed by an [`Engine`], see
+/// [`Engine::into_stream`] and [`Engine::into_stream_with`].
+///
+/// Like [`Engine::run`], the stream waits between rounds as suggested by the scheduler, polling
+/// schedulers without a time-based wakeup every `poll_interval`. It ends once the engine is
+/// shut down.
+pub struct EngineStream<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+{
+    engine: Engine<Id, Out, Env, S, Net, Sch>,
+    poll_interval: Duration,
+    clock: Box<dyn Clock>,
+    sleep: Box<dyn FnMut(Duration) -> Sleep>,
+    waiting: Option<Sleep>,
+}
+impl<Id, Out, Env, S, Net, Sch> EngineStream<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+{
+    /// Drive `engine` with the time read from `clock`, awaiting the futures returned by `sleep`
+    /// between rounds, e.g. `tokio::time::sleep` or `embassy_time::Timer::after`.
+    pub fn new<F, Fut>(
+        engine: Engine<Id, Out, Env, S, Net, Sch>,
+        poll_interval: Duration,
+        clock: impl Clock + 'static,
+        mut sleep: F,
+    ) -> Self
+    where
+        F: FnMut(Duration) -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        Self {
+            engine,
+            poll_interval,
+            clock: Box::new(clock),
+            sleep: Box::new(move |wait| Box::pin(sleep(wait))),
+            waiting: None,
+        }
+    }
+
+    /// The driven engine.
+    pub const fn engine(&self) -> &Engine<Id, Out, Env, S, Net, Sch> {
+        &self.engine
+    }
+
+    /// The driven engine, e.g. to update its environment or to shut it down.
+    pub const fn engine_mut(&mut self) -> &mut Engine<Id, Out, Env, S, Net, Sch> {
+        &mut self.engine
+    }
+
+    /// Stop streaming and give the engine back.
+    pub fn into_inner(self) -> Engine<Id, Out, Env, S, Net, Sch> {
+        self.engine
+    }
+}
+impl<Id, Out, Env, S, Net, Sch> Stream for EngineStream<Id, Out, Env, S, Net, Sch>
+where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+    Self: Unpin,
+{
+    type Item = Result<RoundReport<Out>, AggregateError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(waiting) = this.waiting.as_mut() {
+                if waiting.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.waiting = None;
+            }
+            if this.engine.is_stopped() {
+                return Poll::Ready(None);
+            }
+            let result = this.engine.tick(this.clock.now());
+            let after_round = this.clock.now();
+            let wait = this
+                .engine
+                .scheduler()
+                .next_wakeup(after_round)
+                .map_or(this.poll_interval, |wakeup| {
+                    wakeup.saturating_sub(after_round).min(this.poll_interval)
+                });
+            this.waiting = Some((this.sleep)(wait));
+            if let Some(result) = result {
+                return Poll::Ready(Some(result));
+            }
+        }
+    }
+}
+
+/// Completion flag and waker of a [`ThreadSleep`].
+#[cfg(feature = "std")]
+type SleepState = Arc<Mutex<(bool, Option<Waker>)>>;
+
+/// Wait completed by a thread sleeping on its behalf, so that streams need no async runtime.
+#[cfg(feature = "std")]
+pub(crate) struct ThreadSleep {
+    state: SleepState,
+}
+#[cfg(feature = "std")]
+impl ThreadSleep {
+    pub(crate) fn new(wait: Duration) -> Self {
+        let state = SleepState::new(Mutex::new((wait.is_zero(), None)));
+        if !wait.is_zero() {
+            let shared = Arc::clone(&state);
+            std::thread::spawn(move || {
+                std::thread::sleep(wait);
+                let mut done = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                done.0 = true;
+                if let Some(waker) = done.1.take() {
+                    waker.wake();
+                }
+            });
+        }
+        Self { state }
+    }
+}
+#[cfg(feature = "std")]
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.0 {
+            return Poll::Ready(());
+        }
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi::aggregate::{Aggregate, VM};
+    use crate::rufi::network::NoNetwork;
+    use crate::rufi::scheduler::Periodic;
+    use crate::rufi::test_utils::MockSerializer;
+    use crate::rufi::time::TickClock;
+    use std::task::{Wake, Waker};
+    use std::thread::Thread;
+
+    /// Wakes the test thread parked while the stream is pending.
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn next<T: Stream + Unpin>(stream: &mut T) -> Option<T::Item> {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::new(&mut *stream).poll_next(&mut cx) {
+                Poll::Ready(item) => return item,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    fn engine() -> Engine<u32, u64, (), MockSerializer, NoNetwork> {
+        Engine::new(
+            1,
+            NoNetwork,
+            (),
+            MockSerializer,
+            |_env, vm: &mut VM<u32, _>| vm.repeat(&0u64, |count, _| count.saturating_add(1)),
+        )
+    }
+
+    #[test]
+    fn streams_round_results_until_shutdown() {
+        let mut stream = engine()
+            .with_scheduler(Periodic::new(Duration::from_secs(1)))
+            .into_stream_with(Duration::from_secs(1), TickClock::default(), |_| {
+                core::future::ready(())
+            });
+        for expected in 1..=3 {
+            let report = next(&mut stream).unwrap().unwrap();
+            assert_eq!(report.output, expected);
+        }
+        assert!(stream.engine_mut().shutdown().is_ok());
+        assert!(next(&mut stream).is_none());
+    }
+
+    #[test]
+    fn std_streams_wait_for_the_scheduler() {
+        let mut stream = engine()
+            .with_scheduler(Periodic::new(Duration::from_millis(20)))
+            .into_stream(Duration::from_secs(1));
+        assert_eq!(
+            next(&mut stream).unwrap().map(|report| report.output),
+            Ok(1)
+        );
+        let started = std::time::Instant::now();
+        assert_eq!(
+            next(&mut stream).unwrap().map(|report| report.output),
+            Ok(2)
+        );
+        assert!(started.elapsed() >= Duration::from_millis(10));
+    }
+}
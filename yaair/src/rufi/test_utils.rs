@@ -0,0 +1,106 @@
+#[cfg(feature = "std")]
+use crate::rufi::aggregate::VM;
+use crate::rufi::messages::serializer::Serializer;
+#[cfg(feature = "std")]
+use crate::rufi::messages::Map;
+#[cfg(feature = "std")]
+use crate::rufi::simulator::simulation::{Execution, JoinPolicy, Simulator};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// JSON serializer shared by the unit tests of the crate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockSerializer;
+
+impl Serializer for MockSerializer {
+    type Error = serde_json::Error;
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn deserialize<'de, T: Deserialize<'de>>(&self, value: &'de [u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(value)
+    }
+}
+
+/// Undirected line topology `0 - 1 - ... - (n - 1)`.
+#[cfg(feature = "std")]
+pub fn line(n: u32) -> Map<u32, Vec<u32>> {
+    (0..n)
+        .map(|id| {
+            let neighbors = [
+                id.checked_sub(1),
+                id.checked_add(1).filter(|next| *next < n),
+            ];
+            (id, neighbors.into_iter().flatten().collect())
+        })
+        .collect()
+}
+
+/// Undirected `width` x `height` grid, where device `y * width + x` is linked to the devices
+/// above, below, left and right of it.
+#[cfg(feature = "std")]
+pub fn grid(width: u32, height: u32) -> Map<u32, Vec<u32>> {
+    let cell = |x: u32, y: u32| {
+        let row = y.checked_mul(width)?;
+        (x < width && y < height)
+            .then(|| row.checked_add(x))
+            .flatten()
+    };
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let neighbors = [
+                x.checked_sub(1).and_then(|left| cell(left, y)),
+                x.checked_add(1).and_then(|right| cell(right, y)),
+                y.checked_sub(1).and_then(|below| cell(x, below)),
+                y.checked_add(1).and_then(|above| cell(x, above)),
+            ];
+            Some((cell(x, y)?, neighbors.into_iter().flatten().collect()))
+        })
+        .collect()
+}
+
+/// Run `program` on every device of `topology` for `rounds` synchronous rounds.
+///
+/// # Returns
+/// The result of the last round of every device
+#[cfg(feature = "std")]
+pub fn run_rounds<V, P>(
+    topology: &Map<u32, Vec<u32>>,
+    rounds: usize,
+    program: P,
+) -> BTreeMap<u32, V>
+where
+    P: Fn(u32, &mut VM<u32, MockSerializer>) -> V,
+{
+    run_rounds_with(topology, rounds, Execution::Lockstep, program)
+}
+
+/// Like [`run_rounds`], executing the rounds according to `execution`.
+#[cfg(feature = "std")]
+pub fn run_rounds_with<V, P>(
+    topology: &Map<u32, Vec<u32>>,
+    rounds: usize,
+    execution: Execution,
+    program: P,
+) -> BTreeMap<u32, V>
+where
+    P: Fn(u32, &mut VM<u32, MockSerializer>) -> V,
+{
+    let mut simulator = Simulator::new(MockSerializer, program).with_execution(execution);
+    for id in topology.keys() {
+        simulator.add_device(*id, JoinPolicy::Fresh);
+    }
+    for (id, neighbors) in topology {
+        for neighbor in neighbors {
+            simulator.connect(*id, *neighbor);
+        }
+    }
+    simulator.run(rounds).unwrap();
+    simulator.into_results()
+}
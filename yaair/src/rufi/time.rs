@@ -0,0 +1,82 @@
+use core::time::Duration;
+
+/// Source of time for aggregate programs, sampled once at the beginning of every round.
+pub trait Clock {
+    /// The current time, relative to an arbitrary epoch.
+    fn now(&mut self) -> Duration;
+}
+
+/// Logical clock advancing by a fixed `tick` every time it is sampled, i.e. every round.
+///
+/// Suitable for `no_std` targets without a time source and for deterministic tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickClock {
+    tick: Duration,
+    elapsed: Duration,
+}
+impl TickClock {
+    pub const fn new(tick: Duration) -> Self {
+        Self {
+            tick,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+impl Default for TickClock {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+impl Clock for TickClock {
+    fn now(&mut self) -> Duration {
+        let now = self.elapsed;
+        self.elapsed = self.elapsed.saturating_add(self.tick);
+        now
+    }
+}
+
+/// Wall clock measuring the time elapsed since its creation.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    epoch: std::time::Instant,
+}
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            epoch: std::time::Instant::now(),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&mut self) -> Duration {
+        self.epoch.elapsed()
+    }
+}
+
+/// Access to the time of the current round from aggregate programs.
+pub trait TimeSensor {
+    /// The time at which the current round started.
+    fn current_time(&self) -> Duration;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_clock_advances_on_every_sample() {
+        let mut clock = TickClock::new(Duration::from_millis(500));
+        assert_eq!(clock.now(), Duration::ZERO);
+        assert_eq!(clock.now(), Duration::from_millis(500));
+        assert_eq!(clock.now(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,89 @@
+//! Heap allocations of the rounds of a device, counted by a global allocator.
+//!
+//! Kept in its own test target, so that no other test allocates while a round is measured.
+//! Without `std` the maps of the VM are `BTreeMap`s, which free their nodes when cleared, so
+//! rounds are only allocation-free with `std`.
+
+#![cfg(feature = "std")]
+
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use yaair::rufi::aggregate::{Aggregate, VM};
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+
+/// Allocations made so far by the test binary.
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+// SAFETY: every call is forwarded to the system allocator
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        // SAFETY: same contract as the caller
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: same contract as the caller
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        // SAFETY: same contract as the caller
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+struct JsonSerializer;
+impl Serializer for JsonSerializer {
+    type Error = serde_json::Error;
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn deserialize<'de, T: Deserialize<'de>>(&self, value: &'de [u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(value)
+    }
+}
+
+/// Nested operators keeping state, without exchanging values with the neighbors.
+fn program(vm: &mut VM<u32, JsonSerializer>) -> u32 {
+    let rounds = vm.repeat(&0u32, |rounds, _| rounds.saturating_add(1));
+    vm.branch(
+        rounds > 0,
+        |vm| {
+            (0..8u32).fold(0u32, |total, step| {
+                let value = vm.repeat(&step, |value, vm| {
+                    vm.repeat(&value, |nested, _| nested.saturating_add(1))
+                });
+                total.saturating_add(value)
+            })
+        },
+        |_| 0,
+    )
+}
+
+/// Allocations of a round of `vm` executing `program`.
+fn allocations_of_round(vm: &mut VM<u32, JsonSerializer>) -> usize {
+    vm.prepare_new_round(InboundMessage::default());
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    program(vm);
+    ALLOCATIONS.load(Ordering::Relaxed).saturating_sub(before)
+}
+
+#[test]
+fn operators_keeping_state_do_not_allocate_after_the_first_round() {
+    let mut vm = VM::new(1u32, JsonSerializer);
+    // paths are interned and values stored in the first round only
+    assert!(allocations_of_round(&mut vm) > 0);
+    for _ in 0..3 {
+        assert_eq!(allocations_of_round(&mut vm), 0);
+    }
+}
@@ -0,0 +1,48 @@
+//! Consumer of the crate that only relies on `core` and `alloc`, as a `no_std` firmware would.
+//!
+//! CI also runs it with `--no-default-features`, linking the library built without `std`, to
+//! check that a round can be executed and exchanged using only the `no_std` public API.
+#![no_std]
+
+extern crate alloc;
+// required by the test harness only
+extern crate std;
+
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use yaair::rufi::aggregate::{Aggregate, VM};
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::outbound::OutboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::messages::valuetree::ValueTree;
+use yaair::rufi::messages::Map;
+
+struct JsonSerializer;
+impl Serializer for JsonSerializer {
+    type Error = serde_json::Error;
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_json::to_vec(value)
+    }
+
+    fn deserialize<'de, T: Deserialize<'de>>(&self, value: &'de [u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(value)
+    }
+}
+
+#[test]
+fn neighbors_exchange_values_without_std() {
+    let mut sender = VM::new(1u32, JsonSerializer);
+    sender.prepare_new_round(InboundMessage::new(Map::new()));
+    sender.neighboring(&10u32).unwrap();
+    let bytes = sender.get_outbound().unwrap();
+    let export: OutboundMessage<u32> = JsonSerializer.deserialize(&bytes).unwrap();
+
+    let mut receiver = VM::new(0u32, JsonSerializer);
+    let mut inbound = Map::new();
+    inbound.insert(1, ValueTree::from(export));
+    receiver.prepare_new_round(InboundMessage::new(inbound));
+    let field = receiver.neighboring(&20u32).unwrap();
+    assert_eq!(field.local(), &20);
+    assert_eq!(field.get(&1), Some(&10));
+}
@@ -0,0 +1,8 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+// embassy-time still depends on both embedded-hal 0.2 and 1.0
+#![allow(clippy::multiple_crate_versions)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+pub mod rufi_embedded;
@@ -0,0 +1,23 @@
+use core::time::Duration;
+use embassy_time::Instant;
+use yaair::rufi::time::Clock;
+
+/// Time elapsed since boot, as measured by the Embassy time driver.
+pub fn uptime() -> Duration {
+    Duration::from_micros(Instant::now().as_micros())
+}
+
+/// [`Clock`] reading the Embassy time driver, so that programs see the same time base as the
+/// timers driving the rounds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbassyClock;
+impl EmbassyClock {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+impl Clock for EmbassyClock {
+    fn now(&mut self) -> Duration {
+        uptime()
+    }
+}
@@ -0,0 +1,5 @@
+pub mod clock;
+pub mod neighbors;
+pub mod network;
+pub mod runner;
+pub mod serial;
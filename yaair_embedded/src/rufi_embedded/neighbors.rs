@@ -0,0 +1,100 @@
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap;
+use core::hash::Hash;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::outbound::OutboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::messages::valuetree::ValueTree;
+use yaair::rufi::sensors::neighborhood::{NeighborReading, NeighborhoodReadings};
+
+/// Reception time, sequence number and full tree of the last message of a neighbor.
+type LastMessage = (Duration, u64, ValueTree);
+
+/// Last message received from every neighbor, retained until `retention` expires.
+///
+/// Times are the uptime of the device, so that no wall clock is required.
+#[derive(Debug)]
+pub struct NeighborTable<Id: Ord + Hash + Copy> {
+    local_id: Id,
+    retention: Duration,
+    last_messages: BTreeMap<Id, LastMessage>,
+}
+impl<Id> NeighborTable<Id>
+where
+    Id: DeviceId,
+{
+    pub const fn new(local_id: Id, retention: Duration) -> Self {
+        Self {
+            local_id,
+            retention,
+            last_messages: BTreeMap::new(),
+        }
+    }
+
+    /// Decode a serialized [`OutboundMessage`] received at `now` and store it as the last message
+    /// of its sender.
+    ///
+    /// Messages sent by the local device are ignored, as well as deltas whose base message has
+    /// not been received.
+    ///
+    /// # Returns
+    /// `false` if the payload could not be decoded
+    pub fn receive<S: Serializer>(
+        &mut self,
+        serializer: &S,
+        payload: &[u8],
+        now: Duration,
+    ) -> bool {
+        let Ok(message) = OutboundMessage::<Id>::decode(serializer, payload) else {
+            return false;
+        };
+        let sender = message.sender;
+        if sender == self.local_id {
+            return true;
+        }
+        let sequence = message.sequence();
+        let last = self
+            .last_messages
+            .get(&sender)
+            .map(|(_, last_sequence, tree)| (*last_sequence, tree));
+        if let Some(tree) = message.resolve(last) {
+            self.last_messages.insert(sender, (now, sequence, tree));
+        }
+        true
+    }
+
+    /// Evict the neighbors expired at `now` and build the inbound message for the next round.
+    pub fn inbound(&mut self, now: Duration) -> InboundMessage<Id> {
+        let retention = self.retention;
+        self.last_messages
+            .retain(|_, (received_at, _, _)| now.saturating_sub(*received_at) <= retention);
+        InboundMessage::new(
+            self.last_messages
+                .iter()
+                .map(|(id, (_, _, tree))| (*id, tree.clone()))
+                .collect(),
+        )
+    }
+
+    /// Age of the last message of every neighbor at `now`.
+    pub fn readings(&self, now: Duration) -> NeighborhoodReadings<Id> {
+        NeighborhoodReadings::new(
+            self.last_messages
+                .iter()
+                .map(|(id, (received_at, _, _))| {
+                    (
+                        *id,
+                        NeighborReading {
+                            lag: Some(now.saturating_sub(*received_at)),
+                            ..NeighborReading::default()
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+}
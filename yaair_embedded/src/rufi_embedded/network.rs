@@ -0,0 +1,146 @@
+use crate::rufi_embedded::clock::uptime;
+use crate::rufi_embedded::neighbors::NeighborTable;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::net::SocketAddr;
+use core::time::Duration;
+use embedded_nal::UdpFullStack;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+
+/// Configuration of a [`NalNetwork`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NalConfig {
+    /// Local port the socket is bound to.
+    pub port: u16,
+    /// Addresses every outbound message is sent to (peers or broadcast addresses).
+    pub targets: Vec<SocketAddr>,
+    /// Size of the receive buffer; larger datagrams are truncated by the stack and dropped.
+    pub max_datagram: usize,
+    /// How long the last message of a silent neighbor is retained.
+    pub retention: Duration,
+}
+impl NalConfig {
+    pub const fn new(port: u16) -> Self {
+        Self {
+            port,
+            targets: Vec::new(),
+            // fits an Ethernet frame without IP fragmentation
+            max_datagram: 1472,
+            retention: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_target(mut self, target: SocketAddr) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    pub const fn with_max_datagram(mut self, max_datagram: usize) -> Self {
+        self.max_datagram = max_datagram;
+        self
+    }
+
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+/// Connectionless [`Network`] over any [`embedded_nal`] UDP stack (smoltcp, embassy-net,
+/// modem drivers), exchanging one datagram per round with every target.
+///
+/// Stack operations are non-blocking: inbound datagrams are drained at the beginning of every
+/// round and a send that would block is dropped, like a lost datagram.
+pub struct NalNetwork<Stack: UdpFullStack, Id: Ord + Hash + Copy, S: Serializer> {
+    stack: Stack,
+    socket: Stack::UdpSocket,
+    targets: Vec<SocketAddr>,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+    buffer: Vec<u8>,
+}
+impl<Stack, Id, S> NalNetwork<Stack, Id, S>
+where
+    Stack: UdpFullStack,
+    Id: DeviceId,
+    S: Serializer,
+{
+    /// Open a socket on `stack` bound to the configured port.
+    ///
+    /// # Errors
+    /// Returns the error of the stack if no socket is available or the port cannot be bound
+    pub fn bind(
+        mut stack: Stack,
+        local_id: Id,
+        config: NalConfig,
+        serializer: S,
+    ) -> Result<Self, Stack::Error> {
+        let mut socket = stack.socket()?;
+        stack.bind(&mut socket, config.port)?;
+        Ok(Self {
+            stack,
+            socket,
+            targets: config.targets,
+            serializer,
+            neighbors: NeighborTable::new(local_id, config.retention),
+            buffer: vec![0; config.max_datagram],
+        })
+    }
+
+    /// Close the socket and give the stack back.
+    ///
+    /// # Errors
+    /// Returns the error of the stack if the socket cannot be closed
+    pub fn close(mut self) -> Result<Stack, Stack::Error> {
+        self.stack.close(self.socket)?;
+        Ok(self.stack)
+    }
+
+    fn drain(&mut self, now: Duration) {
+        while let Ok((size, _)) = self.stack.receive(&mut self.socket, &mut self.buffer) {
+            if let Some(payload) = self.buffer.get(..size) {
+                self.neighbors.receive(&self.serializer, payload, now);
+            }
+        }
+    }
+}
+impl<Stack, Id, S> Network<Id, S> for NalNetwork<Stack, Id, S>
+where
+    Stack: UdpFullStack,
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        let mut failure = None;
+        for target in &self.targets {
+            // keep sending to the other targets, reporting the first failure
+            let sent = self
+                .stack
+                .send_to(&mut self.socket, *target, &outbound_message);
+            if let Err(err) = sent {
+                failure.get_or_insert_with(|| match err {
+                    nb::Error::WouldBlock => NetworkError::Congested,
+                    nb::Error::Other(_) => NetworkError::Other("UDP stack send failed".into()),
+                });
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        let now = uptime();
+        self.drain(now);
+        self.neighbors.inbound(now)
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings(uptime())
+    }
+}
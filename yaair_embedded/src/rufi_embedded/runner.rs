@@ -0,0 +1,50 @@
+use crate::rufi_embedded::clock::uptime;
+use core::time::Duration;
+use embassy_time::Timer;
+use yaair::rufi::aggregate::AggregateError;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::engine::{Engine, RoundReport};
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::Network;
+use yaair::rufi::scheduler::Scheduler;
+
+/// Drive `engine` from an Embassy task, awaiting a timer between rounds as suggested by its
+/// [`Scheduler`].
+///
+/// This is the async counterpart of `Engine::run`: `on_round` is invoked with the result of
+/// every round and returning `false` stops the loop. Schedulers without a time-based wakeup
+/// are polled every `poll_interval`. Install an [`EmbassyClock`](crate::rufi_embedded::clock::EmbassyClock)
+/// on the engine so that programs and scheduler share the same time base.
+// Embassy tasks are polled by a single-threaded executor, so the future need not be `Send`
+#[allow(clippy::future_not_send)]
+pub async fn run<Id, Out, Env, S, Net, Sch, F>(
+    engine: &mut Engine<Id, Out, Env, S, Net, Sch>,
+    poll_interval: Duration,
+    mut on_round: F,
+) where
+    Id: DeviceId,
+    S: Serializer,
+    Net: Network<Id, S>,
+    Sch: Scheduler,
+    F: FnMut(Result<RoundReport<Out>, AggregateError>) -> bool,
+{
+    loop {
+        if let Some(result) = engine.tick(uptime()) {
+            if !on_round(result) {
+                return;
+            }
+        }
+        let after_round = uptime();
+        let wait = engine
+            .scheduler()
+            .next_wakeup(after_round)
+            .map_or(poll_interval, |wakeup| {
+                wakeup.saturating_sub(after_round).min(poll_interval)
+            });
+        // waits longer than the driver can represent are capped to the poll interval
+        let wait = embassy_time::Duration::try_from(wait)
+            .or_else(|_| embassy_time::Duration::try_from(poll_interval))
+            .unwrap_or(embassy_time::Duration::MAX);
+        Timer::after(wait).await;
+    }
+}
@@ -0,0 +1,302 @@
+use crate::rufi_embedded::clock::uptime;
+use crate::rufi_embedded::neighbors::NeighborTable;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::time::Duration;
+use embedded_io::{Error as _, ErrorKind, Read, ReadReady, Write};
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+
+/// Byte delimiting the COBS-encoded frames on the wire.
+const DELIMITER: u8 = 0;
+
+/// Size of the CRC appended to the payload of every frame.
+const CRC_SIZE: usize = 2;
+
+/// Longest run of non-zero bytes of a COBS block.
+const COBS_BLOCK: usize = 254;
+
+/// Configuration of a [`SerialNetwork`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// Largest payload sent or accepted; longer frames are dropped as corrupted.
+    pub max_payload: usize,
+    /// How long the last message of a silent neighbor is retained.
+    pub retention: Duration,
+}
+impl SerialConfig {
+    pub const fn new() -> Self {
+        Self {
+            // fits a 255 bytes LoRa packet once the CRC, the COBS overhead and the delimiter are added
+            max_payload: 250,
+            retention: Duration::from_secs(30),
+        }
+    }
+
+    pub const fn with_max_payload(mut self, max_payload: usize) -> Self {
+        self.max_payload = max_payload;
+        self
+    }
+
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [`Network`] over a byte stream, e.g. the UART of a LoRa modem or of a radio in transparent
+/// mode, exchanging one frame per round.
+///
+/// Every frame carries the payload followed by its CRC-16/CCITT, COBS-encoded and terminated by a
+/// zero byte, so that the receiver resynchronizes on the next frame after noise or a lost byte.
+/// Frames with a wrong CRC or a payload above [`SerialConfig::max_payload`] are dropped.
+///
+/// Only the bytes already available on the port are read at the beginning of every round, so the
+/// port is never waited for; sending blocks until the frame is written.
+pub struct SerialNetwork<Port, Id: Ord + Hash + Copy, S: Serializer> {
+    port: Port,
+    serializer: S,
+    max_payload: usize,
+    neighbors: NeighborTable<Id>,
+    /// Encoded bytes of the frame being received.
+    incoming: Vec<u8>,
+    /// Whether the frame being received exceeded the maximum size and must be dropped.
+    overflow: bool,
+}
+impl<Port, Id, S> SerialNetwork<Port, Id, S>
+where
+    Port: Read + ReadReady + Write,
+    Id: DeviceId,
+    S: Serializer,
+{
+    pub const fn new(port: Port, local_id: Id, config: SerialConfig, serializer: S) -> Self {
+        Self {
+            port,
+            serializer,
+            max_payload: config.max_payload,
+            neighbors: NeighborTable::new(local_id, config.retention),
+            incoming: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Give the port back.
+    pub fn release(self) -> Port {
+        self.port
+    }
+
+    /// Longest COBS encoding of a frame with the maximum payload, delimiter excluded.
+    const fn max_frame(&self) -> usize {
+        let crc_payload = self.max_payload.saturating_add(CRC_SIZE);
+        crc_payload
+            .saturating_add(crc_payload / COBS_BLOCK)
+            .saturating_add(1)
+    }
+
+    fn drain(&mut self, now: Duration) {
+        let mut chunk = [0; 64];
+        while matches!(self.port.read_ready(), Ok(true)) {
+            let Ok(size) = self.port.read(&mut chunk) else {
+                return;
+            };
+            if size == 0 {
+                return;
+            }
+            for &byte in chunk.iter().take(size) {
+                if byte != DELIMITER {
+                    self.overflow |= self.incoming.len() >= self.max_frame();
+                    if !self.overflow {
+                        self.incoming.push(byte);
+                    }
+                    continue;
+                }
+                if !self.overflow {
+                    if let Some(payload) = decode_frame(&self.incoming, self.max_payload) {
+                        self.neighbors.receive(&self.serializer, &payload, now);
+                    }
+                }
+                self.incoming.clear();
+                self.overflow = false;
+            }
+        }
+    }
+}
+impl<Port, Id, S> Network<Id, S> for SerialNetwork<Port, Id, S>
+where
+    Port: Read + ReadReady + Write,
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        if outbound_message.len() > self.max_payload {
+            return Err(NetworkError::TooLarge(outbound_message.len()));
+        }
+        let frame = encode_frame(&outbound_message);
+        self.port
+            .write_all(&frame)
+            .and_then(|()| self.port.flush())
+            .map_err(|err| serial_error(err.kind()))
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        let now = uptime();
+        self.drain(now);
+        self.neighbors.inbound(now)
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings(uptime())
+    }
+}
+
+fn serial_error(kind: ErrorKind) -> NetworkError {
+    if matches!(kind, ErrorKind::TimedOut | ErrorKind::Interrupted) {
+        NetworkError::Congested
+    } else if matches!(
+        kind,
+        ErrorKind::NotConnected | ErrorKind::BrokenPipe | ErrorKind::ConnectionReset
+    ) {
+        NetworkError::Disconnected
+    } else {
+        NetworkError::Other("Serial write failed".into())
+    }
+}
+
+/// Append the CRC to `payload`, COBS-encode it and terminate it with the delimiter.
+fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let crc = crc16(payload).to_be_bytes();
+    let mut frame = Vec::with_capacity(
+        payload
+            .len()
+            .saturating_add(CRC_SIZE)
+            .saturating_add(payload.len() / COBS_BLOCK)
+            .saturating_add(2),
+    );
+    // index of the code byte of the current block, patched once the block ends
+    let mut code_index = 0;
+    frame.push(0);
+    for &byte in payload.iter().chain(&crc) {
+        if byte != DELIMITER {
+            frame.push(byte);
+        }
+        let block = frame.len().saturating_sub(code_index);
+        if byte == DELIMITER || block > COBS_BLOCK {
+            if let Some(code) = frame.get_mut(code_index) {
+                *code = u8::try_from(block).unwrap_or(u8::MAX);
+            }
+            code_index = frame.len();
+            frame.push(0);
+        }
+    }
+    let block = frame.len().saturating_sub(code_index);
+    if let Some(code) = frame.get_mut(code_index) {
+        *code = u8::try_from(block).unwrap_or(u8::MAX);
+    }
+    frame.push(DELIMITER);
+    frame
+}
+
+/// Decode a COBS frame without its delimiter, returning the payload if its CRC matches.
+fn decode_frame(frame: &[u8], max_payload: usize) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(frame.len());
+    let mut rest = frame;
+    while let Some((&code, tail)) = rest.split_first() {
+        let length = usize::from(code).checked_sub(1)?;
+        let block = tail.get(..length)?;
+        decoded.extend_from_slice(block);
+        rest = tail.get(length..)?;
+        if usize::from(code) <= COBS_BLOCK && !rest.is_empty() {
+            decoded.push(DELIMITER);
+        }
+    }
+    let payload_size = decoded.len().checked_sub(CRC_SIZE)?;
+    if payload_size > max_payload {
+        return None;
+    }
+    let crc = decoded.split_off(payload_size);
+    (crc == crc16(&decoded).to_be_bytes()).then_some(decoded)
+}
+
+/// CRC-16/CCITT-FALSE, computed bitwise to avoid a lookup table on small devices.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            }
+        })
+    })
+}
+
+/// [`embedded_io`] view of a host serial port, e.g. a USB LoRa modem opened with
+/// [`serialport::new`].
+#[cfg(feature = "serialport")]
+pub struct SerialPortIo(pub Box<dyn serialport::SerialPort>);
+#[cfg(feature = "serialport")]
+impl embedded_io::ErrorType for SerialPortIo {
+    type Error = std::io::Error;
+}
+#[cfg(feature = "serialport")]
+impl Read for SerialPortIo {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        std::io::Read::read(&mut self.0, buf)
+    }
+}
+#[cfg(feature = "serialport")]
+impl ReadReady for SerialPortIo {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.0.bytes_to_read()? > 0)
+    }
+}
+#[cfg(feature = "serialport")]
+impl Write for SerialPortIo {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        std::io::Write::write(&mut self.0, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        std::io::Write::flush(&mut self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip_through_cobs() {
+        let long_run: Vec<u8> = (0..600).map(|i| u8::try_from(i % 255).unwrap()).collect();
+        for payload in [&[][..], &[0, 0, 1, 0], &[7; 300], &long_run] {
+            let frame = encode_frame(payload);
+            let (delimiter, encoded) = frame.split_last().unwrap();
+            assert_eq!(*delimiter, DELIMITER);
+            assert!(!encoded.contains(&DELIMITER));
+            assert_eq!(decode_frame(encoded, 600).as_deref(), Some(payload));
+            if let Some(limit) = payload.len().checked_sub(1) {
+                assert_eq!(decode_frame(encoded, limit), None);
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted_frames_fail_the_crc() {
+        let mut frame = encode_frame(b"aggregate");
+        frame.pop();
+        if let Some(byte) = frame.get_mut(3) {
+            *byte ^= 0x01;
+        }
+        assert_eq!(decode_frame(&frame, 250), None);
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+}
@@ -0,0 +1,142 @@
+//! Integration tests for the embedded adapter, over an in-memory `embedded-nal` stack and the
+//! Embassy mock time driver.
+
+use core::net::{Ipv4Addr, SocketAddr};
+use core::time::Duration;
+use embassy_time::MockDriver;
+use embedded_nal::{UdpClientStack, UdpFullStack};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use yaair::rufi::aggregate::{Aggregate, AggregateError, VM};
+use yaair::rufi::engine::Engine;
+use yaair::rufi::network::Network;
+use yaair::rufi::scheduler::Periodic;
+use yaair_embedded::rufi_embedded::clock::EmbassyClock;
+use yaair_embedded::rufi_embedded::network::{NalConfig, NalNetwork};
+use yaair_embedded::rufi_embedded::runner::run;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Datagrams in flight, as `(destination, source, payload)`.
+type Medium = Rc<RefCell<VecDeque<(SocketAddr, SocketAddr, Vec<u8>)>>>;
+
+/// Stack of a single host attached to a shared medium; every socket shares the host address.
+struct MemoryStack {
+    address: SocketAddr,
+    medium: Medium,
+}
+impl UdpClientStack for MemoryStack {
+    type UdpSocket = ();
+    type Error = ();
+
+    fn socket(&mut self) -> Result<(), ()> {
+        Ok(())
+    }
+
+    fn connect(&mut self, _socket: &mut (), _remote: SocketAddr) -> Result<(), ()> {
+        Err(())
+    }
+
+    fn send(&mut self, _socket: &mut (), _buffer: &[u8]) -> nb::Result<(), ()> {
+        Err(nb::Error::Other(()))
+    }
+
+    fn receive(
+        &mut self,
+        _socket: &mut (),
+        buffer: &mut [u8],
+    ) -> nb::Result<(usize, SocketAddr), ()> {
+        let mut medium = self.medium.borrow_mut();
+        let position = medium
+            .iter()
+            .position(|(destination, _, _)| *destination == self.address)
+            .ok_or(nb::Error::WouldBlock)?;
+        let (_, source, payload) = medium.remove(position).ok_or(nb::Error::WouldBlock)?;
+        let size = buffer
+            .iter_mut()
+            .zip(&payload)
+            .map(|(slot, byte)| *slot = *byte)
+            .count();
+        Ok((size, source))
+    }
+
+    fn close(&mut self, _socket: ()) -> Result<(), ()> {
+        Ok(())
+    }
+}
+impl UdpFullStack for MemoryStack {
+    fn bind(&mut self, _socket: &mut (), local_port: u16) -> Result<(), ()> {
+        self.address.set_port(local_port);
+        Ok(())
+    }
+
+    fn send_to(
+        &mut self,
+        _socket: &mut (),
+        remote: SocketAddr,
+        buffer: &[u8],
+    ) -> nb::Result<(), ()> {
+        self.medium
+            .borrow_mut()
+            .push_back((remote, self.address, buffer.to_vec()));
+        Ok(())
+    }
+}
+
+fn host(octet: u8, medium: &Medium) -> MemoryStack {
+    MemoryStack {
+        address: SocketAddr::from((Ipv4Addr::new(10, 0, 0, octet), 0)),
+        medium: Rc::clone(medium),
+    }
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // signature imposed by `Engine`
+fn neighbors_count(_env: &(), vm: &mut VM<u32, JsonSerializer>) -> Result<usize, AggregateError> {
+    let id = vm.local_id;
+    vm.neighboring(&id).map(|field| field.size())
+}
+
+#[test]
+fn engines_exchange_datagrams_and_forget_silent_neighbors() {
+    let medium = Medium::default();
+    let target = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 2), 4000));
+    let sender = NalNetwork::bind(
+        host(1, &medium),
+        1u32,
+        NalConfig::new(4000).with_target(target),
+        JsonSerializer,
+    )
+    .unwrap();
+    let mut receiver = NalNetwork::bind(
+        host(2, &medium),
+        2u32,
+        NalConfig::new(4000).with_retention(Duration::from_secs(5)),
+        JsonSerializer,
+    )
+    .unwrap();
+    let mut sender = Engine::new(1u32, sender, (), JsonSerializer, neighbors_count);
+    assert_eq!(sender.cycle().map(|report| report.output), Ok(Ok(1)));
+    assert!(receiver.prepare_inbound().get(&1).is_some());
+    MockDriver::get().advance(embassy_time::Duration::from_secs(6));
+    assert!(receiver.prepare_inbound().get(&1).is_none());
+    assert!(receiver.close().is_ok());
+}
+
+#[test]
+fn runner_awaits_the_scheduler_between_rounds() {
+    let medium = Medium::default();
+    let network =
+        NalNetwork::bind(host(3, &medium), 3u32, NalConfig::new(4000), JsonSerializer).unwrap();
+    let mut engine = Engine::new(3u32, network, (), JsonSerializer, neighbors_count)
+        .with_scheduler(Periodic::new(Duration::from_secs(1)));
+    engine.set_clock(EmbassyClock::new());
+    let mut rounds = 0;
+    embassy_futures::block_on(run(&mut engine, Duration::from_secs(10), |result| {
+        assert_eq!(result.map(|report| report.output), Ok(Ok(1)));
+        rounds += 1;
+        // time only moves forward when the test says so
+        MockDriver::get().advance(embassy_time::Duration::from_secs(1));
+        rounds < 3
+    }));
+    assert_eq!(rounds, 3);
+}
@@ -0,0 +1,101 @@
+//! Integration tests for the serial network, over in-memory byte streams.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::rc::Rc;
+use yaair::rufi::messages::outbound::OutboundMessage;
+use yaair::rufi::messages::path::Path;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair_embedded::rufi_embedded::serial::{SerialConfig, SerialNetwork};
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Bytes written to one end of a serial line and not yet read from the other.
+type Wire = Rc<RefCell<VecDeque<u8>>>;
+
+/// End of a serial line, e.g. the UART connected to a modem.
+struct Port {
+    rx: Wire,
+    tx: Wire,
+}
+impl embedded_io::ErrorType for Port {
+    type Error = Infallible;
+}
+impl embedded_io::Read for Port {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        let mut rx = self.rx.borrow_mut();
+        Ok(buf
+            .iter_mut()
+            .map_while(|slot| rx.pop_front().map(|byte| *slot = byte))
+            .count())
+    }
+}
+impl embedded_io::ReadReady for Port {
+    fn read_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.rx.borrow().is_empty())
+    }
+}
+impl embedded_io::Write for Port {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        self.tx.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+fn message(value: u8) -> OutboundMessage<u32> {
+    let mut message = OutboundMessage::empty(1);
+    message.append(&Path::from("share:0"), vec![value]);
+    message
+}
+
+#[test]
+fn frames_cross_the_line_and_noise_is_skipped() {
+    let (line_a, line_b) = (Wire::default(), Wire::default());
+    let mut modem = SerialNetwork::new(
+        Port {
+            rx: Rc::clone(&line_a),
+            tx: Rc::clone(&line_b),
+        },
+        1u32,
+        SerialConfig::new(),
+        JsonSerializer,
+    );
+    let mut receiver = SerialNetwork::new(
+        Port {
+            rx: Rc::clone(&line_b),
+            tx: Rc::clone(&line_a),
+        },
+        2u32,
+        SerialConfig::new(),
+        JsonSerializer,
+    );
+
+    // noise on the line before the first frame is dropped at the next delimiter
+    line_b.borrow_mut().extend([0x13, 0x37, 0x00]);
+    let first = JsonSerializer.serialize(&message(4)).unwrap();
+    modem.prepare_outbound(first).unwrap();
+    let inbound = receiver.prepare_inbound();
+    let tree = inbound.get(&1).unwrap();
+    assert_eq!(tree.get(&Path::from("share:0")), Some(&[4][..]));
+
+    // a corrupted frame keeps the last message received
+    let second = JsonSerializer.serialize(&message(5)).unwrap();
+    modem.prepare_outbound(second).unwrap();
+    if let Some(byte) = line_b.borrow_mut().get_mut(4) {
+        *byte ^= 0x01;
+    }
+    let kept = receiver.prepare_inbound();
+    let kept_tree = kept.get(&1).unwrap();
+    assert_eq!(kept_tree.get(&Path::from("share:0")), Some(&[4][..]));
+
+    let oversized = vec![1; SerialConfig::new().max_payload + 1];
+    assert_eq!(
+        modem.prepare_outbound(oversized),
+        Err(NetworkError::TooLarge(251))
+    );
+}
@@ -0,0 +1,55 @@
+/*
+ * C interface of the Yaair engines hosted by firmware, see the `c_api` module of `yaair_ffi`.
+ *
+ * Engines are created by constructors exported from the Rust side of the firmware, which build
+ * the aggregate program, and released with yaair_engine_free.
+ */
+#ifndef YAAIR_H
+#define YAAIR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The call succeeded. */
+#define YAAIR_OK 0
+/* No round was due at the given time. */
+#define YAAIR_NOT_DUE 1
+/* A required pointer was null. */
+#define YAAIR_ERR_NULL (-1)
+/* The round failed, see yaair_engine_last_error. */
+#define YAAIR_ERR_ROUND (-2)
+/* The buffer is too small: the required size has been written to `length`. */
+#define YAAIR_ERR_BUFFER_TOO_SMALL (-3)
+
+typedef struct FfiEngine yaair_engine;
+
+/* Queue a message received by the firmware, decoded at the beginning of the next round. */
+int32_t yaair_engine_receive(yaair_engine *engine, const uint8_t *data, size_t length);
+
+/* Execute a round if due at `now_ms`, the time in milliseconds of a monotonic clock. */
+int32_t yaair_engine_tick(yaair_engine *engine, uint64_t now_ms);
+
+/* Copy the message to broadcast and remove it from the engine; `length` is 0 if none. */
+int32_t yaair_engine_outbound(yaair_engine *engine, uint8_t *buffer, size_t capacity,
+                              size_t *length);
+
+/* Copy the serialized result of the last successful round. */
+int32_t yaair_engine_result(yaair_engine *engine, uint8_t *buffer, size_t capacity,
+                            size_t *length);
+
+/* Copy the description of the last failed round, as UTF-8 without a terminator. */
+int32_t yaair_engine_last_error(yaair_engine *engine, uint8_t *buffer, size_t capacity,
+                                size_t *length);
+
+/* Release an engine; NULL is ignored. */
+void yaair_engine_free(yaair_engine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* YAAIR_H */
@@ -0,0 +1,157 @@
+//! `extern "C"` functions driving an [`FfiEngine`], declared for C in `include/yaair.h`.
+//!
+//! Functions return a status code; sizes are written through `length` pointers, so that callers
+//! can retry with a larger buffer when [`YAAIR_ERR_BUFFER_TOO_SMALL`] is returned.
+
+use crate::rufi_ffi::engine::FfiEngine;
+use crate::rufi_ffi::network::copy_into;
+use std::time::Duration;
+
+/// The call succeeded.
+pub const YAAIR_OK: i32 = 0;
+/// No round was due at the given time.
+pub const YAAIR_NOT_DUE: i32 = 1;
+/// A required pointer was null.
+pub const YAAIR_ERR_NULL: i32 = -1;
+/// The round failed, see [`yaair_engine_last_error`].
+pub const YAAIR_ERR_ROUND: i32 = -2;
+/// The buffer is too small: the required size has been written to `length`.
+pub const YAAIR_ERR_BUFFER_TOO_SMALL: i32 = -3;
+
+/// Queue a message received by the firmware, decoded at the beginning of the next round.
+///
+/// # Safety
+/// `engine` must be null or returned by [`FfiEngine::into_raw`] and not freed, and `data` must
+/// point to `length` readable bytes (it may be null if `length` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn yaair_engine_receive(
+    engine: *mut FfiEngine,
+    data: *const u8,
+    length: usize,
+) -> i32 {
+    let (Some(engine), Some(message)) = (engine.as_ref(), input(data, length)) else {
+        return YAAIR_ERR_NULL;
+    };
+    engine.receive(message);
+    YAAIR_OK
+}
+
+/// Execute a round if the scheduler of the engine considers it due at `now_ms`, the time in
+/// milliseconds of a monotonic clock of the firmware.
+///
+/// # Safety
+/// `engine` must be null or returned by [`FfiEngine::into_raw`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn yaair_engine_tick(engine: *mut FfiEngine, now_ms: u64) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return YAAIR_ERR_NULL;
+    };
+    match engine.tick(Duration::from_millis(now_ms)) {
+        None => YAAIR_NOT_DUE,
+        Some(Ok(_)) => YAAIR_OK,
+        Some(Err(_)) => YAAIR_ERR_ROUND,
+    }
+}
+
+/// Copy the message to broadcast into `buffer` and remove it from the engine; `length` is set to
+/// 0 if no round produced a message since the last call.
+///
+/// # Safety
+/// `engine` must be null or returned by [`FfiEngine::into_raw`] and not freed, `buffer` must point
+/// to `capacity` writable bytes (it may be null if `capacity` is 0), and `length` must be null or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn yaair_engine_outbound(
+    engine: *mut FfiEngine,
+    buffer: *mut u8,
+    capacity: usize,
+    length: *mut usize,
+) -> i32 {
+    let (Some(engine), Some(buffer), Some(length)) =
+        (engine.as_ref(), output(buffer, capacity), length.as_mut())
+    else {
+        return YAAIR_ERR_NULL;
+    };
+    status(engine.take_outbound(buffer), length)
+}
+
+/// Copy the serialized result of the last successful round into `buffer`.
+///
+/// # Safety
+/// Same as [`yaair_engine_outbound`].
+#[no_mangle]
+pub unsafe extern "C" fn yaair_engine_result(
+    engine: *mut FfiEngine,
+    buffer: *mut u8,
+    capacity: usize,
+    length: *mut usize,
+) -> i32 {
+    let (Some(engine), Some(buffer), Some(length)) =
+        (engine.as_ref(), output(buffer, capacity), length.as_mut())
+    else {
+        return YAAIR_ERR_NULL;
+    };
+    status(engine.copy_result(buffer), length)
+}
+
+/// Copy the description of the last failed round into `buffer`, as UTF-8 without a terminator.
+///
+/// # Safety
+/// Same as [`yaair_engine_outbound`].
+#[no_mangle]
+pub unsafe extern "C" fn yaair_engine_last_error(
+    engine: *mut FfiEngine,
+    buffer: *mut u8,
+    capacity: usize,
+    length: *mut usize,
+) -> i32 {
+    let (Some(engine), Some(buffer), Some(length)) =
+        (engine.as_ref(), output(buffer, capacity), length.as_mut())
+    else {
+        return YAAIR_ERR_NULL;
+    };
+    status(copy_into(engine.last_error().as_bytes(), buffer), length)
+}
+
+/// Release an engine; null is ignored.
+///
+/// # Safety
+/// `engine` must be null or returned by [`FfiEngine::into_raw`] and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn yaair_engine_free(engine: *mut FfiEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Bytes passed by C, `None` if the pointer is null but the length is not 0.
+const unsafe fn input<'a>(data: *const u8, length: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), length) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(std::slice::from_raw_parts(data, length)),
+    }
+}
+
+/// Buffer provided by C, `None` if the pointer is null but the capacity is not 0.
+const unsafe fn output<'a>(buffer: *mut u8, capacity: usize) -> Option<&'a mut [u8]> {
+    match (buffer.is_null(), capacity) {
+        (_, 0) => Some(&mut []),
+        (true, _) => None,
+        (false, _) => Some(std::slice::from_raw_parts_mut(buffer, capacity)),
+    }
+}
+
+/// Write the copied or required size to `length`, returning the matching status.
+const fn status(copied: Result<usize, usize>, length: &mut usize) -> i32 {
+    match copied {
+        Ok(size) => {
+            *length = size;
+            YAAIR_OK
+        }
+        Err(required) => {
+            *length = required;
+            YAAIR_ERR_BUFFER_TOO_SMALL
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::rufi_ffi::network::{copy_into, Mailbox};
+use serde::Serialize;
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::engine::Engine;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::Network;
+use yaair::rufi::scheduler::Scheduler;
+
+/// A round of the wrapped engine if due at the given time, with its result serialized.
+type Round = Box<dyn FnMut(Duration) -> Option<Result<Vec<u8>, String>>>;
+
+/// Handle to an [`Engine`] hosted by C firmware, see [`c_api`](crate::rufi_ffi::c_api).
+///
+/// Aggregate programs are Rust functions, so the engine is built on the Rust side of the firmware
+/// (a static library depending on this crate) and handed to C through an exported constructor:
+///
+/// ```ignore
+/// #[no_mangle]
+/// pub extern "C" fn gradient_engine(id: u32) -> *mut FfiEngine {
+///     let mailbox = Mailbox::new();
+///     let network = FfiNetwork::new(mailbox.clone(), id, PostcardSerializer);
+///     let engine = Engine::new(id, network, (), PostcardSerializer, gradient);
+///     FfiEngine::new(engine, mailbox, PostcardSerializer).into_raw()
+/// }
+/// ```
+pub struct FfiEngine {
+    round: Round,
+    mailbox: Mailbox,
+    result: Vec<u8>,
+    error: String,
+}
+impl FfiEngine {
+    /// Wrap `engine`, exchanging messages through `mailbox` (shared with its network) and
+    /// serializing the result of every round with `serializer`.
+    pub fn new<Id, Out, Env, S, Net, Sch, R>(
+        mut engine: Engine<Id, Out, Env, S, Net, Sch>,
+        mailbox: Mailbox,
+        serializer: R,
+    ) -> Self
+    where
+        Id: DeviceId + 'static,
+        Out: Serialize + 'static,
+        Env: 'static,
+        S: Serializer + 'static,
+        Net: Network<Id, S> + 'static,
+        Sch: Scheduler + 'static,
+        R: Serializer + 'static,
+    {
+        Self {
+            round: Box::new(move |now| {
+                let report = match engine.tick(now)? {
+                    Ok(report) => report,
+                    Err(err) => return Some(Err(err.to_string())),
+                };
+                Some(
+                    serializer
+                        .serialize(&report.output)
+                        .map_err(|err| err.to_string()),
+                )
+            }),
+            mailbox,
+            result: Vec::new(),
+            error: String::new(),
+        }
+    }
+
+    /// Move the handle to the heap, for C to release it with
+    /// [`yaair_engine_free`](crate::rufi_ffi::c_api::yaair_engine_free).
+    pub fn into_raw(self) -> *mut Self {
+        Box::into_raw(Box::new(self))
+    }
+
+    /// Queue a message received by the firmware.
+    pub fn receive(&self, message: &[u8]) {
+        self.mailbox.deliver(message);
+    }
+
+    /// Execute a round if the scheduler considers it due at `now`.
+    ///
+    /// # Returns
+    /// `None` if no round was due, the serialized result or the description of the error
+    /// otherwise
+    pub fn tick(&mut self, now: Duration) -> Option<Result<&[u8], &str>> {
+        Some(match (self.round)(now)? {
+            Ok(result) => {
+                self.result = result;
+                Ok(&self.result)
+            }
+            Err(error) => {
+                self.error = error;
+                Err(&self.error)
+            }
+        })
+    }
+
+    /// Copy the message to broadcast into `buffer`, see [`Mailbox::take_outbound`].
+    pub fn take_outbound(&self, buffer: &mut [u8]) -> Result<usize, usize> {
+        self.mailbox.take_outbound(buffer)
+    }
+
+    /// Serialized result of the last successful round, empty before the first one.
+    pub fn result(&self) -> &[u8] {
+        &self.result
+    }
+
+    /// Description of the last failed round, empty if none failed.
+    pub fn last_error(&self) -> &str {
+        &self.error
+    }
+
+    /// Copy the serialized result of the last successful round into `buffer`.
+    ///
+    /// # Returns
+    /// The size of the result, or `Err` with the required size if `buffer` is too small
+    pub fn copy_result(&self, buffer: &mut [u8]) -> Result<usize, usize> {
+        copy_into(&self.result, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi_ffi::network::FfiNetwork;
+    use yaair::rufi::aggregate::{Aggregate, VM};
+    use yaair_serde::rufi_serde::json::JsonSerializer;
+
+    #[test]
+    fn rounds_serialize_results_and_fill_the_mailbox() {
+        let mailbox = Mailbox::new();
+        let network = FfiNetwork::new(mailbox.clone(), 1u32, JsonSerializer);
+        let engine = Engine::new(
+            1u32,
+            network,
+            (),
+            JsonSerializer,
+            |_env, vm: &mut VM<u32, _>| vm.repeat(&0u64, |count, _| count.saturating_add(1)),
+        );
+        let mut engine = FfiEngine::new(engine, mailbox, JsonSerializer);
+        assert!(engine.result().is_empty());
+        assert_eq!(engine.tick(Duration::ZERO), Some(Ok(&b"1"[..])));
+        assert_eq!(engine.result(), b"1");
+        assert_eq!(engine.tick(Duration::ZERO), None);
+
+        let mut small = [0u8; 1];
+        let Err(required) = engine.take_outbound(&mut small) else {
+            panic!("the outbound message should not fit");
+        };
+        let mut buffer = vec![0u8; required];
+        assert_eq!(engine.take_outbound(&mut buffer), Ok(required));
+        assert_eq!(engine.take_outbound(&mut buffer), Ok(0));
+    }
+}
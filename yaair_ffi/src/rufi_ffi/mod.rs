@@ -0,0 +1,3 @@
+pub mod c_api;
+pub mod engine;
+pub mod network;
@@ -0,0 +1,117 @@
+use std::cell::RefCell;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+use yaair_net::rufi_net::neighbors::NeighborTable;
+
+/// Messages received by the firmware and not yet decoded, and the last message to broadcast.
+#[derive(Default)]
+struct Queues {
+    inbound: Vec<Vec<u8>>,
+    outbound: Option<Vec<u8>>,
+}
+
+/// Messages exchanged with the firmware, shared by an [`FfiNetwork`] and the
+/// [`FfiEngine`](crate::rufi_ffi::engine::FfiEngine) driving its engine.
+#[derive(Clone, Default)]
+pub struct Mailbox(Rc<RefCell<Queues>>);
+impl Mailbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a message received by the firmware, decoded at the beginning of the next round.
+    pub fn deliver(&self, message: &[u8]) {
+        self.0.borrow_mut().inbound.push(message.to_vec());
+    }
+
+    /// Copy the message to broadcast into `buffer`, removing it from the mailbox.
+    ///
+    /// # Returns
+    /// The size of the message, `0` if no round produced one since the last call, or `Err` with
+    /// the required size if `buffer` is too small, leaving the message in place
+    pub fn take_outbound(&self, buffer: &mut [u8]) -> Result<usize, usize> {
+        let mut queues = self.0.borrow_mut();
+        let Some(message) = queues.outbound.as_deref() else {
+            return Ok(0);
+        };
+        let size = copy_into(message, buffer)?;
+        queues.outbound = None;
+        Ok(size)
+    }
+}
+
+/// Copy `bytes` at the beginning of `buffer`.
+///
+/// # Returns
+/// The number of copied bytes, or `Err` with the required size if `buffer` is too small
+pub(crate) fn copy_into(bytes: &[u8], buffer: &mut [u8]) -> Result<usize, usize> {
+    buffer
+        .get_mut(..bytes.len())
+        .ok_or(bytes.len())?
+        .copy_from_slice(bytes);
+    Ok(bytes.len())
+}
+
+/// [`Network`] handing messages to the firmware through a [`Mailbox`], for radios and buses
+/// driven by C code.
+///
+/// The firmware broadcasts the outbound message of every round as-is, and delivers every message
+/// it receives; the messages of silent neighbors are retained for 5 seconds unless configured
+/// otherwise.
+pub struct FfiNetwork<Id: Ord + Hash + Copy, S: Serializer> {
+    mailbox: Mailbox,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+}
+impl<Id, S> FfiNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    pub fn new(mailbox: Mailbox, local_id: Id, serializer: S) -> Self {
+        Self {
+            mailbox,
+            serializer,
+            neighbors: NeighborTable::new(local_id, Duration::from_secs(5)),
+        }
+    }
+
+    /// How long the last message of a silent neighbor is retained.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.neighbors.set_retention(retention);
+        self
+    }
+}
+impl<Id, S> Network<Id, S> for FfiNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        self.mailbox.0.borrow_mut().outbound = Some(outbound_message);
+        Ok(())
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        let received = std::mem::take(&mut self.mailbox.0.borrow_mut().inbound);
+        for message in received {
+            self.neighbors.receive(&self.serializer, &message);
+        }
+        self.neighbors.inbound()
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        self.neighbors.has_pending() || !self.mailbox.0.borrow().inbound.is_empty()
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
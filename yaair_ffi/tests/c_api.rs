@@ -0,0 +1,64 @@
+//! Integration tests driving engines through the C interface, as firmware would.
+
+use yaair::rufi::aggregate::{Aggregate, VM};
+use yaair::rufi::engine::Engine;
+use yaair_ffi::rufi_ffi::c_api::{
+    yaair_engine_free, yaair_engine_outbound, yaair_engine_receive, yaair_engine_result,
+    yaair_engine_tick, YAAIR_ERR_BUFFER_TOO_SMALL, YAAIR_ERR_NULL, YAAIR_NOT_DUE, YAAIR_OK,
+};
+use yaair_ffi::rufi_ffi::engine::FfiEngine;
+use yaair_ffi::rufi_ffi::network::{FfiNetwork, Mailbox};
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Constructor exported by the Rust side of the firmware.
+extern "C" fn neighbors_engine(id: u32) -> *mut FfiEngine {
+    let mailbox = Mailbox::new();
+    let network = FfiNetwork::new(mailbox.clone(), id, JsonSerializer);
+    let engine = Engine::new(
+        id,
+        network,
+        (),
+        JsonSerializer,
+        |_env, vm: &mut VM<u32, _>| {
+            let local_id = vm.local_id;
+            vm.neighboring(&local_id).map_or(0, |field| field.size())
+        },
+    );
+    FfiEngine::new(engine, mailbox, JsonSerializer).into_raw()
+}
+
+#[test]
+fn firmware_exchanges_messages_through_buffers() {
+    let (first, second) = (neighbors_engine(1), neighbors_engine(2));
+    let mut buffer = [0u8; 512];
+    let mut length = 0usize;
+    unsafe {
+        assert_eq!(yaair_engine_tick(first, 0), YAAIR_OK);
+        assert_eq!(yaair_engine_tick(first, 0), YAAIR_NOT_DUE);
+        assert_eq!(
+            yaair_engine_outbound(first, buffer.as_mut_ptr(), 1, &raw mut length),
+            YAAIR_ERR_BUFFER_TOO_SMALL
+        );
+        assert_eq!(
+            yaair_engine_outbound(first, buffer.as_mut_ptr(), buffer.len(), &raw mut length),
+            YAAIR_OK
+        );
+        assert_eq!(
+            yaair_engine_receive(second, buffer.as_ptr(), length),
+            YAAIR_OK
+        );
+
+        assert_eq!(yaair_engine_tick(second, 0), YAAIR_OK);
+        assert_eq!(
+            yaair_engine_result(second, buffer.as_mut_ptr(), buffer.len(), &raw mut length),
+            YAAIR_OK
+        );
+        assert_eq!(buffer.get(..length), Some(&b"2"[..]));
+        assert_eq!(
+            yaair_engine_receive(core::ptr::null_mut(), buffer.as_ptr(), 0),
+            YAAIR_ERR_NULL
+        );
+        yaair_engine_free(first);
+        yaair_engine_free(second);
+    }
+}
@@ -0,0 +1,65 @@
+// syn 2 is still used by the macros of the ecosystem, serde moved to syn 3
+#![allow(clippy::multiple_crate_versions)]
+
+mod rufi_macros;
+
+use proc_macro::TokenStream;
+
+/// Aggregate programs written without threading the VM by hand.
+///
+/// Every function of the block takes the VM as an implicit first parameter named `vm`, generic
+/// over the device id `Id` and the serializer `S` unless the block starts with
+/// `type Vm = VM<...>;`. In the bodies:
+/// - operators are called without receiver, e.g. `neighboring(&value)?`, and the closures given
+///   to them omit the VM parameter, e.g. `share(&0.0, |field| ...)`;
+/// - calls to the other functions of the block are aligned under their name, so that the
+///   operators of different calls do not interfere;
+/// - `if` expressions become `branch`es, so that neighbors only align with the devices that took
+///   the same branch; a branch propagating errors with `?` must use [`AggregateError`], and
+///   cannot `return`.
+///
+/// Other closures and macros are left as plain Rust, where the VM can still be used as `vm`.
+///
+/// ```ignore
+/// aggregate! {
+///     type Vm = VM<u32, JsonSerializer>;
+///
+///     fn gradient(source: bool) -> Result<f64, AggregateError> {
+///         share(&f64::INFINITY, |distances| {
+///             let through = distances.aligned_map(&nbr_range(), |distance, range| distance + range);
+///             if source { 0.0 } else { *through.partial_min() }
+///         })
+///     }
+/// }
+/// ```
+///
+/// [`AggregateError`]: https://docs.rs/yaair/latest/yaair/rufi/aggregate/enum.AggregateError.html
+#[proc_macro]
+pub fn aggregate(input: TokenStream) -> TokenStream {
+    syn::parse_macro_input!(input as rufi_macros::program::Programs)
+        .expand()
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Check that a composite value can be exchanged with neighbors through `neighboring` and
+/// `share`.
+///
+/// Values are exportable when they implement `Serialize`, `Deserialize` and `Clone` without
+/// borrowing; the derive reports the fields breaking these bounds where the type is declared, and
+/// requires the type parameters to be exportable too. Nothing is implemented: the derives of
+/// serde and `Clone` are still needed.
+///
+/// ```ignore
+/// #[derive(Clone, Serialize, Deserialize, Exportable)]
+/// struct Reading {
+///     range: f64,
+///     position: (f64, f64),
+/// }
+/// ```
+#[proc_macro_derive(Exportable)]
+pub fn derive_exportable(input: TokenStream) -> TokenStream {
+    rufi_macros::exportable::derive(syn::parse_macro_input!(input as syn::DeriveInput))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
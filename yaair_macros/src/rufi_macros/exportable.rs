@@ -0,0 +1,92 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_quote, Data, DeriveInput, GenericParam, WherePredicate};
+
+/// Assertions that `input` and the types of all its fields are exportable, failing at the field
+/// that cannot be exchanged with neighbors.
+///
+/// # Errors
+/// Returns an error for unions and for types borrowing data, which can never be exportable
+pub fn derive(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields: Vec<_> = match &input.data {
+        Data::Struct(data) => data.fields.iter().collect(),
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .flat_map(|variant| &variant.fields)
+            .collect(),
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "unions cannot be exchanged with neighbors",
+            ))
+        }
+    };
+    let field_assertions: Vec<_> = fields
+        .into_iter()
+        .map(|field| {
+            let ty = &field.ty;
+            quote_spanned!(ty.span()=> assert_exportable::<#ty>();)
+        })
+        .collect();
+    let exportable = quote!(::yaair::rufi::data::exportable::Exportable);
+    let mut bounds: Vec<WherePredicate> = Vec::new();
+    for param in &input.generics.params {
+        match param {
+            GenericParam::Lifetime(lifetime) => {
+                return Err(syn::Error::new_spanned(
+                    lifetime,
+                    "values exchanged with neighbors cannot borrow: own the data instead",
+                ))
+            }
+            GenericParam::Type(param) => {
+                let ident = &param.ident;
+                bounds.push(parse_quote!(#ident: #exportable));
+            }
+            GenericParam::Const(_) => {}
+        }
+    }
+    input.generics.make_where_clause().predicates.extend(bounds);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        const _: () = {
+            #[allow(clippy::extra_unused_type_parameters)]
+            fn assert_exportable<V: #exportable>() {}
+
+            #[allow(dead_code)]
+            fn assert_fields #impl_generics () #where_clause {
+                #(#field_assertions)*
+                assert_exportable::<#name #ty_generics>();
+            }
+        };
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_field_is_checked() {
+        let input = parse_quote! {
+            enum Reading<T> { Missing, Range(f64), Sample { value: T, at: u64 } }
+        };
+        let expanded = derive(input).unwrap().to_string();
+        for ty in ["f64", "T", "u64"] {
+            assert!(expanded.contains(&format!("assert_exportable :: < {ty} > ()")));
+        }
+        assert!(expanded.contains("where T : :: yaair :: rufi :: data :: exportable :: Exportable"));
+        assert!(expanded.contains("assert_exportable :: < Reading < T > > ()"));
+    }
+
+    #[test]
+    fn borrowing_types_are_rejected() {
+        assert!(derive(parse_quote!(
+            struct Name<'a>(&'a str);
+        ))
+        .is_err());
+        assert!(derive(parse_quote!(union Bits { int: u32, float: f32 })).is_err());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod exportable;
+pub mod program;
+pub mod rewrite;
@@ -0,0 +1,183 @@
+use crate::rufi_macros::rewrite::Rewriter;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use std::collections::BTreeSet;
+use syn::parse::{Parse, ParseStream};
+use syn::visit_mut::VisitMut;
+use syn::{parse_quote, FnArg, Ident, Item, ItemFn, Pat, Type};
+
+/// Functions of an `aggregate!` block, with the VM type they share.
+pub struct Programs {
+    /// Type set with `type Vm = ...;`, `None` for functions generic over the VM.
+    vm_type: Option<Type>,
+    functions: Vec<ItemFn>,
+}
+impl Parse for Programs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut vm_type = None;
+        let mut functions = Vec::new();
+        while !input.is_empty() {
+            let item = input.parse::<Item>()?;
+            let unexpected = |tokens: &dyn ToTokens| {
+                syn::Error::new_spanned(
+                    tokens,
+                    "expected a function, or `type Vm = ...;` before the functions",
+                )
+            };
+            if let Item::Fn(function) = item {
+                functions.push(function);
+            } else if let Item::Type(alias) = item {
+                if alias.ident != "Vm" || vm_type.is_some() {
+                    return Err(unexpected(&alias));
+                }
+                if !functions.is_empty() {
+                    return Err(syn::Error::new_spanned(
+                        alias,
+                        "`type Vm` must come before the functions",
+                    ));
+                }
+                vm_type = Some(*alias.ty);
+            } else {
+                return Err(unexpected(&item));
+            }
+        }
+        Ok(Self { vm_type, functions })
+    }
+}
+impl Programs {
+    /// Rust functions taking the VM as their first parameter.
+    ///
+    /// # Errors
+    /// Returns the errors of all the functions that cannot be expanded, combined
+    pub fn expand(self) -> syn::Result<TokenStream> {
+        let names: BTreeSet<String> = self
+            .functions
+            .iter()
+            .map(|function| function.sig.ident.to_string())
+            .collect();
+        let mut rewriter = Rewriter::new(&names);
+        let mut errors: Vec<syn::Error> = Vec::new();
+        let functions: Vec<ItemFn> = self
+            .functions
+            .into_iter()
+            .filter_map(|function| {
+                expand_function(function, self.vm_type.as_ref(), &mut rewriter)
+                    .map_err(|err| errors.push(err))
+                    .ok()
+            })
+            .collect();
+        errors.extend(rewriter.into_errors());
+        if let Some(combined) = errors.into_iter().reduce(|mut combined, err| {
+            combined.combine(err);
+            combined
+        }) {
+            return Err(combined);
+        }
+        Ok(quote!(#(#functions)*))
+    }
+}
+
+fn expand_function(
+    mut function: ItemFn,
+    vm_type: Option<&Type>,
+    rewriter: &mut Rewriter<'_>,
+) -> syn::Result<ItemFn> {
+    let signature = &mut function.sig;
+    if let Some(asyncness) = signature.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "aggregate programs cannot be async",
+        ));
+    }
+    for input in &signature.inputs {
+        let FnArg::Typed(typed) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "aggregate programs cannot take `self`",
+            ));
+        };
+        if matches!(&*typed.pat, Pat::Ident(binding) if binding.ident == "vm") {
+            return Err(syn::Error::new_spanned(
+                &typed.pat,
+                "`vm` is the implicit first parameter of aggregate programs",
+            ));
+        }
+    }
+    let vm = Ident::new("vm", Span::call_site());
+    let vm_type: Type = if let Some(vm_type) = vm_type {
+        vm_type.clone()
+    } else {
+        signature.generics.params.push(parse_quote!(Id));
+        signature.generics.params.push(parse_quote!(S));
+        let where_clause = signature.generics.make_where_clause();
+        where_clause
+            .predicates
+            .push(parse_quote!(Id: ::yaair::rufi::device::DeviceId));
+        where_clause
+            .predicates
+            .push(parse_quote!(S: ::yaair::rufi::messages::serializer::Serializer));
+        parse_quote!(::yaair::rufi::aggregate::VM<Id, S>)
+    };
+    signature.inputs.insert(
+        0,
+        parse_quote!(#[allow(unused_variables)] #vm: &mut #vm_type),
+    );
+    rewriter.visit_block_mut(&mut function.block);
+    let imports = parse_quote! {{
+        #[allow(unused_imports)]
+        use ::yaair::rufi::aggregate::Aggregate as _;
+        #[allow(unused_imports)]
+        use ::yaair::rufi::sensors::neighborhood::NeighborhoodSensors as _;
+        #[allow(unused_imports)]
+        use ::yaair::rufi::time::TimeSensor as _;
+    }};
+    let syn::Block { stmts, .. } = imports;
+    function.block.stmts.splice(0..0, stmts);
+    Ok(function)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn functions_take_the_vm_first() {
+        let programs: Programs = syn::parse2(quote! {
+            type Vm = VM<u32, JsonSerializer>;
+            pub fn hops(source: bool) -> u32 { 0 }
+            fn generic() {}
+        })
+        .unwrap();
+        let expanded = programs.expand().unwrap().to_string();
+        assert!(expanded.contains("pub fn hops (# [allow (unused_variables)] vm : & mut VM < u32 , JsonSerializer > , source : bool)"));
+        assert!(expanded.contains(
+            "fn generic (# [allow (unused_variables)] vm : & mut VM < u32 , JsonSerializer >)"
+        ));
+
+        let generic: Programs = syn::parse2(quote!(
+            fn generic() {}
+        ))
+        .unwrap();
+        let generic = generic.expand().unwrap().to_string();
+        assert!(generic.contains("fn generic < Id , S >"));
+        assert!(generic.contains("where Id : :: yaair :: rufi :: device :: DeviceId"));
+    }
+
+    #[test]
+    fn unsupported_items_are_rejected() {
+        assert!(syn::parse2::<Programs>(quote!(
+            struct Nope;
+        ))
+        .is_err());
+        let with_vm: Programs = syn::parse2(quote!(
+            fn f(vm: u32) {}
+        ))
+        .unwrap();
+        assert!(with_vm.expand().is_err());
+        let asynchronous: Programs = syn::parse2(quote!(
+            async fn f() {}
+        ))
+        .unwrap();
+        assert!(asynchronous.expand().is_err());
+    }
+}
@@ -0,0 +1,402 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use std::collections::BTreeSet;
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, Expr, ExprCall, ExprIf, Ident, Item, Pat};
+
+/// Where the VM goes among the parameters of the closures given to an operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VmParameter {
+    /// `FnOnce(&mut VM, ...)`, like the evolution of `share` or the bodies of `branch`.
+    First,
+    /// `FnOnce(..., &mut VM)`, like the evolution of `repeat`.
+    Last,
+}
+
+/// Operator called without receiver in an aggregate program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Method(VmParameter),
+    LocalId,
+}
+
+fn operator(name: &str) -> Option<Operator> {
+    match name {
+        "repeat" | "repeat_keyed" => Some(Operator::Method(VmParameter::Last)),
+        "neighboring"
+        | "neighboring_owned"
+        | "neighboring_keyed"
+        | "neighboring_with_priority"
+        | "neighboring_with_hop_limit"
+        | "share"
+        | "share_owned"
+        | "share_keyed"
+        | "share_inspect"
+        | "branch"
+        | "branch_keyed"
+        | "namespace"
+        | "align_on_value"
+        | "aligned"
+        | "cached"
+        | "incremental"
+        | "neighbor_count"
+        | "aligned_devices"
+        | "is_isolated"
+        | "nbr_range"
+        | "nbr_lag"
+        | "nbr_vector"
+        | "nbr_timestamp"
+        | "nbr_sequence"
+        | "nbr_position"
+        | "nbr_hops"
+        | "current_time" => Some(Operator::Method(VmParameter::First)),
+        "local_id" => Some(Operator::LocalId),
+        _ => None,
+    }
+}
+
+/// Rewrites the bodies of aggregate programs, threading the VM named `vm`.
+///
+/// Closures not given to an operator and nested items are plain Rust and left untouched.
+pub struct Rewriter<'a> {
+    /// Functions of the `aggregate!` block, whose calls are aligned.
+    programs: &'a BTreeSet<String>,
+    errors: Vec<syn::Error>,
+}
+impl<'a> Rewriter<'a> {
+    pub const fn new(programs: &'a BTreeSet<String>) -> Self {
+        Self {
+            programs,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Errors met while rewriting, e.g. a `return` inside a branch.
+    pub fn into_errors(self) -> Vec<syn::Error> {
+        self.errors
+    }
+
+    fn vm() -> Ident {
+        Ident::new("vm", Span::call_site())
+    }
+
+    /// Evaluate the arguments of `call` in order, then invoke `invoke` with them; closures are
+    /// kept inline, with the VM added to their parameters, so that their types are inferred.
+    fn hoist_arguments(
+        &mut self,
+        call: &mut ExprCall,
+        parameter: VmParameter,
+        invoke: impl FnOnce(Vec<TokenStream>) -> TokenStream,
+    ) -> Expr {
+        let vm = Self::vm();
+        let mut bindings = Vec::new();
+        let mut arguments = Vec::new();
+        for (index, argument) in call.args.iter_mut().enumerate() {
+            if let Expr::Closure(closure) = argument {
+                let vm_pattern: Pat = parse_quote!(#vm);
+                match parameter {
+                    VmParameter::First => closure.inputs.insert(0, vm_pattern),
+                    VmParameter::Last => closure.inputs.push(vm_pattern),
+                }
+                self.visit_expr_mut(&mut closure.body);
+                arguments.push(closure.to_token_stream());
+            } else {
+                self.visit_expr_mut(argument);
+                let binding =
+                    format_ident!("__yaair_argument_{}", index, span = Span::mixed_site());
+                bindings.push(quote!(let #binding = #argument;));
+                arguments.push(binding.to_token_stream());
+            }
+        }
+        let invocation = invoke(arguments);
+        parse_quote!({ #(#bindings)* #invocation })
+    }
+
+    /// Rewrite a call to an operator or to a function of the block, `None` for other calls.
+    fn rewrite_call(&mut self, call: &mut ExprCall) -> Option<Expr> {
+        let Expr::Path(function) = &*call.func else {
+            return None;
+        };
+        if function.qself.is_some() {
+            return None;
+        }
+        let name = function.path.get_ident()?.clone();
+        let vm = Self::vm();
+        if self.programs.contains(&name.to_string()) {
+            let token = name.to_string();
+            return Some(self.hoist_arguments(
+                call,
+                VmParameter::First,
+                |arguments| quote!(#vm.aligned(#token, move |#vm| #name(#vm, #(#arguments),*))),
+            ));
+        }
+        match operator(&name.to_string())? {
+            Operator::LocalId if call.args.is_empty() => Some(parse_quote!(#vm.local_id)),
+            Operator::LocalId => None,
+            Operator::Method(parameter) => Some(self.hoist_arguments(
+                call,
+                parameter,
+                |arguments| quote!(#vm.#name(#(#arguments),*)),
+            )),
+        }
+    }
+
+    /// Turn `if` into a `branch`, propagating the errors raised with `?` inside it.
+    fn rewrite_if(&mut self, mut expr_if: ExprIf) -> Expr {
+        self.visit_expr_mut(&mut expr_if.cond);
+        self.visit_block_mut(&mut expr_if.then_branch);
+        let mut otherwise: Expr = match expr_if.else_branch.take() {
+            Some((_, otherwise)) => *otherwise,
+            None => parse_quote!({}),
+        };
+        self.visit_expr_mut(&mut otherwise);
+        let then_branch = Expr::Block(syn::ExprBlock {
+            attrs: Vec::new(),
+            label: None,
+            block: expr_if.then_branch,
+        });
+        let mut escapes = Escapes::default();
+        escapes.visit_expr(&then_branch);
+        escapes.visit_expr(&otherwise);
+        if let Some(escape) = escapes.escape {
+            self.errors.push(syn::Error::new_spanned(
+                escape,
+                "an `if` of an aggregate program becomes a `branch`, which cannot be left with \
+                 `return`, `break` or `continue`: compute the result of the branch instead",
+            ));
+        }
+        let vm = Self::vm();
+        let condition = format_ident!("__yaair_condition", span = Span::mixed_site());
+        let cond = &expr_if.cond;
+        if escapes.fallible {
+            let ok =
+                quote!(::core::result::Result::<_, ::yaair::rufi::aggregate::AggregateError>::Ok);
+            parse_quote!({
+                let #condition = #cond;
+                #vm.branch(#condition, |#vm| #ok(#then_branch), |#vm| #ok(#otherwise))?
+            })
+        } else {
+            parse_quote!({
+                let #condition = #cond;
+                #vm.branch(#condition, |#vm| #then_branch, |#vm| #otherwise)
+            })
+        }
+    }
+}
+impl VisitMut for Rewriter<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let rewritten = if let Expr::Closure(_) = expr {
+            return;
+        } else if let Expr::Call(call) = expr {
+            self.rewrite_call(call)
+        } else if let Expr::If(expr_if) = expr {
+            (!binds_patterns(&expr_if.cond)).then(|| self.rewrite_if(expr_if.clone()))
+        } else {
+            None
+        };
+        match rewritten {
+            Some(rewritten) => *expr = rewritten,
+            None => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _item: &mut Item) {}
+}
+
+/// Whether `condition` is an `if let`, whose branches are kept as plain Rust.
+fn binds_patterns(condition: &Expr) -> bool {
+    #[derive(Default)]
+    struct Lets(bool);
+    impl Visit<'_> for Lets {
+        fn visit_expr_let(&mut self, _: &syn::ExprLet) {
+            self.0 = true;
+        }
+    }
+    let mut lets = Lets::default();
+    lets.visit_expr(condition);
+    lets.0
+}
+
+/// What the body of a branch does beyond computing its value, outside nested closures.
+#[derive(Default)]
+struct Escapes {
+    /// Whether errors are propagated with `?`.
+    fallible: bool,
+    /// A `return`, or a `break` or `continue` leaving the branch.
+    escape: Option<TokenStream>,
+    /// Loops entered, which `break` and `continue` can leave.
+    loops: usize,
+}
+impl Escapes {
+    fn in_loop(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.loops = self.loops.saturating_add(1);
+        visit(self);
+        self.loops = self.loops.saturating_sub(1);
+    }
+
+    fn escape(&mut self, tokens: &impl ToTokens) {
+        if self.escape.is_none() {
+            self.escape = Some(tokens.to_token_stream());
+        }
+    }
+}
+impl Visit<'_> for Escapes {
+    fn visit_expr_try(&mut self, expr: &syn::ExprTry) {
+        self.fallible = true;
+        visit::visit_expr_try(self, expr);
+    }
+
+    fn visit_expr_return(&mut self, expr: &syn::ExprReturn) {
+        self.escape(expr);
+    }
+
+    fn visit_expr_break(&mut self, expr: &syn::ExprBreak) {
+        if self.loops == 0 {
+            self.escape(expr);
+        }
+    }
+
+    fn visit_expr_continue(&mut self, expr: &syn::ExprContinue) {
+        if self.loops == 0 {
+            self.escape(expr);
+        }
+    }
+
+    fn visit_expr_for_loop(&mut self, expr: &syn::ExprForLoop) {
+        self.visit_expr(&expr.expr);
+        self.in_loop(|escapes| escapes.visit_block(&expr.body));
+    }
+
+    fn visit_expr_while(&mut self, expr: &syn::ExprWhile) {
+        self.visit_expr(&expr.cond);
+        self.in_loop(|escapes| escapes.visit_block(&expr.body));
+    }
+
+    fn visit_expr_loop(&mut self, expr: &syn::ExprLoop) {
+        self.in_loop(|escapes| escapes.visit_block(&expr.body));
+    }
+
+    fn visit_expr_closure(&mut self, _: &syn::ExprClosure) {}
+
+    fn visit_expr_async(&mut self, _: &syn::ExprAsync) {}
+
+    fn visit_item(&mut self, _: &Item) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(programs: &[&str], block: TokenStream) -> Result<String, Vec<syn::Error>> {
+        let programs = programs.iter().map(ToString::to_string).collect();
+        let mut rewriter = Rewriter::new(&programs);
+        let mut block: syn::Block = syn::parse2(block).unwrap();
+        rewriter.visit_block_mut(&mut block);
+        let errors = rewriter.into_errors();
+        if errors.is_empty() {
+            Ok(block.to_token_stream().to_string())
+        } else {
+            Err(errors)
+        }
+    }
+
+    #[test]
+    fn operators_receive_the_vm() {
+        let rewritten = rewrite(
+            &[],
+            quote!({ share(&0, |field| field.size() + neighbor_count()) }),
+        )
+        .unwrap();
+        assert!(rewritten.contains("vm . share (__yaair_argument_0 , | vm , field |"));
+        assert!(rewritten.contains("vm . neighbor_count ()"));
+        let repeated = rewrite(&[], quote!({ repeat(&0, |count| count + 1) })).unwrap();
+        assert!(repeated.contains("| count , vm |"));
+        let id = rewrite(&[], quote!({ local_id() })).unwrap();
+        assert!(id.contains("vm . local_id"));
+    }
+
+    #[test]
+    fn plain_closures_are_left_untouched() {
+        let rewritten = rewrite(
+            &[],
+            quote!({
+                values
+                    .iter()
+                    .map(|value| if *value { share(&0, f) } else { 0 })
+            }),
+        )
+        .unwrap();
+        assert!(!rewritten.contains("vm"));
+    }
+
+    #[test]
+    fn calls_of_the_block_are_aligned() {
+        let rewritten = rewrite(&["gradient"], quote!({ gradient(source) + other(1) })).unwrap();
+        assert!(rewritten.contains(
+            "vm . aligned (\"gradient\" , move | vm | gradient (vm , __yaair_argument_0))"
+        ));
+        assert!(rewritten.contains("other (1)"));
+    }
+
+    #[test]
+    fn ifs_become_branches() {
+        let rewritten = rewrite(
+            &[],
+            quote!({
+                if source {
+                    0
+                } else {
+                    1
+                }
+            }),
+        )
+        .unwrap();
+        assert!(rewritten.contains("vm . branch (__yaair_condition , | vm | { 0 } , | vm | { 1 })"));
+        let fallible = rewrite(
+            &[],
+            quote!({
+                if source {
+                    neighboring(&1)?.size()
+                } else {
+                    0
+                }
+            }),
+        )
+        .unwrap();
+        assert!(fallible.contains("AggregateError > :: Ok ({"));
+        assert!(fallible.ends_with(")) ? } }"));
+        let pattern = rewrite(
+            &[],
+            quote!({
+                if let Some(x) = y {
+                    x
+                } else {
+                    0
+                }
+            }),
+        )
+        .unwrap();
+        assert!(!pattern.contains("branch"));
+        assert!(rewrite(
+            &[],
+            quote!({
+                if source {
+                    return 0;
+                }
+            })
+        )
+        .is_err());
+        assert!(rewrite(
+            &[],
+            quote!({
+                if source {
+                    for x in y {
+                        break;
+                    }
+                }
+            })
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,64 @@
+//! Programs written with `aggregate!`, executed by the simulator on a line of devices.
+
+use yaair::rufi::aggregate::{AggregateError, VM};
+use yaair::rufi::simulator::simulation::{JoinPolicy, Simulator};
+use yaair_macros::aggregate;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+aggregate! {
+    type Vm = VM<u32, JsonSerializer>;
+
+    fn hops(source: bool) -> Result<u32, AggregateError> {
+        share(&u32::MAX, |distances| {
+            if source {
+                0
+            } else {
+                distances.fold_neighbors(u32::MAX, |min, distance| min.min(distance.saturating_add(1)))
+            }
+        })
+    }
+
+    /// Hops to the first and to the last device, computed by two aligned calls of `hops`.
+    fn distances(last: u32) -> Result<(u32, u32), AggregateError> {
+        Ok((hops(local_id() == 0)?, hops(local_id() == last)?))
+    }
+
+    /// Neighbors on the same side of the line, as the other side takes the other branch.
+    fn same_side(left: bool) -> Result<usize, AggregateError> {
+        let count = if left {
+            neighboring(&1)?.excluding_self().count()
+        } else {
+            neighboring(&2)?.excluding_self().count()
+        };
+        Ok(count)
+    }
+}
+
+fn line<Out>(
+    program: impl Fn(u32, &mut VM<u32, JsonSerializer>) -> Out + 'static,
+) -> Simulator<'static, u32, JsonSerializer, Out> {
+    let mut simulator = Simulator::new(JsonSerializer, program);
+    for id in 0..4 {
+        simulator.add_device(id, JoinPolicy::Fresh);
+    }
+    for (previous, id) in (0..4).zip(1..4) {
+        simulator.connect(previous, id);
+    }
+    simulator
+}
+
+#[test]
+fn calls_of_the_block_do_not_interfere() {
+    let mut simulator = line(|_, vm| distances(vm, 3).unwrap());
+    simulator.run(5).unwrap();
+    let results: Vec<_> = (0..4).map(|id| *simulator.result(id).unwrap()).collect();
+    assert_eq!(results, vec![(0, 3), (1, 2), (2, 1), (3, 0)]);
+}
+
+#[test]
+fn ifs_partition_the_neighborhood() {
+    let mut simulator = line(|id, vm| same_side(vm, id < 2).unwrap());
+    simulator.run(2).unwrap();
+    let results: Vec<_> = (0..4).map(|id| *simulator.result(id).unwrap()).collect();
+    assert_eq!(results, vec![1, 1, 1, 1]);
+}
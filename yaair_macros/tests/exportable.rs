@@ -0,0 +1,34 @@
+//! Composite values checked by `#[derive(Exportable)]`, then exchanged with `share`.
+
+use serde::{Deserialize, Serialize};
+use yaair::rufi::aggregate::{Aggregate, VM};
+use yaair_macros::Exportable;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Exportable)]
+struct Sample<T> {
+    value: T,
+    hops: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Exportable)]
+enum Reading {
+    Missing,
+    Range(f64),
+    Samples(Vec<Sample<f64>>),
+}
+
+#[test]
+fn derived_values_can_be_shared() {
+    let mut vm = VM::new(0u32, JsonSerializer);
+    let initial = Reading::Samples(vec![Sample {
+        value: 1.5,
+        hops: 0,
+    }]);
+    let shared = vm
+        .share(&initial, |_, field| field.local().clone())
+        .unwrap();
+    assert_eq!(shared, initial);
+    assert_ne!(shared, Reading::Missing);
+    assert_ne!(shared, Reading::Range(1.5));
+}
@@ -0,0 +1,6 @@
+// the uniffi macros still depend on syn 2, serde on syn 3
+#![allow(clippy::multiple_crate_versions)]
+
+pub mod rufi_mobile;
+
+uniffi::setup_scaffolding!();
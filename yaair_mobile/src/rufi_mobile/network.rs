@@ -0,0 +1,131 @@
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+use yaair_net::rufi_net::neighbors::NeighborTable;
+
+/// Why a [`PlatformLink`] could not broadcast a message, mirroring [`NetworkError`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+pub enum LinkError {
+    /// The link cannot accept the message right now.
+    Congested,
+    /// No peer is connected, e.g. Wi-Fi Aware is not attached yet.
+    Disconnected,
+    /// The message of the given size exceeds what the link can carry.
+    TooLarge {
+        size: u64,
+    },
+    Other {
+        reason: String,
+    },
+}
+impl Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        NetworkError::from(self.clone()).fmt(f)
+    }
+}
+impl std::error::Error for LinkError {}
+impl From<LinkError> for NetworkError {
+    fn from(err: LinkError) -> Self {
+        match err {
+            LinkError::Congested => Self::Congested,
+            LinkError::Disconnected => Self::Disconnected,
+            LinkError::TooLarge { size } => {
+                Self::TooLarge(usize::try_from(size).unwrap_or(usize::MAX))
+            }
+            LinkError::Other { reason } => Self::Other(reason),
+        }
+    }
+}
+impl From<uniffi::UnexpectedUniFFICallbackError> for LinkError {
+    fn from(err: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        Self::Other { reason: err.reason }
+    }
+}
+
+/// Radio provided by the platform, e.g. Wi-Fi Aware publish/subscribe sessions or Nearby
+/// Connections on Android, implemented in Kotlin or Swift.
+///
+/// Messages received by the platform are handed back with
+/// [`MobileNode::deliver`](crate::rufi_mobile::node::MobileNode::deliver).
+#[uniffi::export(with_foreign)]
+pub trait PlatformLink: Send + Sync {
+    /// Send `message` to every peer in range.
+    fn broadcast(&self, message: Vec<u8>) -> Result<(), LinkError>;
+}
+
+/// Messages delivered by the platform and not yet decoded.
+pub(crate) type Inbox = Arc<Mutex<Vec<Vec<u8>>>>;
+
+/// [`Network`] broadcasting through a [`PlatformLink`] and reading the messages delivered by the
+/// platform, retaining those of silent neighbors for 5 seconds.
+pub struct PlatformNetwork<Id: Ord + Hash + Copy, S: Serializer> {
+    link: Arc<dyn PlatformLink>,
+    inbox: Inbox,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+}
+impl<Id, S> PlatformNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    pub(crate) fn new(
+        link: Arc<dyn PlatformLink>,
+        inbox: Inbox,
+        local_id: Id,
+        serializer: S,
+    ) -> Self {
+        Self {
+            link,
+            inbox,
+            serializer,
+            neighbors: NeighborTable::new(local_id, Duration::from_secs(5)),
+        }
+    }
+
+    /// How long the last message of a silent neighbor is retained.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.neighbors.set_retention(retention);
+        self
+    }
+}
+impl<Id, S> Network<Id, S> for PlatformNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        self.link
+            .broadcast(outbound_message)
+            .map_err(NetworkError::from)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        let received =
+            std::mem::take(&mut *self.inbox.lock().unwrap_or_else(PoisonError::into_inner));
+        for message in received {
+            self.neighbors.receive(&self.serializer, &message);
+        }
+        self.neighbors.inbound()
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        self.neighbors.has_pending()
+            || !self
+                .inbox
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_empty()
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
@@ -0,0 +1,219 @@
+use crate::rufi_mobile::network::{Inbox, PlatformLink, PlatformNetwork};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::engine::Engine;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::scheduler::Scheduler;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Outcome of the rounds, written by the thread of the engine.
+#[derive(Debug, Default)]
+struct Status {
+    result: Option<String>,
+    error: Option<String>,
+    rounds: u64,
+}
+
+/// Handle to an [`Engine`] running on its own thread, exported to Kotlin and Swift.
+///
+/// Aggregate programs are Rust functions, so nodes are created by constructors exported from the
+/// Rust library of the app, handing over the link implemented by the platform:
+///
+/// ```ignore
+/// #[uniffi::export]
+/// fn gradient_node(id: u32, link: Arc<dyn PlatformLink>) -> Arc<MobileNode> {
+///     MobileNode::spawn(link, id, JsonSerializer, Duration::from_millis(100), move |network| {
+///         Engine::new(id, network, (), JsonSerializer, gradient)
+///     })
+/// }
+/// ```
+///
+/// Results are exposed as JSON, e.g. to be parsed with `kotlinx.serialization`.
+#[derive(Debug, uniffi::Object)]
+pub struct MobileNode {
+    inbox: Inbox,
+    status: Arc<Mutex<Status>>,
+    running: Arc<AtomicBool>,
+}
+#[uniffi::export]
+impl MobileNode {
+    /// Hand over a message received by the platform link, decoded at the next round.
+    pub fn deliver(&self, message: Vec<u8>) {
+        self.inbox
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(message);
+    }
+
+    /// Result of the last successful round as JSON, `None` before the first one.
+    pub fn latest_result(&self) -> Option<String> {
+        self.status().result.clone()
+    }
+
+    /// Description of the last failed round, `None` if none failed.
+    pub fn last_error(&self) -> Option<String> {
+        self.status().error.clone()
+    }
+
+    /// Number of rounds executed so far.
+    pub fn rounds(&self) -> u64 {
+        self.status().rounds
+    }
+
+    /// Stop the engine after its current round; it is shut down on its thread.
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+// generic constructors cannot be exported, hence the separate block
+#[allow(clippy::multiple_inherent_impl)]
+impl MobileNode {
+    /// Build the engine returned by `build` on a new thread and run it, broadcasting through
+    /// `link`; schedulers without a time-based wakeup are polled every `poll_interval`.
+    pub fn spawn<Id, Out, Env, S, Sch, F>(
+        link: Arc<dyn PlatformLink>,
+        local_id: Id,
+        serializer: S,
+        poll_interval: Duration,
+        build: F,
+    ) -> Arc<Self>
+    where
+        Id: DeviceId + Send + 'static,
+        Out: Serialize,
+        S: Serializer + Send + 'static,
+        Sch: Scheduler,
+        F: FnOnce(PlatformNetwork<Id, S>) -> Engine<Id, Out, Env, S, PlatformNetwork<Id, S>, Sch>
+            + Send
+            + 'static,
+    {
+        let node = Arc::new(Self {
+            inbox: Inbox::default(),
+            status: Arc::default(),
+            running: Arc::new(AtomicBool::new(true)),
+        });
+        let inbox = Arc::clone(&node.inbox);
+        let status = Arc::clone(&node.status);
+        let running = Arc::clone(&node.running);
+        let spawned = std::thread::Builder::new()
+            .name("yaair-node".to_owned())
+            .spawn(move || {
+                let network = PlatformNetwork::new(link, inbox, local_id, serializer);
+                let mut engine = build(network);
+                engine.run(poll_interval, |result| {
+                    let json = result.map_err(|err| err.to_string()).and_then(|report| {
+                        JsonSerializer
+                            .serialize(&report.output)
+                            .map_err(|err| err.to_string())
+                    });
+                    let mut status = status.lock().unwrap_or_else(PoisonError::into_inner);
+                    status.rounds = status.rounds.saturating_add(1);
+                    match json {
+                        Ok(json) => status.result = String::from_utf8(json).ok(),
+                        Err(err) => status.error = Some(err),
+                    }
+                    drop(status);
+                    running.load(Ordering::Relaxed)
+                });
+                if let Err(err) = engine.shutdown() {
+                    status.lock().unwrap_or_else(PoisonError::into_inner).error =
+                        Some(err.to_string());
+                }
+            });
+        if let Err(err) = spawned {
+            node.stop();
+            node.status().error = Some(err.to_string());
+        }
+        node
+    }
+
+    fn status(&self) -> std::sync::MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+impl Drop for MobileNode {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rufi_mobile::network::LinkError;
+    use std::time::Instant;
+    use yaair::rufi::aggregate::{Aggregate, VM};
+    use yaair::rufi::scheduler::Periodic;
+
+    /// Nodes in range of each other, standing for the radio.
+    #[derive(Default)]
+    struct Air {
+        nodes: Mutex<Vec<Arc<MobileNode>>>,
+    }
+
+    struct AirLink(Arc<Air>);
+    impl PlatformLink for AirLink {
+        fn broadcast(&self, message: Vec<u8>) -> Result<(), LinkError> {
+            for node in self.0.nodes.lock().unwrap().iter() {
+                node.deliver(message.clone());
+            }
+            Ok(())
+        }
+    }
+
+    fn node(air: &Arc<Air>, id: u32) -> Arc<MobileNode> {
+        let link = Arc::new(AirLink(Arc::clone(air)));
+        let node = MobileNode::spawn(
+            link,
+            id,
+            JsonSerializer,
+            Duration::from_millis(5),
+            move |network| {
+                Engine::new(
+                    id,
+                    network,
+                    (),
+                    JsonSerializer,
+                    |_env, vm: &mut VM<u32, _>| {
+                        let local_id = vm.local_id;
+                        vm.neighboring(&local_id).map_or(0, |field| field.size())
+                    },
+                )
+                .with_scheduler(Periodic::new(Duration::from_millis(10)))
+            },
+        );
+        air.nodes.lock().unwrap().push(Arc::clone(&node));
+        node
+    }
+
+    #[test]
+    fn nodes_discover_each_other_through_the_platform_link() {
+        let air = Arc::new(Air::default());
+        let nodes = [node(&air, 1), node(&air, 2)];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while nodes
+            .iter()
+            .any(|node| node.latest_result().as_deref() != Some("2"))
+        {
+            assert!(
+                Instant::now() < deadline,
+                "nodes did not discover each other"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(nodes
+            .iter()
+            .all(|node| node.rounds() > 0 && node.last_error().is_none()));
+        for node in &nodes {
+            node.stop();
+            assert!(!node.is_running());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::rufi_net::udp::UdpConfig;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::discovery::Discovery;
+use yaair::rufi::messages::serializer::Serializer;
+
+/// Size of the buffer receiving beacons, which only carry a serialized id.
+const MAX_BEACON: usize = 1024;
+
+/// [`Discovery`] broadcasting a beacon with the local id at every round, on a socket separate
+/// from the one carrying the exports.
+///
+/// A device is discovered when its beacon is heard, and lost once no beacon has been heard for
+/// the retention of the [`UdpConfig`].
+pub struct UdpBeacons<Id: Ord + Hash + Copy, S: Serializer> {
+    local_id: Id,
+    socket: UdpSocket,
+    targets: Vec<SocketAddr>,
+    retention: Duration,
+    serializer: S,
+    heard: HashMap<Id, Duration>,
+    buffer: Vec<u8>,
+}
+impl<Id, S> UdpBeacons<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    pub fn bind(local_id: Id, config: UdpConfig, serializer: S) -> io::Result<Self> {
+        let socket = UdpSocket::bind(config.bind)?;
+        socket.set_nonblocking(true)?;
+        socket.set_broadcast(config.broadcast)?;
+        for (group, interface) in &config.multicast_groups {
+            socket.join_multicast_v4(group, interface)?;
+        }
+        Ok(Self {
+            local_id,
+            socket,
+            targets: config.targets,
+            retention: config.retention,
+            serializer,
+            heard: HashMap::new(),
+            buffer: vec![0; MAX_BEACON],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Record the beacons received so far as heard at `now`.
+    fn drain(&mut self, now: Duration) {
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((size, _)) => {
+                    let Some(payload) = self.buffer.get(..size) else {
+                        continue;
+                    };
+                    if let Ok(id) = self.serializer.deserialize::<Id>(payload) {
+                        self.heard.insert(id, now);
+                    }
+                }
+                // Windows reports ICMP port-unreachable of previous sends as a receive error
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+impl<Id, S> Discovery<Id> for UdpBeacons<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn discover(&mut self, now: Duration) -> BTreeSet<Id> {
+        if let Ok(beacon) = self.serializer.serialize(&self.local_id) {
+            for target in &self.targets {
+                // Beacons are best-effort: a lost one is compensated by the next
+                let _ = self.socket.send_to(&beacon, target);
+            }
+        }
+        self.drain(now);
+        let retention = self.retention;
+        self.heard
+            .retain(|_, heard_at| now.saturating_sub(*heard_at) <= retention);
+        self.heard
+            .keys()
+            .copied()
+            .filter(|id| *id != self.local_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use yaair_serde::rufi_serde::json::JsonSerializer;
+
+    fn beacons(id: u32) -> UdpBeacons<u32, JsonSerializer> {
+        let config =
+            UdpConfig::new((Ipv4Addr::LOCALHOST, 0).into()).with_retention(Duration::from_secs(2));
+        UdpBeacons::bind(id, config, JsonSerializer).unwrap()
+    }
+
+    #[test]
+    fn devices_are_discovered_until_their_beacons_stop() {
+        let mut first = beacons(1);
+        let mut second = beacons(2);
+        first.targets.push(second.local_addr().unwrap());
+        second.targets.push(first.local_addr().unwrap());
+
+        first.discover(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(second.discover(Duration::ZERO), BTreeSet::from([1]));
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(first.discover(Duration::from_secs(1)), BTreeSet::from([2]));
+        assert!(first.discover(Duration::from_secs(4)).is_empty());
+    }
+}
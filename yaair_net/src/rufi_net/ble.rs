@@ -0,0 +1,294 @@
+#[cfg(feature = "encryption")]
+use crate::rufi_net::crypto::GroupCipher;
+use crate::rufi_net::neighbors::NeighborTable;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+
+/// Size of the `[message, index, count]` header of every fragment.
+const HEADER_SIZE: usize = 3;
+
+/// Manufacturer data available in a legacy (31 bytes) advertisement, after the AD structure
+/// header and the company identifier.
+pub const ADVERTISEMENT_FRAME_SIZE: usize = 27;
+
+/// Payload of a GATT write with the default ATT MTU of 23 bytes.
+pub const GATT_FRAME_SIZE: usize = 20;
+
+/// Radio carrying the frames of a [`BleNetwork`], e.g. advertisements or writes to a GATT
+/// characteristic.
+///
+/// Frames are best effort: they can be lost, duplicated or received out of order.
+pub trait BleLink {
+    /// Identifies the device a frame was received from, e.g. its Bluetooth address.
+    type Address: Eq + Hash + Clone;
+
+    /// Largest frame the link can carry, header included.
+    fn frame_size(&self) -> usize;
+
+    /// Send the frames of an outbound message to every device in range.
+    ///
+    /// # Errors
+    /// Returns why the frames could not be sent
+    fn broadcast(&mut self, frames: &[Vec<u8>]) -> Result<(), NetworkError>;
+
+    /// Take the frames received since the last call, with the address of their sender.
+    fn receive(&mut self) -> Vec<(Self::Address, Vec<u8>)>;
+
+    /// Whether frames have been received since the last call to [`BleLink::receive`].
+    fn has_pending(&self) -> bool;
+}
+
+/// Fragments received of the last message of a device.
+struct Partial {
+    message: u8,
+    fragments: Vec<Option<Vec<u8>>>,
+}
+
+/// [`Network`] over Bluetooth Low Energy, splitting outbound messages into frames small enough
+/// for advertisements or GATT characteristics.
+///
+/// Every frame starts with the number of the message (wrapping at 256), the index of the
+/// fragment and the number of fragments. Only the last message of every device is reassembled:
+/// the fragments of an older message are dropped as soon as a newer one starts arriving.
+pub struct BleNetwork<Id: Ord + Hash + Copy, S: Serializer, L: BleLink> {
+    link: L,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+    partials: HashMap<L::Address, Partial>,
+    message: u8,
+}
+impl<Id, S, L> BleNetwork<Id, S, L>
+where
+    Id: DeviceId,
+    S: Serializer,
+    L: BleLink,
+{
+    /// Exchange the messages of `local_id` over `link`, retaining silent neighbors for 5 seconds.
+    pub fn new(local_id: Id, link: L, serializer: S) -> Self {
+        Self {
+            link,
+            serializer,
+            neighbors: NeighborTable::new(local_id, Duration::from_secs(5)),
+            partials: HashMap::new(),
+            message: 0,
+        }
+    }
+
+    /// How long the last message of a silent neighbor is retained.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.neighbors.set_retention(retention);
+        self
+    }
+
+    /// Encrypt every message with the key shared by the group; see [`GroupCipher`].
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: GroupCipher) -> Self {
+        self.neighbors.set_cipher(cipher);
+        self
+    }
+
+    pub const fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// Split `payload` into frames of the link.
+    fn fragment(&self, payload: &[u8]) -> Result<Vec<Vec<u8>>, NetworkError> {
+        let chunk_size = self.link.frame_size().saturating_sub(HEADER_SIZE);
+        if chunk_size == 0 {
+            return Err(NetworkError::Other(format!(
+                "Frames of {} bytes cannot carry the fragment header",
+                self.link.frame_size()
+            )));
+        }
+        let chunks: Vec<&[u8]> = if payload.is_empty() {
+            vec![payload]
+        } else {
+            payload.chunks(chunk_size).collect()
+        };
+        let count =
+            u8::try_from(chunks.len()).map_err(|_| NetworkError::TooLarge(payload.len()))?;
+        Ok(chunks
+            .into_iter()
+            .zip(0..=u8::MAX)
+            .map(|(chunk, index)| {
+                let mut frame = Vec::with_capacity(chunk.len().saturating_add(HEADER_SIZE));
+                frame.extend_from_slice(&[self.message, index, count]);
+                frame.extend_from_slice(chunk);
+                frame
+            })
+            .collect())
+    }
+
+    /// Store a received frame, returning the payload of its message once all of its fragments
+    /// have been received.
+    fn reassemble(&mut self, address: &L::Address, frame: &[u8]) -> Option<Vec<u8>> {
+        let (&[message, index, count], chunk) = frame.split_first_chunk::<HEADER_SIZE>()?;
+        if index >= count {
+            return None;
+        }
+        let partial = self
+            .partials
+            .entry(address.clone())
+            .or_insert_with(|| Partial {
+                message,
+                fragments: Vec::new(),
+            });
+        if partial.message != message || partial.fragments.len() != usize::from(count) {
+            *partial = Partial {
+                message,
+                fragments: vec![None; usize::from(count)],
+            };
+        }
+        if let Some(fragment) = partial.fragments.get_mut(usize::from(index)) {
+            *fragment = Some(chunk.to_vec());
+        }
+        if partial.fragments.iter().any(Option::is_none) {
+            return None;
+        }
+        let payload = partial
+            .fragments
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+        self.partials.remove(address);
+        Some(payload)
+    }
+
+    fn drain(&mut self) {
+        for (address, frame) in self.link.receive() {
+            if let Some(payload) = self.reassemble(&address, &frame) {
+                self.neighbors.receive(&self.serializer, &payload);
+            }
+        }
+    }
+}
+impl<Id, S, L> Network<Id, S> for BleNetwork<Id, S, L>
+where
+    Id: DeviceId,
+    S: Serializer,
+    L: BleLink,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        let outbound_message = self.neighbors.seal(outbound_message)?;
+        let frames = self.fragment(&outbound_message)?;
+        self.message = self.message.wrapping_add(1);
+        self.link.broadcast(&frames)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        self.drain();
+        self.neighbors.inbound()
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        self.neighbors.has_pending() || self.link.has_pending()
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use yaair::rufi::messages::outbound::OutboundMessage;
+    use yaair::rufi::messages::path::Path;
+    use yaair_serde::rufi_serde::json::JsonSerializer;
+
+    /// Air shared by the test links, with the frames sent by every address.
+    type Air = Rc<RefCell<Vec<(u8, Vec<u8>)>>>;
+
+    struct AirLink {
+        address: u8,
+        air: Air,
+        received: usize,
+    }
+    impl BleLink for AirLink {
+        type Address = u8;
+
+        fn frame_size(&self) -> usize {
+            ADVERTISEMENT_FRAME_SIZE
+        }
+
+        fn broadcast(&mut self, frames: &[Vec<u8>]) -> Result<(), NetworkError> {
+            let mut air = self.air.borrow_mut();
+            // the frames of a message are received in reverse order
+            air.extend(
+                frames
+                    .iter()
+                    .rev()
+                    .map(|frame| (self.address, frame.clone())),
+            );
+            Ok(())
+        }
+
+        fn receive(&mut self) -> Vec<(u8, Vec<u8>)> {
+            let air = self.air.borrow();
+            let frames = air.get(self.received..).unwrap_or_default().to_vec();
+            self.received = air.len();
+            frames
+                .into_iter()
+                .filter(|(address, _)| *address != self.address)
+                .collect()
+        }
+
+        fn has_pending(&self) -> bool {
+            self.air.borrow().len() > self.received
+        }
+    }
+
+    fn network(id: u32, air: &Air) -> BleNetwork<u32, JsonSerializer, AirLink> {
+        let link = AirLink {
+            address: u8::try_from(id).unwrap(),
+            air: Rc::clone(air),
+            received: 0,
+        };
+        BleNetwork::new(id, link, JsonSerializer)
+    }
+
+    #[test]
+    fn large_messages_are_fragmented_and_reassembled() {
+        let air = Air::default();
+        let mut sender = network(1, &air);
+        let mut receiver = network(2, &air);
+        let mut message = OutboundMessage::empty(1);
+        message.append(&Path::from("share:0"), vec![7; 100]);
+        let payload = JsonSerializer.serialize(&message).unwrap();
+
+        sender.prepare_outbound(payload).unwrap();
+        assert!(air.borrow().len() > 1);
+        assert!(receiver.has_pending_inbound());
+        let inbound = receiver.prepare_inbound();
+        let tree = inbound.get(&1).unwrap();
+        assert_eq!(tree.get(&Path::from("share:0")), Some(&[7; 100][..]));
+    }
+
+    #[test]
+    fn fragments_of_an_older_message_are_dropped() {
+        let air = Air::default();
+        let mut receiver = network(2, &air);
+        let sender = network(1, &air);
+        let frames = sender.fragment(&[1; 60]).unwrap();
+        let [first, second, third] = <[Vec<u8>; 3]>::try_from(frames).unwrap();
+        let mut newer = second.clone();
+        *newer.first_mut().unwrap() = 1;
+        assert_eq!(receiver.reassemble(&1, &first), None);
+        assert_eq!(receiver.reassemble(&1, &newer), None);
+        assert_eq!(receiver.reassemble(&1, &second), None);
+        assert_eq!(receiver.reassemble(&1, &third), None);
+        assert_eq!(receiver.reassemble(&1, &first), Some(vec![1; 60]));
+    }
+}
@@ -0,0 +1,138 @@
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Size of the key shared by all the devices of a group.
+pub const KEY_SIZE: usize = 32;
+
+const PREFIX_SIZE: usize = 4;
+const NONCE_SIZE: usize = 12;
+
+/// Authenticated encryption of whole messages with ChaCha20-Poly1305 and a key shared by the group.
+///
+/// Every sealed message starts with its 12-byte nonce: the 4-byte prefix of the sender followed
+/// by a 64-bit big-endian counter. Prefixes must be unique within the group; the counter starts
+/// from the wall clock in microseconds, so that a rebooted device does not reuse nonces.
+/// Receivers drop messages whose counter is not greater than the last one seen for the same
+/// prefix, rejecting replays.
+pub struct GroupCipher {
+    aead: ChaCha20Poly1305,
+    prefix: [u8; PREFIX_SIZE],
+    counter: u64,
+    last_seen: HashMap<[u8; PREFIX_SIZE], u64>,
+}
+impl std::fmt::Debug for GroupCipher {
+    // never print the key
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GroupCipher")
+            .field("prefix", &self.prefix)
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+impl GroupCipher {
+    pub fn new(key: &[u8; KEY_SIZE], prefix: u32) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX)
+            });
+        Self {
+            aead: ChaCha20Poly1305::new(Key::from_slice(key)),
+            prefix: prefix.to_be_bytes(),
+            counter: now,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Start counting nonces from `counter`, e.g. a value persisted across reboots.
+    #[must_use]
+    pub const fn with_initial_counter(mut self, counter: u64) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Encrypt and authenticate `plaintext`.
+    ///
+    /// # Returns
+    /// `None` once the nonce counter is exhausted
+    pub fn seal(&mut self, plaintext: &[u8]) -> Option<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        let (prefix, counter) = nonce.split_at_mut(PREFIX_SIZE);
+        prefix.copy_from_slice(&self.prefix);
+        counter.copy_from_slice(&self.counter.to_be_bytes());
+        self.counter = self.counter.checked_add(1)?;
+        let ciphertext = self
+            .aead
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .ok()?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Some(sealed)
+    }
+
+    /// Decrypt a message produced by [`GroupCipher::seal`].
+    ///
+    /// # Returns
+    /// `None` if the message is truncated, has been tampered with, was encrypted with another
+    /// key or is a replay
+    pub fn open(&mut self, sealed: &[u8]) -> Option<Vec<u8>> {
+        let nonce = sealed.get(..NONCE_SIZE)?;
+        let prefix: [u8; PREFIX_SIZE] = nonce.get(..PREFIX_SIZE)?.try_into().ok()?;
+        let counter = u64::from_be_bytes(nonce.get(PREFIX_SIZE..)?.try_into().ok()?);
+        if self
+            .last_seen
+            .get(&prefix)
+            .is_some_and(|last| counter <= *last)
+        {
+            return None;
+        }
+        let plaintext = self
+            .aead
+            .decrypt(Nonce::from_slice(nonce), sealed.get(NONCE_SIZE..)?)
+            .ok()?;
+        self.last_seen.insert(prefix, counter);
+        Some(plaintext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; KEY_SIZE] = [7; KEY_SIZE];
+
+    #[test]
+    fn sealed_messages_round_trip() {
+        let mut sender = GroupCipher::new(&KEY, 1);
+        let mut receiver = GroupCipher::new(&KEY, 2);
+        let sealed = sender.seal(b"share").unwrap();
+        assert!(!sealed.windows(5).any(|window| window == b"share"));
+        assert_eq!(receiver.open(&sealed), Some(b"share".to_vec()));
+    }
+
+    #[test]
+    fn tampered_and_foreign_messages_are_rejected() {
+        let mut sender = GroupCipher::new(&KEY, 1);
+        let mut sealed = sender.seal(b"share").unwrap();
+        let mut outsider = GroupCipher::new(&[8; KEY_SIZE], 3);
+        assert_eq!(outsider.open(&sealed), None);
+        if let Some(byte) = sealed.last_mut() {
+            *byte ^= 1;
+        }
+        assert_eq!(GroupCipher::new(&KEY, 2).open(&sealed), None);
+        assert_eq!(GroupCipher::new(&KEY, 2).open(&[0; 4]), None);
+    }
+
+    #[test]
+    fn replays_are_rejected() {
+        let mut sender = GroupCipher::new(&KEY, 1).with_initial_counter(10);
+        let mut receiver = GroupCipher::new(&KEY, 2);
+        let first = sender.seal(b"a").unwrap();
+        let second = sender.seal(b"b").unwrap();
+        assert!(receiver.open(&second).is_some());
+        assert_eq!(receiver.open(&first), None);
+        assert_eq!(receiver.open(&second), None);
+    }
+}
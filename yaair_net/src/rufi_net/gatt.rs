@@ -0,0 +1,284 @@
+use crate::rufi_net::ble::{BleLink, GATT_FRAME_SIZE};
+use btleplug::api::{
+    Central, CentralEvent, CharPropFlags, Characteristic, Manager as _, Peripheral as _,
+    ScanFilter, ValueNotification, WriteType,
+};
+use btleplug::platform::{Adapter, Manager, Peripheral, PeripheralId};
+use futures::stream::{Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::runtime::{Builder, Runtime};
+use uuid::Uuid;
+use yaair::rufi::network::NetworkError;
+
+/// Frames received by the background tasks, with the peripheral that sent them.
+type Received = Arc<Mutex<Vec<(PeripheralId, Vec<u8>)>>>;
+
+/// Company identifier reserved by the Bluetooth SIG for tests, used until one is configured.
+const TEST_COMPANY_ID: u16 = 0xFFFF;
+
+/// Configuration of a [`GattLink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GattConfig {
+    /// Service exposed by the devices of the neighborhood.
+    pub service: Uuid,
+    /// Characteristic the frames are written to and notified from.
+    pub characteristic: Uuid,
+    /// Company identifier of the manufacturer data advertised by devices broadcasting frames.
+    pub company_id: u16,
+    /// Largest frame written to the characteristic, header included.
+    pub frame_size: usize,
+}
+impl GattConfig {
+    pub const fn new(service: Uuid, characteristic: Uuid) -> Self {
+        Self {
+            service,
+            characteristic,
+            company_id: TEST_COMPANY_ID,
+            frame_size: GATT_FRAME_SIZE,
+        }
+    }
+
+    pub const fn with_company_id(mut self, company_id: u16) -> Self {
+        self.company_id = company_id;
+        self
+    }
+
+    /// Write larger frames to peripherals that negotiate an ATT MTU above the default one.
+    pub const fn with_frame_size(mut self, frame_size: usize) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+}
+
+/// [`BleLink`] of a central (phone, laptop, gateway) exchanging frames with the peripherals
+/// around it through btleplug.
+///
+/// btleplug cannot advertise: frames are written to the characteristic of every peripheral
+/// exposing the service, and received both from its notifications and from the manufacturer
+/// data advertised by the devices in range. Peripherals are connected when the frames of the next
+/// round are sent, and reconnected after a failed write.
+pub struct GattLink {
+    runtime: Runtime,
+    adapter: Adapter,
+    config: GattConfig,
+    received: Received,
+    /// Peripherals advertising the service that are not connected yet.
+    discovered: Arc<Mutex<HashSet<PeripheralId>>>,
+    connected: HashMap<PeripheralId, (Peripheral, Characteristic)>,
+}
+impl GattLink {
+    /// Start scanning with the first Bluetooth adapter of the host.
+    ///
+    /// # Errors
+    /// Returns the error of btleplug, [`btleplug::Error::DeviceNotFound`] if the host has no
+    /// adapter
+    pub fn open(config: GattConfig) -> Result<Self, btleplug::Error> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|err| btleplug::Error::RuntimeError(err.to_string()))?;
+        let (adapter, events) = runtime.block_on(async {
+            let manager = Manager::new().await?;
+            let adapter = manager
+                .adapters()
+                .await?
+                .into_iter()
+                .next()
+                .ok_or(btleplug::Error::DeviceNotFound)?;
+            let events = adapter.events().await?;
+            adapter.start_scan(ScanFilter::default()).await?;
+            Ok::<_, btleplug::Error>((adapter, events))
+        })?;
+        let received = Received::default();
+        let discovered = Arc::default();
+        runtime.spawn(listen(
+            events,
+            config,
+            Arc::clone(&received),
+            Arc::clone(&discovered),
+        ));
+        Ok(Self {
+            runtime,
+            adapter,
+            config,
+            received,
+            discovered,
+            connected: HashMap::new(),
+        })
+    }
+}
+impl BleLink for GattLink {
+    type Address = PeripheralId;
+
+    fn frame_size(&self) -> usize {
+        self.config.frame_size
+    }
+
+    fn broadcast(&mut self, frames: &[Vec<u8>]) -> Result<(), NetworkError> {
+        let discovered: Vec<PeripheralId> = lock(&self.discovered).drain().collect();
+        let Self {
+            runtime,
+            adapter,
+            config,
+            received,
+            connected,
+            ..
+        } = self;
+        let lost = runtime.block_on(async {
+            for id in discovered {
+                if connected.contains_key(&id) {
+                    continue;
+                }
+                // peripherals that cannot be connected are retried when advertised again
+                if let Ok(peer) = connect(adapter, &id, config, received).await {
+                    connected.insert(id, peer);
+                }
+            }
+            let mut lost = Vec::new();
+            for (id, (peripheral, characteristic)) in connected.iter() {
+                for frame in frames {
+                    if let Err(err) = peripheral
+                        .write(characteristic, frame, WriteType::WithoutResponse)
+                        .await
+                    {
+                        lost.push((id.clone(), err));
+                        break;
+                    }
+                }
+            }
+            lost
+        });
+        let mut failure = None;
+        for (id, err) in lost {
+            self.connected.remove(&id);
+            lock(&self.discovered).insert(id);
+            failure.get_or_insert_with(|| network_error(&err));
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn receive(&mut self) -> Vec<(PeripheralId, Vec<u8>)> {
+        std::mem::take(&mut *lock(&self.received))
+    }
+
+    fn has_pending(&self) -> bool {
+        !lock(&self.received).is_empty()
+    }
+}
+impl Drop for GattLink {
+    fn drop(&mut self) {
+        let Self {
+            runtime,
+            adapter,
+            connected,
+            ..
+        } = self;
+        runtime.block_on(async {
+            let _ = adapter.stop_scan().await;
+            for (peripheral, _) in connected.values() {
+                let _ = peripheral.disconnect().await;
+            }
+        });
+    }
+}
+
+/// Collect the frames advertised as manufacturer data and the peripherals exposing the service.
+async fn listen(
+    mut events: Pin<Box<dyn Stream<Item = CentralEvent> + Send>>,
+    config: GattConfig,
+    received: Received,
+    discovered: Arc<Mutex<HashSet<PeripheralId>>>,
+) {
+    while let Some(event) = events.next().await {
+        match event {
+            CentralEvent::ManufacturerDataAdvertisement {
+                id,
+                mut manufacturer_data,
+            } => {
+                if let Some(frame) = manufacturer_data.remove(&config.company_id) {
+                    lock(&received).push((id, frame));
+                }
+            }
+            CentralEvent::ServicesAdvertisement { id, services } => {
+                if services.contains(&config.service) {
+                    lock(&discovered).insert(id);
+                }
+            }
+            CentralEvent::DeviceDiscovered(_)
+            | CentralEvent::DeviceUpdated(_)
+            | CentralEvent::DeviceConnected(_)
+            | CentralEvent::DeviceDisconnected(_)
+            | CentralEvent::ServiceDataAdvertisement { .. }
+            | CentralEvent::StateUpdate(_) => {}
+        }
+    }
+}
+
+/// Connect to a peripheral, find the characteristic of the service and forward its
+/// notifications to `received`.
+async fn connect(
+    adapter: &Adapter,
+    id: &PeripheralId,
+    config: &GattConfig,
+    received: &Received,
+) -> Result<(Peripheral, Characteristic), btleplug::Error> {
+    let peripheral = adapter.peripheral(id).await?;
+    if !peripheral.is_connected().await? {
+        peripheral.connect().await?;
+    }
+    peripheral.discover_services().await?;
+    let characteristic = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|characteristic| {
+            characteristic.service_uuid == config.service
+                && characteristic.uuid == config.characteristic
+        })
+        .ok_or(btleplug::Error::NoSuchCharacteristic)?;
+    if characteristic.properties.contains(CharPropFlags::NOTIFY) {
+        peripheral.subscribe(&characteristic).await?;
+        let notifications = peripheral.notifications().await?;
+        tokio::spawn(forward(
+            notifications,
+            id.clone(),
+            config.characteristic,
+            Arc::clone(received),
+        ));
+    }
+    Ok((peripheral, characteristic))
+}
+
+/// Forward the notifications of `characteristic` sent by the peripheral `id`.
+async fn forward(
+    mut notifications: Pin<Box<dyn Stream<Item = ValueNotification> + Send>>,
+    id: PeripheralId,
+    characteristic: Uuid,
+    received: Received,
+) {
+    while let Some(notification) = notifications.next().await {
+        if notification.uuid == characteristic {
+            lock(&received).push((id.clone(), notification.value));
+        }
+    }
+}
+
+fn network_error(err: &btleplug::Error) -> NetworkError {
+    if matches!(
+        err,
+        btleplug::Error::NotConnected | btleplug::Error::DeviceNotFound
+    ) {
+        NetworkError::Disconnected
+    } else if matches!(err, btleplug::Error::TimedOut(_)) {
+        NetworkError::Congested
+    } else {
+        NetworkError::Other(err.to_string())
+    }
+}
+
+/// Lock shared state, recovering it if a background task panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,319 @@
+#[cfg(feature = "encryption")]
+use crate::rufi_net::crypto::GroupCipher;
+use crate::rufi_net::neighbors::NeighborTable;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+
+/// Size of the big-endian length prefix of every message in a `GET /messages` response.
+const HEADER_SIZE: usize = 4;
+
+/// Requests and responses with a longer header are rejected.
+const MAX_HEAD_SIZE: usize = 8192;
+
+/// Configuration of an [`HttpNetwork`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConfig {
+    /// Address of the [`HttpGateway`].
+    pub gateway: SocketAddr,
+    /// Name under which the device posts its messages: ASCII letters, digits, `-`, `_` or `.`.
+    pub name: String,
+    /// Timeout of every request to the gateway.
+    pub timeout: Duration,
+    /// Responses larger than this are considered corrupted and discarded.
+    pub max_response_size: usize,
+    /// How long the last message of a silent neighbor is retained.
+    pub retention: Duration,
+}
+impl HttpConfig {
+    pub fn new(gateway: SocketAddr, name: impl Into<String>) -> Self {
+        Self {
+            gateway,
+            name: name.into(),
+            timeout: Duration::from_millis(500),
+            max_response_size: 1 << 20,
+            retention: Duration::from_secs(5),
+        }
+    }
+
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+/// [`Network`] exchanging messages through an [`HttpGateway`], for nodes that can only open
+/// outgoing HTTP connections.
+///
+/// Every round the device POSTs its outbound message and GETs the last message of every
+/// device registered at the gateway: all the devices of a gateway are neighbors. Requests are
+/// blocking, bounded by [`HttpConfig::timeout`]; a failed request is retried at the next round.
+pub struct HttpNetwork<Id: Ord + Hash + Copy, S: Serializer> {
+    config: HttpConfig,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+}
+impl<Id, S> HttpNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    /// # Errors
+    /// Returns [`ErrorKind::InvalidInput`] if the name of the device cannot be used in a URL path
+    pub fn new(local_id: Id, config: HttpConfig, serializer: S) -> io::Result<Self> {
+        if !is_valid_name(&config.name) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "device names may only contain ASCII letters, digits, '-', '_' and '.'",
+            ));
+        }
+        let neighbors = NeighborTable::new(local_id, config.retention);
+        Ok(Self {
+            config,
+            serializer,
+            neighbors,
+        })
+    }
+
+    /// Encrypt every message with the key shared by the group; see [`GroupCipher`].
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: GroupCipher) -> Self {
+        self.neighbors.set_cipher(cipher);
+        self
+    }
+
+    fn request(&self, head: &str, body: &[u8]) -> io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect_timeout(&self.config.gateway, self.config.timeout)?;
+        stream.set_read_timeout(Some(self.config.timeout))?;
+        stream.set_write_timeout(Some(self.config.timeout))?;
+        write!(
+            stream,
+            "{head} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.config.gateway,
+            body.len(),
+        )?;
+        stream.write_all(body)?;
+        let (status, response) = read_message(&mut stream, self.config.max_response_size)?;
+        if status.split(' ').nth(1) == Some("200") {
+            Ok(response)
+        } else {
+            Err(io::Error::other(status))
+        }
+    }
+}
+
+impl<Id, S> Network<Id, S> for HttpNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        let outbound_message = self.neighbors.seal(outbound_message)?;
+        let head = format!("POST /messages/{}", self.config.name);
+        self.request(&head, &outbound_message)
+            .map(|_| ())
+            .map_err(NetworkError::from)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        if let Ok(mut frames) = self.request("GET /messages", &[]) {
+            while let Some(message) = next_frame(&mut frames) {
+                self.neighbors.receive(&self.serializer, &message);
+            }
+        }
+        self.neighbors.inbound()
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
+
+/// Minimal HTTP broker relaying the messages of the devices of an [`HttpNetwork`].
+///
+/// - `POST /messages/{name}` replaces the last message of the device `name`;
+/// - `GET /messages` returns the last message of every device, each one prefixed by its
+///   32-bit big-endian length.
+///
+/// Messages are opaque to the gateway, so it works with any serializer and with encryption.
+pub struct HttpGateway {
+    listener: TcpListener,
+    messages: HashMap<String, (Instant, Vec<u8>)>,
+    retention: Duration,
+    max_request_size: usize,
+    timeout: Duration,
+}
+impl HttpGateway {
+    pub fn bind(address: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            messages: HashMap::new(),
+            retention: Duration::from_secs(5),
+            max_request_size: 1 << 16,
+            timeout: Duration::from_millis(500),
+        })
+    }
+
+    /// Forget the devices that did not post anything for `retention`.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve requests until the listener fails.
+    ///
+    /// # Errors
+    /// Returns the error that stopped the listener; errors of single connections are ignored
+    pub fn serve(&mut self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            // A misbehaving client only loses its own request
+            let _ = self.handle(stream);
+        }
+    }
+
+    fn handle(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let Ok((request_line, body)) = read_message(&mut stream, self.max_request_size) else {
+            return respond(&mut stream, "400 Bad Request", &[]);
+        };
+        let mut parts = request_line.split(' ');
+        let retention = self.retention;
+        self.messages
+            .retain(|_, (posted_at, _)| posted_at.elapsed() <= retention);
+        match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/messages")) => {
+                let mut frames = Vec::new();
+                for (_, message) in self.messages.values() {
+                    if let Ok(size) = u32::try_from(message.len()) {
+                        frames.extend_from_slice(&size.to_be_bytes());
+                        frames.extend_from_slice(message);
+                    }
+                }
+                respond(&mut stream, "200 OK", &frames)
+            }
+            (Some("POST"), Some(path)) => match path.strip_prefix("/messages/") {
+                Some(name) if is_valid_name(name) => {
+                    self.messages
+                        .insert(name.to_owned(), (Instant::now(), body));
+                    respond(&mut stream, "200 OK", &[])
+                }
+                _ => respond(&mut stream, "404 Not Found", &[]),
+            },
+            _ => respond(&mut stream, "404 Not Found", &[]),
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len(),
+    )?;
+    stream.write_all(body)
+}
+
+/// Read an HTTP request or response with a `Content-Length` body of at most `max_body` bytes.
+///
+/// # Returns
+/// The first line of the message together with its body
+fn read_message(stream: &mut TcpStream, max_body: usize) -> io::Result<(String, Vec<u8>)> {
+    let invalid = |reason: &str| io::Error::new(ErrorKind::InvalidData, reason.to_owned());
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+        if buffer.len() > MAX_HEAD_SIZE {
+            return Err(invalid("header too large"));
+        }
+        let size = stream.read(&mut chunk)?;
+        if size == 0 {
+            return Err(invalid("connection closed before the end of the header"));
+        }
+        buffer.extend_from_slice(chunk.get(..size).unwrap_or_default());
+    };
+    let head = String::from_utf8_lossy(buffer.get(..head_end).unwrap_or_default()).into_owned();
+    let mut lines = head.split("\r\n");
+    let first_line = lines.next().unwrap_or_default().to_owned();
+    let length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .map_or(Ok(0), |(_, value)| value.trim().parse::<usize>())
+        .map_err(|_| invalid("malformed Content-Length"))?;
+    if length > max_body {
+        return Err(invalid("body too large"));
+    }
+    let mut body = buffer
+        .get(head_end.saturating_add(4)..)
+        .unwrap_or_default()
+        .to_vec();
+    while body.len() < length {
+        let size = stream.read(&mut chunk)?;
+        if size == 0 {
+            return Err(invalid("connection closed before the end of the body"));
+        }
+        body.extend_from_slice(chunk.get(..size).unwrap_or_default());
+    }
+    body.truncate(length);
+    Ok((first_line, body))
+}
+
+/// Extract the next length-prefixed message of a `GET /messages` response.
+fn next_frame(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let header: [u8; HEADER_SIZE] = buffer.get(..HEADER_SIZE)?.try_into().ok()?;
+    let size = usize::try_from(u32::from_be_bytes(header)).ok()?;
+    let end = HEADER_SIZE.checked_add(size)?;
+    let message = buffer.get(HEADER_SIZE..end)?.to_vec();
+    buffer.drain(..end);
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_url_safe_names_are_accepted() {
+        assert!(is_valid_name("node-1.lab_a"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name("a b"));
+    }
+
+    #[test]
+    fn frames_are_split_by_length() {
+        let mut buffer = vec![0, 0, 0, 1, b'a', 0, 0, 0, 2, b'b', b'c', 0];
+        assert_eq!(next_frame(&mut buffer), Some(b"a".to_vec()));
+        assert_eq!(next_frame(&mut buffer), Some(b"bc".to_vec()));
+        assert_eq!(next_frame(&mut buffer), None);
+    }
+}
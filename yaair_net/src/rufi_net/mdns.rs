@@ -0,0 +1,93 @@
+use mdns_sd::{Receiver, ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use yaair::rufi::discovery::Discovery;
+
+/// Service type under which devices advertise themselves.
+pub const SERVICE_TYPE: &str = "_yaair._udp.local.";
+
+/// TXT property carrying the id of the advertised device.
+const ID_PROPERTY: &str = "id";
+
+/// [`Discovery`] advertising the device over multicast DNS and browsing for the other devices
+/// of the same service type.
+///
+/// A device is lost when its advertisement expires or is withdrawn, e.g. when its
+/// [`MdnsDiscovery`] is dropped.
+pub struct MdnsDiscovery<Id: Ord + Copy> {
+    local_id: Id,
+    daemon: ServiceDaemon,
+    fullname: String,
+    events: Receiver<ServiceEvent>,
+    /// Discovered devices by the full name of their service instance.
+    found: HashMap<String, Id>,
+}
+impl<Id> MdnsDiscovery<Id>
+where
+    Id: Ord + Copy + Display + FromStr,
+{
+    /// Advertise `local_id` as reachable at `address`, the address of its transport, and start
+    /// browsing.
+    ///
+    /// # Errors
+    /// Returns the error of the mDNS daemon
+    pub fn advertise(local_id: Id, address: SocketAddr) -> Result<Self, mdns_sd::Error> {
+        let daemon = ServiceDaemon::new()?;
+        let name = format!("yaair-{local_id}");
+        let id = local_id.to_string();
+        let service = ServiceInfo::new(
+            SERVICE_TYPE,
+            &name,
+            &format!("{name}.local."),
+            address.ip(),
+            address.port(),
+            &[(ID_PROPERTY, id.as_str())][..],
+        )?;
+        let fullname = service.get_fullname().to_owned();
+        daemon.register(service)?;
+        let events = daemon.browse(SERVICE_TYPE)?;
+        Ok(Self {
+            local_id,
+            daemon,
+            fullname,
+            events,
+            found: HashMap::new(),
+        })
+    }
+}
+impl<Id> Discovery<Id> for MdnsDiscovery<Id>
+where
+    Id: Ord + Copy + Display + FromStr,
+{
+    fn discover(&mut self, _now: Duration) -> BTreeSet<Id> {
+        for event in self.events.try_iter() {
+            match event {
+                ServiceEvent::ServiceResolved(service) => {
+                    let id = service
+                        .get_property_val_str(ID_PROPERTY)
+                        .and_then(|id| id.parse().ok());
+                    if let Some(id) = id.filter(|id| *id != self.local_id) {
+                        self.found.insert(service.get_fullname().to_owned(), id);
+                    }
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    self.found.remove(&fullname);
+                }
+                ServiceEvent::SearchStarted(_)
+                | ServiceEvent::ServiceFound(..)
+                | ServiceEvent::SearchStopped(_) => {}
+            }
+        }
+        self.found.values().copied().collect()
+    }
+}
+impl<Id: Ord + Copy> Drop for MdnsDiscovery<Id> {
+    fn drop(&mut self) {
+        // withdraw the advertisement, so that the other devices lose this one promptly
+        let _ = self.daemon.unregister(&self.fullname);
+        let _ = self.daemon.shutdown();
+    }
+}
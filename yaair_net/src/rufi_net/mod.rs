@@ -0,0 +1,17 @@
+#[cfg(feature = "udp")]
+pub mod beacons;
+#[cfg(feature = "ble")]
+pub mod ble;
+#[cfg(feature = "encryption")]
+pub mod crypto;
+#[cfg(feature = "btleplug")]
+pub mod gatt;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "mdns")]
+pub mod mdns;
+pub mod neighbors;
+#[cfg(feature = "tcp")]
+pub mod tcp;
+#[cfg(feature = "udp")]
+pub mod udp;
@@ -0,0 +1,215 @@
+#[cfg(feature = "encryption")]
+use crate::rufi_net::crypto::GroupCipher;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::outbound::OutboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::messages::valuetree::ValueTree;
+use yaair::rufi::network::NetworkError;
+use yaair::rufi::sensors::neighborhood::{NeighborReading, NeighborhoodReadings};
+
+/// Reception time, sequence number and full tree of the last message of a neighbor.
+type LastMessage = (Instant, u64, ValueTree);
+
+/// Last message received from every neighbor, retained until `retention` expires.
+///
+/// Transports deliver messages asynchronously with respect to rounds: a neighbor that did not
+/// send anything since the previous round is still part of the neighborhood until its last
+/// message becomes too old.
+#[derive(Debug)]
+pub struct NeighborTable<Id: Ord + Hash + Copy> {
+    local_id: Id,
+    retention: Duration,
+    last_messages: HashMap<Id, LastMessage>,
+    pending: bool,
+    #[cfg(feature = "encryption")]
+    cipher: Option<GroupCipher>,
+}
+impl<Id> NeighborTable<Id>
+where
+    Id: DeviceId,
+{
+    pub fn new(local_id: Id, retention: Duration) -> Self {
+        Self {
+            local_id,
+            retention,
+            last_messages: HashMap::new(),
+            pending: false,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+
+    /// How long the last message of a silent neighbor is retained.
+    pub const fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    /// Encrypt the messages sent through [`NeighborTable::seal`] and require received ones to be
+    /// encrypted with the same group key.
+    #[cfg(feature = "encryption")]
+    pub fn set_cipher(&mut self, cipher: GroupCipher) {
+        self.cipher = Some(cipher);
+    }
+
+    /// Prepare a serialized outbound message for the transport, encrypting it if a cipher is set.
+    ///
+    /// # Errors
+    /// Returns [`NetworkError::Other`] if the message cannot be sealed and must not be sent
+    #[cfg_attr(
+        not(feature = "encryption"),
+        allow(
+            clippy::unnecessary_wraps,
+            clippy::missing_const_for_fn,
+            clippy::needless_pass_by_ref_mut
+        )
+    )]
+    pub fn seal(&mut self, outbound_message: Vec<u8>) -> Result<Vec<u8>, NetworkError> {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = self.cipher.as_mut() {
+            return cipher
+                .seal(&outbound_message)
+                .ok_or_else(|| NetworkError::Other("Failed to encrypt the message".into()));
+        }
+        Ok(outbound_message)
+    }
+
+    /// Decode a serialized [`OutboundMessage`] and store it as the last message of its sender.
+    ///
+    /// Messages sent by the local device (e.g. looped back by broadcast) are ignored, as well as
+    /// deltas whose base message has not been received.
+    ///
+    /// # Returns
+    /// `false` if the payload could not be decrypted or decoded, e.g. because of an unsupported
+    /// wire format version
+    pub fn receive<S: Serializer>(&mut self, serializer: &S, received: &[u8]) -> bool {
+        let Some(payload) = self.open(received) else {
+            return false;
+        };
+        let Ok(message) = OutboundMessage::<Id>::decode(serializer, &payload) else {
+            return false;
+        };
+        let sender = message.sender;
+        if sender == self.local_id {
+            return true;
+        }
+        let sequence = message.sequence();
+        let last = self
+            .last_messages
+            .get(&sender)
+            .map(|(_, last_sequence, tree)| (*last_sequence, tree));
+        if let Some(tree) = message.resolve(last) {
+            self.last_messages
+                .insert(sender, (Instant::now(), sequence, tree));
+            self.pending = true;
+        }
+        true
+    }
+
+    /// Decrypt a received message if a cipher is set.
+    #[cfg_attr(
+        not(feature = "encryption"),
+        allow(
+            clippy::unnecessary_wraps,
+            clippy::missing_const_for_fn,
+            clippy::unused_self,
+            clippy::needless_pass_by_ref_mut
+        )
+    )]
+    fn open<'a>(&mut self, received: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = self.cipher.as_mut() {
+            return cipher.open(received).map(Cow::Owned);
+        }
+        Some(Cow::Borrowed(received))
+    }
+
+    /// Whether messages have been received since the last call to [`NeighborTable::inbound`].
+    pub const fn has_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Evict expired neighbors and build the inbound message for the next round.
+    pub fn inbound(&mut self) -> InboundMessage<Id> {
+        let retention = self.retention;
+        self.last_messages
+            .retain(|_, (received_at, _, _)| received_at.elapsed() <= retention);
+        self.pending = false;
+        InboundMessage::new(
+            self.last_messages
+                .iter()
+                .map(|(id, (_, _, tree))| (*id, tree.clone()))
+                .collect(),
+        )
+    }
+
+    /// Age of the last message of every neighbor.
+    pub fn readings(&self) -> NeighborhoodReadings<Id> {
+        NeighborhoodReadings::new(
+            self.last_messages
+                .iter()
+                .map(|(id, (received_at, _, _))| {
+                    let reading = NeighborReading {
+                        lag: Some(received_at.elapsed()),
+                        ..NeighborReading::default()
+                    };
+                    (*id, reading)
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaair::rufi::messages::path::Path;
+    use yaair_serde::rufi_serde::json::JsonSerializer;
+
+    fn message(sequence: u64, value: u8) -> OutboundMessage<u32> {
+        let mut message = OutboundMessage::empty(1);
+        message.append(&Path::from("share:0"), vec![value]);
+        message.append(&Path::from("share:1"), vec![0]);
+        message.set_sequence(sequence);
+        message
+    }
+
+    fn received_value(table: &mut NeighborTable<u32>) -> Option<Vec<u8>> {
+        let inbound = table.inbound();
+        inbound
+            .get(&1)
+            .and_then(|tree| tree.get(&Path::from("share:0")))
+            .map(<[u8]>::to_vec)
+    }
+
+    #[test]
+    fn deltas_are_merged_only_on_their_base() {
+        let serializer = JsonSerializer;
+        let mut table = NeighborTable::new(0, Duration::from_secs(30));
+        let encode = |message: &OutboundMessage<u32>| serializer.serialize(message).unwrap();
+
+        assert!(table.receive(&serializer, &encode(&message(0, 1))));
+        assert!(table.receive(
+            &serializer,
+            &encode(&message(1, 2).delta_from(&message(0, 1)))
+        ));
+        assert_eq!(received_value(&mut table), Some(vec![2]));
+        assert!(table
+            .inbound()
+            .get(&1)
+            .unwrap()
+            .contains_key(&Path::from("share:1")));
+
+        // the delta based on message 2 is dropped, since message 2 was lost
+        assert!(table.receive(
+            &serializer,
+            &encode(&message(3, 4).delta_from(&message(2, 3)))
+        ));
+        assert!(!table.has_pending());
+        assert_eq!(received_value(&mut table), Some(vec![2]));
+    }
+}
@@ -0,0 +1,237 @@
+#[cfg(feature = "encryption")]
+use crate::rufi_net::crypto::GroupCipher;
+use crate::rufi_net::neighbors::NeighborTable;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+
+/// Size of the big-endian length prefix of every frame.
+const HEADER_SIZE: usize = 4;
+
+/// Configuration of a [`TcpNetwork`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpConfig {
+    /// Local address the listener is bound to.
+    pub bind: SocketAddr,
+    /// Addresses of the peers every outbound message is sent to.
+    pub peers: Vec<SocketAddr>,
+    /// How long to wait when (re)connecting to a peer.
+    pub connect_timeout: Duration,
+    /// Frames larger than this are considered corrupted and close the connection.
+    pub max_frame_size: usize,
+    /// How long the last message of a silent neighbor is retained.
+    pub retention: Duration,
+}
+impl TcpConfig {
+    pub const fn new(bind: SocketAddr) -> Self {
+        Self {
+            bind,
+            peers: Vec::new(),
+            connect_timeout: Duration::from_millis(200),
+            max_frame_size: 1 << 20,
+            retention: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_peer(mut self, peer: SocketAddr) -> Self {
+        self.peers.push(peer);
+        self
+    }
+
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+/// An accepted connection with the bytes received but not yet framed.
+struct Incoming {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+/// Connection-oriented [`Network`] sending length-prefixed frames to a static set of peers.
+///
+/// Outgoing connections are opened lazily and re-established at the next round after a failure.
+pub struct TcpNetwork<Id: Ord + Hash + Copy, S: Serializer> {
+    listener: TcpListener,
+    config: TcpConfig,
+    outgoing: HashMap<SocketAddr, TcpStream>,
+    incoming: Vec<Incoming>,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+}
+impl<Id, S> TcpNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    pub fn bind(local_id: Id, config: TcpConfig, serializer: S) -> io::Result<Self> {
+        let listener = TcpListener::bind(config.bind)?;
+        listener.set_nonblocking(true)?;
+        let neighbors = NeighborTable::new(local_id, config.retention);
+        Ok(Self {
+            listener,
+            config,
+            outgoing: HashMap::new(),
+            incoming: Vec::new(),
+            serializer,
+            neighbors,
+        })
+    }
+
+    /// Encrypt every message with the key shared by the group; see [`GroupCipher`].
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: GroupCipher) -> Self {
+        self.neighbors.set_cipher(cipher);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    fn send_to(&mut self, peer: SocketAddr, frame: &[u8]) -> io::Result<()> {
+        let stream = match self.outgoing.entry(peer) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let stream = TcpStream::connect_timeout(&peer, self.config.connect_timeout)?;
+                stream.set_nodelay(true)?;
+                entry.insert(stream)
+            }
+        };
+        stream.write_all(frame)
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // Accepted sockets inherit the listener mode on some platforms but not on others
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.incoming.push(Incoming {
+                            stream,
+                            buffer: Vec::new(),
+                        });
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn read_frames(&mut self) {
+        let max_frame_size = self.config.max_frame_size;
+        let serializer = &self.serializer;
+        let neighbors = &mut self.neighbors;
+        self.incoming.retain_mut(|connection| {
+            let open = read_available(&mut connection.stream, &mut connection.buffer);
+            while let Some(frame) = next_frame(&mut connection.buffer, max_frame_size) {
+                match frame {
+                    Ok(payload) => {
+                        neighbors.receive(serializer, &payload);
+                    }
+                    Err(()) => return false,
+                }
+            }
+            open
+        });
+    }
+}
+
+/// Read everything currently available on a non-blocking stream.
+///
+/// # Returns
+/// `false` once the peer has closed the connection or the connection failed
+fn read_available(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> bool {
+    let mut chunk = [0u8; 4096];
+    loop {
+        match stream.read(&mut chunk) {
+            Ok(0) => return false,
+            Ok(size) => buffer.extend_from_slice(chunk.get(..size).unwrap_or_default()),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return true,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Extract the next complete frame from `buffer`.
+///
+/// # Returns
+/// `None` if no complete frame is available, `Some(Err(()))` if the frame exceeds `max_size`
+fn next_frame(buffer: &mut Vec<u8>, max_size: usize) -> Option<Result<Vec<u8>, ()>> {
+    let header: [u8; HEADER_SIZE] = buffer.get(..HEADER_SIZE)?.try_into().ok()?;
+    let Ok(size) = usize::try_from(u32::from_be_bytes(header)) else {
+        return Some(Err(()));
+    };
+    if size > max_size {
+        return Some(Err(()));
+    }
+    let end = HEADER_SIZE.checked_add(size)?;
+    let payload = buffer.get(HEADER_SIZE..end)?.to_vec();
+    buffer.drain(..end);
+    Some(Ok(payload))
+}
+
+impl<Id, S> Network<Id, S> for TcpNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        let outbound_message = self.neighbors.seal(outbound_message)?;
+        let size = u32::try_from(outbound_message.len())
+            .map_err(|_| NetworkError::TooLarge(outbound_message.len()))?;
+        let mut frame = size.to_be_bytes().to_vec();
+        frame.extend_from_slice(&outbound_message);
+        let mut failure = None;
+        for peer in self.config.peers.clone() {
+            if let Err(err) = self.send_to(peer, &frame) {
+                // Reconnect at the next round
+                self.outgoing.remove(&peer);
+                failure.get_or_insert_with(|| NetworkError::from(err));
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        self.accept();
+        self.read_frames();
+        self.neighbors.inbound()
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_extracted_once_complete() {
+        let mut buffer = vec![0, 0, 0, 3, b'a', b'b'];
+        assert_eq!(next_frame(&mut buffer, 16), None);
+        buffer.extend_from_slice(&[b'c', 0, 0]);
+        assert_eq!(next_frame(&mut buffer, 16), Some(Ok(b"abc".to_vec())));
+        assert_eq!(buffer, vec![0, 0]);
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut buffer = vec![0, 0, 1, 0];
+        assert_eq!(next_frame(&mut buffer, 16), Some(Err(())));
+    }
+}
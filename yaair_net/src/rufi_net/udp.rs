@@ -0,0 +1,159 @@
+#[cfg(feature = "encryption")]
+use crate::rufi_net::crypto::GroupCipher;
+use crate::rufi_net::neighbors::NeighborTable;
+use std::hash::Hash;
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+
+/// Maximum payload of a UDP datagram over IPv4.
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Configuration of a [`UdpNetwork`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdpConfig {
+    /// Local address the socket is bound to.
+    pub bind: SocketAddr,
+    /// Addresses every outbound message is sent to (peers, broadcast or multicast groups).
+    pub targets: Vec<SocketAddr>,
+    /// Allow sending to broadcast addresses (`SO_BROADCAST`).
+    pub broadcast: bool,
+    /// Multicast groups to join, as `(group, interface)` pairs.
+    pub multicast_groups: Vec<(Ipv4Addr, Ipv4Addr)>,
+    /// How long the last message of a silent neighbor is retained.
+    pub retention: Duration,
+}
+impl UdpConfig {
+    pub const fn new(bind: SocketAddr) -> Self {
+        Self {
+            bind,
+            targets: Vec::new(),
+            broadcast: false,
+            multicast_groups: Vec::new(),
+            retention: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_target(mut self, target: SocketAddr) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    pub const fn with_broadcast(mut self, broadcast: bool) -> Self {
+        self.broadcast = broadcast;
+        self
+    }
+
+    pub fn with_multicast_group(mut self, group: Ipv4Addr, interface: Ipv4Addr) -> Self {
+        self.multicast_groups.push((group, interface));
+        self
+    }
+
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+}
+
+/// Connectionless [`Network`] exchanging one datagram per round with every target.
+///
+/// The socket is non-blocking: inbound datagrams are drained at the beginning of every round.
+pub struct UdpNetwork<Id: Ord + Hash + Copy, S: Serializer> {
+    socket: UdpSocket,
+    targets: Vec<SocketAddr>,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+    buffer: Vec<u8>,
+}
+impl<Id, S> UdpNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    pub fn bind(local_id: Id, config: UdpConfig, serializer: S) -> io::Result<Self> {
+        let socket = UdpSocket::bind(config.bind)?;
+        socket.set_nonblocking(true)?;
+        socket.set_broadcast(config.broadcast)?;
+        for (group, interface) in &config.multicast_groups {
+            socket.join_multicast_v4(group, interface)?;
+        }
+        Ok(Self {
+            socket,
+            targets: config.targets,
+            serializer,
+            neighbors: NeighborTable::new(local_id, config.retention),
+            buffer: vec![0; MAX_DATAGRAM],
+        })
+    }
+
+    /// Encrypt every message with the key shared by the group; see [`GroupCipher`].
+    #[cfg(feature = "encryption")]
+    #[must_use]
+    pub fn with_cipher(mut self, cipher: GroupCipher) -> Self {
+        self.neighbors.set_cipher(cipher);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn drain(&mut self) {
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((size, _)) => {
+                    if let Some(payload) = self.buffer.get(..size) {
+                        self.neighbors.receive(&self.serializer, payload);
+                    }
+                }
+                // Windows reports ICMP port-unreachable of previous sends as a receive error
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+impl<Id, S> Network<Id, S> for UdpNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        let outbound_message = self.neighbors.seal(outbound_message)?;
+        if outbound_message.len() > MAX_DATAGRAM {
+            return Err(NetworkError::TooLarge(outbound_message.len()));
+        }
+        let mut failure = None;
+        for target in &self.targets {
+            // keep sending to the other targets, reporting the first failure
+            if let Err(err) = self.socket.send_to(&outbound_message, target) {
+                failure.get_or_insert_with(|| NetworkError::from(err));
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        self.drain();
+        self.neighbors.inbound()
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        let mut probe = [0u8; 1];
+        self.neighbors.has_pending()
+            || match self.socket.peek_from(&mut probe) {
+                Ok(_) => true,
+                // Windows fails with WSAEMSGSIZE when the datagram exceeds the probe buffer
+                Err(err) => err.kind() != ErrorKind::WouldBlock,
+            }
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
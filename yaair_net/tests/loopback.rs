@@ -0,0 +1,186 @@
+//! Loopback integration tests for the socket backends.
+//!
+//! CI runs them on Linux, macOS and Windows, where socket options (broadcast, non-blocking
+//! accept, ICMP errors on UDP sockets) behave differently.
+
+#![cfg(any(feature = "udp", feature = "tcp", feature = "http"))]
+
+#[cfg(feature = "udp")]
+use std::net::UdpSocket;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use yaair::rufi::aggregate::{Aggregate, AggregateError, VM};
+use yaair::rufi::engine::Engine;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::network::Network;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+fn loopback() -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, 0))
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // signature imposed by `Engine`
+fn neighbors_count(_env: &(), vm: &mut VM<u32, JsonSerializer>) -> Result<usize, AggregateError> {
+    let id = vm.local_id;
+    vm.neighboring(&id).map(|field| field.size())
+}
+
+/// Poll `network` until it reports a non-empty neighborhood or the timeout expires.
+fn wait_for_neighbors<N: Network<u32, JsonSerializer>>(network: &mut N) -> InboundMessage<u32> {
+    let started = Instant::now();
+    loop {
+        let inbound = network.prepare_inbound();
+        if inbound.get(&1).is_some() || started.elapsed() > Duration::from_secs(5) {
+            return inbound;
+        }
+        sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(feature = "udp")]
+mod udp {
+    use super::*;
+    use yaair_net::rufi_net::udp::{UdpConfig, UdpNetwork};
+
+    #[test]
+    fn engines_exchange_datagrams() {
+        let mut receiver =
+            UdpNetwork::bind(2u32, UdpConfig::new(loopback()), JsonSerializer).unwrap();
+        let sender_config = UdpConfig::new(loopback()).with_target(receiver.local_addr().unwrap());
+        let sender = UdpNetwork::bind(1u32, sender_config, JsonSerializer).unwrap();
+        let mut sender = Engine::new(1u32, sender, (), JsonSerializer, neighbors_count);
+        assert_eq!(sender.cycle().map(|report| report.output), Ok(Ok(1)));
+        let inbound = wait_for_neighbors(&mut receiver);
+        assert!(inbound.get(&1).is_some());
+    }
+
+    #[test]
+    fn own_messages_are_ignored() {
+        let config = UdpConfig::new(loopback());
+        let mut network = UdpNetwork::bind(1u32, config, JsonSerializer).unwrap();
+        let address = network.local_addr().unwrap();
+        let socket = UdpSocket::bind(loopback()).unwrap();
+        let own = br#"{"sender":1,"underlying":{}}"#;
+        socket.send_to(own, address).unwrap();
+        sleep(Duration::from_millis(50));
+        assert!(network.prepare_inbound().get(&1).is_none());
+    }
+
+    #[test]
+    fn sending_to_a_closed_port_does_not_break_receiving() {
+        let closed = UdpSocket::bind(loopback()).unwrap().local_addr().unwrap();
+        let config = UdpConfig::new(loopback()).with_target(closed);
+        let mut network = UdpNetwork::bind(1u32, config, JsonSerializer).unwrap();
+        Network::<u32, JsonSerializer>::prepare_outbound(&mut network, b"{}".to_vec()).unwrap();
+        sleep(Duration::from_millis(50));
+        let inbound = network.prepare_inbound();
+        assert!(inbound.get(&2).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn encrypted_datagrams_require_the_group_key() {
+        use yaair_net::rufi_net::crypto::{GroupCipher, KEY_SIZE};
+        let key = [42; KEY_SIZE];
+        let mut receiver = UdpNetwork::bind(2u32, UdpConfig::new(loopback()), JsonSerializer)
+            .unwrap()
+            .with_cipher(GroupCipher::new(&key, 2));
+        let target = receiver.local_addr().unwrap();
+
+        let outsider = UdpNetwork::bind(
+            3u32,
+            UdpConfig::new(loopback()).with_target(target),
+            JsonSerializer,
+        )
+        .unwrap()
+        .with_cipher(GroupCipher::new(&[0; KEY_SIZE], 3));
+        let mut outsider = Engine::new(3u32, outsider, (), JsonSerializer, neighbors_count);
+        assert_eq!(outsider.cycle().map(|report| report.output), Ok(Ok(1)));
+
+        let member = UdpNetwork::bind(
+            1u32,
+            UdpConfig::new(loopback()).with_target(target),
+            JsonSerializer,
+        )
+        .unwrap()
+        .with_cipher(GroupCipher::new(&key, 1));
+        let mut member = Engine::new(1u32, member, (), JsonSerializer, neighbors_count);
+        assert_eq!(member.cycle().map(|report| report.output), Ok(Ok(1)));
+
+        let inbound = wait_for_neighbors(&mut receiver);
+        assert!(inbound.get(&1).is_some());
+        assert!(inbound.get(&3).is_none());
+    }
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use super::*;
+    use yaair_net::rufi_net::http::{HttpConfig, HttpGateway, HttpNetwork};
+
+    #[test]
+    fn engines_exchange_messages_through_the_gateway() {
+        let mut gateway = HttpGateway::bind(loopback()).unwrap();
+        let address = gateway.local_addr().unwrap();
+        std::thread::spawn(move || gateway.serve());
+
+        let mut receiver =
+            HttpNetwork::new(2u32, HttpConfig::new(address, "receiver"), JsonSerializer).unwrap();
+        let sender =
+            HttpNetwork::new(1u32, HttpConfig::new(address, "sender"), JsonSerializer).unwrap();
+        let mut sender = Engine::new(1u32, sender, (), JsonSerializer, neighbors_count);
+        assert_eq!(sender.cycle().map(|report| report.output), Ok(Ok(1)));
+        let inbound = wait_for_neighbors(&mut receiver);
+        assert!(inbound.get(&1).is_some());
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let config = HttpConfig::new(loopback(), "../admin");
+        assert!(HttpNetwork::<u32, _>::new(1, config, JsonSerializer).is_err());
+    }
+
+    #[test]
+    fn unreachable_gateway_is_skipped() {
+        let unreachable = {
+            let listener = std::net::TcpListener::bind(loopback()).unwrap();
+            listener.local_addr().unwrap()
+        };
+        let network =
+            HttpNetwork::new(1u32, HttpConfig::new(unreachable, "alone"), JsonSerializer).unwrap();
+        let mut engine = Engine::new(1u32, network, (), JsonSerializer, neighbors_count);
+        assert_eq!(engine.cycle().map(|report| report.output), Ok(Ok(1)));
+    }
+}
+
+#[cfg(feature = "tcp")]
+mod tcp {
+    use super::*;
+    use yaair_net::rufi_net::tcp::{TcpConfig, TcpNetwork};
+
+    #[test]
+    fn engines_exchange_frames() {
+        let mut receiver =
+            TcpNetwork::bind(2u32, TcpConfig::new(loopback()), JsonSerializer).unwrap();
+        let sender_config = TcpConfig::new(loopback()).with_peer(receiver.local_addr().unwrap());
+        let sender = TcpNetwork::bind(1u32, sender_config, JsonSerializer).unwrap();
+        let mut sender = Engine::new(1u32, sender, (), JsonSerializer, neighbors_count);
+        assert_eq!(sender.cycle().map(|report| report.output), Ok(Ok(1)));
+        let inbound = wait_for_neighbors(&mut receiver);
+        assert!(inbound.get(&1).is_some());
+    }
+
+    #[test]
+    fn unreachable_peers_are_skipped() {
+        let unreachable = {
+            let listener = std::net::TcpListener::bind(loopback()).unwrap();
+            listener.local_addr().unwrap()
+        };
+        let config = TcpConfig::new(loopback()).with_peer(unreachable);
+        let network = TcpNetwork::bind(1u32, config, JsonSerializer).unwrap();
+        let mut engine = Engine::new(1u32, network, (), JsonSerializer, neighbors_count);
+        assert_eq!(engine.cycle().map(|report| report.output), Ok(Ok(1)));
+        assert_eq!(engine.cycle().map(|report| report.output), Ok(Ok(1)));
+    }
+}
@@ -0,0 +1,14 @@
+// the pyo3 macros still depend on syn 2, serde on syn 3
+#![allow(clippy::multiple_crate_versions)]
+
+pub mod rufi_py;
+
+use pyo3::prelude::*;
+
+/// Python module `yaair_py`, built with maturin.
+#[pymodule]
+fn yaair_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<rufi_py::program::Program>()?;
+    module.add_class::<rufi_py::simulator::PySimulator>()?;
+    Ok(())
+}
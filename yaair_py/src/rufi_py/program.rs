@@ -0,0 +1,147 @@
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use yaair::rufi::aggregate::{Aggregate, AggregateError, VM};
+use yaair::rufi::blocks::area::count_devices_in_area;
+use yaair::rufi::blocks::channel::channel;
+use yaair::rufi::blocks::collect::collect_sum;
+use yaair::rufi::blocks::gradient::{gradient, hop_gradient};
+use yaair::rufi::blocks::leader::elect_leader;
+use yaair::rufi::data::field::Field;
+use yaair::rufi::sensors::neighborhood::NeighborhoodSensors;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Values set from Python for every device, e.g. which devices are sources.
+pub type Sensors = Rc<RefCell<BTreeMap<u32, BTreeMap<String, f64>>>>;
+
+/// Block of the library run by every device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Block {
+    Gradient,
+    HopGradient,
+    Channel { width: f64 },
+    ElectLeader { grain: f64 },
+    CountInArea { radius: f64 },
+    CollectSum,
+}
+
+/// Aggregate program of the blocks library, reading its inputs from sensors set from Python.
+///
+/// Boolean sensors (`source`, `target`, `inside`) hold when their value is not zero, and every
+/// sensor defaults to zero; results are converted to floats.
+#[pyclass(frozen, skip_from_py_object)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Program {
+    block: Block,
+}
+#[pymethods]
+impl Program {
+    /// Distance from the closest `source`.
+    #[staticmethod]
+    pub const fn gradient() -> Self {
+        Self {
+            block: Block::Gradient,
+        }
+    }
+
+    /// Number of hops to the closest `source`.
+    #[staticmethod]
+    pub const fn hop_gradient() -> Self {
+        Self {
+            block: Block::HopGradient,
+        }
+    }
+
+    /// 1 on the devices along the shortest path between the closest `source` and `target`,
+    /// tolerating detours up to `width`, 0 elsewhere.
+    #[staticmethod]
+    pub const fn channel(width: f64) -> Self {
+        Self {
+            block: Block::Channel { width },
+        }
+    }
+
+    /// Id of the leader followed by the device, elected within distance `grain`.
+    #[staticmethod]
+    pub const fn elect_leader(grain: f64) -> Self {
+        Self {
+            block: Block::ElectLeader { grain },
+        }
+    }
+
+    /// Number of devices in the area where `inside` holds, with leaders elected within `radius`.
+    #[staticmethod]
+    pub const fn count_in_area(radius: f64) -> Self {
+        Self {
+            block: Block::CountInArea { radius },
+        }
+    }
+
+    /// Sum of the `value` sensors collected by the closest `source`.
+    #[staticmethod]
+    pub const fn collect_sum() -> Self {
+        Self {
+            block: Block::CollectSum,
+        }
+    }
+
+    // Python methods cannot take `self` by value
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn __repr__(&self) -> String {
+        match self.block {
+            Block::Gradient => "Program.gradient()".to_owned(),
+            Block::HopGradient => "Program.hop_gradient()".to_owned(),
+            Block::Channel { width } => format!("Program.channel({width})"),
+            Block::ElectLeader { grain } => format!("Program.elect_leader({grain})"),
+            Block::CountInArea { radius } => format!("Program.count_in_area({radius})"),
+            Block::CollectSum => "Program.collect_sum()".to_owned(),
+        }
+    }
+}
+
+// methods taking Rust types cannot be exported, hence the separate block
+#[allow(clippy::multiple_inherent_impl)]
+impl Program {
+    /// Execute a round of the program on `vm`, measuring distances with the ranges of the
+    /// neighbors if `ranged`, in hops otherwise.
+    pub(crate) fn execute(
+        self,
+        id: u32,
+        vm: &mut VM<u32, JsonSerializer>,
+        sensors: &Sensors,
+        ranged: bool,
+    ) -> Result<f64, AggregateError> {
+        let sensor = |name: &str| {
+            sensors
+                .borrow()
+                .get(&id)
+                .and_then(|values| values.get(name))
+                .copied()
+                .unwrap_or_default()
+        };
+        let metric: Field<u32, f64> = if ranged {
+            vm.nbr_range()
+        } else {
+            vm.neighboring(&())?.map(|()| 1.0)
+        };
+        let source = sensor("source") != 0.0;
+        match self.block {
+            Block::Gradient => gradient(vm, source, &metric),
+            Block::HopGradient => hop_gradient(vm, source),
+            Block::Channel { width } => {
+                let target = sensor("target") != 0.0;
+                let on_path = channel(vm, source, target, width, &metric)?;
+                Ok(if on_path { 1.0 } else { 0.0 })
+            }
+            Block::ElectLeader { grain } => elect_leader(vm, &id, grain, &metric).map(f64::from),
+            Block::CountInArea { radius } => {
+                count_devices_in_area(vm, sensor("inside") != 0.0, &id, radius, &metric)
+            }
+            Block::CollectSum => {
+                let potential = gradient(vm, source, &metric)?;
+                collect_sum(vm, potential, sensor("value"))
+            }
+        }
+    }
+}
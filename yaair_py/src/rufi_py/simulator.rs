@@ -0,0 +1,193 @@
+use crate::rufi_py::program::{Program, Sensors};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use yaair::rufi::aggregate::AggregateError;
+use yaair::rufi::simulator::simulation::{Execution, JoinPolicy, Simulator};
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Result of the last round of every device.
+type Results = BTreeMap<u32, f64>;
+
+/// Python handle to a [`Simulator`] running a [`Program`] on devices identified by integers.
+///
+/// Devices are linked explicitly with `connect`, or by distance when they are placed and a
+/// communication range is given, in which case the blocks measure distances instead of hops.
+/// Rounds are executed in Rust; only the results cross into Python.
+///
+/// ```python
+/// simulator = Simulator(Program.gradient(), communication_range=1.5)
+/// for id in range(10):
+///     simulator.add_device(id, (float(id), 0.0))
+/// simulator.set_sensor(0, "source", 1.0)
+/// history = simulator.run(20)
+/// ```
+#[pyclass(unsendable, name = "Simulator")]
+pub struct PySimulator {
+    simulator: Simulator<'static, u32, JsonSerializer, Result<f64, AggregateError>>,
+    sensors: Sensors,
+}
+#[pymethods]
+impl PySimulator {
+    /// Simulate `program`, in lockstep rounds or in rounds where devices fire one at a time in
+    /// an order shuffled with `seed`.
+    #[new]
+    #[pyo3(signature = (program, communication_range = None, seed = None))]
+    pub fn new(
+        program: &Bound<'_, Program>,
+        communication_range: Option<f64>,
+        seed: Option<u64>,
+    ) -> Self {
+        let block = *program.get();
+        let sensors = Sensors::default();
+        let shared = Rc::clone(&sensors);
+        let ranged = communication_range.is_some();
+        let mut simulator = Simulator::new(JsonSerializer, move |id, vm| {
+            block.execute(id, vm, &shared, ranged)
+        });
+        if let Some(range) = communication_range {
+            simulator = simulator.with_communication_range(range);
+        }
+        if let Some(seed) = seed {
+            simulator.set_execution(Execution::FairAsync { seed });
+        }
+        Self { simulator, sensors }
+    }
+
+    /// Add a device, optionally placed at `position`, taking part in the rounds from the next
+    /// one; returns `False` if it is already running.
+    #[pyo3(signature = (id, position = None))]
+    pub fn add_device(&mut self, id: u32, position: Option<(f64, f64)>) -> bool {
+        match position {
+            Some(position) => self
+                .simulator
+                .add_device_at(id, position, JoinPolicy::Fresh),
+            None => self.simulator.add_device(id, JoinPolicy::Fresh),
+        }
+    }
+
+    /// Remove a device and its links; returns `False` if it is not running.
+    pub fn remove_device(&mut self, id: u32) -> bool {
+        self.simulator.remove_device(id)
+    }
+
+    pub fn set_position(&mut self, id: u32, position: (f64, f64)) {
+        self.simulator.set_position(id, position);
+    }
+
+    pub fn connect(&mut self, a: u32, b: u32) {
+        self.simulator.connect(a, b);
+    }
+
+    pub fn disconnect(&mut self, a: u32, b: u32) {
+        self.simulator.disconnect(a, b);
+    }
+
+    /// Set the sensor `name` of device `id`, read by the program from the next round.
+    pub fn set_sensor(&mut self, id: u32, name: String, value: f64) {
+        self.sensors
+            .borrow_mut()
+            .entry(id)
+            .or_default()
+            .insert(name, value);
+    }
+
+    /// Execute a round, returning the result of every device.
+    pub fn step(&mut self) -> PyResult<Results> {
+        self.simulator.step().map_err(|err| error(&err))?;
+        self.results()
+    }
+
+    /// Execute `rounds` rounds, returning the results of every device after each of them, e.g.
+    /// to plot how the program converges.
+    pub fn run(&mut self, rounds: usize) -> PyResult<Vec<Results>> {
+        (0..rounds).map(|_| self.step()).collect()
+    }
+
+    /// Result of the last round of every device.
+    ///
+    /// Raises `RuntimeError` if the round failed on any device.
+    pub fn results(&self) -> PyResult<Results> {
+        self.simulator
+            .results()
+            .iter()
+            .map(|(id, result)| result.as_ref().map(|value| (*id, *value)).map_err(error))
+            .collect()
+    }
+
+    /// Position of every placed device, e.g. to draw the results on a scatter plot.
+    pub fn positions(&self) -> BTreeMap<u32, (f64, f64)> {
+        self.simulator
+            .devices()
+            .filter_map(|id| self.simulator.position(id).map(|position| (id, position)))
+            .collect()
+    }
+
+    /// Ids of the neighbors of device `id`.
+    pub fn neighbors(&self, id: u32) -> Vec<u32> {
+        self.simulator
+            .neighbors(id)
+            .into_iter()
+            .map(|(neighbor, _)| neighbor)
+            .collect()
+    }
+
+    /// Number of rounds executed so far.
+    #[getter]
+    pub const fn round(&self) -> u64 {
+        self.simulator.round()
+    }
+}
+
+fn error(err: &AggregateError) -> PyErr {
+    PyRuntimeError::new_err(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_converges_along_a_line() {
+        Python::initialize();
+        let program = Python::attach(|py| Py::new(py, Program::hop_gradient()).unwrap());
+        let mut simulator = Python::attach(|py| PySimulator::new(program.bind(py), None, None));
+        for id in 0..4 {
+            assert!(simulator.add_device(id, None));
+        }
+        for id in 1..4 {
+            simulator.connect(id - 1, id);
+        }
+        simulator.set_sensor(0, "source".to_owned(), 1.0);
+        let history = simulator.run(5).unwrap();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.last(), Some(&simulator.results().unwrap()));
+        assert_eq!(simulator.results().unwrap().get(&3), Some(&3.0));
+        assert_eq!(simulator.round(), 5);
+    }
+
+    #[test]
+    fn ranged_channel_marks_the_shortest_path() {
+        Python::initialize();
+        let program = Python::attach(|py| Py::new(py, Program::channel(0.5)).unwrap());
+        let mut simulator =
+            Python::attach(|py| PySimulator::new(program.bind(py), Some(1.5), Some(7)));
+        for x in 0..3 {
+            for y in 0..3 {
+                simulator.add_device(x * 3 + y, Some((f64::from(x), f64::from(y))));
+            }
+        }
+        simulator.set_sensor(0, "source".to_owned(), 1.0);
+        simulator.set_sensor(2, "target".to_owned(), 1.0);
+        simulator.run(10).unwrap();
+        let results = simulator.results().unwrap();
+        let on_path: Vec<u32> = results
+            .iter()
+            .filter(|(_, value)| **value > 0.5)
+            .map(|(id, _)| *id)
+            .collect();
+        assert_eq!(on_path, vec![0, 1, 2]);
+        assert_eq!(simulator.positions().len(), 9);
+    }
+}
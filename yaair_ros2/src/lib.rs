@@ -0,0 +1 @@
+pub mod rufi_ros2;
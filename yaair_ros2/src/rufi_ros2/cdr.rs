@@ -0,0 +1,82 @@
+//! Encoding of the few ROS 2 messages handled by the adapter, in the serialized form (CDR with
+//! its encapsulation header) exchanged by the client libraries.
+
+/// Encapsulation header of a little-endian CDR message.
+const CDR_LE: [u8; 4] = [0x00, 0x01, 0x00, 0x00];
+
+/// Size of the encapsulation header preceding every message.
+const HEADER_SIZE: usize = 4;
+
+/// Serialize `data` as a `std_msgs/msg/UInt8MultiArray` with an empty layout.
+pub fn encode_byte_array(data: &[u8]) -> Option<Vec<u8>> {
+    let length = u32::try_from(data.len()).ok()?;
+    let mut message = Vec::with_capacity(data.len().saturating_add(16));
+    message.extend_from_slice(&CDR_LE);
+    // layout: no dimensions, no data offset
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(&length.to_le_bytes());
+    message.extend_from_slice(data);
+    Some(message)
+}
+
+/// Deserialize the data of a `std_msgs/msg/UInt8MultiArray` with an empty layout, as published
+/// by [`encode_byte_array`].
+pub fn decode_byte_array(message: &[u8]) -> Option<&[u8]> {
+    let little_endian = little_endian(message)?;
+    let body = message.get(HEADER_SIZE..)?;
+    let read_u32 = |offset: usize| {
+        let bytes: [u8; 4] = body.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    if read_u32(0)? != 0 {
+        return None;
+    }
+    let length = usize::try_from(read_u32(8)?).ok()?;
+    body.get(12..length.checked_add(12)?)
+}
+
+/// Deserialize a `std_msgs/msg/Float64`, e.g. a distance published by a range sensor.
+pub fn decode_float64(message: &[u8]) -> Option<f64> {
+    let little_endian = little_endian(message)?;
+    let bytes: [u8; 8] = message.get(HEADER_SIZE..HEADER_SIZE + 8)?.try_into().ok()?;
+    Some(if little_endian {
+        f64::from_le_bytes(bytes)
+    } else {
+        f64::from_be_bytes(bytes)
+    })
+}
+
+/// Serialize `value` as a `std_msgs/msg/Float64`.
+pub fn encode_float64(value: f64) -> Vec<u8> {
+    let mut message = CDR_LE.to_vec();
+    message.extend_from_slice(&value.to_le_bytes());
+    message
+}
+
+/// Byte order of a message, from its encapsulation header.
+fn little_endian(message: &[u8]) -> Option<bool> {
+    match message.get(..2)? {
+        [0x00, 0x00] => Some(false),
+        [0x00, 0x01] => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_arrays_round_trip() {
+        let message = encode_byte_array(b"exports").unwrap();
+        assert_eq!(message.len(), 4 + 12 + 7);
+        assert_eq!(decode_byte_array(&message), Some(&b"exports"[..]));
+        assert_eq!(decode_byte_array(message.split_at(20).0), None);
+        assert_eq!(decode_float64(&encode_float64(1.5)), Some(1.5));
+    }
+}
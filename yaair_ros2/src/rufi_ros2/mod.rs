@@ -0,0 +1,4 @@
+pub mod cdr;
+pub mod network;
+pub mod node;
+pub mod sensors;
@@ -0,0 +1,91 @@
+use crate::rufi_ros2::cdr;
+use crate::rufi_ros2::node::RosNode;
+use std::hash::Hash;
+use std::time::Duration;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::NeighborhoodReadings;
+use yaair_net::rufi_net::neighbors::NeighborTable;
+
+/// Topic shared by the robots of a swarm unless configured otherwise.
+pub const SWARM_TOPIC: &str = "/yaair/swarm";
+
+/// [`Network`] publishing the outbound message of every round on a topic shared by the swarm,
+/// and collecting the messages of the neighbors from its subscription.
+///
+/// Messages are `std_msgs/msg/UInt8MultiArray`, so that they can be inspected with the ROS
+/// tooling (`ros2 topic echo`, bags). Which robots are neighbors is decided by the ROS middleware:
+/// combine with a [`Discovery`](yaair::rufi::discovery::Discovery) to restrict the neighborhood,
+/// e.g. by distance.
+pub struct RosNetwork<N: RosNode, Id: Ord + Hash + Copy, S: Serializer> {
+    node: N,
+    topic: String,
+    serializer: S,
+    neighbors: NeighborTable<Id>,
+}
+impl<N, Id, S> RosNetwork<N, Id, S>
+where
+    N: RosNode,
+    Id: DeviceId,
+    S: Serializer,
+{
+    /// Exchange the messages of `local_id` on [`SWARM_TOPIC`], retaining silent neighbors for
+    /// 5 seconds.
+    pub fn new(node: N, local_id: Id, serializer: S) -> Self {
+        Self {
+            node,
+            topic: SWARM_TOPIC.to_owned(),
+            serializer,
+            neighbors: NeighborTable::new(local_id, Duration::from_secs(5)),
+        }
+    }
+
+    /// Exchange the messages on `topic`, e.g. to run several swarms on the same ROS domain.
+    #[must_use]
+    pub fn with_topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = topic.into();
+        self
+    }
+
+    /// How long the last message of a silent neighbor is retained.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.neighbors.set_retention(retention);
+        self
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+}
+impl<N, Id, S> Network<Id, S> for RosNetwork<N, Id, S>
+where
+    N: RosNode,
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        let message = cdr::encode_byte_array(&outbound_message)
+            .ok_or(NetworkError::TooLarge(outbound_message.len()))?;
+        self.node.publish(&self.topic, &message)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        for message in self.node.take(&self.topic) {
+            if let Some(payload) = cdr::decode_byte_array(&message) {
+                self.neighbors.receive(&self.serializer, payload);
+            }
+        }
+        self.neighbors.inbound()
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        self.neighbors.has_pending() || self.node.has_pending(&self.topic)
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        self.neighbors.readings()
+    }
+}
@@ -0,0 +1,42 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use yaair::rufi::network::NetworkError;
+
+/// The ROS 2 node hosting an engine, as seen by the adapter: serialized (CDR) messages published
+/// on and taken from topics.
+///
+/// Client libraries expose serialized publishers and subscriptions (e.g. the untyped ones of
+/// `r2r` or the serialized messages of `rclrs`), so a binding only forwards bytes: subscriptions
+/// are created on the first [`RosNode::take`] of a topic and buffer the messages received
+/// between two calls.
+pub trait RosNode {
+    /// Publish a serialized message on `topic`.
+    ///
+    /// # Errors
+    /// Returns why the message could not be published
+    fn publish(&mut self, topic: &str, message: &[u8]) -> Result<(), NetworkError>;
+
+    /// Take the serialized messages received on `topic` since the last call, oldest first.
+    fn take(&mut self, topic: &str) -> Vec<Vec<u8>>;
+
+    /// Whether messages are waiting on `topic`; nodes that cannot tell report `true`.
+    fn has_pending(&self, _topic: &str) -> bool {
+        true
+    }
+}
+
+/// A node shared by the [`RosNetwork`](crate::rufi_ros2::network::RosNetwork) and the
+/// [`TopicSensors`](crate::rufi_ros2::sensors::TopicSensors) of the same engine.
+impl<N: RosNode> RosNode for Rc<RefCell<N>> {
+    fn publish(&mut self, topic: &str, message: &[u8]) -> Result<(), NetworkError> {
+        self.borrow_mut().publish(topic, message)
+    }
+
+    fn take(&mut self, topic: &str) -> Vec<Vec<u8>> {
+        self.borrow_mut().take(topic)
+    }
+
+    fn has_pending(&self, topic: &str) -> bool {
+        self.borrow().has_pending(topic)
+    }
+}
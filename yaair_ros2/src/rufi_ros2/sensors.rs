@@ -0,0 +1,80 @@
+use crate::rufi_ros2::node::RosNode;
+use std::time::Duration;
+use yaair::rufi::aggregate::AggregateError;
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::engine::{Engine, RoundReport};
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::Network;
+use yaair::rufi::scheduler::Scheduler;
+use yaair::rufi::sensors::typed::{EnvKey, TypedEnv};
+
+/// Stores the value decoded from a serialized message into the environment, returning `false`
+/// if the message could not be decoded.
+type Update = Box<dyn Fn(&[u8], &mut TypedEnv) -> bool>;
+
+/// Entries of a [`TypedEnv`] fed by ROS topics, e.g. the readings of a range sensor or the pose
+/// estimated by the localization stack.
+///
+/// Only the last message of every topic matters: older ones received between two rounds are
+/// skipped, and a message that cannot be decoded leaves the previous value in place.
+pub struct TopicSensors<N: RosNode> {
+    node: N,
+    sensors: Vec<(String, Update)>,
+}
+impl<N: RosNode> TopicSensors<N> {
+    pub const fn new(node: N) -> Self {
+        Self {
+            node,
+            sensors: Vec::new(),
+        }
+    }
+
+    /// Feed the entry `K` with the messages of `topic`, deserialized by `decode` (see
+    /// [`cdr`](crate::rufi_ros2::cdr) for common message types).
+    #[must_use]
+    pub fn with_sensor<K: EnvKey>(
+        mut self,
+        topic: impl Into<String>,
+        decode: impl Fn(&[u8]) -> Option<K::Value> + 'static,
+    ) -> Self {
+        let update: Update = Box::new(move |message, environment| {
+            decode(message)
+                .map(|value| environment.insert::<K>(value))
+                .is_some()
+        });
+        self.sensors.push((topic.into(), update));
+        self
+    }
+
+    /// Store the last message received on every sensor topic into `environment`.
+    pub fn refresh(&mut self, environment: &mut TypedEnv) {
+        for (topic, update) in &self.sensors {
+            // fall back to older messages if the last one is malformed
+            for message in self.node.take(topic).iter().rev() {
+                if update(message, environment) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Refresh the environment of `engine`, then execute a round if its scheduler considers it
+    /// due at `now`; call it from a ROS timer or after spinning the executor.
+    ///
+    /// # Returns
+    /// `None` if no round was due, the round result otherwise
+    pub fn spin_once<Id, Out, S, Net, Sch>(
+        &mut self,
+        engine: &mut Engine<Id, Out, TypedEnv, S, Net, Sch>,
+        now: Duration,
+    ) -> Option<Result<RoundReport<Out>, AggregateError>>
+    where
+        Id: DeviceId,
+        S: Serializer,
+        Net: Network<Id, S>,
+        Sch: Scheduler,
+    {
+        self.refresh(engine.environment_mut());
+        engine.tick(now)
+    }
+}
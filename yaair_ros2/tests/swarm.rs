@@ -0,0 +1,101 @@
+//! Integration tests for the ROS 2 adapter, over an in-memory bus standing for the middleware.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use yaair::rufi::aggregate::{Aggregate, AggregateError, VM};
+use yaair::rufi::engine::Engine;
+use yaair::rufi::network::NetworkError;
+use yaair::rufi::sensors::typed::{EnvKey, TypedEnv};
+use yaair_ros2::rufi_ros2::cdr;
+use yaair_ros2::rufi_ros2::network::{RosNetwork, SWARM_TOPIC};
+use yaair_ros2::rufi_ros2::node::RosNode;
+use yaair_ros2::rufi_ros2::sensors::TopicSensors;
+use yaair_serde::rufi_serde::json::JsonSerializer;
+
+/// Every message published on every topic, in order.
+type Bus = Rc<RefCell<HashMap<String, Vec<Vec<u8>>>>>;
+
+/// Node reading the bus from where it stopped on every topic.
+struct BusNode {
+    bus: Bus,
+    read: HashMap<String, usize>,
+}
+impl RosNode for BusNode {
+    fn publish(&mut self, topic: &str, message: &[u8]) -> Result<(), NetworkError> {
+        let mut bus = self.bus.borrow_mut();
+        bus.entry(topic.to_owned())
+            .or_default()
+            .push(message.to_vec());
+        Ok(())
+    }
+
+    fn take(&mut self, topic: &str) -> Vec<Vec<u8>> {
+        let bus = self.bus.borrow();
+        let messages = bus.get(topic).map(Vec::as_slice).unwrap_or_default();
+        let read = self.read.entry(topic.to_owned()).or_default();
+        let unread = messages.get(*read..).unwrap_or_default().to_vec();
+        *read = messages.len();
+        unread
+    }
+}
+
+struct Range;
+impl EnvKey for Range {
+    type Value = f64;
+}
+
+fn node(bus: &Bus) -> Rc<RefCell<BusNode>> {
+    Rc::new(RefCell::new(BusNode {
+        bus: Rc::clone(bus),
+        read: HashMap::new(),
+    }))
+}
+
+fn neighbors_in_range(
+    env: &TypedEnv,
+    vm: &mut VM<u32, JsonSerializer>,
+) -> Result<(usize, f64), AggregateError> {
+    let id = vm.local_id;
+    let neighbors = vm.neighboring(&id)?.size();
+    Ok((neighbors, env.get::<Range>().copied().unwrap_or_default()))
+}
+
+#[test]
+fn robots_exchange_exports_and_read_sensor_topics() {
+    let bus = Bus::default();
+    let (first, second) = (node(&bus), node(&bus));
+    let mut robots: Vec<_> = [(1u32, &first), (2u32, &second)]
+        .into_iter()
+        .map(|(id, node)| {
+            let network = RosNetwork::new(Rc::clone(node), id, JsonSerializer);
+            Engine::new(
+                id,
+                network,
+                TypedEnv::new(),
+                JsonSerializer,
+                neighbors_in_range,
+            )
+        })
+        .collect();
+    let mut sensors = TopicSensors::new(Rc::clone(&first))
+        .with_sensor::<Range>("/scan/range", cdr::decode_float64);
+    second
+        .borrow_mut()
+        .publish("/scan/range", &cdr::encode_float64(2.5))
+        .unwrap();
+
+    let mut outputs = Vec::new();
+    for round in 0..2 {
+        let now = Duration::from_secs(round);
+        let (robot, others) = robots.split_first_mut().unwrap();
+        let report = sensors.spin_once(robot, now).unwrap().unwrap();
+        outputs.push(report.output.unwrap());
+        for other in others {
+            other.tick(now).unwrap().unwrap();
+        }
+    }
+    assert_eq!(outputs, vec![(1, 2.5), (2, 2.5)]);
+    assert_eq!(bus.borrow().get(SWARM_TOPIC).map(Vec::len), Some(4));
+}
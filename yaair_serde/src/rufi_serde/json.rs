@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use yaair::rufi::messages::serializer::Serializer;
 
 #[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct JsonSerializer;
 impl Serializer for JsonSerializer {
     type Error = serde_json::Error;
@@ -10,7 +11,7 @@ impl Serializer for JsonSerializer {
         serde_json::to_vec(value)
     }
 
-    fn deserialize<T: for<'de> Deserialize<'de>>(&self, value: &[u8]) -> Result<T, Self::Error> {
+    fn deserialize<'de, T: Deserialize<'de>>(&self, value: &'de [u8]) -> Result<T, Self::Error> {
         serde_json::from_slice(value)
     }
 }
@@ -46,4 +47,12 @@ mod tests {
         let result: i32 = serializer.deserialize(&bytes).expect("deserialize ok");
         assert_eq!(value, result);
     }
+
+    #[test]
+    fn test_deserialize_borrowed_str() {
+        let serializer = JsonSerializer;
+        let bytes = serializer.serialize(&"ciao").expect("serialize ok");
+        let result: &str = serializer.deserialize(&bytes).expect("deserialize ok");
+        assert_eq!(result, "ciao");
+    }
 }
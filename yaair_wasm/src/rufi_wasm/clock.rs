@@ -0,0 +1,27 @@
+use core::time::Duration;
+use yaair::rufi::time::Clock;
+
+/// Clock reading `performance.now()`, for browsers where `std::time::Instant` is unavailable.
+///
+/// Falls back to `Date.now()` outside of a window context, e.g. in web workers without a
+/// `window` global.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerformanceClock;
+impl PerformanceClock {
+    pub const fn new() -> Self {
+        Self
+    }
+}
+impl Clock for PerformanceClock {
+    fn now(&mut self) -> Duration {
+        Duration::from_secs_f64(now_millis() / 1000.0)
+    }
+}
+
+/// Milliseconds elapsed since an arbitrary epoch, from the most precise source available.
+pub(crate) fn now_millis() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map_or_else(js_sys::Date::now, |performance| performance.now())
+        .max(0.0)
+}
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+use yaair::rufi::aggregate::AggregateError;
+use yaair::rufi::engine::Engine;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::network::Network;
+use yaair::rufi::scheduler::Scheduler;
+
+/// A round of the wrapped engine, with its result converted to a JavaScript value.
+type Round = Box<dyn FnMut() -> Result<JsValue, JsValue>>;
+
+/// JavaScript handle to an [`Engine`].
+///
+/// Aggregate programs are Rust functions, so the engine is built on the Rust side and handed to
+/// JavaScript wrapped in a `JsEngine`, e.g. returned by an exported constructor:
+///
+/// ```ignore
+/// #[wasm_bindgen]
+/// pub fn gradient_engine(id: u32, relay: &str) -> Result<JsEngine, JsValue> {
+///     let network = WebSocketNetwork::connect(id, relay, JsonSerializer)?;
+///     let mut engine = Engine::new(id, network, (), JsonSerializer, gradient);
+///     engine.set_clock(PerformanceClock::new());
+///     Ok(JsEngine::new(engine))
+/// }
+/// ```
+///
+/// JavaScript then drives the rounds, typically from `setInterval`.
+#[wasm_bindgen]
+pub struct JsEngine {
+    round: Round,
+}
+impl JsEngine {
+    pub fn new<Id, Out, Env, S, Net, Sch>(mut engine: Engine<Id, Out, Env, S, Net, Sch>) -> Self
+    where
+        Id: Ord + core::hash::Hash + Copy + Serialize + for<'de> serde::Deserialize<'de> + 'static,
+        Out: Serialize + 'static,
+        Env: 'static,
+        S: Serializer + 'static,
+        Net: Network<Id, S> + 'static,
+        Sch: Scheduler + 'static,
+    {
+        Self {
+            round: Box::new(move || {
+                let result = engine.cycle().map_err(|err| error(&err))?;
+                to_js(&result.output)
+            }),
+        }
+    }
+}
+
+// generic constructors cannot be exported, hence the separate block
+#[allow(clippy::multiple_inherent_impl)]
+#[wasm_bindgen]
+impl JsEngine {
+    /// Execute a round, returning its result as a plain JavaScript value.
+    ///
+    /// # Errors
+    /// Throws a JavaScript `Error` if the round fails
+    pub fn cycle(&mut self) -> Result<JsValue, JsValue> {
+        (self.round)()
+    }
+}
+
+/// Convert a result through its JSON representation.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(value)
+        .map_err(|err| JsValue::from(js_sys::Error::new(&err.to_string())))?;
+    js_sys::JSON::parse(&json)
+}
+
+fn error(err: &AggregateError) -> JsValue {
+    js_sys::Error::new(&err.to_string()).into()
+}
@@ -0,0 +1,3 @@
+pub mod clock;
+pub mod engine;
+pub mod websocket;
@@ -0,0 +1,154 @@
+use crate::rufi_wasm::clock::now_millis;
+use core::hash::Hash;
+use core::time::Duration;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+use yaair::rufi::device::DeviceId;
+use yaair::rufi::messages::inbound::InboundMessage;
+use yaair::rufi::messages::outbound::OutboundMessage;
+use yaair::rufi::messages::serializer::Serializer;
+use yaair::rufi::messages::valuetree::ValueTree;
+use yaair::rufi::network::{Network, NetworkError};
+use yaair::rufi::sensors::neighborhood::{NeighborReading, NeighborhoodReadings};
+
+/// Reception time in milliseconds, sequence number and full tree of the last message of a neighbor.
+type LastMessage = (f64, u64, ValueTree);
+
+/// [`Network`] exchanging binary WebSocket frames through a relay.
+///
+/// The endpoint is expected to forward every binary frame to all the other clients connected
+/// to it, so that browsers and native nodes connected to the same relay share a neighborhood.
+/// Frames are buffered by the browser event loop and decoded at the beginning of every round;
+/// a neighbor is retained until its last message is older than the retention.
+pub struct WebSocketNetwork<Id: Ord + Hash + Copy, S: Serializer> {
+    local_id: Id,
+    socket: WebSocket,
+    received: Rc<RefCell<Vec<Vec<u8>>>>,
+    // keeps the callback alive as long as the socket
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    serializer: S,
+    retention: Duration,
+    last_messages: HashMap<Id, LastMessage>,
+}
+impl<Id, S> WebSocketNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    /// Open a connection to the relay at `url` (`ws://` or `wss://`).
+    ///
+    /// # Errors
+    /// Returns the exception raised by the `WebSocket` constructor, e.g. for a malformed URL
+    pub fn connect(local_id: Id, url: &str, serializer: S) -> Result<Self, JsValue> {
+        let socket = WebSocket::new(url)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let queue = Rc::clone(&received);
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                queue
+                    .borrow_mut()
+                    .push(js_sys::Uint8Array::new(&buffer).to_vec());
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        Ok(Self {
+            local_id,
+            socket,
+            received,
+            _on_message: on_message,
+            serializer,
+            retention: Duration::from_secs(5),
+            last_messages: HashMap::new(),
+        })
+    }
+
+    /// How long the last message of a silent neighbor is retained.
+    #[must_use]
+    pub const fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Whether the connection to the relay is open.
+    pub fn is_open(&self) -> bool {
+        self.socket.ready_state() == WebSocket::OPEN
+    }
+
+    fn receive(&mut self, payload: &[u8], now: f64) {
+        let Ok(message) = OutboundMessage::<Id>::decode(&self.serializer, payload) else {
+            return;
+        };
+        let sender = message.sender;
+        if sender == self.local_id {
+            return;
+        }
+        let sequence = message.sequence();
+        let last = self
+            .last_messages
+            .get(&sender)
+            .map(|(_, last_sequence, tree)| (*last_sequence, tree));
+        if let Some(tree) = message.resolve(last) {
+            self.last_messages.insert(sender, (now, sequence, tree));
+        }
+    }
+}
+
+impl<Id, S> Network<Id, S> for WebSocketNetwork<Id, S>
+where
+    Id: DeviceId,
+    S: Serializer,
+{
+    fn prepare_outbound(&mut self, outbound_message: Vec<u8>) -> Result<(), NetworkError> {
+        if !self.is_open() {
+            return Err(NetworkError::Disconnected);
+        }
+        self.socket
+            .send_with_u8_array(&outbound_message)
+            .map_err(|_| NetworkError::Disconnected)
+    }
+
+    fn prepare_inbound(&mut self) -> InboundMessage<Id> {
+        let now = now_millis();
+        let received = self.received.take();
+        for payload in &received {
+            self.receive(payload, now);
+        }
+        let retention = self.retention.as_secs_f64() * 1000.0;
+        self.last_messages
+            .retain(|_, (received_at, _, _)| now - *received_at <= retention);
+        InboundMessage::new(
+            self.last_messages
+                .iter()
+                .map(|(id, (_, _, tree))| (*id, tree.clone()))
+                .collect(),
+        )
+    }
+
+    fn has_pending_inbound(&self) -> bool {
+        !self.received.borrow().is_empty()
+    }
+
+    fn sense_neighborhood(&mut self) -> NeighborhoodReadings<Id> {
+        let now = now_millis();
+        NeighborhoodReadings::new(
+            self.last_messages
+                .iter()
+                .map(|(id, (received_at, _, _))| {
+                    let lag = Duration::from_secs_f64((now - *received_at).max(0.0) / 1000.0);
+                    (
+                        *id,
+                        NeighborReading {
+                            lag: Some(lag),
+                            ..NeighborReading::default()
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+}